@@ -0,0 +1,112 @@
+//! The file `git-switch export` writes and `git-switch import <file>` reads
+//! to move a setup between machines: accounts with every field (SSH options
+//! included, from which the SSH config blocks are rebuilt), rules, and
+//! optionally the accounts' keys with the private halves encrypted.
+//!
+//! ```toml
+//! version = 1
+//!
+//! [[account]]
+//! name = "work"
+//! # ...
+//!
+//! [[rule]]
+//! pattern = "github.com/mycompany/*"
+//! account = "work"
+//!
+//! [[key]]
+//! account = "work"
+//! public_key = "ssh-ed25519 AAAA... work@example.com"
+//! private_key = "-----BEGIN AGE ENCRYPTED FILE-----\n..."
+//! ```
+
+use crate::config::Account;
+use crate::rules::Rule;
+use serde::{Deserialize, Serialize};
+
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Serialization of a bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BundleFormat {
+    #[default]
+    Toml,
+    Json,
+}
+
+impl BundleFormat {
+    pub const NAMES: [&'static str; 2] = ["toml", "json"];
+
+    pub fn parse(name: &str) -> Option<BundleFormat> {
+        match name {
+            "toml" => Some(BundleFormat::Toml),
+            "json" => Some(BundleFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// An account's key pair; the private key is an armored age file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyMaterial {
+    pub account: String,
+    pub public_key: String,
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bundle {
+    pub version: u32,
+    #[serde(default, rename = "account")]
+    pub accounts: Vec<Account>,
+    #[serde(default, rename = "rule", skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+    #[serde(default, rename = "key", skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<KeyMaterial>,
+}
+
+impl Bundle {
+    pub fn new(accounts: Vec<Account>, rules: Vec<Rule>) -> Bundle {
+        Bundle {
+            version: BUNDLE_VERSION,
+            accounts,
+            rules,
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn to_text(&self, format: BundleFormat) -> Result<String, String> {
+        match format {
+            BundleFormat::Toml => toml::to_string_pretty(self).map_err(|e| e.to_string()),
+            BundleFormat::Json => serde_json::to_string_pretty(self)
+                .map(|json| json + "\n")
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Reads a bundle in either format.
+    pub fn parse(text: &str) -> Result<Bundle, String> {
+        let bundle: Bundle = if text.trim_start().starts_with('{') {
+            serde_json::from_str(text).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(text).map_err(|e| e.to_string())?
+        };
+        if bundle.version > BUNDLE_VERSION {
+            return Err(format!(
+                "bundle version {} is newer than this git-switch supports ({})",
+                bundle.version, BUNDLE_VERSION
+            ));
+        }
+        Ok(bundle)
+    }
+}
+
+/// Rewrites a path under `home` as `~/...`, so it resolves on the machine
+/// the bundle is imported on.
+pub fn portable_path(path: &str, home: &str) -> String {
+    let home = home.trim_end_matches('/');
+    match path.strip_prefix(home) {
+        Some(rest) if !home.is_empty() && rest.starts_with('/') => format!("~{}", rest),
+        _ => path.to_string(),
+    }
+}
@@ -0,0 +1,1376 @@
+//! Command-line interface definition, shared by the binary and the
+//! completion generator.
+
+use crate::bundle::BundleFormat;
+use crate::output::OutputFormat;
+use crate::{
+    completions, config, git, hooks, prompt, provider, render, secrets, session, settings, ssh,
+    utils,
+};
+use clap::{Arg, ArgAction, Command};
+
+const SETTINGS_HELP: &str = "Show or change settings that apply to all accounts, kept in ~/.git-switch/settings.toml. Without arguments, lists them.\n\n\
+mode: 'alias' (default) writes a Host block per account to ~/.ssh/config and points remotes at it; 'sshcommand' leaves ~/.ssh/config alone and sets core.sshCommand to the account's key instead.\n\
+key_type: key type `add` generates without --key-type (default ed25519).\n\
+host: host of accounts added without --host (default github.com).\n\
+emoji, color: 'false' for plain messages and uncolored prompts on a terminal.\n\
+key_dir, key_name, key_comment: where new keys go and their comment; templates with {account}, {type}, {host}, {email} and {hostname}.";
+
+/// Builds the `git-switch` command with all its subcommands and flags.
+pub fn build_cli() -> Command {
+    Command::new("git-switch")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("CLI tool to switch between multiple Git accounts")
+        .arg(
+            Arg::new("log-json")
+                .long("log-json")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Emit every action as a JSON event line on stderr"),
+        )
+        .arg(
+            Arg::new("non-interactive")
+                .long("non-interactive")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Never prompt; use the default answer for every question"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .global(true)
+                .value_name("PATH")
+                .help("Accounts file to use instead of ~/.config/git-switch/accounts.toml [env: GIT_SWITCH_CONFIG]"),
+        )
+        .arg(
+            Arg::new("ssh-config")
+                .long("ssh-config")
+                .global(true)
+                .value_name("PATH")
+                .help("SSH config to manage instead of ~/.ssh/config [env: GIT_SWITCH_SSH_CONFIG]"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .conflicts_with("verbose")
+                .help("Print only results, warnings and errors: no progress messages or command echo"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .global(true)
+                .action(ArgAction::Count)
+                .help("Show the output of the commands git-switch runs (and build details in `version`); -vv also shows the commands it reads with and the files it writes"),
+        )
+        .subcommand(
+            Command::new("add")
+                .about("Add a new Git account")
+                .long_about("Add a new Git account. Without NAME USERNAME EMAIL, asks for them and the key to use step by step.")
+                .arg(
+                    Arg::new("name")
+                        .requires("email")
+                        .help("Name for the account (e.g. 'Work', 'Personal')"),
+                )
+                .arg(Arg::new("username").help("Git username"))
+                .arg(Arg::new("email").help("Git email address"))
+                .arg(
+                    Arg::new("credential-url")
+                        .long("credential-url")
+                        .value_name("URL")
+                        .help("HTTPS base URL whose credential requests belong to this account (e.g. 'https://git.corp.com:8443/gitlab/')"),
+                )
+                .arg(
+                    Arg::new("lfs-url")
+                        .long("lfs-url")
+                        .value_name("URL")
+                        .help("Git LFS endpoint to use while this account is active"),
+                )
+                .arg(
+                    Arg::new("credential-cache")
+                        .long("credential-cache")
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Cache HTTPS credentials for this many seconds while this account is active"),
+                )
+                .arg(
+                    Arg::new("key-type")
+                        .long("key-type")
+                        .value_parser(ssh::KeyType::NAMES)
+                        .help("Type of SSH key to generate (default: the key_type setting, ed25519)"),
+                )
+                .arg(
+                    Arg::new("key-bits")
+                        .long("key-bits")
+                        .value_name("BITS")
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Key size in bits (rsa: >= 2048, default 4096; ecdsa: 256, 384 or 521)"),
+                )
+                .arg(
+                    Arg::new("resident")
+                        .long("resident")
+                        .action(ArgAction::SetTrue)
+                        .help("Keep a security key's key on the authenticator, so `ssh-keygen -K` can restore it elsewhere"),
+                )
+                .arg(
+                    Arg::new("verify-required")
+                        .long("verify-required")
+                        .action(ArgAction::SetTrue)
+                        .help("Make a security key ask for its PIN, not just a touch, on every use"),
+                )
+                .arg(
+                    Arg::new("host")
+                        .long("host")
+                        .value_name("HOST")
+                        .action(ArgAction::Append)
+                        .help("Git host for this account (default: github.com); repeat for multiple hosts"),
+                )
+                .arg(
+                    Arg::new("hostname")
+                        .long("hostname")
+                        .value_name("SERVER")
+                        .help("SSH server of the primary host, if it isn't the host itself (the host, without --host)"),
+                )
+                .arg(
+                    Arg::new("port")
+                        .long("port")
+                        .value_name("PORT")
+                        .value_parser(clap::value_parser!(u16).range(1..))
+                        .help("SSH port of the primary host (e.g. 7999 for Bitbucket Server)"),
+                )
+                .arg(
+                    Arg::new("ssh-user")
+                        .long("ssh-user")
+                        .value_name("USER")
+                        .help("SSH user of the primary host, if it isn't git"),
+                )
+                .arg(
+                    Arg::new("proxy-jump")
+                        .long("proxy-jump")
+                        .value_name("HOST")
+                        .help("Reach the primary host through this jump host (ssh's ProxyJump)"),
+                )
+                .arg(
+                    Arg::new("workspace")
+                        .long("workspace")
+                        .value_name("DIR")
+                        .help("Root directory for this account's checkouts (e.g. '~/work/src')"),
+                )
+                .arg(
+                    Arg::new("signing-key")
+                        .long("signing-key")
+                        .value_name("KEY")
+                        .help("Commit signing key: GPG key ID or SSH public key path"),
+                )
+                .arg(
+                    Arg::new("signing-format")
+                        .long("signing-format")
+                        .value_parser(config::SIGNING_FORMATS)
+                        .help("Signing format (default gpg; ssh signs with the account's SSH key unless --signing-key is given)"),
+                )
+                .arg(
+                    Arg::new("upload")
+                        .long("upload")
+                        .action(ArgAction::SetTrue)
+                        .help("Upload the public key to the host using a stored token, its token variable or the gh/glab login"),
+                )
+                .arg(
+                    Arg::new("key-dir")
+                        .long("key-dir")
+                        .value_name("DIR")
+                        .help("Generate the key in this directory instead of the key_dir setting (default ~/.ssh)"),
+                )
+                .arg(
+                    Arg::new("ssh-key")
+                        .long("ssh-key")
+                        .value_name("PATH")
+                        .conflicts_with_all(["key-type", "key-bits", "key-dir", "resident", "verify-required", "passphrase", "passphrase-stdin"])
+                        .help("Use this existing private key instead of generating one; `remove` keeps it"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("Replace a saved account with the same name, or add one whose username another account already uses on the same host"),
+                )
+                .arg(
+                    Arg::new("exclusive-key")
+                        .long("exclusive-key")
+                        .action(ArgAction::SetTrue)
+                        .help("Have `use` remove the other accounts' keys from ssh-agent, so hosts are offered only this account's key"),
+                )
+                .arg(
+                    Arg::new("agent-ttl")
+                        .long("agent-ttl")
+                        .value_name("DURATION")
+                        .value_parser(utils::parse_duration)
+                        .help("Let the key expire from ssh-agent this long after `use` loads it (e.g. 8h, 30m)"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .value_name("TAG")
+                        .value_parser(|tag: &str| config::check_tag(tag).map(|()| tag.to_string()))
+                        .action(ArgAction::Append)
+                        .help("Tag the account for `list --tag` and bulk commands (e.g. client-x); repeatable"),
+                )
+                .arg(
+                    Arg::new("org")
+                        .long("org")
+                        .value_name("OWNER")
+                        .value_parser(|org: &str| config::check_org(org).map(|()| org.to_string()))
+                        .action(ArgAction::Append)
+                        .help("Send SSH URLs of this user, organization or group through the account after a global `use`; repeatable"),
+                )
+                .arg(
+                    Arg::new("provider")
+                        .long("provider")
+                        .value_name("KIND")
+                        .value_parser(provider::PROVIDER_KINDS)
+                        .help("Software a self-hosted --host runs, for key uploads and settings links"),
+                )
+                .arg(
+                    Arg::new("auth")
+                        .long("auth")
+                        .value_parser(config::AUTH_METHODS)
+                        .help("How git authenticates: ssh (default) or https with a personal access token"),
+                )
+                .arg(
+                    Arg::new("token-stdin")
+                        .long("token-stdin")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("passphrase-stdin")
+                        .help("Read the personal access token of an https account from the first line of stdin"),
+                )
+                .arg(
+                    Arg::new("passphrase")
+                        .long("passphrase")
+                        .action(ArgAction::SetTrue)
+                        .help("Protect the new key with a passphrase, asked for without echoing"),
+                )
+                .arg(
+                    Arg::new("passphrase-stdin")
+                        .long("passphrase-stdin")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("passphrase")
+                        .help("Protect the new key with the passphrase on the first line of stdin"),
+                )
+                .arg(
+                    Arg::new("ssh-option")
+                        .long("ssh-option")
+                        .value_name("KEY=VALUE")
+                        .value_parser(ssh::parse_ssh_option)
+                        .action(ArgAction::Append)
+                        .help("Extra option for the account's SSH config entry (e.g. 'Port=2222'); repeatable"),
+                )
+                .arg(
+                    Arg::new("git-config")
+                        .long("git-config")
+                        .value_name("KEY=VALUE")
+                        .value_parser(git::parse_git_config)
+                        .action(ArgAction::Append)
+                        .help("Git setting `use` applies with the account (e.g. 'pull.rebase=true'); repeatable"),
+                )
+                .arg(
+                    Arg::new("smtp-server")
+                        .long("smtp-server")
+                        .value_name("HOST")
+                        .help("SMTP server `git send-email` uses while this account is active"),
+                )
+                .arg(
+                    Arg::new("smtp-port")
+                        .long("smtp-port")
+                        .value_name("PORT")
+                        .requires("smtp-server")
+                        .value_parser(clap::value_parser!(u16))
+                        .help("SMTP server port"),
+                )
+                .arg(
+                    Arg::new("smtp-user")
+                        .long("smtp-user")
+                        .value_name("USER")
+                        .requires("smtp-server")
+                        .help("SMTP login name"),
+                )
+                .arg(
+                    Arg::new("smtp-encryption")
+                        .long("smtp-encryption")
+                        .requires("smtp-server")
+                        .value_parser(config::SMTP_ENCRYPTIONS)
+                        .help("SMTP encryption: tls (STARTTLS) or ssl"),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Create accounts from a file written by `export`, or from the Git host entries in ~/.ssh/config")
+                .arg(
+                    Arg::new("file")
+                        .value_name("FILE")
+                        .help("File written by `export`; without it, ~/.ssh/config and your git identities are searched"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("file")
+                        .help("Only list the accounts that would be proposed"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["dry-run", "file"])
+                        .help("Import every proposal as suggested, without asking"),
+                )
+                .arg(
+                    Arg::new("passphrase-stdin")
+                        .long("passphrase-stdin")
+                        .action(ArgAction::SetTrue)
+                        .requires("file")
+                        .help("Read the passphrase of exported keys from the first line of stdin"),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write all accounts and rules to a file for `import` on another machine")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(BundleFormat::NAMES)
+                        .default_value("toml")
+                        .help("File format"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Write to FILE (readable only by you) instead of stdout"),
+                )
+                .arg(
+                    Arg::new("include-keys")
+                        .long("include-keys")
+                        .action(ArgAction::SetTrue)
+                        .help("Include the accounts' keys, private keys encrypted with a passphrase"),
+                )
+                .arg(
+                    Arg::new("encrypt")
+                        .long("encrypt")
+                        .action(ArgAction::SetTrue)
+                        .help("Encrypt the whole file with a passphrase"),
+                )
+                .arg(
+                    Arg::new("passphrase-stdin")
+                        .long("passphrase-stdin")
+                        .action(ArgAction::SetTrue)
+                        .help("Read the passphrase from the first line of stdin"),
+                ),
+        )
+        .subcommand(
+            Command::new("encrypt")
+                .about("Encrypt the accounts file with a passphrase or an age identity")
+                .arg(
+                    Arg::new("identity")
+                        .long("identity")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Encrypt to the age identity in FILE (from `age-keygen`) instead of a passphrase"),
+                )
+                .arg(
+                    Arg::new("passphrase-stdin")
+                        .long("passphrase-stdin")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("identity")
+                        .help("Read the new passphrase from the first line of stdin"),
+                ),
+        )
+        .subcommand(
+            Command::new("decrypt").about("Turn an encrypted accounts file back into plain TOML"),
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("Keep accounts in step across machines through a private git repository")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("setup")
+                        .about("Clone the repository and sync with it")
+                        .arg(
+                            Arg::new("url")
+                                .required(true)
+                                .help("URL of a private git repository, e.g. git@github.com:me/git-switch-sync.git"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("push")
+                        .about("Merge in remote changes and push this machine's accounts"),
+                )
+                .subcommand(
+                    Command::new("pull").about("Take in accounts changed on other machines"),
+                ),
+        )
+        .subcommand(
+            Command::new("use")
+                .about("Switch to a saved Git account")
+                .arg(
+                    Arg::new("name")
+                        .help("Name or username of the account to use")
+                        .long_help("Name or username of the account to use. Without it, pick one from a list."),
+                )
+                .arg(
+                    Arg::new("local")
+                        .long("local")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("global")
+                        .help("Set the identity for the current repository only (default inside a repository)"),
+                )
+                .arg(
+                    Arg::new("global")
+                        .long("global")
+                        .action(ArgAction::SetTrue)
+                        .help("Set the identity in the global git config (default outside a repository)"),
+                )
+                .arg(
+                    Arg::new("acknowledge")
+                        .long("acknowledge")
+                        .action(ArgAction::SetTrue)
+                        .help("Acknowledge machine policy warnings that require confirmation"),
+                )
+                .arg(
+                    Arg::new("update-remote")
+                        .long("update-remote")
+                        .value_name("REPO")
+                        .help("Point the current repository's origin at REPO ('owner/repo' or 'repo') without asking"),
+                )
+                .arg(
+                    Arg::new("no-remote")
+                        .long("no-remote")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("update-remote")
+                        .help("Leave the current repository's remote alone without asking"),
+                )
+                .arg(
+                    Arg::new("plain-remote")
+                        .long("plain-remote")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("no-remote")
+                        .help("Write the remote as git@<host>: instead of through the account's SSH host alias"),
+                )
+                .arg(
+                    Arg::new("rewrite-remotes")
+                        .long("rewrite-remotes")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("session")
+                        .help("Point every remote and submodule on the account's hosts, including other accounts' aliases, at this account"),
+                )
+                .arg(
+                    Arg::new("session")
+                        .long("session")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["local", "global", "update-remote"])
+                        .help("Switch only the current shell, without touching git config (needs `git-switch init`)"),
+                )
+                .arg(
+                    Arg::new("end-session")
+                        .long("end-session")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["name", "session"])
+                        .help("End the current shell's session"),
+                )
+                .arg(
+                    Arg::new("print-env")
+                        .long("print-env")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["session", "end-session"])
+                        .help("Also print shell exports for the SSH agent and key, for `eval \"$(git-switch use <name> --print-env)\"`"),
+                )
+                .arg(
+                    Arg::new("exclusive")
+                        .long("exclusive")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["session", "end-session"])
+                        .help("Remove the other accounts' keys from ssh-agent this time, as accounts added with --exclusive-key always do"),
+                )
+                .arg(
+                    Arg::new("mode")
+                        .long("mode")
+                        .value_parser(settings::SwitchMode::NAMES)
+                        .conflicts_with_all(["session", "end-session"])
+                        .help("Reach the account's hosts through its SSH host alias, or through core.sshCommand without ~/.ssh/config (default: the `mode` setting)"),
+                ),
+        )
+        .subcommand(
+            Command::new("env")
+                .about("Load an account's key into an SSH agent and print the exports that point your shell at it")
+                .long_about("Load an account's key into an SSH agent, starting one if needed, and print SSH_AUTH_SOCK, SSH_AGENT_PID and GIT_SSH_COMMAND exports. Run it as `eval \"$(git-switch env <name>)\"`; git config is left alone.")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or username of the account"),
+                ),
+        )
+        .subcommand(
+            Command::new("tui")
+                .visible_alias("dashboard")
+                .about("Open a terminal dashboard to view, switch, add, edit and verify accounts"),
+        )
+        .subcommand(
+            Command::new("status")
+                .visible_alias("whoami")
+                .about("Show the active Git identity, SSH agent keys and current remote")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("current")
+                .about("Print the active account's name, for shell prompts (exits 1 if none is active)")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["plain", "json"])
+                        .default_value("plain")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("prompt")
+                .about("Print a colored prompt segment that warns when the repository belongs to another account")
+                .long_about("Print the active account for a shell prompt: green when it's the account the repository belongs to, red with the mismatch template when it isn't. Templates can use {account}, {username}, {email} and {expected}. Prints nothing when no saved account is involved.")
+                .arg(
+                    Arg::new("template")
+                        .long("template")
+                        .value_name("TEMPLATE")
+                        .default_value(prompt::DEFAULT_TEMPLATE)
+                        .help("Segment when the account matches or the repository isn't mapped"),
+                )
+                .arg(
+                    Arg::new("mismatch-template")
+                        .long("mismatch-template")
+                        .value_name("TEMPLATE")
+                        .default_value(prompt::DEFAULT_MISMATCH_TEMPLATE)
+                        .help("Segment when the repository belongs to another account"),
+                )
+                .arg(
+                    Arg::new("shell")
+                        .long("shell")
+                        .value_parser(prompt::SHELLS)
+                        .default_value("raw")
+                        .help("Wrap color codes for bash's or zsh's prompt; raw for fish, starship and others"),
+                )
+                .arg(
+                    Arg::new("no-color")
+                        .long("no-color")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the segment without color codes"),
+                ),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("Show the log of account switches, changes and remote rewrites")
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .short('n')
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("20")
+                        .help("Show the last N entries"),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("limit")
+                        .help("Show every entry"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Show an account's key, agent status, signing key, rules and mapped directories")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or username of the account"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List all saved Git accounts")
+                .arg(
+                    Arg::new("names")
+                        .long("names")
+                        .action(ArgAction::SetTrue)
+                        .hide(true)
+                        .help("Print only account names, one per line (used by shell completions)"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .value_name("TAG")
+                        .help("List only the accounts with this tag"),
+                )
+                .arg(
+                    Arg::new("keys")
+                        .long("keys")
+                        .action(ArgAction::SetTrue)
+                        .help("Show each key's fingerprint and whether ssh-agent holds it"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(OutputFormat::NAMES)
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Remove a saved Git account and its SSH key")
+                .arg(
+                    Arg::new("name")
+                        .help("Name of the account to remove")
+                        .long_help("Name of the account to remove. Without it, pick one from a list."),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(ArgAction::SetTrue)
+                        .help("Don't ask for confirmation"),
+                )
+                .arg(
+                    Arg::new("keep-keys")
+                        .long("keep-keys")
+                        .action(ArgAction::SetTrue)
+                        .help("Leave the account's SSH key files in place"),
+                )
+                .arg(
+                    Arg::new("config-only")
+                        .long("config-only")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("keep-keys")
+                        .help("Remove only the account entry; keep its SSH config entries, keys, secrets, mappings and rules"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Show what would be deleted without removing anything"),
+                ),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Restore the accounts file and ~/.ssh/config as they were before the last command that changed them"),
+        )
+        .subcommand(
+            Command::new("prune")
+                .about("Delete SSH config entries, keys and settings left behind by accounts that no longer exist")
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("List what would be deleted without deleting it"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(ArgAction::SetTrue)
+                        .help("Don't ask for confirmation"),
+                ),
+        )
+        .subcommand(
+            Command::new("default")
+                .about("Show or set the account used when none is given or no rule matches")
+                .long_about("Show or set the default account. `use` without a name switches to it outside a terminal, new shells with the `init` hook start as it, and `auto` and `clone` fall back to it when no rule matches.")
+                .arg(Arg::new("name").help("Name or username of the account to make the default"))
+                .arg(
+                    Arg::new("clear")
+                        .long("clear")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("name")
+                        .help("Have no default account"),
+                ),
+        )
+        .subcommand(
+            Command::new("rename")
+                .about("Rename an account along with its SSH key files and config")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Current name of the account"),
+                )
+                .arg(
+                    Arg::new("new-name")
+                        .required(true)
+                        .help("New name for the account"),
+                ),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Test the SSH connection of an account, falling back to port 443 where the host offers it")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or username of the account to test"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(ArgAction::SetTrue)
+                        .help("Write a working port 443 fallback into the SSH config without asking"),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check that a host signs the account's key in as its username")
+                .arg(
+                    Arg::new("name")
+                        .required_unless_present("tag")
+                        .help("Name or username of the account to verify"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .value_name("TAG")
+                        .conflicts_with("name")
+                        .help("Verify every account with this tag"),
+                )
+                .arg(
+                    Arg::new("api")
+                        .long("api")
+                        .action(ArgAction::SetTrue)
+                        .help("Ask the provider's API who the token belongs to and whether it has the key"),
+                ),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Modify a saved account, or all accounts in $EDITOR")
+                .arg_required_else_help(true)
+                .arg(
+                    Arg::new("name")
+                        .required_unless_present("all")
+                        .help("Name or username of the account to modify"),
+                )
+                .arg(
+                    Arg::new("email")
+                        .long("email")
+                        .help("New email address"),
+                )
+                .arg(
+                    Arg::new("username")
+                        .long("username")
+                        .help("New username"),
+                )
+                .arg(
+                    Arg::new("ssh-key")
+                        .long("ssh-key")
+                        .value_name("PATH")
+                        .help("Path of an existing private key to use instead"),
+                )
+                .arg(
+                    Arg::new("ssh-option")
+                        .long("ssh-option")
+                        .value_name("KEY=VALUE")
+                        .value_parser(ssh::parse_ssh_option)
+                        .action(ArgAction::Append)
+                        .help("Set an option of the account's SSH config entry; 'KEY=' removes it"),
+                )
+                .arg(
+                    Arg::new("git-config")
+                        .long("git-config")
+                        .value_name("KEY=VALUE")
+                        .value_parser(git::parse_git_config)
+                        .action(ArgAction::Append)
+                        .help("Set a git setting `use` applies with the account; 'KEY=' removes it"),
+                )
+                .arg(
+                    Arg::new("exclusive-key")
+                        .long("exclusive-key")
+                        .value_name("BOOL")
+                        .value_parser(clap::value_parser!(bool))
+                        .help("Whether `use` removes the other accounts' keys from ssh-agent (true or false)"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .value_name("TAG")
+                        .value_parser(|tag: &str| config::check_tag(tag).map(|()| tag.to_string()))
+                        .action(ArgAction::Append)
+                        .help("Add a tag; repeatable"),
+                )
+                .arg(
+                    Arg::new("untag")
+                        .long("untag")
+                        .value_name("TAG")
+                        .action(ArgAction::Append)
+                        .help("Remove a tag; repeatable"),
+                )
+                .arg(
+                    Arg::new("org")
+                        .long("org")
+                        .value_name("OWNER")
+                        .value_parser(|org: &str| config::check_org(org).map(|()| org.to_string()))
+                        .action(ArgAction::Append)
+                        .help("Add an owner whose SSH URLs go through the account; repeatable"),
+                )
+                .arg(
+                    Arg::new("remove-org")
+                        .long("remove-org")
+                        .value_name("OWNER")
+                        .action(ArgAction::Append)
+                        .help("Remove an owner; repeatable"),
+                )
+                .arg(
+                    Arg::new("provider")
+                        .long("provider")
+                        .value_name("KIND")
+                        .value_parser(["github", "gitlab", "gitea", "none"])
+                        .help("Set the software a self-hosted host runs; none clears it"),
+                )
+                .arg(
+                    Arg::new("hostname")
+                        .long("hostname")
+                        .value_name("SERVER")
+                        .help("Set the SSH server of the primary host; empty clears it"),
+                )
+                .arg(
+                    Arg::new("port")
+                        .long("port")
+                        .value_name("PORT")
+                        .value_parser(clap::value_parser!(u16).range(1..))
+                        .help("Set the SSH port of the primary host; 22 clears it"),
+                )
+                .arg(
+                    Arg::new("ssh-user")
+                        .long("ssh-user")
+                        .value_name("USER")
+                        .help("Set the SSH user of the primary host; empty clears it"),
+                )
+                .arg(
+                    Arg::new("proxy-jump")
+                        .long("proxy-jump")
+                        .value_name("HOST")
+                        .help("Set the jump host of the primary host; empty clears it"),
+                )
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["name", "email", "username", "ssh-key", "ssh-option", "git-config", "exclusive-key", "tag", "untag", "org", "remove-org", "provider", "hostname", "port", "ssh-user", "proxy-jump"])
+                        .help("Edit all accounts at once as a TOML document"),
+                ),
+        )
+        .subcommand(
+            Command::new("key")
+                .about("Manage an account's SSH key")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("passphrase")
+                        .about("Change the passphrase of an account's SSH key")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .help("Name or username of the account"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Print an account's public key (and its fingerprint on stderr)")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .help("Name or username of the account"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("copy")
+                        .about("Copy an account's public key to the clipboard")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .help("Name or username of the account"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("upload")
+                        .about("Upload an account's public key to its Git host")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .help("Name or username of the account"),
+                        )
+                        .arg(
+                            Arg::new("provider")
+                                .long("provider")
+                                .value_name("KIND")
+                                .value_parser(provider::PROVIDER_KINDS)
+                                .help("Treat a self-hosted host as this software for this upload"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("attest")
+                        .about("Export or import the hardware attestation of an account's key")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .help("Name or username of the account"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .long("output")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("Write the attestation (and FILE.challenge) here; prints its path otherwise"),
+                        )
+                        .arg(
+                            Arg::new("import")
+                                .long("import")
+                                .value_name("FILE")
+                                .conflicts_with("output")
+                                .help("Record an attestation produced by other tooling (e.g. for TPM-backed keys)"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("rotate")
+                        .about("Replace an account's SSH key with a new one, keeping the old one as <key>.old")
+                        .arg(
+                            Arg::new("name")
+                                .required_unless_present("tag")
+                                .help("Name or username of the account"),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .long("tag")
+                                .value_name("TAG")
+                                .conflicts_with("name")
+                                .help("Rotate the keys of every account with this tag"),
+                        )
+                        .arg(
+                            Arg::new("passphrase")
+                                .long("passphrase")
+                                .action(ArgAction::SetTrue)
+                                .help("Protect the new keys with a passphrase, asked for once without echoing"),
+                        )
+                        .arg(
+                            Arg::new("passphrase-stdin")
+                                .long("passphrase-stdin")
+                                .action(ArgAction::SetTrue)
+                                .conflicts_with("passphrase")
+                                .help("Protect the new keys with the passphrase on the first line of stdin"),
+                        )
+                        .arg(
+                            Arg::new("upload")
+                                .long("upload")
+                                .action(ArgAction::SetTrue)
+                                .help("Upload the new public keys through the provider API"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("secret")
+                .about("Keep an account's key passphrase or API token in the OS keyring")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("set")
+                        .about("Store a secret, asked for without echoing")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .help("Name or username of the account"),
+                        )
+                        .arg(Arg::new("kind").required(true).value_parser(secrets::KINDS))
+                        .arg(
+                            Arg::new("host")
+                                .long("host")
+                                .value_name("HOST")
+                                .help("Host the token is for (default: the account's primary host)"),
+                        )
+                        .arg(
+                            Arg::new("stdin")
+                                .long("stdin")
+                                .action(ArgAction::SetTrue)
+                                .help("Read the secret from the first line of stdin"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Delete a stored secret")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .help("Name or username of the account"),
+                        )
+                        .arg(Arg::new("kind").required(true).value_parser(secrets::KINDS))
+                        .arg(
+                            Arg::new("host")
+                                .long("host")
+                                .value_name("HOST")
+                                .help("Host the token is for (default: the account's primary host)"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("pending")
+                .about("List or retry provider actions that failed, such as key uploads")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(Command::new("list").about("List queued actions"))
+                .subcommand(Command::new("retry").about("Retry queued actions")),
+        )
+        .subcommand(
+            Command::new("map")
+                .about("Use an account automatically for every repository under a directory")
+                .arg(
+                    Arg::new("dir")
+                        .required_unless_present("list")
+                        .help("Directory whose repositories should use the account"),
+                )
+                .arg(
+                    Arg::new("account")
+                        .required_unless_present_any(["list", "remove"])
+                        .help("Name or username of the account"),
+                )
+                .arg(
+                    Arg::new("remove")
+                        .long("remove")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("account")
+                        .help("Remove the mapping for the directory"),
+                )
+                .arg(
+                    Arg::new("list")
+                        .long("list")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["dir", "account", "remove"])
+                        .help("List mapped directories"),
+                ),
+        )
+        .subcommand(
+            Command::new("rule")
+                .about("Pick accounts for repositories by host and path patterns")
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Use an account for repositories matching a pattern")
+                        .arg(Arg::new("pattern").required(true).help(
+                            "Pattern like 'github.com/mycompany/*' (* matches one path segment, ** any number)",
+                        ))
+                        .arg(
+                            Arg::new("account")
+                                .required(true)
+                                .help("Name or username of the account"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Remove the rule for a pattern")
+                        .arg(Arg::new("pattern").required(true)),
+                )
+                .subcommand(Command::new("list").about("List rules in the order they are tried")),
+        )
+        .subcommand(
+            Command::new("auto")
+                .about(
+                    "Switch the current repository to the account a rule picks for its remote (exits 1 if none matches)",
+                )
+                .arg(
+                    Arg::new("silent")
+                        .long("silent")
+                        .action(ArgAction::SetTrue)
+                        .help("Print nothing, and do nothing outside repositories, without a matching rule or when the identity is already right (for shell hooks)"),
+                ),
+        )
+        .subcommand(
+            Command::new("scan")
+                .about("Check which account each repository under a directory commits as")
+                .arg(
+                    Arg::new("dirs")
+                        .num_args(0..)
+                        .value_name("DIR")
+                        .help("Directories to scan (default: all account workspaces)"),
+                )
+                .arg(
+                    Arg::new("fix")
+                        .long("fix")
+                        .action(ArgAction::SetTrue)
+                        .help("Set the expected account's identity in mismatched repositories"),
+                )
+                .arg(
+                    Arg::new("rewrite-remotes")
+                        .long("rewrite-remotes")
+                        .action(ArgAction::SetTrue)
+                        .requires("fix")
+                        .help("With --fix, also rewrite remotes to the account's SSH host alias"),
+                )
+                .arg(
+                    Arg::new("interactive")
+                        .long("interactive")
+                        .short('i')
+                        .action(ArgAction::SetTrue)
+                        .requires("fix")
+                        .help("With --fix, confirm each repository before changing it"),
+                ),
+        )
+        .subcommand(
+            Command::new("fix")
+                .about("Give every repository under a directory the identity of the account it belongs to")
+                .arg(
+                    Arg::new("dirs")
+                        .num_args(0..)
+                        .value_name("DIR")
+                        .help("Directories to fix (default: all account workspaces)"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Show the planned changes without applying them"),
+                )
+                .arg(
+                    Arg::new("rewrite-remotes")
+                        .long("rewrite-remotes")
+                        .action(ArgAction::SetTrue)
+                        .help("Also rewrite remotes to the account's SSH host alias"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("dry-run")
+                        .help("Apply the changes without asking"),
+                ),
+        )
+        .subcommand(
+            Command::new("hook")
+                .about("Refuse commits made with another identity than the repository's account, or switch on entering repositories")
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("install")
+                        .about("Install the identity check as a hook in the current repository")
+                        .arg(
+                            Arg::new("type")
+                                .long("type")
+                                .value_parser(hooks::HOOK_TYPES)
+                                .default_value("pre-commit")
+                                .help("Hook to install"),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .action(ArgAction::SetTrue)
+                                .help("Move an existing hook aside instead of refusing"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("uninstall")
+                        .about("Remove the hook, restoring one it replaced")
+                        .arg(
+                            Arg::new("type")
+                                .long("type")
+                                .value_parser(hooks::HOOK_TYPES)
+                                .default_value("pre-commit")
+                                .help("Hook to remove"),
+                        ),
+                )
+                .subcommand(Command::new("check").about(
+                    "Exit 1 if the next commit's author email isn't the repository's account's (run by the hook)",
+                ))
+                .subcommand(
+                    Command::new("shell")
+                        .about("Print a shell hook that runs `auto --silent` on changing directory")
+                        .arg(
+                            Arg::new("shell")
+                                .required(true)
+                                .value_parser(hooks::SHELLS)
+                                .help("Shell to print the hook for"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("check").about(
+                "Check the current repository's identity against its .git-switch.toml (exits 1 on violations)",
+            ),
+        )
+        .subcommand(
+            Command::new("render")
+                .about("Print configuration snippets for an account without writing anything")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or username of the account"),
+                )
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .value_parser(render::TARGETS)
+                        .default_value("ssh")
+                        .help("Kind of snippet to print"),
+                ),
+        )
+        .subcommand(
+            Command::new("direnv")
+                .about("Print, or write into an .envrc, the exports that give a directory an account's identity under direnv")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or username of the account"),
+                )
+                .arg(
+                    Arg::new("write")
+                        .long("write")
+                        .value_name("DIR")
+                        .num_args(0..=1)
+                        .default_missing_value(".")
+                        .help("Write the exports into DIR/.envrc (default: the current directory), keeping its other lines"),
+                ),
+        )
+        .subcommand(
+            Command::new("clone")
+                .about("Clone a repository using an account's SSH host alias")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or username of the account to clone with, or a repository URL or 'host/owner/repo' to pick the account by rule"),
+                )
+                .arg(
+                    Arg::new("repo")
+                        .help("Repository as 'owner/repo' or just 'repo' (owned by the account)"),
+                )
+                .arg(Arg::new("dir").help(
+                    "Destination directory (defaults to the account's workspace directory)",
+                ))
+                .arg(
+                    Arg::new("mode")
+                        .long("mode")
+                        .value_parser(settings::SwitchMode::NAMES)
+                        .help("Clone through the account's SSH host alias, or with core.sshCommand set to its key (default: the `mode` setting)"),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Get or set settings that apply to all accounts")
+                .long_about(SETTINGS_HELP)
+                // The command's earlier name, and its `<key> [<value>]` form
+                .alias("settings")
+                .args_conflicts_with_subcommands(true)
+                .arg(
+                    Arg::new("key")
+                        .value_parser(settings::KEYS)
+                        .hide(true)
+                        .help("Setting to show or change"),
+                )
+                .arg(
+                    Arg::new("value")
+                        .requires("key")
+                        .hide(true)
+                        .help("New value; empty for the default"),
+                )
+                .subcommand(
+                    Command::new("get")
+                        .about("Print a setting")
+                        .arg(Arg::new("key").required(true).value_parser(settings::KEYS)),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Change a setting")
+                        .arg(Arg::new("key").required(true).value_parser(settings::KEYS))
+                        .arg(Arg::new("value").required(true).help("New value; empty for the default")),
+                )
+                .subcommand(
+                    Command::new("unset")
+                        .about("Put a setting back to its default")
+                        .arg(Arg::new("key").required(true).value_parser(settings::KEYS)),
+                )
+                .subcommand(Command::new("list").about("Print every setting (the default without a subcommand)")),
+        )
+        .subcommand(
+            Command::new("cd")
+                .about("Print an account's workspace directory (use with `cd \"$(git-switch cd <account>)\"`)")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("Name or username of the account"),
+                ),
+        )
+        .subcommand(
+            Command::new("version").about(
+                "Show version and build information; with --verbose also the git commit, build date, enabled features and resolved paths",
+            ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check the git-switch setup for problems")
+                .arg(
+                    Arg::new("token-expiry-days")
+                        .long("token-expiry-days")
+                        .value_name("DAYS")
+                        .value_parser(clap::value_parser!(u32))
+                        .default_value("14")
+                        .help("Warn about provider tokens expiring within this many days"),
+                ),
+        )
+        .subcommand(
+            Command::new("credential")
+                .about("Git credential helper (configure with credential.helper='!git-switch credential')")
+                .arg(
+                    Arg::new("operation")
+                        .required(true)
+                        .value_parser(["get", "store", "erase"])
+                        .help("Credential helper operation requested by git"),
+                )
+                .arg(
+                    Arg::new("account")
+                        .long("account")
+                        .value_name("NAME")
+                        .help("Answer for this account only (set up by `use` for HTTPS accounts)"),
+                ),
+        )
+        .subcommand(
+            Command::new("init")
+                .about("Print the shell hook that applies `use --session`")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(session::SHELLS)
+                        .help("Shell to print the hook for"),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Generate shell completion scripts")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(completions::SUPPORTED_SHELLS)
+                        .help("Shell to generate completions for"),
+                ),
+        )
+}
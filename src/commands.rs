@@ -1,181 +1,4574 @@
-use crate::config::{Account, delete_account, load_accounts, save_account};
-use crate::git::update_git_remote;
+//! The `git-switch` subcommands. They report progress on the terminal and
+//! return a [`GitSwitchError`] when they can't finish; they are the binary's
+//! building blocks rather than a library API.
+
+use crate::bundle::{Bundle, BundleFormat, KeyMaterial, portable_path};
+use crate::completions;
+use crate::config::{
+    Account, AccountChange, DEFAULT_HOST, PASSPHRASE_VAR, SendEmail, StoreEncryption,
+    accounts_from_toml, accounts_to_toml, check_account_name, default_account, delete_account,
+    diff_accounts, get_data_dir, get_default_config_path, home_dir, is_valid_email, load_accounts,
+    match_identity, read_accounts_file, replace_account, save_account, set_default_account,
+    set_store_encryption, store_encryption, update_account, validate_accounts, write_accounts,
+};
+use crate::credential::{CredentialRequest, read_insteadof_rules, resolve_account, token_host};
+use crate::crypto::{self, Key};
+use crate::doctor::{self, Severity};
+use crate::editor::edit_text;
+use crate::error::{GitSwitchError, IoContext, Result};
+use crate::files;
+use crate::git::{
+    ConfigScope, RemoteUrl, UrlRewrite, account_fragment_path, account_fragments,
+    apply_account_fragment, apply_identity, apply_org_url_rewrites, apply_signing_config,
+    apply_ssh_command, clear_org_url_rewrites, clear_ssh_command, clone_destination, config_value,
+    configure_repository, effective_identity, is_inside_work_tree, plan_url_rewrites,
+    remove_account_fragment, rename_org_url_rewrites, render_account_fragment, repo_ssh_url,
+    rewrite_remotes, rewritten_remote_url, update_git_remote,
+};
+use crate::history;
+use crate::hooks;
+use crate::import;
+use crate::includes;
+use crate::manifest;
+use crate::output::{self, OutputFormat};
+use crate::pending;
+use crate::policy::{self, load_policy};
+use crate::prompt::{self, PromptState};
+use crate::provider::{account_provider, provider_for, self_hosted};
+use crate::render;
+use crate::rules;
+use crate::scan::{self, RepoStatus};
+use crate::secrets::{self, Secret};
+use crate::session;
+use crate::settings::{self, SwitchMode};
 use crate::ssh::{
-    add_ssh_key, delete_ssh_key_files, display_public_key, generate_ssh_key,
-    remove_ssh_config_entry, update_ssh_config,
+    KeyType, add_ssh_key, add_ssh_key_with_passphrase, agent_fingerprints, attestation_path,
+    challenge_path, change_key_passphrase, delete_ssh_key_files, derived_key_path,
+    display_public_key, ensure_agent, generate_ssh_key, get_ssh_config_path, host_alias,
+    identity_files, key_comment, key_fingerprint, key_is_encrypted, key_time_left, managed_blocks,
+    read_public_key, readd_ssh_key, remove_managed_block, remove_managed_blocks,
+    remove_ssh_config_entries, rename_ssh_key_files, renamed_key_path, replace_key_passphrase,
+    security_key_args, unload_key, unload_other_keys, unused_keys, update_ssh_config,
+};
+use crate::sync::{Prefer, SyncRepo, merge_accounts, sync_dir};
+use crate::undo;
+use crate::utils::{
+    Verbosity, command_output, format_duration, format_timestamp, is_interactive, pick,
+    prompt_line, prompt_password, read_stdin_line, run_command, set_non_interactive, set_verbosity,
+    today,
 };
-use crate::utils::run_command;
-use std::io::{self, Write};
+use crate::{problem, report, say};
+use clap::Command;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 
-pub fn add_account(name: &str, username: &str, email: &str) {
-    // Generate SSH key path based on account name
-    let ssh_key_path = format!("~/.ssh/id_rsa_{}", name.replace(' ', "_").to_lowercase());
+/// Optional settings for `add`, beyond the required name/username/email.
+#[derive(Debug, Default)]
+pub struct AddOptions {
+    pub credential_url: Option<String>,
+    pub lfs_url: Option<String>,
+    pub credential_cache_timeout: Option<u64>,
+    pub key_type: KeyType,
+    pub key_bits: Option<u32>,
+    pub workspace: Option<String>,
+    /// Git hosts for the account; the first is the primary host.
+    pub hosts: Vec<String>,
+    pub signing_key: Option<String>,
+    pub signing_format: Option<String>,
+    /// Upload the public key through the provider API after generating it.
+    pub upload: bool,
+    /// Passphrase for the new key; `None` leaves the key unencrypted.
+    pub passphrase: Option<String>,
+    /// Existing private key to use instead of generating one.
+    pub ssh_key: Option<String>,
+    /// Authenticate over HTTPS with a personal access token; no SSH key.
+    pub https: bool,
+    /// Personal access token of an HTTPS account; asked for when `None`.
+    pub token: Option<String>,
+    /// Extra options for the account's SSH config blocks.
+    pub ssh_options: BTreeMap<String, String>,
+    /// Extra git settings applied on `use`.
+    pub git_config: BTreeMap<String, String>,
+    pub sendemail: Option<SendEmail>,
+    /// Replace a saved account with the same name, and allow a username
+    /// another account already uses on the same host.
+    pub force: bool,
+    /// Set `exclusive_key` on the new account.
+    pub exclusive_key: bool,
+    /// Seconds the key stays in ssh-agent once loaded.
+    pub key_ttl: Option<u64>,
+    pub tags: Vec<String>,
+    /// Owners whose SSH URLs go through the account's alias.
+    pub orgs: Vec<String>,
+    /// Provider software of a self-hosted host (`github`, `gitlab`, `gitea`).
+    pub provider: Option<String>,
+    /// SSH server, port and user of the primary host, when they aren't the
+    /// host itself, 22 and the provider's user.
+    pub hostname: Option<String>,
+    pub port: Option<u16>,
+    pub ssh_user: Option<String>,
+    /// Jump host the primary host is reached through.
+    pub proxy_jump: Option<String>,
+    /// FIDO2 options for a security key (`resident`, `verify-required`).
+    pub key_options: Vec<String>,
+    /// Directory to generate the key in, instead of the `key_dir` setting.
+    pub key_dir: Option<String>,
+}
 
-    // Create parent directory if it doesn't exist
-    let expanded_key_path = shellexpand::tilde(&ssh_key_path).to_string();
-    if let Some(parent) = std::path::Path::new(&expanded_key_path).parent() {
-        if !parent.exists() {
-            std::fs::create_dir_all(parent).expect("Failed to create SSH directory");
-        }
+pub fn add_account(name: &str, username: &str, email: &str, mut options: AddOptions) -> Result<()> {
+    // A server given without a host is the host
+    if options.hosts.is_empty()
+        && let Some(hostname) = options.hostname.take()
+    {
+        options.hosts.push(hostname);
     }
-
-    // Generate SSH key automatically
-    generate_ssh_key(&ssh_key_path);
+    // Otherwise the `host` setting names it
+    let default_host = settings::load_settings().host().to_string();
+    if options.hosts.is_empty() && default_host != DEFAULT_HOST {
+        options.hosts.push(default_host);
+    }
+    let accounts = load_accounts();
+    let replacing = check_new_account(&accounts, name, username, email, &options)?.cloned();
+    if options.https {
+        return add_https_account(name, username, email, options, replacing.as_ref());
+    }
+    // Taken before the key exists, so a failed step can undo the whole add
+    let snapshot = Snapshot::take(&[
+        get_default_config_path(),
+        PathBuf::from(get_ssh_config_path()),
+        rules::rules_path(),
+    ]);
+    let external_key = options.ssh_key.is_some();
+    let (ssh_key_path, new_key) = match &options.ssh_key {
+        Some(key_path) => (register_existing_key(key_path)?, false),
+        None => generate_account_key(name, email, &options, replacing.as_ref())?,
+    };
 
     // Create and save account
-    let account = Account {
+    let mut account = Account {
         name: name.to_string(),
         username: username.to_string(),
         email: email.to_string(),
         ssh_key: ssh_key_path.clone(),
+        external_key,
+        exclusive_key: options.exclusive_key,
+        key_ttl: options.key_ttl,
+        key_options: options.key_options,
+        tags: options.tags,
+        orgs: options.orgs,
+        provider: options.provider.clone(),
+        hostname: options.hostname,
+        port: options.port,
+        ssh_user: options.ssh_user,
+        proxy_jump: options.proxy_jump,
+        created: Some(today()),
+        credential_url: options.credential_url,
+        lfs_url: options.lfs_url,
+        credential_cache_timeout: options.credential_cache_timeout,
+        workspace: options.workspace,
+        signing_format: options.signing_format.clone(),
+        ssh_options: options.ssh_options,
+        git_config: options.git_config,
+        sendemail: options.sendemail,
+        // SSH signing defaults to the account's own key
+        signing_key: options.signing_key.or_else(|| {
+            (options.signing_format.as_deref() == Some("ssh"))
+                .then(|| format!("{}.pub", ssh_key_path))
+        }),
+        ..Default::default()
     };
+    let attestation = attestation_path(&ssh_key_path);
+    if !external_key
+        && options.key_type.is_hardware_backed()
+        && Path::new(&*shellexpand::tilde(&attestation)).exists()
+    {
+        say!("📜 Recorded key attestation: {}", attestation);
+        account.attestation = Some(attestation);
+    }
+    if let Some((primary, extra)) = options.hosts.split_first() {
+        account.host = primary.clone();
+        account.extra_hosts = extra.to_vec();
+    }
 
-    save_account(&account);
-
-    if let Err(e) = update_ssh_config(name, &ssh_key_path) {
-        eprintln!("❌ Failed to update SSH config: {}", e);
+    let saved = store_new_account(&mut account, replacing.as_ref())
+        .and_then(|()| update_ssh_config(&account).context("Failed to update the SSH config"));
+    if let Err(e) = saved {
+        snapshot.restore();
+        if new_key {
+            roll_back_new_key(&account);
+        }
+        problem!("↩️ Rolled back adding account '{}'.", account.name);
+        return Err(e);
     }
+    say!("✅ Account '{}' saved.", account.name);
+    history::record("add", &account.name, None, Some(identity_label(&account)));
 
     // Display the public key for the user to copy
-    println!("✅ Account '{}' added successfully!", name);
-    println!("\n🔑 Here is your public SSH key to add to GitHub:");
+    say!("✅ Account '{}' added successfully!", name);
+    say!("\n🔑 Here is your public SSH key to add to your Git host:");
     println!("--------------------------------------------------");
     display_public_key(&ssh_key_path);
     println!("--------------------------------------------------");
-    println!(
-        "Copy this key and add it to your GitHub account at: https://github.com/settings/keys"
+    let uploaded = options.upload
+        && upload_key_for(&account)
+            .inspect_err(|e| problem!("❌ {}", e))
+            .is_ok();
+    if !uploaded {
+        for host in account.hosts() {
+            match account_provider(&account, host) {
+                Some(provider) => say!(
+                    "Copy this key and add it to your {} account at: {}",
+                    provider.name,
+                    provider.key_settings_url
+                ),
+                None => say!(
+                    "Copy this key and add it to your account's SSH keys on {}",
+                    host
+                ),
+            }
+        }
+    }
+    say!(
+        "Then run `git-switch verify {}` to check that the key signs in as {}.",
+        account.name,
+        account.username
     );
+    Ok(())
 }
 
-pub fn use_account(name_or_username: &str) {
-    let accounts = load_accounts();
+/// Adds an account that reaches its hosts over HTTPS, keeping its personal
+/// access token in the OS keyring for `git-switch credential`.
+fn add_https_account(
+    name: &str,
+    username: &str,
+    email: &str,
+    options: AddOptions,
+    replacing: Option<&Account>,
+) -> Result<()> {
+    if options.ssh_key.is_some() || options.passphrase.is_some() || options.upload {
+        return Err(GitSwitchError::InvalidInput(
+            "https accounts have no SSH key; drop --ssh-key, --passphrase and --upload."
+                .to_string(),
+        ));
+    }
+    let mut account = Account {
+        name: name.to_string(),
+        username: username.to_string(),
+        email: email.to_string(),
+        auth: Some("https".to_string()),
+        created: Some(today()),
+        credential_url: options.credential_url,
+        lfs_url: options.lfs_url,
+        credential_cache_timeout: options.credential_cache_timeout,
+        workspace: options.workspace,
+        signing_key: options.signing_key,
+        signing_format: options.signing_format,
+        git_config: options.git_config,
+        sendemail: options.sendemail,
+        tags: options.tags,
+        provider: options.provider,
+        ..Default::default()
+    };
+    if let Some((primary, extra)) = options.hosts.split_first() {
+        account.host = primary.clone();
+        account.extra_hosts = extra.to_vec();
+    }
+    let token = options
+        .token
+        .or_else(|| {
+            (secrets::AVAILABLE && is_interactive())
+                .then(|| {
+                    prompt_password(&format!(
+                        "Personal access token for {} (empty to add it later): ",
+                        account.host
+                    ))
+                })
+                .flatten()
+        })
+        .filter(|token| !token.is_empty());
 
-    // Try to find account by name first, then by username
-    let account = accounts
+    store_new_account(&mut account, replacing)?;
+    say!("✅ Account '{}' added (HTTPS).", account.name);
+    history::record("add", &account.name, None, Some(identity_label(&account)));
+    let stored = match token {
+        Some(token) => match secrets::set(&account.name, Secret::Token(&account.host), &token) {
+            Ok(()) => {
+                say!("🔐 Saved the token in the OS keyring.");
+                true
+            }
+            Err(e) => {
+                problem!("❌ Failed to save the token: {}", e);
+                false
+            }
+        },
+        None => false,
+    };
+    if !stored {
+        say!(
+            "Store a personal access token with `git-switch secret set {} token`; until then git asks for a password.",
+            account.name
+        );
+    }
+    say!(
+        "Then run `git-switch use {}`; git gets the token from git-switch for https://{}.",
+        account.name,
+        account.host
+    );
+    Ok(())
+}
+
+/// Checks `add`'s arguments against the saved accounts: a usable name, a
+/// plausible email, and no account with the same name, or the same username
+/// on one of the same hosts, unless `--force` is given. Returns the account
+/// with the same name, which the new one replaces.
+fn check_new_account<'a>(
+    accounts: &'a [Account],
+    name: &str,
+    username: &str,
+    email: &str,
+    options: &AddOptions,
+) -> Result<Option<&'a Account>> {
+    check_account_name(name).map_err(GitSwitchError::InvalidInput)?;
+    if username.trim().is_empty() {
+        return Err(GitSwitchError::InvalidInput(
+            "The username must not be empty.".to_string(),
+        ));
+    }
+    if !is_valid_email(email) {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "'{}' is not an email address.",
+            email
+        )));
+    }
+    if !options.key_options.is_empty() && !options.key_type.is_hardware_backed() {
+        return Err(GitSwitchError::InvalidInput(
+            "--resident and --verify-required need --key-type ed25519-sk or ecdsa-sk.".to_string(),
+        ));
+    }
+    let same_name = accounts
         .iter()
-        .find(|acc| acc.name == name_or_username || acc.username == name_or_username)
-        .cloned();
-
-    match account {
-        Some(acc) => {
-            // Set Git global config
-            run_command("git", &["config", "--global", "user.name", &acc.username]);
-            run_command("git", &["config", "--global", "user.email", &acc.email]);
-
-            // Start ssh-agent if not already running
-            // Note: ssh-agent -s might output shell commands to be eval'd.
-            // For a robust solution, consider parsing its output or using a library.
-            // For now, we assume it sets up the agent if not running.
-            println!("🔄 Ensuring SSH agent is running...");
-            if !cfg!(windows) {
-                // `ssh-agent -s` is typical for Unix-like systems
-                let output = std::process::Command::new("ssh-agent").arg("-s").output();
-                if let Ok(out) = output {
-                    if !out.status.success() {
-                        eprintln!(
-                            "⚠️ Failed to start ssh-agent. SSH key might not be added automatically."
-                        );
-                        eprintln!("Error: {}", String::from_utf8_lossy(&out.stderr));
-                    } else {
-                        // On Unix, `ssh-agent -s` prints shell commands to set env vars.
-                        // For this tool to affect the parent shell, the user would typically run:
-                        // eval $(git-switch use <account>)
-                        // or source the output. Directly running `ssh-agent -s` in a subprocess
-                        // doesn't set environment variables for the parent shell of git-switch.
-                        // This is a common challenge for tools managing ssh-agent.
-                        // For simplicity, we'll proceed, but ssh-add might fail if agent isn't truly ready.
-                        println!(
-                            "ℹ️ ssh-agent command executed. You might need to run `eval $(ssh-agent -s)` in your shell if keys are not added."
-                        );
+        .find(|acc| acc.name.eq_ignore_ascii_case(name));
+    let hosts: Vec<&str> = if options.hosts.is_empty() {
+        vec![DEFAULT_HOST]
+    } else {
+        options.hosts.iter().map(String::as_str).collect()
+    };
+    let same_username = accounts.iter().find(|acc| {
+        same_name.is_none_or(|replaced| replaced.name != acc.name)
+            && acc.username.eq_ignore_ascii_case(username)
+            && acc.hosts().iter().any(|host| hosts.contains(host))
+    });
+    if !options.force {
+        if let Some(existing) = same_name {
+            return Err(GitSwitchError::InvalidInput(format!(
+                "An account named '{}' already exists; pass --force to replace it.",
+                existing.name
+            )));
+        }
+        if let Some(existing) = same_username {
+            return Err(GitSwitchError::InvalidInput(format!(
+                "Account '{}' already uses the username '{}' on {}; pass --force to add another account for it.",
+                existing.name, existing.username, existing.host
+            )));
+        }
+    }
+    Ok(same_name)
+}
+
+/// Saves a new account, in place of the account `add --force` replaces. The
+/// replaced account's default flag carries over.
+fn store_new_account(account: &mut Account, replacing: Option<&Account>) -> Result<()> {
+    let Some(old) = replacing else {
+        return save_account(account).context(format!("Failed to save account '{}'", account.name));
+    };
+    account.default = old.default;
+    replace_account(&old.name, account)
+        .context(format!("Failed to replace account '{}'", old.name))?;
+    if old.name != account.name {
+        if let Err(e) = remove_ssh_config_entries(old) {
+            problem!(
+                "⚠️ Could not remove the SSH config entries of '{}': {}",
+                old.name,
+                e
+            );
+        }
+        if let Err(e) = rules::rename_account_rules(&old.name, Some(&account.name)) {
+            problem!("⚠️ Could not update the rules for '{}': {}", old.name, e);
+        }
+    }
+    say!("♻️ Replaced the saved account '{}'.", old.name);
+    Ok(())
+}
+
+/// `add` without arguments: asks for the account's details one at a time,
+/// offers keys in `~/.ssh` no account uses yet, then adds the account like
+/// `add` would with the answers as arguments. `options` holds any flags
+/// given alongside.
+pub fn add_account_wizard(mut options: AddOptions) -> Result<()> {
+    if !is_interactive() {
+        return Err(GitSwitchError::InvalidInput(
+            "`add` needs NAME USERNAME EMAIL, or a terminal for the guided setup.".to_string(),
+        ));
+    }
+    let cancelled = || GitSwitchError::Cancelled("Nothing was added.".to_string());
+    let accounts = load_accounts();
+    say!("➕ Adding a Git account. Press Ctrl-D to stop at any point.\n");
+
+    let name = loop {
+        let name = prompt_line("Account name (e.g. Work, Personal): ").ok_or_else(cancelled)?;
+        if name.is_empty() {
+            continue;
+        }
+        if let Err(e) = check_account_name(&name) {
+            say!("❌ {}", e);
+            continue;
+        }
+        if !options.force
+            && accounts
+                .iter()
+                .any(|acc| acc.name.eq_ignore_ascii_case(&name))
+        {
+            say!("❌ An account named '{}' already exists.", name);
+            continue;
+        }
+        break name;
+    };
+    let username = loop {
+        let username = prompt_line("Git host username: ").ok_or_else(cancelled)?;
+        if !username.is_empty() {
+            break username;
+        }
+    };
+    let email = loop {
+        let email = prompt_line("Commit email: ").ok_or_else(cancelled)?;
+        if is_valid_email(&email) {
+            break email;
+        }
+        say!("❌ That doesn't look like an email address.");
+    };
+    if options.hosts.is_empty() {
+        let default_host = settings::load_settings().host().to_string();
+        let host = prompt_line(&format!("Git host [{}]: ", default_host)).ok_or_else(cancelled)?;
+        options.hosts.push(
+            Some(host)
+                .filter(|host| !host.is_empty())
+                .unwrap_or(default_host),
+        );
+    }
+
+    if options.ssh_key.is_none() && !options.https {
+        let ssh_dir = home_dir().unwrap_or_default().join(".ssh");
+        let reusable = unused_keys(&ssh_dir, &accounts);
+        let mut items: Vec<String> = KeyType::NAMES
+            .iter()
+            .map(|name| {
+                let key_type = KeyType::parse(name).unwrap_or_default();
+                let note = match key_type {
+                    KeyType::Ed25519 => " (recommended)",
+                    KeyType::Rsa => " (for hosts without ed25519 support)",
+                    _ if key_type.is_hardware_backed() => " (on a security key)",
+                    _ => "",
+                };
+                format!("Generate a new {} key{}", name, note)
+            })
+            .collect();
+        items.extend(
+            reusable
+                .iter()
+                .map(|key| format!("Reuse {}", key.display())),
+        );
+        let https_choice = items.len();
+        items.push("Use HTTPS with a personal access token instead of SSH".to_string());
+        let default = KeyType::NAMES
+            .iter()
+            .position(|name| *name == options.key_type.as_str())
+            .unwrap_or(0);
+        let choice = pick("SSH key", &items, default).ok_or_else(cancelled)?;
+        match reusable.get(choice.wrapping_sub(KeyType::NAMES.len())) {
+            _ if choice == https_choice => options.https = true,
+            Some(key) => {
+                let file_name = key.file_name().unwrap_or_default().to_string_lossy();
+                options.ssh_key = Some(format!("~/.ssh/{}", file_name));
+            }
+            None => {
+                options.key_type = KeyType::parse(KeyType::NAMES[choice]).unwrap_or_default();
+                if options.passphrase.is_none() && !options.key_type.is_hardware_backed() {
+                    let answer = prompt_line("Protect the new key with a passphrase? [y/N]: ")
+                        .unwrap_or_default();
+                    if answer.eq_ignore_ascii_case("y") {
+                        options.passphrase = Some(read_new_passphrase(false, "the new key")?);
                     }
-                } else {
-                    eprintln!(
-                        "⚠️ Failed to execute ssh-agent. SSH key might not be added automatically."
-                    );
                 }
             }
+        }
+    }
 
-            // Add SSH key to agent
-            if add_ssh_key(&acc.ssh_key) {
-                println!(
-                    "✅ Switched to Git account: {} ({})",
-                    acc.name, acc.username
-                );
+    let host = options.hosts.first().map_or(DEFAULT_HOST, String::as_str);
+    if cfg!(feature = "provider-api")
+        && !options.upload
+        && !options.https
+        && let Some(provider) = provider_for(host)
+            .or_else(|| self_hosted(options.provider.as_deref()?, host))
+            .filter(|provider| provider.key_api.is_some())
+    {
+        let answer = prompt_line(&format!(
+            "Upload the public key to {} now (needs an API token)? [y/N]: ",
+            provider.name
+        ))
+        .unwrap_or_default();
+        options.upload = answer.eq_ignore_ascii_case("y");
+    }
+
+    println!();
+    add_account(&name, &username, &email, options)?;
+    say!("\n👉 Next steps:");
+    say!("   Switch to it:                  git-switch use {}", name);
+    say!(
+        "   Use it for a directory:        git-switch map <dir> {}",
+        name
+    );
+    say!(
+        "   Use it for matching remotes:   git-switch rule add <pattern> {}",
+        name
+    );
+    Ok(())
+}
+
+/// Generates the key of a new account at the path the `key_dir` and
+/// `key_name` settings derive from its name, key type and host. Returns the path, and whether the key is new rather than
+/// one already there.
+fn generate_account_key(
+    name: &str,
+    email: &str,
+    options: &AddOptions,
+    replacing: Option<&Account>,
+) -> Result<(String, bool)> {
+    let key_bits = options
+        .key_type
+        .resolve_bits(options.key_bits)
+        .map_err(GitSwitchError::InvalidInput)?;
+
+    let settings = settings::load_settings();
+    let derived_key_path = derived_key_path(
+        &settings,
+        options.key_dir.as_deref(),
+        name,
+        options.key_type,
+        options.hosts.first().map_or(DEFAULT_HOST, String::as_str),
+    );
+    // The account `--force` replaces hands its key over
+    let others: Vec<Account> = load_accounts()
+        .into_iter()
+        .filter(|acc| replacing.is_none_or(|old| old.name != acc.name))
+        .collect();
+    let ssh_key_path = replacing
+        .map(|old| old.ssh_key.clone())
+        .filter(|key| shellexpand::tilde(key) == shellexpand::tilde(&derived_key_path))
+        .or_else(|| resolve_key_path_conflict(&derived_key_path, &others))
+        .ok_or_else(|| GitSwitchError::Cancelled(format!("Aborted adding account '{}'.", name)))?;
+
+    // Create parent directory if it doesn't exist
+    let expanded_key_path = shellexpand::tilde(&ssh_key_path).to_string();
+    if let Some(parent) = std::path::Path::new(&expanded_key_path).parent()
+        && !parent.exists()
+    {
+        std::fs::create_dir_all(parent)
+            .context(format!("Failed to create {}", parent.display()))?;
+    }
 
-                // Ask if user wants to update current repo's remote URL
-                print!("Do you want to update remote URL for the current repository? (y/n): ");
-                io::stdout().flush().unwrap();
-                let mut response = String::new();
-                io::stdin().read_line(&mut response).unwrap();
-
-                if response.trim().to_lowercase() == "y" {
-                    print!("Enter repository name (e.g., 'username/repo' or just 'repo'): ");
-                    io::stdout().flush().unwrap();
-                    let mut repo = String::new();
-                    io::stdin().read_line(&mut repo).unwrap();
-                    // Clippy fix: needless_borrow
-                    update_git_remote(&acc.username, repo.trim());
+    // Generate SSH key automatically
+    let passphrase = options.passphrase.as_deref();
+    let sk_options = security_key_args(name, &options.key_options);
+    let created = generate_ssh_key(
+        &ssh_key_path,
+        options.key_type,
+        key_bits,
+        passphrase,
+        &sk_options,
+        &key_comment(&settings, name, email),
+    )
+    .context(format!("Failed to generate SSH key {}", ssh_key_path))?;
+    match created {
+        false => {
+            say!("✅ SSH key already exists: {}", ssh_key_path);
+            if passphrase.is_some() {
+                say!("ℹ️ The passphrase was not used; the existing key keeps its own.");
+            }
+        }
+        true => {
+            // Load the key now so the passphrase isn't asked for again
+            if let Some(passphrase) = passphrase {
+                if let Err(e) =
+                    add_ssh_key_with_passphrase(&ssh_key_path, passphrase, options.key_ttl)
+                {
+                    problem!("⚠️ Could not add the new key to ssh-agent: {}", e);
+                }
+                if secrets::AVAILABLE {
+                    match secrets::set(name, Secret::Passphrase, passphrase) {
+                        Ok(()) => say!("🔐 Saved the key's passphrase in the OS keyring."),
+                        Err(e) => problem!("⚠️ Could not save the passphrase: {}", e),
+                    }
                 }
-            } else {
-                eprintln!(
-                    "❌ Failed to add SSH key to agent. Ensure ssh-agent is running and configured."
-                );
             }
         }
-        None => {
-            println!(
-                "❌ Account with name or username '{}' not found.",
-                name_or_username
-            );
+    }
+    Ok((ssh_key_path, created))
+}
 
-            // List available accounts to help the user
-            if !accounts.is_empty() {
-                println!("\nAvailable accounts:");
-                println!("----------------------------------------");
-                println!("Account Name | Git Username | Email");
-                println!("----------------------------------------");
-                for acc in &accounts {
-                    println!("{} | {} | {}", acc.name, acc.username, acc.email);
-                }
-                println!("----------------------------------------");
+/// Checks a key given with `add --ssh-key`, writing its `.pub` file if it's
+/// missing. Returns the path to store: `~/...` as given, otherwise absolute.
+fn register_existing_key(key_path: &str) -> Result<String> {
+    let expanded = shellexpand::tilde(key_path).into_owned();
+    if expanded.ends_with(".pub") {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "{} is a public key; pass the private key next to it.",
+            key_path
+        )));
+    }
+    if !Path::new(&expanded).is_file() {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "SSH key not found: {}",
+            expanded
+        )));
+    }
+    let stored = if key_path.starts_with('~') {
+        key_path.to_string()
+    } else {
+        std::path::absolute(&expanded)
+            .context(format!("Failed to resolve {}", expanded))?
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    let public_key_path = format!("{}.pub", expanded);
+    if !Path::new(&public_key_path).exists() {
+        // Asks for the passphrase of an encrypted key
+        say!("🔑 Extracting the public key to {}", public_key_path);
+        let output = run_command("ssh-keygen", &["-y", "-f", &expanded])?;
+        std::fs::write(&public_key_path, output.stdout)
+            .context(format!("Failed to write {}", public_key_path))?;
+    }
+    if key_fingerprint(&stored).is_none() {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "{} is not a valid SSH public key.",
+            public_key_path
+        )));
+    }
+    if let Some(owner) = load_accounts()
+        .iter()
+        .find(|acc| shellexpand::tilde(&acc.ssh_key) == expanded)
+    {
+        say!(
+            "⚠️ SSH key {} is also used by account '{}'; hosts tell accounts apart by their keys.",
+            key_path,
+            owner.name
+        );
+    }
+    say!("✅ Using existing SSH key: {}", stored);
+    Ok(stored)
+}
+
+/// Reads a passphrase that will protect something new (`what`, e.g. "the
+/// new key"): the first line of stdin with `from_stdin`, otherwise a hidden
+/// prompt that asks twice.
+pub fn read_new_passphrase(from_stdin: bool, what: &str) -> Result<String> {
+    let passphrase = read_passphrase(from_stdin, &format!("Passphrase for {}: ", what))?;
+    if !from_stdin {
+        let again = prompt_password("Enter the same passphrase again: ").unwrap_or_default();
+        if passphrase != again {
+            return Err(GitSwitchError::InvalidInput(
+                "Passphrases do not match.".to_string(),
+            ));
+        }
+    }
+    Ok(passphrase)
+}
+
+/// Reads a passphrase from the first line of stdin with `from_stdin`,
+/// otherwise from a hidden prompt.
+fn read_passphrase(from_stdin: bool, prompt: &str) -> Result<String> {
+    let passphrase = if from_stdin {
+        read_stdin_line().context("Failed to read the passphrase from stdin")?
+    } else {
+        prompt_password(prompt).ok_or_else(|| {
+            GitSwitchError::InvalidInput(
+                "Reading a passphrase needs a terminal; use --passphrase-stdin in scripts."
+                    .to_string(),
+            )
+        })?
+    };
+    if passphrase.is_empty() {
+        return Err(GitSwitchError::InvalidInput(
+            "The passphrase is empty.".to_string(),
+        ));
+    }
+    Ok(passphrase)
+}
+
+/// Decides which key path `add` should use when the derived path already exists.
+///
+/// A key that is not registered to any managed account is most likely the
+/// user's own key (possibly registered with a different provider account), so
+/// instead of silently reusing it the user chooses to adopt it, generate a new
+/// key under a different name, or abort. Returns `None` on abort.
+fn resolve_key_path_conflict(key_path: &str, accounts: &[Account]) -> Option<String> {
+    let exists = |path: &str| std::path::Path::new(&*shellexpand::tilde(path)).exists();
+    match key_path_conflict(key_path, accounts, exists) {
+        KeyPathConflict::Free => Some(key_path.to_string()),
+        KeyPathConflict::UsedBy(owner) => {
+            say!(
+                "⚠️ SSH key {} is already used by account '{}'.",
+                key_path,
+                owner
+            );
+            prompt_alternative_key_path(key_path, exists)
+        }
+        KeyPathConflict::Unmanaged => {
+            say!(
+                "⚠️ SSH key {} already exists but is not managed by git-switch.",
+                key_path
+            );
+            println!("It may already be registered to a different account on your Git host.");
+            println!("  [a] Adopt the existing key for this account");
+            println!("  [g] Generate a new key under a different name");
+            println!("  [q] Abort");
+            match key_path_choice(prompt_line("Choose an option (a/g/q): ").as_deref()) {
+                KeyPathChoice::Adopt => Some(key_path.to_string()),
+                KeyPathChoice::Separate => prompt_alternative_key_path(key_path, exists),
+                KeyPathChoice::Abort => None,
             }
         }
     }
 }
 
-pub fn remove_account(name: &str) {
+/// Whether a new account's key path is taken, and by what.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum KeyPathConflict {
+    Free,
+    /// The key of the named saved account.
+    UsedBy(String),
+    /// A key no saved account uses.
+    Unmanaged,
+}
+
+pub(crate) fn key_path_conflict(
+    key_path: &str,
+    accounts: &[Account],
+    exists: impl Fn(&str) -> bool,
+) -> KeyPathConflict {
+    if !exists(key_path) {
+        return KeyPathConflict::Free;
+    }
+    let expanded = shellexpand::tilde(key_path);
+    match accounts
+        .iter()
+        .find(|acc| shellexpand::tilde(&acc.ssh_key) == expanded)
+    {
+        Some(owner) => KeyPathConflict::UsedBy(owner.name.clone()),
+        None => KeyPathConflict::Unmanaged,
+    }
+}
+
+/// The answer to the unmanaged-key question.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum KeyPathChoice {
+    /// Use the existing key for the account.
+    Adopt,
+    /// Generate a new key under a different name.
+    Separate,
+    Abort,
+}
+
+/// Reads the answer; anything but `a` or `g`, and a closed or
+/// non-interactive stdin (`None`), aborts.
+pub(crate) fn key_path_choice(answer: Option<&str>) -> KeyPathChoice {
+    match answer.map(str::to_lowercase).as_deref() {
+        Some("a") => KeyPathChoice::Adopt,
+        Some("g") => KeyPathChoice::Separate,
+        _ => KeyPathChoice::Abort,
+    }
+}
+
+/// Asks for a new key path, suggesting the first free `<path>_N` variant.
+fn prompt_alternative_key_path(key_path: &str, exists: impl Fn(&str) -> bool) -> Option<String> {
+    let suggestion = (2..)
+        .map(|n| format!("{}_{}", key_path, n))
+        .find(|candidate| !exists(candidate))?;
+    let answer = prompt_line(&format!("New key path [{}]: ", suggestion));
+    let chosen = alternative_key_path(&suggestion, answer.as_deref(), &exists);
+    if chosen.is_none() && answer.is_some() {
+        say!("❌ {} already exists as well.", answer.unwrap_or_default());
+    }
+    chosen
+}
+
+/// The path the answer to "New key path" picks: the suggestion when empty,
+/// nothing when stdin is closed (`None`) or the path is taken too.
+pub(crate) fn alternative_key_path(
+    suggestion: &str,
+    answer: Option<&str>,
+    exists: impl Fn(&str) -> bool,
+) -> Option<String> {
+    let chosen = match answer? {
+        "" => suggestion,
+        answer => answer,
+    };
+    (!exists(chosen)).then(|| chosen.to_string())
+}
+
+/// What `use` does about the current repository's remote.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum RemoteAction {
+    /// Ask on stdin (the default for interactive use).
+    #[default]
+    Prompt,
+    /// Leave the remote alone.
+    Skip,
+    /// Point `origin` at this repository (`owner/repo` or `repo`).
+    Update(String),
+}
+
+/// Options for `use`.
+#[derive(Debug, Default)]
+pub struct UseOptions {
+    /// `None` picks local inside a repository and global elsewhere.
+    pub scope: Option<ConfigScope>,
+    /// Confirms policy rules that require acknowledgement.
+    pub acknowledge: bool,
+    pub remote: RemoteAction,
+    /// Switch only the current shell through its session file.
+    pub session: bool,
+    /// Write remote URLs with the plain host instead of the account's SSH
+    /// host alias.
+    pub plain_remote: bool,
+    /// Move every remote on the account's hosts to the account.
+    pub rewrite_remotes: bool,
+    /// Print shell exports for the agent and key on stdout, for `eval`.
+    pub print_env: bool,
+    /// Remove the other accounts' keys from ssh-agent, as if the account
+    /// had `exclusive_key` set.
+    pub exclusive: bool,
+    /// How git uses the account's key; `None` takes the `mode` setting.
+    pub mode: Option<SwitchMode>,
+}
+
+/// Switches to an account: sets its identity in the git config, makes git
+/// use its key (through the SSH host alias or `core.sshCommand`, per
+/// `options.mode` or the `mode` setting) and loads the key into ssh-agent.
+///
+/// `options.scope` is `None` to pick local inside a repository and global
+/// elsewhere; a global switch also sends the account's orgs through its
+/// alias. `options.remote` says whether to point `origin` at the account,
+/// and `options.session` switches only the current shell instead of the
+/// git config. Fails before changing anything if a policy rule or the
+/// repository's manifest rejects the account.
+pub fn use_account(name_or_username: &str, options: UseOptions) -> Result<()> {
+    let scope = options.scope;
     let accounts = load_accounts();
-    let account_to_delete = accounts.iter().find(|acc| acc.name == name);
 
-    match account_to_delete {
-        Some(account) => {
-            // 1. Remove from config.rs
-            if let Err(e) = delete_account(name) {
-                eprintln!("❌ Failed to remove account from config: {}", e);
-                // Optionally, decide if you want to proceed with SSH key deletion if config deletion fails
+    // Try to find account by name first, then by username
+    let Some(acc) = accounts
+        .iter()
+        .find(|acc| acc.name == name_or_username || acc.username == name_or_username)
+        .cloned()
+    else {
+        // List available accounts to help the user
+        if !accounts.is_empty() {
+            println!("Available accounts:");
+            println!("----------------------------------------");
+            println!("Account Name | Git Username | Email");
+            println!("----------------------------------------");
+            for acc in &accounts {
+                println!("{} | {} | {}", acc.name, acc.username, acc.email);
             }
+            println!("----------------------------------------");
+        }
+        return Err(GitSwitchError::AccountNotFound(
+            name_or_username.to_string(),
+        ));
+    };
+
+    check_policy(&acc, options.acknowledge)?;
+    if !manifest_allows_use(&acc, &accounts) {
+        return Err(GitSwitchError::Cancelled(format!(
+            "Kept the current account; '{}' doesn't comply with this repository's manifest.",
+            acc.name
+        )));
+    }
+    if options.session {
+        return start_shell_session(&acc);
+    }
 
-            // 2. Remove SSH config entry
-            if let Err(e) = remove_ssh_config_entry(name) {
-                eprintln!("❌ Failed to remove SSH config entry: {}", e);
+    // Set the identity in the repository or global git config
+    let scope = scope.unwrap_or_else(ConfigScope::default_for_cwd);
+    apply_identity(&acc, scope).context("Failed to apply the account's identity")?;
+    let mode = options
+        .mode
+        .unwrap_or_else(|| settings::load_settings().mode);
+    if mode == SwitchMode::SshCommand && !acc.uses_https() {
+        apply_ssh_command(&acc, scope).context("Failed to set core.sshCommand")?;
+        say!("🔑 core.sshCommand now uses {}", acc.ssh_key);
+    } else {
+        clear_ssh_command(&accounts, scope).context("Failed to unset core.sshCommand")?;
+    }
+    if render_account_fragment(&acc).is_some() {
+        say!(
+            "✅ Applied account settings from {}",
+            account_fragment_path(&acc.name).display()
+        );
+    }
+    if scope == ConfigScope::Global {
+        if mode == SwitchMode::Alias {
+            apply_org_url_rewrites(&acc, &accounts)
+                .context("Failed to set url.*.insteadOf rules")?;
+            for host in acc.hosts().into_iter().filter(|_| !acc.uses_https()) {
+                for org in &acc.orgs {
+                    say!(
+                        "🔀 {}:{}/ repositories now go through {}",
+                        host,
+                        org,
+                        host_alias(host, &acc.name)
+                    );
+                }
             }
+        } else {
+            clear_org_url_rewrites(&accounts).context("Failed to unset url.*.insteadOf rules")?;
+        }
+    }
+
+    let agent = if acc.uses_https() {
+        say!(
+            "🔐 HTTPS account: git gets the token for {} from git-switch.",
+            acc.hosts().join(", ")
+        );
+        Vec::new()
+    } else {
+        start_agent_and_load_key(&acc, options.exclusive, options.print_env)?
+    };
+    say!(
+        "✅ Switched to Git account: {} ({})",
+        acc.name,
+        acc.username
+    );
+    let repo_root = repository_root();
+    history::record(
+        "use",
+        &acc.name,
+        repo_root.clone(),
+        Some(scope.flag().trim_start_matches('-').to_string()),
+    );
+    if scope == ConfigScope::Local {
+        say!("ℹ️ Identity set for this repository only; use --global to switch everywhere.");
+    }
 
-            // 3. Delete SSH key files
-            if let Err(e) = delete_ssh_key_files(&account.ssh_key) {
-                eprintln!("❌ Failed to delete SSH key files: {}", e);
+    // Update the current repo's remote URL if requested, or ask
+    let repo = match options.remote {
+        RemoteAction::Skip => None,
+        RemoteAction::Update(repo) => Some(repo),
+        RemoteAction::Prompt => {
+            let response =
+                prompt_line("Do you want to update remote URL for the current repository? (y/n): ")
+                    .unwrap_or_default();
+            if response.eq_ignore_ascii_case("y") {
+                prompt_line("Enter repository name (e.g., 'username/repo' or just 'repo'): ")
+            } else {
+                None
+            }
+        }
+    };
+    let via_alias = !options.plain_remote && mode == SwitchMode::Alias;
+    if let Some(repo) = repo.filter(|repo| !repo.is_empty()) {
+        let url = update_git_remote(&acc, &accounts, &repo, via_alias)?;
+        history::record(
+            "remote",
+            &acc.name,
+            repo_root.clone(),
+            Some(format!("origin → {}", url)),
+        );
+        if !options.rewrite_remotes {
+            for rewrite in offer_url_rewrites(&acc, &accounts, via_alias)? {
+                record_rewrite(&acc, &rewrite);
             }
+        }
+    }
+    if options.rewrite_remotes {
+        let rewritten = rewrite_remotes(&acc, &accounts, via_alias)?;
+        if rewritten.is_empty() {
+            say!(
+                "ℹ️ No remotes or submodules on {} needed rewriting.",
+                acc.hosts().join(", ")
+            );
+        }
+        for rewrite in rewritten {
+            say!(
+                "✅ {} now uses {}",
+                rewrite.describe(Path::new(".")),
+                rewrite.new_url
+            );
+            record_rewrite(&acc, &rewrite);
+        }
+    }
+    if options.print_env {
+        print_agent_env(&acc, agent);
+    }
+    Ok(())
+}
 
-            println!(
-                "✅ Account '{}' and its associated SSH configurations and keys have been removed.",
-                name
+/// `username <email>`, for history entries.
+fn identity_label(acc: &Account) -> String {
+    format!("{} <{}>", acc.username, acc.email)
+}
+
+fn record_rewrite(acc: &Account, rewrite: &UrlRewrite) {
+    history::record(
+        "remote",
+        &acc.name,
+        Some(rewrite.repo.display().to_string()),
+        Some(format!(
+            "{} → {}",
+            rewrite.describe(&rewrite.repo),
+            rewrite.new_url
+        )),
+    );
+}
+
+/// After `origin` was pointed at an account, asks whether the repository's
+/// other remotes and its submodules on the account's hosts should follow,
+/// so the whole working tree authenticates the same way. Returns the
+/// rewrites made.
+fn offer_url_rewrites(
+    acc: &Account,
+    accounts: &[Account],
+    via_alias: bool,
+) -> Result<Vec<UrlRewrite>> {
+    if !is_interactive() {
+        return Ok(Vec::new());
+    }
+    let here = Path::new(".");
+    let rewrites = plan_url_rewrites(here, acc, accounts, via_alias);
+    if rewrites.is_empty() {
+        return Ok(Vec::new());
+    }
+    println!("\nThese also point at {}:", acc.hosts().join(", "));
+    for rewrite in &rewrites {
+        println!("   {}: {}", rewrite.describe(here), rewrite.old_url);
+    }
+    let answer =
+        prompt_line(&format!("Point them at '{}' too? [y/N]: ", acc.name)).unwrap_or_default();
+    if !answer.eq_ignore_ascii_case("y") {
+        return Ok(Vec::new());
+    }
+    for rewrite in &rewrites {
+        rewrite.apply()?;
+        say!("✅ {} now uses {}", rewrite.describe(here), rewrite.new_url);
+    }
+    Ok(rewrites)
+}
+
+/// Loads the account's key into a reachable agent, starting one if needed,
+/// and returns the agent's variables. A started agent lives on after
+/// git-switch exits, but the shell only finds it through those variables;
+/// unless they are printed for `eval`, says how to get them. With
+/// `exclusive` or the account's `exclusive_key`, the other accounts' keys
+/// are removed from the agent first.
+fn start_agent_and_load_key(
+    acc: &Account,
+    exclusive: bool,
+    printing_env: bool,
+) -> Result<Vec<(String, String)>> {
+    say!("🔄 Ensuring SSH agent is running...");
+    let socket = std::env::var_os("SSH_AUTH_SOCK");
+    let agent = match ensure_agent() {
+        Ok(agent) => agent,
+        Err(e) => {
+            problem!(
+                "⚠️ No SSH agent; the key won't be added automatically: {}",
+                e
             );
+            Vec::new()
         }
-        None => {
-            println!("❌ Account with name '{}' not found.", name);
+    };
+    if exclusive || acc.exclusive_key {
+        let unloaded = unload_other_keys(acc, &load_accounts());
+        if !unloaded.is_empty() {
+            say!(
+                "🔒 Removed the keys of {} from ssh-agent, so only {}'s key is offered.",
+                unloaded.join(", "),
+                acc.name
+            );
+        }
+    }
+    load_key(acc)?;
+    let started = std::env::var_os("SSH_AUTH_SOCK") != socket;
+    if started && !printing_env {
+        say!(
+            "ℹ️ Started an ssh-agent your shell doesn't know about; run `eval \"$(git-switch env {})\"` to use it.",
+            acc.name
+        );
+    }
+    Ok(agent)
+}
+
+/// Prints shell code that points the calling shell at the agent holding the
+/// account's key and makes git offer only that key.
+fn print_agent_env(acc: &Account, mut vars: Vec<(String, String)>) {
+    if !acc.uses_https() {
+        vars.push(("GIT_SSH_COMMAND".to_string(), render::ssh_command(acc)));
+    }
+    print!("{}", render::shell_exports(&vars));
+}
+
+/// `git-switch env`: loads the account's key into an agent, starting one if
+/// needed, and prints the exports for `eval "$(git-switch env <name>)"`.
+/// Git config is left alone.
+pub fn print_account_env(name_or_username: &str) -> Result<()> {
+    let acc = find_account(name_or_username)?;
+    let agent = if acc.uses_https() {
+        say!(
+            "ℹ️ '{}' is an HTTPS account; it needs no SSH agent.",
+            acc.name
+        );
+        Vec::new()
+    } else {
+        start_agent_and_load_key(&acc, false, true)?
+    };
+    print_agent_env(&acc, agent);
+    Ok(())
+}
+
+/// Lets the user pick a saved account when `use` or `remove` is run without
+/// a name; the active account is preselected.
+pub fn pick_account(prompt: &str) -> Result<String> {
+    let accounts = load_accounts();
+    if accounts.is_empty() {
+        return Err(GitSwitchError::InvalidInput(
+            "No saved accounts; add one with `git-switch add`.".to_string(),
+        ));
+    }
+    if !is_interactive() {
+        return Err(GitSwitchError::InvalidInput(
+            "No account name given; pass one, or run in a terminal to pick one.".to_string(),
+        ));
+    }
+    let active_email = config_value(None, "user.email").unwrap_or_default();
+    let default = accounts
+        .iter()
+        .position(|acc| acc.email == active_email)
+        .unwrap_or(0);
+    let items: Vec<String> = accounts
+        .iter()
+        .map(|acc| {
+            format!(
+                "{}  {} <{}>  {}",
+                acc.name, acc.username, acc.email, acc.host
+            )
+        })
+        .collect();
+    pick(prompt, &items, default)
+        .map(|index| accounts[index].name.clone())
+        .ok_or_else(|| GitSwitchError::Cancelled("No account was picked.".to_string()))
+}
+
+/// Options for `remove`.
+#[derive(Debug, Default)]
+pub struct RemoveOptions {
+    /// Don't ask for confirmation.
+    pub yes: bool,
+    /// Leave the key files in place.
+    pub keep_keys: bool,
+    /// Remove only the account entry; SSH config, keys, secrets, mappings
+    /// and rules stay.
+    pub config_only: bool,
+    /// Show what would be removed and stop.
+    pub dry_run: bool,
+}
+
+/// Removes an account after showing what goes with it and confirming on a
+/// terminal, unless `yes`. Without a terminal it goes ahead, as scripts
+/// expect.
+pub fn remove_account(name: &str, options: RemoveOptions) -> Result<()> {
+    let accounts = load_accounts();
+    let Some(account) = accounts.iter().find(|acc| acc.name == name) else {
+        return Err(GitSwitchError::AccountNotFound(name.to_string()));
+    };
+    let plan = removal_plan(account, &accounts, &options);
+    let keeps_keys = !account.ssh_key.is_empty()
+        && (options.keep_keys || options.config_only || account.external_key);
+    if options.dry_run {
+        report!("🔍 Removing '{}' would delete:", name);
+        for item in &plan {
+            report!("   {}", item);
+        }
+        if keeps_keys {
+            report!("   (keeps the key {})", account.ssh_key);
+        }
+        return Ok(());
+    }
+    if !options.yes && is_interactive() {
+        say!("🗑️ Removing '{}' deletes:", name);
+        for item in &plan {
+            say!("   {}", item);
+        }
+        let choices = [String::from("Cancel"), format!("Remove '{}'", name)];
+        if pick(&format!("Remove account '{}'?", name), &choices, 0) != Some(1) {
+            return Err(GitSwitchError::Cancelled(format!(
+                "Account '{}' was kept.",
+                name
+            )));
         }
     }
+
+    // 1. Remove from config.rs
+    delete_account(name).context(format!("Failed to remove account '{}' from config", name))?;
+    say!("🗑️ Account '{}' removed from config.", name);
+    history::record("remove", name, None, Some(identity_label(account)));
+    if options.config_only {
+        say!(
+            "✅ Account '{}' removed; its SSH config entries, keys, secrets, mappings and rules were left in place.",
+            name
+        );
+        return Ok(());
+    }
+
+    // 2. Remove SSH config entry
+    if let Err(e) = remove_ssh_config_entries(account) {
+        problem!("❌ Failed to remove SSH config entry: {}", e);
+    }
+
+    // 3. Delete SSH key files, unless they were registered with --ssh-key
+    //    or are to be kept
+    if account.external_key {
+        say!(
+            "ℹ️ Kept SSH key {}; it wasn't generated by git-switch.",
+            account.ssh_key
+        );
+    } else if keeps_keys {
+        say!("ℹ️ Kept SSH key {}.", account.ssh_key);
+    } else if !account.ssh_key.is_empty()
+        && let Err(e) = delete_ssh_key_files(&account.ssh_key)
+    {
+        problem!("❌ Failed to delete SSH key files: {}", e);
+    }
+
+    if let Err(e) = secrets::delete_account(account) {
+        problem!("❌ Failed to delete stored secrets: {}", e);
+    }
+
+    // 4. Remove the account's gitconfig fragment (credential cache, LFS)
+    if let Err(e) = remove_account_fragment(name) {
+        problem!("❌ Failed to remove account settings fragment: {}", e);
+    }
+    if let Err(e) = clear_org_url_rewrites(std::slice::from_ref(account)) {
+        problem!("❌ Failed to remove url.*.insteadOf rules: {}", e);
+    }
+
+    // 5. Remove directory mappings to the account
+    if let Err(e) = includes::remove_account_mappings(name) {
+        problem!("❌ Failed to remove directory mappings: {}", e);
+    }
+
+    // 6. Drop queued provider actions for the account
+    if let Err(e) = pending::rename_account_actions(name, None) {
+        problem!("❌ Failed to update pending actions: {}", e);
+    }
+
+    // 7. Drop rules naming the account
+    if let Err(e) = rules::rename_account_rules(name, None) {
+        problem!("❌ Failed to update rules: {}", e);
+    }
+
+    if keeps_keys {
+        say!(
+            "✅ Account '{}' and its associated SSH configurations have been removed.",
+            name
+        );
+    } else {
+        say!(
+            "✅ Account '{}' and its associated SSH configurations and keys have been removed.",
+            name
+        );
+    }
+    Ok(())
 }
 
-pub fn list_accounts() {
-    crate::config::list_accounts();
+/// Removes what accounts that no longer exist left behind: their marked SSH
+/// config blocks, key files only those blocks point at, and their settings
+/// fragments. Asks first unless `yes`; off a terminal, where it can't ask,
+/// it deletes nothing without `yes`.
+pub fn prune(dry_run: bool, yes: bool) -> Result<()> {
+    let accounts = load_accounts();
+    let expand = |path: &str| PathBuf::from(&*shellexpand::tilde(path));
+    let ssh_config = fs::read_to_string(get_ssh_config_path()).unwrap_or_default();
+    let orphans: Vec<(String, Vec<String>)> = managed_blocks(&ssh_config)
+        .into_iter()
+        .filter(|(name, _)| !accounts.iter().any(|acc| &acc.name == name))
+        .collect();
+    let orphan_names: Vec<String> = orphans.iter().map(|(name, _)| name.clone()).collect();
+
+    // A key is still in use if an account or any other SSH config entry names it
+    let mut remaining = ssh_config.clone();
+    for name in &orphan_names {
+        while let Some((updated, _)) = remove_managed_block(&remaining, name) {
+            remaining = updated;
+        }
+    }
+    let in_use: Vec<PathBuf> = accounts
+        .iter()
+        .map(|acc| expand(&acc.ssh_key))
+        .chain(identity_files(&remaining).iter().map(|path| expand(path)))
+        .collect();
+    let mut keys: Vec<String> = Vec::new();
+    for key in orphans.iter().flat_map(|(_, files)| files) {
+        if expand(key).is_file() && !in_use.contains(&expand(key)) && !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+    let fragments: Vec<PathBuf> = account_fragments()
+        .into_iter()
+        .filter(|path| {
+            !accounts
+                .iter()
+                .any(|acc| &account_fragment_path(&acc.name) == path)
+        })
+        .collect();
+
+    if orphans.is_empty() && keys.is_empty() && fragments.is_empty() {
+        say!("✅ Nothing to prune; everything belongs to a saved account.");
+        return Ok(());
+    }
+    report!("🧹 Left behind by accounts that no longer exist:");
+    for name in &orphan_names {
+        report!("   SSH config entries of '{}'", name);
+    }
+    for key in &keys {
+        report!("   Key file {} (and its .pub)", key);
+    }
+    for fragment in &fragments {
+        report!("   Settings fragment {}", fragment.display());
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !yes {
+        if !is_interactive() {
+            return Err(GitSwitchError::Cancelled(
+                "Nothing was pruned; pass --yes to delete these without asking.".to_string(),
+            ));
+        }
+        let answer = prompt_line("Delete them? [y/N]: ").unwrap_or_default();
+        if !answer.eq_ignore_ascii_case("y") {
+            return Err(GitSwitchError::Cancelled("Nothing was pruned.".to_string()));
+        }
+    }
+
+    remove_managed_blocks(&orphan_names).context("Failed to update the SSH config")?;
+    for key in &keys {
+        if let Err(e) = delete_ssh_key_files(key) {
+            problem!("❌ Failed to delete {}: {}", key, e);
+        }
+    }
+    for fragment in &fragments {
+        let stem = fragment.file_stem().unwrap_or_default().to_string_lossy();
+        if let Err(e) = remove_account_fragment(&stem) {
+            problem!("❌ Failed to remove {}: {}", fragment.display(), e);
+        }
+    }
+    say!("✅ Pruned.");
+    Ok(())
+}
+
+/// Everything `remove` deletes for `account`, one line each, shown before
+/// it asks and by `--dry-run`.
+fn removal_plan(account: &Account, accounts: &[Account], options: &RemoveOptions) -> Vec<String> {
+    let mut plan = vec![format!(
+        "Account entry in {}",
+        get_default_config_path().display()
+    )];
+    if options.config_only {
+        return plan;
+    }
+    if !account.uses_https() {
+        plan.push(format!(
+            "SSH config entries: Host {}",
+            host_aliases(account)
+        ));
+    }
+    if !account.ssh_key.is_empty() && !account.external_key && !options.keep_keys {
+        let key = shellexpand::tilde(&account.ssh_key).into_owned();
+        for path in [
+            key.clone(),
+            format!("{}.pub", key),
+            attestation_path(&key),
+            challenge_path(&key),
+        ] {
+            if Path::new(&path).exists() {
+                plan.push(format!("Key file {}", path));
+            }
+        }
+    }
+    for entry in secrets::stored_entries(account) {
+        plan.push(format!("OS keyring entry {}", entry));
+    }
+    let fragment = account_fragment_path(&account.name);
+    if fragment.exists() {
+        plan.push(format!("Settings fragment {}", fragment.display()));
+    }
+    for (dir, mapped) in directory_mappings(accounts) {
+        if mapped.name == account.name {
+            plan.push(format!("Directory mapping {}", dir));
+        }
+    }
+    for rule in rules::load_rules().unwrap_or_default() {
+        if rule.account == account.name {
+            plan.push(format!("Rule {}", rule.pattern));
+        }
+    }
+    plan
+}
+
+/// Renames an account together with its key files, SSH config blocks,
+/// directory mappings and gitconfig fragment.
+pub fn rename_account(old_name: &str, new_name: &str) -> Result<()> {
+    let accounts = load_accounts();
+    let Some(account) = accounts.iter().find(|acc| acc.name == old_name) else {
+        return Err(GitSwitchError::AccountNotFound(old_name.to_string()));
+    };
+    if new_name.trim().is_empty() {
+        return Err(GitSwitchError::InvalidInput(
+            "The new account name must not be empty.".to_string(),
+        ));
+    }
+    if accounts
+        .iter()
+        .any(|acc| acc.name != old_name && acc.name.eq_ignore_ascii_case(new_name))
+    {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "An account named '{}' already exists.",
+            new_name
+        )));
+    }
+
+    let mut renamed = account.clone();
+    renamed.name = new_name.to_string();
+    if let Some(new_key) = renamed_key_path(&account.ssh_key, old_name, new_name)
+        && new_key != account.ssh_key
+    {
+        rename_ssh_key_files(&account.ssh_key, &new_key)
+            .context("Failed to rename SSH key files")?;
+        let moved = |path: &Option<String>| {
+            path.as_ref()
+                .map(|path| path.replacen(&account.ssh_key, &new_key, 1))
+        };
+        renamed.signing_key = moved(&account.signing_key);
+        renamed.attestation = moved(&account.attestation);
+        renamed.ssh_key = new_key;
+    }
+
+    let updated: Vec<Account> = accounts
+        .iter()
+        .map(|acc| {
+            if acc.name == old_name {
+                renamed.clone()
+            } else {
+                acc.clone()
+            }
+        })
+        .collect();
+    write_accounts(&updated).context("Failed to save accounts")?;
+
+    if let Err(e) = remove_ssh_config_entries(account).and_then(|_| update_ssh_config(&renamed)) {
+        problem!("❌ Failed to rewrite SSH config entries: {}", e);
+    }
+    if let Err(e) = includes::rename_account_mappings(old_name, &renamed) {
+        problem!("❌ Failed to update directory mappings: {}", e);
+    }
+    if let Err(e) = pending::rename_account_actions(old_name, Some(new_name)) {
+        problem!("❌ Failed to update pending actions: {}", e);
+    }
+    if let Err(e) = rules::rename_account_rules(old_name, Some(new_name)) {
+        problem!("❌ Failed to update rules: {}", e);
+    }
+    if let Err(e) = secrets::rename_account(account, new_name) {
+        problem!("❌ Failed to move stored secrets: {}", e);
+    }
+    if let Err(e) = rename_org_url_rewrites(account, &renamed) {
+        problem!("❌ Failed to update url.*.insteadOf rules: {}", e);
+    }
+
+    // Re-apply global settings that point at the old key or fragment paths
+    let active = config_value(Some(ConfigScope::Global), "user.email").as_deref()
+        == Some(account.email.as_str());
+    if account_fragment_path(old_name) != account_fragment_path(new_name)
+        && account_fragment_path(old_name).exists()
+    {
+        if let Err(e) = remove_account_fragment(old_name) {
+            problem!("❌ Failed to remove the old settings fragment: {}", e);
+        }
+        if active && let Err(e) = apply_account_fragment(&renamed, ConfigScope::Global) {
+            problem!("❌ Failed to apply account settings: {}", e);
+        }
+    }
+    if active
+        && renamed.signing_key != account.signing_key
+        && let Err(e) = apply_signing_config(&renamed, ConfigScope::Global)
+    {
+        problem!("❌ Failed to update signing settings: {}", e);
+    }
+
+    say!("✅ Renamed account '{}' to '{}'.", old_name, new_name);
+    history::record(
+        "rename",
+        new_name,
+        None,
+        Some(format!("from '{}'", old_name)),
+    );
+    Ok(())
+}
+
+/// Shows the active git identity, the account it matches, the keys in the
+/// SSH agent and the current repository's origin.
+/// The account commits made here are authored as, and where that comes
+/// from: the shell's session, `GIT_AUTHOR_*` variables or git config.
+fn active_account(accounts: &[Account]) -> Option<(&Account, &'static str)> {
+    if let Ok(name) = std::env::var(session::SESSION_VAR)
+        && let Some(acc) = accounts.iter().find(|acc| acc.name == name)
+    {
+        return Some((acc, "session"));
+    }
+    if let Ok(email) = std::env::var("GIT_AUTHOR_EMAIL") {
+        let name = std::env::var("GIT_AUTHOR_NAME").unwrap_or_default();
+        return match_identity(accounts, &name, &email).map(|acc| (acc, "environment"));
+    }
+    let (name, email) = effective_identity();
+    match_identity(
+        accounts,
+        name.as_deref().unwrap_or_default(),
+        email.as_deref().unwrap_or_default(),
+    )
+    .map(|acc| (acc, "gitconfig"))
+}
+
+/// `git-switch current`: the active account's name for shell prompts. Runs
+/// one `git config` and never asks for anything; exits 1 without printing
+/// when no saved account is active (JSON output has `"account": null`).
+pub fn print_current(format: OutputFormat) -> Result<()> {
+    set_non_interactive();
+    let accounts = load_accounts();
+    let active = active_account(&accounts);
+    if format == OutputFormat::Json {
+        let current = json!({
+            "account": active.map(|(acc, _)| acc.name.as_str()),
+            "username": active.map(|(acc, _)| acc.username.as_str()),
+            "email": active.map(|(acc, _)| acc.email.as_str()),
+            "source": active.map(|(_, source)| source),
+        });
+        println!("{}", current);
+        return Ok(());
+    }
+    match active {
+        Some((acc, _)) => {
+            println!("{}", acc.name);
+            Ok(())
+        }
+        None => Err(GitSwitchError::CheckFailed(
+            "No saved account matches the current identity.".to_string(),
+        )),
+    }
+}
+
+/// Options for `git-switch prompt`.
+#[derive(Debug, Clone)]
+pub struct PromptOptions {
+    pub template: String,
+    /// Used instead of `template` when the repository belongs to another
+    /// account.
+    pub mismatch_template: String,
+    /// One of [`prompt::SHELLS`].
+    pub shell: String,
+    pub color: bool,
+}
+
+impl Default for PromptOptions {
+    fn default() -> Self {
+        PromptOptions {
+            template: prompt::DEFAULT_TEMPLATE.to_string(),
+            mismatch_template: prompt::DEFAULT_MISMATCH_TEMPLATE.to_string(),
+            shell: "raw".to_string(),
+            color: true,
+        }
+    }
+}
+
+/// The account the current repository belongs to, if in one.
+fn repository_account(accounts: &[Account]) -> Option<&Account> {
+    let root = PathBuf::from(repository_root()?);
+    let remotes: Vec<RemoteUrl> = scan::read_remotes(&root)
+        .iter()
+        .filter_map(|(_, url)| RemoteUrl::parse(url))
+        .collect();
+    let mappings = directory_mappings(accounts);
+    let rules = rules::load_rules().ok()?;
+    let (expected, _) = scan::expected_account(accounts, &root, &remotes, &mappings, &rules)?;
+    accounts.iter().find(|acc| acc.name == expected.name)
+}
+
+/// `git-switch prompt`: the prompt segment for the active account. Prints
+/// nothing when no saved account is active and the repository belongs to
+/// none either.
+pub fn print_prompt(options: PromptOptions) -> Result<()> {
+    set_non_interactive();
+    let accounts = load_accounts();
+    let active = active_account(&accounts).map(|(acc, _)| acc);
+    let expected = repository_account(&accounts);
+    let state = match (active, expected) {
+        (_, None) => PromptState::Unmapped,
+        (Some(active), Some(expected)) if active.email.eq_ignore_ascii_case(&expected.email) => {
+            PromptState::Match
+        }
+        (_, Some(_)) => PromptState::Mismatch,
+    };
+    if active.is_none() && state != PromptState::Mismatch {
+        return Ok(());
+    }
+    let template = if state == PromptState::Mismatch {
+        &options.mismatch_template
+    } else {
+        &options.template
+    };
+    let text = prompt::fill_template(
+        template,
+        &[
+            ("account", active.map_or("?", |acc| acc.name.as_str())),
+            ("username", active.map_or("", |acc| acc.username.as_str())),
+            ("email", active.map_or("", |acc| acc.email.as_str())),
+            ("expected", expected.map_or("", |acc| acc.name.as_str())),
+        ],
+    );
+    let color = options.color && !output::no_color();
+    println!("{}", prompt::colorize(&text, state, &options.shell, color));
+    Ok(())
+}
+
+pub fn print_status(format: OutputFormat) {
+    let accounts = load_accounts();
+    let in_repo = is_inside_work_tree();
+
+    if format == OutputFormat::Json {
+        let identity = |scope: Option<ConfigScope>| {
+            json!({
+                "name": config_value(scope, "user.name"),
+                "email": config_value(scope, "user.email"),
+            })
+        };
+        let name = config_value(None, "user.name").unwrap_or_default();
+        let email = config_value(None, "user.email").unwrap_or_default();
+        let agent_keys = agent_fingerprints().map(|loaded| {
+            loaded
+                .iter()
+                .map(|fingerprint| {
+                    let owner = accounts
+                        .iter()
+                        .find(|acc| key_fingerprint(&acc.ssh_key).as_ref() == Some(fingerprint));
+                    json!({
+                        "fingerprint": fingerprint,
+                        "account": owner.map(|acc| acc.name.as_str()),
+                        "expires_in": key_time_left(fingerprint),
+                    })
+                })
+                .collect::<Vec<_>>()
+        });
+        let status = json!({
+            "global": identity(Some(ConfigScope::Global)),
+            "local": in_repo.then(|| identity(Some(ConfigScope::Local))),
+            "effective": identity(None),
+            "account": match_identity(&accounts, &name, &email).map(|acc| acc.name.as_str()),
+            "agent_keys": agent_keys,
+            "origin": if in_repo { config_value(None, "remote.origin.url") } else { None },
+        });
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&status).unwrap_or_default()
+        );
+        return;
+    }
+
+    println!("Git identity:");
+    let mut scopes = vec![("global", ConfigScope::Global)];
+    if in_repo {
+        scopes.push(("local", ConfigScope::Local));
+    }
+    for (label, scope) in scopes {
+        let name = config_value(Some(scope), "user.name");
+        let email = config_value(Some(scope), "user.email");
+        if name.is_none() && email.is_none() {
+            println!("  {:<9} (not set)", label);
+        } else {
+            println!(
+                "  {:<9} {} <{}>",
+                label,
+                name.as_deref().unwrap_or("-"),
+                email.as_deref().unwrap_or("-")
+            );
+        }
+    }
+
+    let name = config_value(None, "user.name").unwrap_or_default();
+    let email = config_value(None, "user.email").unwrap_or_default();
+    println!("  {:<9} {} <{}>", "effective", name, email);
+    if let Ok(session) = std::env::var(session::SESSION_VAR) {
+        report!(
+            "🪟 This shell uses account '{}' through a session; commits use its identity, not the config above.",
+            session
+        );
+    }
+    match match_identity(&accounts, &name, &email) {
+        Some(acc) => report!("✅ Active account: {} ({})", acc.name, acc.username),
+        None => report!("⚠️ The effective identity doesn't match any saved account."),
+    }
+
+    println!("\nSSH agent:");
+    match agent_fingerprints() {
+        None => report!("  ⚠️ Could not reach the SSH agent."),
+        Some(loaded) if loaded.is_empty() => println!("  No keys loaded."),
+        Some(loaded) => {
+            for fingerprint in &loaded {
+                let owner = accounts
+                    .iter()
+                    .find(|acc| key_fingerprint(&acc.ssh_key).as_ref() == Some(fingerprint));
+                let expiry = key_time_left(fingerprint)
+                    .map(|left| format!(", expires in {}", format_duration(left)))
+                    .unwrap_or_default();
+                match owner {
+                    Some(acc) => println!(
+                        "  {} ({}: {}{})",
+                        fingerprint, acc.name, acc.ssh_key, expiry
+                    ),
+                    None => println!("  {} (not managed by git-switch)", fingerprint),
+                }
+            }
+        }
+    }
+
+    if in_repo {
+        println!("\nRepository:");
+        match config_value(None, "remote.origin.url") {
+            Some(url) => println!("  origin    {}", url),
+            None => println!("  No 'origin' remote."),
+        }
+    }
+}
+
+/// Switches the current shell only, leaving git config untouched.
+fn start_shell_session(account: &Account) -> Result<()> {
+    let Some(pid) = session::shell_pid() else {
+        return Err(GitSwitchError::Unsupported(
+            "Shell sessions are not supported on this platform.".to_string(),
+        ));
+    };
+    let path = session::start_session(account, pid).context("Failed to start the session")?;
+    if let Err(e) = load_key(account) {
+        problem!("⚠️ {}", e);
+    }
+    say!(
+        "✅ This shell now uses account: {} ({})",
+        account.name,
+        account.username
+    );
+    say!(
+        "ℹ️ Applied at the next prompt by the hook from `git-switch init`; written to {}.",
+        path.display()
+    );
+    say!(
+        "ℹ️ Git config is unchanged. Run `git-switch use --end-session` or close the shell to revert."
+    );
+    Ok(())
+}
+
+/// Ends the current shell's session started with `use --session`.
+pub fn end_shell_session() -> Result<()> {
+    let Some(pid) = session::shell_pid() else {
+        return Err(GitSwitchError::Unsupported(
+            "Shell sessions are not supported on this platform.".to_string(),
+        ));
+    };
+    if session::end_session(pid).context("Failed to end the session")? {
+        say!("✅ Session ended; the shell reverts to your Git config at the next prompt.");
+    } else {
+        say!("ℹ️ This shell has no git-switch session.");
+    }
+    Ok(())
+}
+
+/// Prints the shell hook that applies `use --session` switches.
+pub fn print_shell_hook(shell: &str) -> Result<()> {
+    let script = session::hook_script(shell)
+        .ok_or_else(|| GitSwitchError::InvalidInput(format!("Unsupported shell: {}", shell)))?;
+    print!("{}", script);
+    if let Some(account) = default_account(&load_accounts()) {
+        let binary = std::env::current_exe().context("Failed to locate the git-switch binary")?;
+        print!(
+            "{}",
+            session::default_session_script(shell, &binary.to_string_lossy(), &account.name)
+        );
+    }
+    Ok(())
+}
+
+/// Prints the `hook shell` script that runs `auto --silent` on `cd`.
+pub fn print_auto_switch_hook(shell: &str) -> Result<()> {
+    let binary = std::env::current_exe().context("Failed to locate the git-switch binary")?;
+    let script = hooks::render_shell_hook(shell, &binary)
+        .ok_or_else(|| GitSwitchError::InvalidInput(format!("Unsupported shell: {}", shell)))?;
+    print!("{}", script);
+    Ok(())
+}
+
+/// Marks an account as the default, clears the default, or with neither
+/// shows which account it is.
+pub fn set_default(name_or_username: Option<&str>, clear: bool) -> Result<()> {
+    if clear {
+        set_default_account(None).context("Failed to clear the default account")?;
+        say!("✅ No account is the default now.");
+        return Ok(());
+    }
+    let Some(name_or_username) = name_or_username else {
+        match default_account(&load_accounts()) {
+            Some(account) => say!("⭐ {}", account.name),
+            None => say!("ℹ️ No default account; set one with `git-switch default <name>`."),
+        }
+        return Ok(());
+    };
+    let account = find_account(name_or_username)?;
+    set_default_account(Some(&account.name))
+        .context(format!("Failed to make '{}' the default", account.name))?;
+    say!("⭐ '{}' is now the default account.", account.name);
+    Ok(())
+}
+
+/// The account `use` switches to when no name is given: the default
+/// account outside a terminal, otherwise the one picked from the list.
+pub fn account_to_use() -> Result<String> {
+    if !is_interactive()
+        && let Some(account) = default_account(&load_accounts())
+    {
+        say!(
+            "ℹ️ No account name given; using the default account '{}'.",
+            account.name
+        );
+        return Ok(account.name.clone());
+    }
+    pick_account("Use which account?")
+}
+
+/// Prints the machine policy rules that apply to the account. Fails if a
+/// rule requires `--acknowledge` and it wasn't given.
+fn check_policy(account: &Account, acknowledged: bool) -> Result<()> {
+    let policy = load_policy();
+    let mut allowed = true;
+    for rule in policy.evaluate(account) {
+        say!("⚠️ Policy: {}", rule.message);
+        if rule.action == policy::Action::Acknowledge && !acknowledged {
+            allowed = false;
+        }
+    }
+    if !allowed {
+        return Err(GitSwitchError::PolicyDenied(format!(
+            "Account '{}' requires acknowledging the policy above; re-run with --acknowledge.",
+            account.name
+        )));
+    }
+    Ok(())
+}
+
+/// Maps a directory to an account with a conditional include.
+pub fn map_directory(dir: &str, name_or_username: &str) -> Result<()> {
+    let account = find_account(name_or_username)?;
+    let dir = includes::normalize_dir(dir);
+    includes::map_directory(&dir, &account).context(format!("Failed to map {}", dir))?;
+    say!(
+        "✅ Repositories under {} now use account '{}'.",
+        dir,
+        account.name
+    );
+    Ok(())
+}
+
+pub fn unmap_directory(dir: &str) {
+    let dir = includes::normalize_dir(dir);
+    if includes::unmap_directory(&dir) {
+        say!("✅ Removed the mapping for {}.", dir);
+    } else {
+        say!("ℹ️ {} is not mapped to an account.", dir);
+    }
+}
+
+pub fn list_directory_mappings() {
+    let mappings = includes::managed_includes();
+    if mappings.is_empty() {
+        println!("No mapped directories.");
+        return;
+    }
+    let accounts = load_accounts();
+    for (dir, path) in mappings {
+        let account = accounts
+            .iter()
+            .find(|acc| includes::identity_fragment_path(&acc.name) == Path::new(&path))
+            .map(|acc| acc.name.as_str())
+            .unwrap_or("(unknown account)");
+        println!("{} → {}", dir, account);
+    }
+}
+
+/// Adds a rule, or points an existing rule for the same pattern at the account.
+pub fn add_rule(pattern: &str, name_or_username: &str) -> Result<()> {
+    let account = find_account(name_or_username)?;
+    let pattern = rules::normalize_pattern(pattern).map_err(GitSwitchError::InvalidInput)?;
+    let mut rules = rules::load_rules().context("Failed to load rules")?;
+    match rules.iter_mut().find(|rule| rule.pattern == pattern) {
+        Some(rule) => rule.account = account.name.clone(),
+        None => rules.push(rules::Rule {
+            pattern: pattern.clone(),
+            account: account.name.clone(),
+        }),
+    }
+    rules::save_rules(&rules).context("Failed to save rules")?;
+    say!(
+        "✅ Repositories matching {} now use account '{}'.",
+        pattern,
+        account.name
+    );
+    Ok(())
+}
+
+pub fn remove_rule(pattern: &str) -> Result<()> {
+    let pattern = rules::normalize_pattern(pattern).unwrap_or_else(|_| pattern.to_string());
+    let mut rules = rules::load_rules().context("Failed to load rules")?;
+    let count = rules.len();
+    rules.retain(|rule| rule.pattern != pattern);
+    if rules.len() == count {
+        say!("ℹ️ No rule for {}.", pattern);
+        return Ok(());
+    }
+    rules::save_rules(&rules).context("Failed to save rules")?;
+    say!("✅ Removed the rule for {}.", pattern);
+    Ok(())
+}
+
+pub fn list_rules() -> Result<()> {
+    let rules = rules::load_rules().context("Failed to load rules")?;
+    if rules.is_empty() {
+        println!("No rules.");
+        return Ok(());
+    }
+    for rule in rules {
+        println!("{} → {}", rule.pattern, rule.account);
+    }
+    Ok(())
+}
+
+/// Switches the current repository to the account the rules pick for its
+/// remotes, `origin` first. Fails if no rule matches.
+///
+/// With `silent`, for shell hooks running it on every directory change, it
+/// prints nothing and never asks, and does nothing outside repositories,
+/// when no rule matches (the default account isn't applied) or when the
+/// repository already has the account's identity.
+pub fn auto_use(silent: bool) -> Result<()> {
+    if silent {
+        set_non_interactive();
+        set_verbosity(Verbosity::Quiet);
+    }
+    if !is_inside_work_tree() {
+        return if silent {
+            Ok(())
+        } else {
+            Err(GitSwitchError::NotInRepository)
+        };
+    }
+    let accounts = load_accounts();
+    let rules = rules::load_rules().context("Failed to load rules")?;
+    let remotes = scan::read_remotes(Path::new("."));
+    let matched = remotes.into_iter().find_map(|(_, url)| {
+        let remote = RemoteUrl::parse(&url)?;
+        rules::match_remote(&rules, &accounts, &remote).map(|(rule, acc)| (url, rule, acc))
+    });
+    if silent {
+        let Some((_, _, account)) = matched else {
+            return Ok(());
+        };
+        if config_value(Some(ConfigScope::Local), "user.email")
+            .is_some_and(|email| email.eq_ignore_ascii_case(&account.email))
+        {
+            return Ok(());
+        }
+    }
+    let account = match matched {
+        Some((url, rule, account)) => {
+            say!(
+                "🔎 {} matches {} → account '{}'.",
+                url,
+                rule.pattern,
+                account.name
+            );
+            account
+        }
+        None => match default_account(&accounts) {
+            Some(account) => {
+                say!(
+                    "🔎 No rule matches this repository's remotes; using the default account '{}'.",
+                    account.name
+                );
+                account
+            }
+            None => {
+                return Err(GitSwitchError::CheckFailed(
+                    "No rule matches this repository's remotes. Add one with `git-switch rule add`."
+                        .to_string(),
+                ));
+            }
+        },
+    };
+    use_account(
+        &account.name,
+        UseOptions {
+            scope: Some(ConfigScope::Local),
+            remote: RemoteAction::Skip,
+            ..UseOptions::default()
+        },
+    )
+}
+
+/// Options for `scan --fix`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ScanFix {
+    pub rewrite_remotes: bool,
+    pub interactive: bool,
+}
+
+/// `map`ped directories and the accounts they use.
+fn directory_mappings(accounts: &[Account]) -> Vec<(String, &Account)> {
+    includes::managed_includes()
+        .into_iter()
+        .filter_map(|(dir, path)| {
+            accounts
+                .iter()
+                .find(|acc| includes::identity_fragment_path(&acc.name) == Path::new(&path))
+                .map(|acc| (dir, acc))
+        })
+        .collect()
+}
+
+/// Checks every repository below `dirs` (all account workspaces if empty).
+/// Returns `None` if no repositories were found.
+fn scan_reports(dirs: &[String], accounts: &[Account]) -> Result<Option<Vec<scan::RepoReport>>> {
+    let roots: Vec<String> = if dirs.is_empty() {
+        accounts
+            .iter()
+            .filter_map(|acc| acc.workspace.clone())
+            .collect()
+    } else {
+        dirs.to_vec()
+    };
+    if roots.is_empty() {
+        return Err(GitSwitchError::InvalidInput(
+            "No directory given and no account has a workspace. Pass a directory to scan."
+                .to_string(),
+        ));
+    }
+
+    let mappings = directory_mappings(accounts);
+    let rules = rules::load_rules().context("Failed to load rules")?;
+    let repos: Vec<_> = roots
+        .iter()
+        .flat_map(|root| scan::find_repositories(Path::new(&*shellexpand::tilde(root))))
+        .collect();
+    let reports = scan::check_repositories(&repos, accounts, &mappings, &rules);
+    if reports.is_empty() {
+        println!("No repositories found.");
+        return Ok(None);
+    }
+    Ok(Some(reports))
+}
+
+/// Reports the account behind every repository below `dirs` (all account
+/// workspaces if empty) and optionally fixes mismatched identities.
+pub fn scan_repositories(dirs: &[String], fix: Option<ScanFix>) -> Result<()> {
+    let accounts = load_accounts();
+    let Some(reports) = scan_reports(dirs, &accounts)? else {
+        return Ok(());
+    };
+
+    for report in &reports {
+        print_repo_report(report);
+    }
+    let mismatched: Vec<&scan::RepoReport> = reports
+        .iter()
+        .filter(|report| report.status == RepoStatus::Mismatch)
+        .collect();
+    println!(
+        "\n{} repositories, {} mismatched, {} without a known account.",
+        reports.len(),
+        mismatched.len(),
+        reports
+            .iter()
+            .filter(|report| report.status == RepoStatus::Unknown)
+            .count()
+    );
+
+    let Some(fix) = fix else {
+        if !mismatched.is_empty() {
+            println!("Run with --fix to apply the expected identities.");
+        }
+        return Ok(());
+    };
+    let mut fixed = 0;
+    for report in mismatched {
+        let Some(account) = report
+            .expected
+            .as_ref()
+            .and_then(|(name, _)| accounts.iter().find(|acc| &acc.name == name))
+        else {
+            continue;
+        };
+        if fix.interactive {
+            let answer = prompt_line(&format!(
+                "Fix {} to use account '{}'? [y/N]: ",
+                report.path.display(),
+                account.name
+            ))
+            .unwrap_or_default();
+            if !answer.eq_ignore_ascii_case("y") {
+                say!("⏭️ Skipped {}", report.path.display());
+                continue;
+            }
+        }
+        match scan::fix_repository(report, account, fix.rewrite_remotes, false) {
+            Ok(changes) => {
+                fixed += 1;
+                say!("🔧 {}: {}", report.path.display(), changes.join("; "));
+            }
+            Err(e) => problem!("❌ {}: {}", report.path.display(), e),
+        }
+    }
+    say!("✅ Fixed {} repositories.", fixed);
+    Ok(())
+}
+
+/// Options for `fix`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FixOptions {
+    pub rewrite_remotes: bool,
+    /// Only print what would change.
+    pub dry_run: bool,
+    /// Apply without asking for confirmation.
+    pub yes: bool,
+}
+
+/// Gives every repository below `dirs` whose identity doesn't match its
+/// account that account's identity, after showing the planned changes.
+pub fn fix_repositories(dirs: &[String], options: FixOptions) -> Result<()> {
+    let accounts = load_accounts();
+    let Some(reports) = scan_reports(dirs, &accounts)? else {
+        return Ok(());
+    };
+    let planned: Vec<(&scan::RepoReport, &Account, Vec<String>)> = reports
+        .iter()
+        .filter(|report| report.status == RepoStatus::Mismatch)
+        .filter_map(|report| {
+            let (name, _) = report.expected.as_ref()?;
+            let account = accounts.iter().find(|acc| &acc.name == name)?;
+            let changes =
+                scan::fix_repository(report, account, options.rewrite_remotes, true).ok()?;
+            Some((report, account, changes))
+        })
+        .collect();
+    let unknown = reports
+        .iter()
+        .filter(|report| report.status == RepoStatus::Unknown)
+        .count();
+    if planned.is_empty() {
+        say!(
+            "✅ All {} repositories with a known account use its identity.",
+            reports.len() - unknown
+        );
+        return Ok(());
+    }
+
+    say!("📋 Planned changes:");
+    for (report, account, changes) in &planned {
+        println!("  {} → account '{}'", report.path.display(), account.name);
+        for change in changes {
+            println!("      {}", change);
+        }
+    }
+    println!(
+        "{} of {} repositories to fix{}.",
+        planned.len(),
+        reports.len(),
+        if unknown > 0 {
+            format!("; {} without a known account left alone", unknown)
+        } else {
+            String::new()
+        }
+    );
+    if options.dry_run {
+        say!("ℹ️ Dry run; nothing was changed.");
+        return Ok(());
+    }
+    if !options.yes {
+        let answer = prompt_line(&format!(
+            "Apply these changes to {} repositories? [y/N]: ",
+            planned.len()
+        ))
+        .unwrap_or_default();
+        if !answer.eq_ignore_ascii_case("y") {
+            return Err(GitSwitchError::Cancelled(
+                "Nothing was changed.".to_string(),
+            ));
+        }
+    }
+
+    let mut fixed = 0;
+    for (report, account, _) in planned {
+        match scan::fix_repository(report, account, options.rewrite_remotes, false) {
+            Ok(_) => fixed += 1,
+            Err(e) => problem!("❌ {}: {}", report.path.display(), e),
+        }
+    }
+    say!("✅ Fixed {} repositories.", fixed);
+    Ok(())
+}
+
+fn print_repo_report(report: &scan::RepoReport) {
+    let email = report.email.as_deref().unwrap_or("(no user.email)");
+    let remote = report
+        .remotes
+        .first()
+        .map(|(_, url)| url.as_str())
+        .unwrap_or("no remote");
+    match (&report.status, &report.expected) {
+        (RepoStatus::Ok, Some((name, _))) => {
+            report!("✅ {} — {} ({})", report.path.display(), name, email)
+        }
+        (RepoStatus::Mismatch, Some((name, reason))) => report!(
+            "❌ {} — commits as {}{}, expected account '{}' ({})",
+            report.path.display(),
+            email,
+            report
+                .current
+                .as_ref()
+                .map(|current| format!(" (account '{}')", current))
+                .unwrap_or_default(),
+            name,
+            reason
+        ),
+        _ => report!(
+            "⚠️ {} — no account matches ({}, {})",
+            report.path.display(),
+            email,
+            remote
+        ),
+    }
+}
+
+/// Exports the account's key attestation to `output`, or records an
+/// attestation produced elsewhere (e.g. by TPM tooling) from `import`.
+pub fn attest_key(
+    name_or_username: &str,
+    output: Option<&str>,
+    import: Option<&str>,
+) -> Result<()> {
+    let mut account = find_account(name_or_username)?;
+
+    if let Some(import) = import {
+        let destination = attestation_path(&account.ssh_key);
+        fs::copy(import, &*shellexpand::tilde(&destination))
+            .context(format!("Failed to import attestation from {}", import))?;
+        account.attestation = Some(destination);
+        update_account(&account).context(format!("Failed to save account '{}'", account.name))?;
+        say!("✅ Recorded attestation for account '{}'.", account.name);
+        return Ok(());
+    }
+
+    let Some(attestation) = &account.attestation else {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "Account '{}' has no recorded attestation. Only keys generated with --key-type ed25519-sk/ecdsa-sk have one.",
+            account.name
+        )));
+    };
+    let Some(output) = output else {
+        println!("{}", shellexpand::tilde(attestation));
+        return Ok(());
+    };
+    fs::copy(&*shellexpand::tilde(attestation), output).context("Failed to export attestation")?;
+    say!("✅ Wrote attestation to {}", output);
+    let challenge = challenge_path(&account.ssh_key);
+    let challenge_output = format!("{}.challenge", output);
+    if fs::copy(&*shellexpand::tilde(&challenge), &challenge_output).is_ok() {
+        say!("✅ Wrote the attestation challenge to {}", challenge_output);
+    }
+    Ok(())
+}
+
+/// Uploads an account's public key to its Git host, treating the host as
+/// `provider` software when given instead of the account's own setting.
+pub fn upload_key(name_or_username: &str, provider: Option<&str>) -> Result<()> {
+    let mut account = find_account(name_or_username)?;
+    if let Some(provider) = provider {
+        account.provider = Some(provider.to_string());
+    }
+    upload_key_for(&account)
+}
+
+/// Prints an account's public key on stdout and its fingerprint on stderr,
+/// so `git-switch key show work | pbcopy` copies just the key.
+pub fn show_key(name_or_username: &str) -> Result<()> {
+    let account = find_account(name_or_username)?;
+    let public_key = read_public_key(&account.ssh_key)?;
+    println!("{}", public_key);
+    if let Some(fingerprint) = key_fingerprint(&account.ssh_key) {
+        problem!("🔏 Fingerprint: {}", fingerprint);
+    }
+    Ok(())
+}
+
+/// Puts an account's public key on the system clipboard.
+pub fn copy_key(name_or_username: &str) -> Result<()> {
+    let account = find_account(name_or_username)?;
+    let public_key = read_public_key(&account.ssh_key)?;
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(public_key))
+        .map_err(|e| {
+            GitSwitchError::Unsupported(format!(
+                "Could not use the clipboard ({}); print the key with `git-switch key show {}` instead.",
+                e, account.name
+            ))
+        })?;
+    let fingerprint = key_fingerprint(&account.ssh_key).unwrap_or_default();
+    say!(
+        "📋 Copied the public key of '{}' to the clipboard. {}",
+        account.name,
+        fingerprint
+    );
+    Ok(())
+}
+
+/// Failed uploads to supported hosts are queued for `pending retry`.
+#[cfg(feature = "provider-api")]
+fn upload_key_for(account: &Account) -> Result<()> {
+    match crate::provider::upload_public_key(account) {
+        Ok(uploaded) => {
+            say!("✅ Uploaded public key as {}.", uploaded);
+            Ok(())
+        }
+        Err(e) => {
+            if crate::provider::supports_key_upload(account) {
+                match pending::enqueue(pending::PendingKind::UploadKey, &account.name, &e) {
+                    Ok(()) => say!(
+                        "⏳ Queued the upload; run `git-switch pending retry` once the problem is fixed."
+                    ),
+                    Err(e) => problem!("❌ Failed to queue the upload: {}", e),
+                }
+            }
+            Err(GitSwitchError::CheckFailed(e))
+        }
+    }
+}
+
+/// Retries queued provider actions, a few at a time. Each finished batch
+/// is removed from the queue right away, so an interrupted run resumes
+/// where it stopped.
+#[cfg(feature = "provider-api")]
+pub fn retry_pending() -> Result<()> {
+    let lock_path = pending::pending_path().with_extension("lock");
+    if fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&lock_path)
+        .is_err()
+    {
+        return Err(GitSwitchError::CheckFailed(format!(
+            "Another `pending retry` is running (delete {} if it is not).",
+            lock_path.display()
+        )));
+    }
+
+    let accounts = load_accounts();
+    let actions = pending::load_pending();
+    if actions.is_empty() {
+        say!("✅ No pending actions.");
+    }
+    for batch in actions.chunks(pending::MAX_CONCURRENT_ACTIONS) {
+        let results: Vec<(&pending::PendingAction, Result<String, String>)> =
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = batch
+                    .iter()
+                    .map(|action| {
+                        let account = accounts.iter().find(|acc| acc.name == action.account);
+                        scope.spawn(move || match (action.kind, account) {
+                            (_, None) => Err(String::from("the account no longer exists")),
+                            (pending::PendingKind::UploadKey, Some(account)) => {
+                                crate::provider::upload_public_key(account)
+                            }
+                        })
+                    })
+                    .collect();
+                batch
+                    .iter()
+                    .zip(handles)
+                    .map(|(action, handle)| {
+                        let result = handle
+                            .join()
+                            .unwrap_or_else(|_| Err(String::from("the upload panicked")));
+                        (action, result)
+                    })
+                    .collect()
+            });
+
+        // Re-read the queue so actions queued meanwhile by `add --upload` are kept
+        let mut queue = pending::load_pending();
+        for (action, result) in results {
+            let same = |queued: &pending::PendingAction| {
+                queued.kind == action.kind && queued.account == action.account
+            };
+            match result {
+                Ok(uploaded) => {
+                    say!(
+                        "✅ {}: uploaded public key as {}.",
+                        action.account,
+                        uploaded
+                    );
+                    queue.retain(|queued| !same(queued));
+                }
+                Err(e) if !accounts.iter().any(|acc| acc.name == action.account) => {
+                    say!("🗑️ {}: dropped ({}).", action.account, e);
+                    queue.retain(|queued| !same(queued));
+                }
+                Err(e) => {
+                    say!("❌ {}: {}", action.account, e);
+                    if let Some(queued) = queue.iter_mut().find(|queued| same(queued)) {
+                        queued.attempts += 1;
+                        queued.last_error = Some(e);
+                    }
+                }
+            }
+        }
+        if let Err(e) = pending::save_pending(&queue) {
+            problem!("❌ Failed to update the pending queue: {}", e);
+            break;
+        }
+    }
+    let _ = fs::remove_file(&lock_path);
+    Ok(())
+}
+
+#[cfg(not(feature = "provider-api"))]
+pub fn retry_pending() -> Result<()> {
+    Err(GitSwitchError::Unsupported(
+        "Retrying provider actions requires git-switch built with the `provider-api` feature."
+            .to_string(),
+    ))
+}
+
+#[cfg(not(feature = "provider-api"))]
+fn upload_key_for(_account: &Account) -> Result<()> {
+    Err(GitSwitchError::Unsupported(
+        "Key upload requires git-switch built with the `provider-api` feature.".to_string(),
+    ))
+}
+
+/// Connects to each of the account's hosts through its SSH alias and checks
+/// that the host's greeting names the account's username. With `api`, asks
+/// the provider's API instead.
+pub fn verify_account(name_or_username: &str, api: bool) -> Result<()> {
+    let account = find_account(name_or_username)?;
+    if api {
+        return verify_account_api(&account);
+    }
+    let mut failed = Vec::new();
+    for host in account.hosts() {
+        if account.uses_https() {
+            if secrets::get(&account.name, Secret::Token(host)).is_some() {
+                say!("✅ A token for {} is stored in the OS keyring.", host);
+            } else {
+                failed.push(host);
+                say!(
+                    "❌ No token for {} is stored; add one with `git-switch secret set {} token --host {}`.",
+                    host,
+                    account.name,
+                    host
+                );
+            }
+            continue;
+        }
+        let alias = host_alias(host, &account.name);
+        match account.proxy_jump.as_ref().filter(|_| host == account.host) {
+            Some(jump) => say!("🔍 Running `ssh -T {}` (via {})...", alias, jump),
+            None => say!("🔍 Running `ssh -T {}`...", alias),
+        }
+        let Some(output) = doctor::ssh_greeting(&alias) else {
+            return Err(GitSwitchError::CommandFailed {
+                command: format!("ssh -T {}", alias),
+                code: None,
+                stderr: String::new(),
+            });
+        };
+        match crate::provider::authenticated_user(host, &output) {
+            Some(user) if user.eq_ignore_ascii_case(&account.username) => {
+                say!("✅ {} authenticates the key as {}.", host, user);
+            }
+            Some(user) => {
+                failed.push(host);
+                say!(
+                    "❌ {} authenticates the key as '{}', not '{}'.",
+                    host,
+                    user,
+                    account.username
+                );
+                println!(
+                    "   Remove {}.pub from {}'s SSH keys and add it to {}'s,",
+                    account.ssh_key, user, account.username
+                );
+                println!(
+                    "   or, if '{}' is the right username, run `git-switch edit {} --username {}`.",
+                    user, account.name, user
+                );
+            }
+            None => {
+                failed.push(host);
+                say!(
+                    "❌ Could not confirm who {} authenticates the key as:",
+                    host
+                );
+                for line in output.lines().filter(|line| !line.trim().is_empty()) {
+                    println!("   {}", line);
+                }
+                match account_provider(&account, host) {
+                    Some(provider) => println!(
+                        "   Add the public key at {} and try again.",
+                        provider.key_settings_url
+                    ),
+                    None => println!(
+                        "   Add the public key to your account on {} and try again.",
+                        host
+                    ),
+                }
+            }
+        }
+    }
+    if !failed.is_empty() {
+        return Err(GitSwitchError::CheckFailed(format!(
+            "Account '{}' could not be verified on {}.",
+            account.name,
+            failed.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Connects to the account's primary host through its SSH alias. When the
+/// host can't be reached and the provider takes SSH on port 443 too, tries
+/// that and offers to write it into the account's SSH config.
+pub fn test_connection(name_or_username: &str, yes: bool) -> Result<()> {
+    let account = find_account(name_or_username)?;
+    if account.uses_https() {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "Account '{}' uses HTTPS; run `git-switch verify {}` to check its token.",
+            account.name, account.name
+        )));
+    }
+    let host = account.host.as_str();
+    let alias = host_alias(host, &account.name);
+    let (server, port) = account.ssh_endpoint(host);
+    let endpoint = format!("{}:{}", server, port.unwrap_or(22));
+    match &account.proxy_jump {
+        Some(jump) => say!(
+            "🔌 Connecting to {} through {}, via {}...",
+            endpoint,
+            alias,
+            jump
+        ),
+        None => say!("🔌 Connecting to {} through {}...", endpoint, alias),
+    }
+    let ssh_failed = || GitSwitchError::CommandFailed {
+        command: format!("ssh -T {}", alias),
+        code: None,
+        stderr: String::new(),
+    };
+    let output = doctor::ssh_greeting(&alias).ok_or_else(ssh_failed)?;
+    if !doctor::ssh_unreachable(&output) {
+        return report_ssh_login(&account, host, &output);
+    }
+    say!("❌ Could not reach {}:", endpoint);
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        println!("   {}", line);
+    }
+
+    if let Some(jump) = &account.proxy_jump {
+        return Err(GitSwitchError::CheckFailed(format!(
+            "{} is unreachable via {}; check that you can reach the jump host with `ssh {}`.",
+            endpoint, jump, jump
+        )));
+    }
+    let Some(fallback) = account_provider(&account, host)
+        .and_then(|provider| provider.ssh_443)
+        .filter(|fallback| account.hostname.as_deref() != Some(*fallback))
+    else {
+        return Err(GitSwitchError::CheckFailed(format!(
+            "{} is unreachable from this network.",
+            endpoint
+        )));
+    };
+    say!("🔁 Trying {}:443, which also takes SSH...", fallback);
+    let hostname = format!("HostName={}", fallback);
+    // The port 443 server has its own known_hosts entry
+    let output = doctor::ssh_probe(
+        &alias,
+        &[&hostname, "Port=443", "StrictHostKeyChecking=accept-new"],
+    )
+    .ok_or_else(ssh_failed)?;
+    if doctor::ssh_unreachable(&output) {
+        return Err(GitSwitchError::CheckFailed(format!(
+            "Neither {} nor {}:443 is reachable from this network.",
+            endpoint, fallback
+        )));
+    }
+    say!("✅ {}:443 is reachable.", fallback);
+    let answer = if yes {
+        String::from("y")
+    } else {
+        prompt_line(&format!(
+            "Connect '{}' through {}:443 from now on? [Y/n]: ",
+            account.name, fallback
+        ))
+        .unwrap_or_default()
+    };
+    if answer.eq_ignore_ascii_case("n") || (answer.is_empty() && !is_interactive()) {
+        println!(
+            "   Run `git-switch test {} --yes` to write it into the SSH config.",
+            account.name
+        );
+        return Err(GitSwitchError::CheckFailed(format!(
+            "{} is unreachable from this network.",
+            endpoint
+        )));
+    }
+    let mut edited = account.clone();
+    edited.hostname = Some(fallback.to_string());
+    edited.port = Some(443);
+    save_edited_account(&account, edited.clone())?;
+    report_ssh_login(&edited, host, &output)
+}
+
+/// Reports who the host's `ssh -T` greeting says the key logged in as.
+fn report_ssh_login(account: &Account, host: &str, output: &str) -> Result<()> {
+    match crate::provider::authenticated_user(host, output) {
+        Some(user) if user.eq_ignore_ascii_case(&account.username) => {
+            say!("✅ Connected; {} signs the key in as {}.", host, user);
+            Ok(())
+        }
+        Some(user) => Err(GitSwitchError::CheckFailed(format!(
+            "Connected, but {} signs the key in as '{}', not '{}'; run `git-switch verify {}` for details.",
+            host, user, account.username, account.name
+        ))),
+        None => {
+            for line in output.lines().filter(|line| !line.trim().is_empty()) {
+                println!("   {}", line);
+            }
+            Err(GitSwitchError::CheckFailed(format!(
+                "Connected to {}, but it didn't accept the key; add {}.pub to {}'s account there.",
+                host, account.ssh_key, account.username
+            )))
+        }
+    }
+}
+
+/// Checks with the provider's API that the account's token belongs to its
+/// username and that its key is registered to that user.
+#[cfg(feature = "provider-api")]
+fn verify_account_api(account: &Account) -> Result<()> {
+    let identity = crate::provider::api_identity(account).map_err(GitSwitchError::CheckFailed)?;
+    report_api_identity(account, &identity)
+}
+
+/// Compares what the provider said with the account and reports each
+/// finding, failing on a username or key mismatch.
+#[cfg(feature = "provider-api")]
+pub(crate) fn report_api_identity(
+    account: &Account,
+    identity: &crate::provider::ApiIdentity,
+) -> Result<()> {
+    let mut ok = true;
+    if identity.login.eq_ignore_ascii_case(&account.username) {
+        say!(
+            "✅ {} confirms the username {}.",
+            identity.provider,
+            identity.login
+        );
+    } else {
+        ok = false;
+        say!(
+            "❌ The token belongs to {} user '{}', but the account's username is '{}'.",
+            identity.provider,
+            identity.login,
+            account.username
+        );
+        println!(
+            "   If '{}' is the right username, run `git-switch edit {} --username {}`; otherwise use {}'s token.",
+            identity.login, account.name, identity.login, account.username
+        );
+    }
+    match identity.key_registered {
+        Some(true) => say!("✅ The key is registered to {}.", identity.login),
+        Some(false) => {
+            ok = false;
+            say!(
+                "❌ {}.pub is not among {}'s SSH keys; add it with `git-switch key upload {}`.",
+                account.ssh_key,
+                identity.login,
+                account.name
+            );
+        }
+        None if account.uses_https() => {}
+        None => say!(
+            "⚠️ {} didn't list {}'s SSH keys; run `git-switch verify {}` to check the key over SSH.",
+            identity.provider,
+            identity.login,
+            account.name
+        ),
+    }
+    if !ok {
+        return Err(GitSwitchError::CheckFailed(format!(
+            "Account '{}' could not be verified with {}.",
+            account.name, identity.provider
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "provider-api"))]
+fn verify_account_api(_account: &Account) -> Result<()> {
+    Err(GitSwitchError::Unsupported(
+        "Verifying with a provider's API requires git-switch built with the `provider-api` feature."
+            .to_string(),
+    ))
+}
+
+/// Adds the account's key to the agent, with the passphrase from the OS
+/// keyring if one is stored; otherwise ssh-add asks for it.
+fn load_key(account: &Account) -> Result<()> {
+    if let Some(passphrase) = secrets::get(&account.name, Secret::Passphrase) {
+        match add_ssh_key_with_passphrase(&account.ssh_key, &passphrase, account.key_ttl) {
+            Ok(()) => return Ok(()),
+            Err(e) => problem!("⚠️ The stored passphrase didn't work: {}", e),
+        }
+    }
+    add_ssh_key(&account.ssh_key, account.key_ttl)
+}
+
+/// Resolves `secret set`/`secret remove` arguments; a token belongs to one
+/// of the account's hosts.
+fn secret_kind<'a>(account: &'a Account, kind: &str, host: Option<&str>) -> Result<Secret<'a>> {
+    if kind == "passphrase" {
+        return Ok(Secret::Passphrase);
+    }
+    let host = host.unwrap_or(&account.host);
+    account
+        .hosts()
+        .into_iter()
+        .find(|h| *h == host)
+        .map(Secret::Token)
+        .ok_or_else(|| {
+            GitSwitchError::InvalidInput(format!(
+                "Account '{}' doesn't use {}; its hosts are {}.",
+                account.name,
+                host,
+                account.hosts().join(", ")
+            ))
+        })
+}
+
+/// Stores an account's key passphrase or API token in the OS keyring.
+pub fn set_secret(
+    name_or_username: &str,
+    kind: &str,
+    host: Option<&str>,
+    from_stdin: bool,
+) -> Result<()> {
+    let account = find_account(name_or_username)?;
+    let secret = secret_kind(&account, kind, host)?;
+    if !secrets::AVAILABLE {
+        // Fail before asking for the secret
+        return secrets::set(&account.name, secret, "");
+    }
+    let value = if from_stdin {
+        read_stdin_line().context("Failed to read the secret from stdin")?
+    } else {
+        let message = match secret {
+            Secret::Passphrase | Secret::AccountsFile => {
+                format!("Passphrase of {}: ", account.ssh_key)
+            }
+            Secret::Token(host) => format!("API token for {} on {}: ", account.name, host),
+        };
+        prompt_password(&message).unwrap_or_default()
+    };
+    if value.is_empty() {
+        return Err(GitSwitchError::InvalidInput(
+            "Nothing was entered.".to_string(),
+        ));
+    }
+    secrets::set(&account.name, secret, &value)?;
+    say!(
+        "🔐 Saved the {} for '{}' in the OS keyring.",
+        kind,
+        account.name
+    );
+    Ok(())
+}
+
+/// Deletes a stored passphrase or API token.
+pub fn remove_secret(name_or_username: &str, kind: &str, host: Option<&str>) -> Result<()> {
+    let account = find_account(name_or_username)?;
+    let secret = secret_kind(&account, kind, host)?;
+    if secrets::delete(&account.name, secret)? {
+        say!(
+            "🗑️ Removed the {} for '{}' from the OS keyring.",
+            kind,
+            account.name
+        );
+    } else {
+        say!("ℹ️ No {} is stored for '{}'.", kind, account.name);
+    }
+    Ok(())
+}
+
+/// Changes the passphrase of an account's SSH key and refreshes the agent
+/// (and the macOS keychain) so the next `use` picks up the new passphrase.
+/// A passphrase stored in the OS keyring is replaced by the new one.
+pub fn change_passphrase(name_or_username: &str) -> Result<()> {
+    let account = find_account(name_or_username)?;
+    say!(
+        "🔑 Changing passphrase for {} (account '{}')",
+        account.ssh_key,
+        account.name
+    );
+    let new_passphrase = match secrets::get(&account.name, Secret::Passphrase) {
+        Some(old) => {
+            let new = read_new_passphrase(false, "the key")?;
+            replace_key_passphrase(&account.ssh_key, &old, &new)?;
+            match secrets::set(&account.name, Secret::Passphrase, &new) {
+                Ok(()) => say!("🔐 Updated the passphrase stored in the OS keyring."),
+                Err(e) => problem!(
+                    "⚠️ Passphrase changed, but the OS keyring still holds the old one: {}",
+                    e
+                ),
+            }
+            Some(new)
+        }
+        None => {
+            change_key_passphrase(&account.ssh_key)?;
+            None
+        }
+    };
+
+    let loaded = agent_fingerprints().unwrap_or_default();
+    let in_agent = key_fingerprint(&account.ssh_key).is_some_and(|fp| loaded.contains(&fp));
+    let readd = || match &new_passphrase {
+        Some(passphrase) => {
+            unload_key(&account.ssh_key);
+            add_ssh_key_with_passphrase(&account.ssh_key, passphrase, account.key_ttl)
+        }
+        None => readd_ssh_key(&account.ssh_key, account.key_ttl),
+    };
+    if (in_agent || cfg!(target_os = "macos")) && readd().is_err() {
+        say!("⚠️ Passphrase changed, but the key could not be re-added to the SSH agent.");
+        return Ok(());
+    }
+    say!("✅ Passphrase updated for account '{}'.", account.name);
+    Ok(())
+}
+
+pub struct RotateOptions {
+    /// Passphrase for the new keys; otherwise the one stored in the OS
+    /// keyring is kept, or asked for if the old key had one.
+    pub passphrase: Option<String>,
+    /// Upload the new public key through the provider API.
+    pub upload: bool,
+}
+
+/// Replaces an account's SSH key with a new one of the same type at the
+/// same path. The old key is kept as `<key>.old` until it has been removed
+/// from the host, and the agent is moved over to the new key if it held
+/// the old one.
+pub fn rotate_key(name_or_username: &str, options: &RotateOptions) -> Result<()> {
+    let account = find_account(name_or_username)?;
+    if account.uses_https() {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "Account '{}' uses HTTPS and has no SSH key to rotate.",
+            account.name
+        )));
+    }
+    if account.external_key {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "The key of '{}' was registered with --ssh-key, so git-switch leaves it alone; create a new one and switch with `git-switch edit {} --ssh-key PATH`.",
+            account.name, account.name
+        )));
+    }
+    let public_key = read_public_key(&account.ssh_key)?;
+    let (key_type, key_bits) = KeyType::from_public_key(&public_key).ok_or_else(|| {
+        GitSwitchError::InvalidInput(format!(
+            "Can't tell the type of {}.pub, so it can't be rotated.",
+            account.ssh_key
+        ))
+    })?;
+    let passphrase = match options
+        .passphrase
+        .clone()
+        .or_else(|| secrets::get(&account.name, Secret::Passphrase))
+    {
+        Some(passphrase) => Some(passphrase),
+        // Don't quietly replace a protected key with an unprotected one
+        None if key_is_encrypted(&account.ssh_key) => {
+            if !is_interactive() {
+                return Err(GitSwitchError::InvalidInput(format!(
+                    "The key of '{}' has a passphrase; pass --passphrase-stdin to give the new key one.",
+                    account.name
+                )));
+            }
+            Some(read_new_passphrase(
+                false,
+                &format!("the new key of '{}'", account.name),
+            )?)
+        }
+        None => None,
+    };
+
+    let old_fingerprint = key_fingerprint(&account.ssh_key);
+    let in_agent = old_fingerprint.as_ref().is_some_and(|fingerprint| {
+        agent_fingerprints().is_some_and(|loaded| loaded.contains(fingerprint))
+    });
+    let backup = format!("{}.old", account.ssh_key);
+    delete_ssh_key_files(&backup).context("Failed to delete the previous backup")?;
+    rename_ssh_key_files(&account.ssh_key, &backup).context("Failed to back up the old key")?;
+    let sk_options = security_key_args(&account.name, &account.key_options);
+    if let Err(e) = generate_ssh_key(
+        &account.ssh_key,
+        key_type,
+        key_bits,
+        passphrase.as_deref(),
+        &sk_options,
+        &key_comment(&settings::load_settings(), &account.name, &account.email),
+    ) {
+        let _ = delete_ssh_key_files(&account.ssh_key);
+        let _ = rename_ssh_key_files(&backup, &account.ssh_key);
+        return Err(GitSwitchError::io("Failed to generate the new key", e));
+    }
+
+    if in_agent {
+        unload_key(&backup);
+        let loaded = match &passphrase {
+            Some(passphrase) => {
+                add_ssh_key_with_passphrase(&account.ssh_key, passphrase, account.key_ttl)
+            }
+            None => add_ssh_key(&account.ssh_key, account.key_ttl),
+        };
+        if let Err(e) = loaded {
+            problem!("⚠️ Could not add the new key to ssh-agent: {}", e);
+        }
+    }
+    if let Some(passphrase) = &passphrase
+        && secrets::AVAILABLE
+        && let Err(e) = secrets::set(&account.name, Secret::Passphrase, passphrase)
+    {
+        problem!("⚠️ Could not save the passphrase: {}", e);
+    }
+    say!(
+        "✅ Rotated the key of '{}'; the old key is kept at {}.",
+        account.name,
+        backup
+    );
+
+    if options.upload {
+        upload_key_for(&account)?;
+    } else {
+        say!(
+            "📋 Add the new public key to {}:",
+            account.hosts().join(", ")
+        );
+        display_public_key(&account.ssh_key);
+    }
+    if let Some(fingerprint) = old_fingerprint {
+        say!(
+            "ℹ️ Then remove the old key ({}) from the host and delete {}.",
+            fingerprint,
+            backup
+        );
+    }
+    Ok(())
+}
+
+/// Opens all accounts as TOML in `$EDITOR`, then validates, previews and
+/// applies the changes together with the SSH config updates they imply.
+pub fn edit_all_accounts() -> Result<()> {
+    let before = load_accounts();
+    let mut text = accounts_to_toml(&before)
+        .map_err(|e| GitSwitchError::io("Failed to serialize accounts", io::Error::other(e)))?;
+
+    let after = loop {
+        text = edit_text(&text, ".toml").context("Failed to edit the accounts")?;
+        let errors = match accounts_from_toml(&text) {
+            Ok(accounts) => {
+                let errors = validate_accounts(&accounts);
+                if errors.is_empty() {
+                    break accounts;
+                }
+                errors
+            }
+            Err(e) => vec![format!("invalid TOML: {}", e)],
+        };
+        say!("❌ The edited accounts are not valid:");
+        for error in &errors {
+            println!("   - {}", error);
+        }
+        let answer = prompt_line("Re-open the editor? [Y/n]: ").unwrap_or_default();
+        if answer.eq_ignore_ascii_case("n") {
+            return Err(GitSwitchError::Cancelled(
+                "No changes were applied.".to_string(),
+            ));
+        }
+    };
+
+    let changes = diff_accounts(&before, &after);
+    if changes.is_empty() {
+        say!("ℹ️ No changes.");
+        return Ok(());
+    }
+    print_account_changes(&changes);
+
+    let answer = prompt_line("Apply these changes? [y/N]: ").unwrap_or_default();
+    if !answer.eq_ignore_ascii_case("y") {
+        return Err(GitSwitchError::Cancelled(
+            "No changes were applied.".to_string(),
+        ));
+    }
+
+    apply_account_changes(&after, &changes)
+        .context("Failed to apply changes, nothing was modified")?;
+    say!("✅ Applied {} account change(s).", changes.len());
+    Ok(())
+}
+
+/// Field changes requested with `edit <name> --email/--username/--ssh-key`.
+#[derive(Debug, Default)]
+pub struct AccountEdit {
+    pub email: Option<String>,
+    pub username: Option<String>,
+    pub ssh_key: Option<String>,
+    /// SSH options to set; an empty value removes the option.
+    pub ssh_options: Vec<(String, String)>,
+    /// Git settings to set; an empty value removes the setting.
+    pub git_config: Vec<(String, String)>,
+    pub exclusive_key: Option<bool>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    pub add_orgs: Vec<String>,
+    pub remove_orgs: Vec<String>,
+    /// Provider software of a self-hosted host; empty clears it.
+    pub provider: Option<String>,
+    /// SSH server of the primary host; empty clears it.
+    pub hostname: Option<String>,
+    /// SSH port of the primary host; 22 clears it.
+    pub port: Option<u16>,
+    /// SSH user of the primary host; empty clears it.
+    pub ssh_user: Option<String>,
+    /// Jump host of the primary host; empty clears it.
+    pub proxy_jump: Option<String>,
+}
+
+/// Updates fields of a single account and rewrites its SSH config blocks,
+/// keeping its key files.
+pub fn edit_account(name_or_username: &str, edit: AccountEdit) -> Result<()> {
+    if edit.email.is_none()
+        && edit.username.is_none()
+        && edit.ssh_key.is_none()
+        && edit.ssh_options.is_empty()
+        && edit.git_config.is_empty()
+        && edit.exclusive_key.is_none()
+        && edit.add_tags.is_empty()
+        && edit.remove_tags.is_empty()
+        && edit.add_orgs.is_empty()
+        && edit.remove_orgs.is_empty()
+        && edit.provider.is_none()
+        && edit.hostname.is_none()
+        && edit.port.is_none()
+        && edit.ssh_user.is_none()
+        && edit.proxy_jump.is_none()
+    {
+        return Err(GitSwitchError::InvalidInput(
+            "Nothing to change; pass --email, --username, --ssh-key, --ssh-option, --git-config, --exclusive-key, --tag, --untag, --org, --remove-org, --provider, --hostname, --port, --ssh-user or --proxy-jump (or use --all)."
+                .to_string(),
+        ));
+    }
+    let account = find_account(name_or_username)?;
+    let mut edited = account.clone();
+    if let Some(email) = edit.email {
+        edited.email = email;
+    }
+    if let Some(username) = edit.username {
+        edited.username = username;
+    }
+    if let Some(ssh_key) = edit.ssh_key {
+        edited.ssh_key = ssh_key;
+    }
+    if let Some(exclusive_key) = edit.exclusive_key {
+        edited.exclusive_key = exclusive_key;
+    }
+    edited.tags.retain(|tag| {
+        !edit
+            .remove_tags
+            .iter()
+            .any(|gone| gone.eq_ignore_ascii_case(tag))
+    });
+    for tag in edit.add_tags {
+        if !edited.has_tag(&tag) {
+            edited.tags.push(tag);
+        }
+    }
+    if let Some(provider) = edit.provider {
+        edited.provider = Some(provider).filter(|provider| !provider.is_empty());
+    }
+    if let Some(hostname) = edit.hostname {
+        edited.hostname = Some(hostname).filter(|hostname| !hostname.is_empty());
+    }
+    if let Some(port) = edit.port {
+        edited.port = Some(port).filter(|port| *port != 22);
+    }
+    if let Some(ssh_user) = edit.ssh_user {
+        edited.ssh_user = Some(ssh_user).filter(|ssh_user| !ssh_user.is_empty());
+    }
+    if let Some(jump) = edit.proxy_jump {
+        edited.proxy_jump = Some(jump).filter(|jump| !jump.is_empty());
+    }
+    edited.orgs.retain(|org| !edit.remove_orgs.contains(org));
+    for org in edit.add_orgs {
+        if !edited.orgs.contains(&org) {
+            edited.orgs.push(org);
+        }
+    }
+    for (option, value) in edit.ssh_options {
+        // Options are matched case-insensitively, like ssh does
+        edited
+            .ssh_options
+            .retain(|existing, _| !existing.eq_ignore_ascii_case(&option));
+        if !value.is_empty() {
+            edited.ssh_options.insert(option, value);
+        }
+    }
+    for (key, value) in edit.git_config {
+        edited
+            .git_config
+            .retain(|existing, _| !existing.eq_ignore_ascii_case(&key));
+        if !value.is_empty() {
+            edited.git_config.insert(key, value);
+        }
+    }
+    save_edited_account(&account, edited)
+}
+
+/// Saves an edited copy of `account`, rewriting the files its changes
+/// touch.
+fn save_edited_account(account: &Account, edited: Account) -> Result<()> {
+    let before = load_accounts();
+    let after: Vec<Account> = before
+        .iter()
+        .map(|acc| {
+            if acc.name == account.name {
+                edited.clone()
+            } else {
+                acc.clone()
+            }
+        })
+        .collect();
+    let errors = validate_accounts(std::slice::from_ref(&edited));
+    if !errors.is_empty() {
+        return Err(GitSwitchError::InvalidInput(errors.join("\n")));
+    }
+
+    let changes = diff_accounts(&before, &after);
+    if changes.is_empty() {
+        say!("ℹ️ No changes.");
+        return Ok(());
+    }
+    print_account_changes(&changes);
+    apply_account_changes(&after, &changes)
+        .context("Failed to apply changes, nothing was modified")?;
+    say!("✅ Account '{}' updated.", edited.name);
+    if let Some(AccountChange::Modified(_, _, fields)) = changes.first() {
+        history::record("edit", &edited.name, None, Some(fields.join(", ")));
+    }
+
+    let active_email = config_value(None, "user.email").unwrap_or_default();
+    if active_email == account.email {
+        say!(
+            "ℹ️ This account is active; run `git-switch use {}` to apply the changes to your Git config.",
+            edited.name
+        );
+    }
+    Ok(())
+}
+
+/// Puts the files the last command changed back the way they were, from
+/// the backups it made. The restore is itself recorded, so running `undo`
+/// again redoes the command.
+pub fn undo() -> Result<()> {
+    let Some(record) = undo::load_undo() else {
+        return Err(GitSwitchError::InvalidInput("Nothing to undo.".to_string()));
+    };
+    if let Some(missing) = record
+        .files
+        .iter()
+        .filter_map(|file| file.backup.as_ref())
+        .find(|backup| !backup.is_file())
+    {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "Can't undo `{}`: its backup {} is gone.",
+            record.command,
+            missing.display()
+        )));
+    }
+
+    let before = load_accounts();
+    for file in &record.files {
+        match &file.backup {
+            Some(backup) => {
+                let content = fs::read_to_string(backup)
+                    .context(format!("Failed to read {}", backup.display()))?;
+                files::write_with_backup(&file.path, &content)
+                    .context(format!("Failed to restore {}", file.path.display()))?;
+                say!(
+                    "↩️ Restored {} from {}",
+                    file.path.display(),
+                    backup.display()
+                );
+            }
+            None if file.path.exists() => {
+                files::remove_with_backup(&file.path)
+                    .context(format!("Failed to remove {}", file.path.display()))?;
+                say!(
+                    "🗑️ Removed {}, which didn't exist before.",
+                    file.path.display()
+                );
+            }
+            None => {}
+        }
+    }
+    for change in diff_accounts(&before, &load_accounts()) {
+        match change {
+            AccountChange::Added(acc) => say!("   + {} is back", acc.name),
+            AccountChange::Removed(acc) => say!("   - {} is gone again", acc.name),
+            AccountChange::Modified(_, acc, fields) => {
+                say!("   ~ {} (restored: {})", acc.name, fields.join(", "))
+            }
+        }
+    }
+    say!(
+        "✅ Undid `{}` from {} UTC; run `git-switch undo` again to redo it.",
+        record.command,
+        format_timestamp(record.ts)
+    );
+    if ["remove", "prune"]
+        .iter()
+        .any(|command| record.command.starts_with(command))
+    {
+        say!("ℹ️ Key files and OS keyring secrets aren't restored by `undo`.");
+    }
+    Ok(())
+}
+
+fn print_account_changes(changes: &[AccountChange]) {
+    println!("Pending changes:");
+    for change in changes {
+        match change {
+            AccountChange::Added(acc) => {
+                println!("  + {} ({} <{}>)", acc.name, acc.username, acc.email);
+                println!("      SSH config: add Host {}", host_aliases(acc));
+            }
+            AccountChange::Removed(acc) => {
+                println!("  - {}", acc.name);
+                println!("      SSH config: remove Host {}", host_aliases(acc));
+                println!("      Key files are kept: {}", acc.ssh_key);
+            }
+            AccountChange::Modified(old, new, fields) => {
+                println!("  ~ {} (changed: {})", new.name, fields.join(", "));
+                if fields.iter().any(|field| {
+                    matches!(
+                        *field,
+                        "ssh_key" | "hosts" | "ssh endpoint" | "proxy_jump" | "ssh_options"
+                    )
+                }) {
+                    println!(
+                        "      SSH config: replace Host {} with Host {}",
+                        host_aliases(old),
+                        host_aliases(new)
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn host_aliases(account: &Account) -> String {
+    account
+        .hosts()
+        .iter()
+        .map(|host| host_alias(host, &account.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Writes the edited accounts and rewrites the SSH config blocks they need.
+/// If any step fails, the accounts file and SSH config are restored.
+fn apply_account_changes(accounts: &[Account], changes: &[AccountChange]) -> io::Result<()> {
+    let snapshot = Snapshot::take(&[
+        get_default_config_path(),
+        PathBuf::from(get_ssh_config_path()),
+    ]);
+
+    let result = (|| {
+        write_accounts(accounts)?;
+        for change in changes {
+            match change {
+                AccountChange::Added(new) => update_ssh_config(new)?,
+                AccountChange::Removed(old) => remove_ssh_config_entries(old)?,
+                AccountChange::Modified(old, new, fields) => {
+                    if fields.iter().any(|field| {
+                        matches!(
+                            *field,
+                            "ssh_key" | "hosts" | "ssh endpoint" | "proxy_jump" | "ssh_options"
+                        )
+                    }) {
+                        remove_ssh_config_entries(old)?;
+                        update_ssh_config(new)?;
+                    }
+                    includes::refresh_identity_fragment(new)?;
+                }
+            }
+        }
+        Ok(())
+    })();
+
+    if result.is_err() {
+        snapshot.restore();
+    }
+    result
+}
+
+/// Removes the key files (and keyring passphrase) `add` created for an
+/// account it then failed to save.
+fn roll_back_new_key(account: &Account) {
+    if let Err(e) = delete_ssh_key_files(&account.ssh_key) {
+        problem!("⚠️ Could not delete the new key {}: {}", account.ssh_key, e);
+    }
+    if secrets::AVAILABLE {
+        let _ = secrets::delete(&account.name, Secret::Passphrase);
+    }
+}
+
+/// Files as they were before a change of several steps, so a failed step
+/// can put all of them back.
+struct Snapshot(Vec<(PathBuf, Option<String>)>);
+
+impl Snapshot {
+    fn take(paths: &[PathBuf]) -> Snapshot {
+        Snapshot(
+            paths
+                .iter()
+                .map(|path| (path.clone(), fs::read_to_string(path).ok()))
+                .collect(),
+        )
+    }
+
+    fn restore(&self) {
+        for (path, content) in &self.0 {
+            restore_file(path, content.as_deref());
+        }
+    }
+}
+
+fn restore_file(path: impl AsRef<Path>, content: Option<&str>) {
+    let _ = match content {
+        Some(content) => files::write_atomic(path.as_ref(), content),
+        None => fs::remove_file(path),
+    };
+}
+
+/// Finds a saved account by name or username.
+fn find_account(name_or_username: &str) -> Result<Account> {
+    load_accounts()
+        .into_iter()
+        .find(|acc| acc.name == name_or_username || acc.username == name_or_username)
+        .ok_or_else(|| GitSwitchError::AccountNotFound(name_or_username.to_string()))
+}
+
+pub fn clone_repo(
+    name_or_username: &str,
+    repo: &str,
+    dir: Option<&str>,
+    mode: Option<SwitchMode>,
+) -> Result<()> {
+    let acc = find_account(name_or_username)?;
+    let mode = mode.unwrap_or_else(|| settings::load_settings().mode);
+    let url = repo_ssh_url(&acc, repo, mode == SwitchMode::Alias);
+    clone_with(&acc, &url, repo, clone_destination(&acc, repo, dir), mode)
+}
+
+/// Clones a repository given as a remote URL or `host/owner/repo` with the
+/// account the rules pick for it.
+pub fn clone_by_rule(spec: &str, dir: Option<&str>, mode: Option<SwitchMode>) -> Result<()> {
+    let Some(remote) = rules::parse_repository(spec) else {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "'{}' is not a repository URL or 'host/owner/repo'.",
+            spec
+        )));
+    };
+    let accounts = load_accounts();
+    let rules = rules::load_rules().context("Failed to load rules")?;
+    let (acc, reason) = match rules::match_remote(&rules, &accounts, &remote) {
+        Some((rule, acc)) => {
+            say!(
+                "🔎 {} matches {} → account '{}'.",
+                spec,
+                rule.pattern,
+                acc.name
+            );
+            (acc, format!("Rule {}", rule.pattern))
+        }
+        None => match default_account(&accounts) {
+            Some(acc) => {
+                say!(
+                    "🔎 No rule matches {}; using the default account '{}'.",
+                    spec,
+                    acc.name
+                );
+                (acc, "The default account".to_string())
+            }
+            None => {
+                return Err(GitSwitchError::InvalidInput(format!(
+                    "No rule matches {}. Add one with `git-switch rule add` or name the account: `git-switch clone <account> <repo>`.",
+                    rules::remote_key(&remote, &accounts)
+                )));
+            }
+        },
+    };
+    let mode = mode.unwrap_or_else(|| settings::load_settings().mode);
+    let plain_url = format!("git@{}:{}", remote.host, remote.path);
+    let via_alias = mode == SwitchMode::Alias;
+    let Some(url) = rewritten_remote_url(acc, &accounts, &plain_url, via_alias) else {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "{} names account '{}', which is not set up for {}.",
+            reason, acc.name, remote.host
+        )));
+    };
+    let repo = remote.path.clone();
+    clone_with(acc, &url, &repo, clone_destination(acc, &repo, dir), mode)
+}
+
+fn clone_with(
+    acc: &Account,
+    url: &str,
+    repo: &str,
+    destination: Option<String>,
+    mode: SwitchMode,
+) -> Result<()> {
+    let with_ssh_command = mode == SwitchMode::SshCommand && !acc.uses_https();
+    // The key is needed for the clone itself, before the repository's
+    // config exists
+    let ssh_command = format!("core.sshCommand={}", render::ssh_command(acc));
+    let mut args = Vec::new();
+    if with_ssh_command {
+        args.extend(["-c", ssh_command.as_str()]);
+    }
+    args.extend(["clone", url]);
+    if let Some(destination) = &destination {
+        if let Some(parent) = Path::new(destination).parent() {
+            std::fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        args.push(destination);
+    }
+    run_command("git", &args)?;
+    say!("✅ Cloned {} with account '{}'.", repo, acc.name);
+    let cloned = destination.unwrap_or_else(|| {
+        repo.trim_end_matches(".git")
+            .rsplit('/')
+            .next()
+            .unwrap_or(repo)
+            .to_string()
+    });
+    configure_repository(Path::new(&cloned), acc, with_ssh_command).context(format!(
+        "Failed to set the repository's identity (run `git-switch use {} --local` inside it)",
+        acc.name
+    ))?;
+    say!(
+        "✅ Set the repository's identity to {} <{}>.",
+        acc.username,
+        acc.email
+    );
+    report_manifest_violations(Path::new(&cloned), acc, &load_accounts());
+    Ok(())
+}
+
+/// Prints how the account falls short of the repository's manifest and which
+/// accounts would comply. Returns whether there were violations.
+fn report_manifest_violations(repo: &Path, account: &Account, accounts: &[Account]) -> bool {
+    let manifest = match manifest::load_manifest(repo) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => return false,
+        Err(e) => {
+            say!("⚠️ Ignoring invalid {}", e);
+            return false;
+        }
+    };
+    let violations = manifest.account_violations(account);
+    if violations.is_empty() {
+        return false;
+    }
+    say!("⚠️ This repository's {} requires:", manifest::MANIFEST_FILE);
+    for violation in &violations {
+        println!("   - {}", violation);
+    }
+    print_compliant_accounts(&manifest, accounts);
+    true
+}
+
+fn print_compliant_accounts(manifest: &manifest::Manifest, accounts: &[Account]) {
+    let compliant = manifest.compliant_accounts(accounts);
+    if compliant.is_empty() {
+        println!("   No saved account complies. Create one with:");
+        println!("   {}", manifest.add_hint());
+    } else {
+        let names: Vec<&str> = compliant.iter().map(|acc| acc.name.as_str()).collect();
+        println!("   Compliant accounts: {}", names.join(", "));
+    }
+}
+
+/// Checks `use` against the current repository's manifest, asking before
+/// switching to a non-compliant account.
+fn manifest_allows_use(account: &Account, accounts: &[Account]) -> bool {
+    let Some(root) = repository_root() else {
+        return true;
+    };
+    if !report_manifest_violations(Path::new(&root), account, accounts) {
+        return true;
+    }
+    let answer = prompt_line("Use this account anyway? [y/N]: ").unwrap_or_default();
+    answer.eq_ignore_ascii_case("y")
+}
+
+fn repository_root() -> Option<String> {
+    let output = command_output("git", &["rev-parse", "--show-toplevel"]).ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Validates the current repository's identity and remotes against its
+/// manifest. Fails if anything violates it.
+pub fn check_manifest() -> Result<()> {
+    let root = repository_root().ok_or(GitSwitchError::NotInRepository)?;
+    let manifest = match manifest::load_manifest(Path::new(&root)) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => {
+            say!("ℹ️ No {} in this repository.", manifest::MANIFEST_FILE);
+            return Ok(());
+        }
+        Err(e) => return Err(GitSwitchError::InvalidInput(format!("Invalid {}", e))),
+    };
+
+    let accounts = load_accounts();
+    let name = config_value(None, "user.name").unwrap_or_default();
+    let email = config_value(None, "user.email").unwrap_or_default();
+    let active = match_identity(&accounts, &name, &email);
+    let mut violations = match active {
+        Some(acc) => manifest.account_violations(acc),
+        None if !manifest.email_allowed(&email) => vec![format!(
+            "email {} is not in an allowed domain ({})",
+            email,
+            manifest.email_domains.join(", ")
+        )],
+        None => Vec::new(),
+    };
+    // An account without a signing key is already reported above
+    if manifest.require_signing
+        && active.is_none_or(|acc| acc.signing_key.is_some())
+        && config_value(None, "commit.gpgsign").as_deref() != Some("true")
+    {
+        violations.push("commits must be signed, but commit.gpgsign is off".to_string());
+    }
+    if let Some(url) = config_value(None, "remote.origin.url") {
+        violations.extend(manifest.remote_violation(&url));
+    }
+
+    if violations.is_empty() {
+        say!(
+            "✅ Identity {} <{}> complies with {}.",
+            name,
+            email,
+            manifest::MANIFEST_FILE
+        );
+        return Ok(());
+    }
+    say!("❌ This repository's {} requires:", manifest::MANIFEST_FILE);
+    for violation in &violations {
+        println!("   - {}", violation);
+    }
+    print_compliant_accounts(&manifest, &accounts);
+    println!("   Switch with `git-switch use <account> --local`.");
+    Err(GitSwitchError::CheckFailed(format!(
+        "The identity doesn't comply with {}.",
+        manifest::MANIFEST_FILE
+    )))
+}
+
+/// Installs the identity-checking hook in the current repository.
+pub fn install_commit_hook(hook: &str, force: bool) -> Result<()> {
+    let dir = hooks::hooks_dir()?;
+    let path = hooks::install_hook(&dir, hook, force).map_err(GitSwitchError::InvalidInput)?;
+    say!(
+        "✅ Installed {}; commits with another identity than the repository's account are now refused.",
+        path.display()
+    );
+    Ok(())
+}
+
+pub fn uninstall_commit_hook(hook: &str) -> Result<()> {
+    let dir = hooks::hooks_dir()?;
+    let removed = hooks::uninstall_hook(&dir, hook).map_err(|e| {
+        GitSwitchError::InvalidInput(format!("Failed to remove the {} hook: {}", hook, e))
+    })?;
+    if removed {
+        say!("✅ Removed the git-switch {} hook.", hook);
+    } else {
+        say!("ℹ️ No git-switch {} hook in this repository.", hook);
+    }
+    Ok(())
+}
+
+/// Run by the hook: compares the email the next commit will be authored
+/// with against the account the repository belongs to (by host alias, rule,
+/// mapping, workspace or owner). Fails if they differ.
+pub fn check_commit_identity() -> Result<()> {
+    let root = repository_root().ok_or(GitSwitchError::NotInRepository)?;
+    let accounts = load_accounts();
+    let mappings = directory_mappings(&accounts);
+    let rules = rules::load_rules().context("Failed to load rules")?;
+    let report = scan::check_repository(Path::new(&root), &accounts, &mappings, &rules);
+    let Some((name, reason)) = report.expected else {
+        return Ok(());
+    };
+    let Some(account) = accounts.iter().find(|acc| acc.name == name) else {
+        return Ok(());
+    };
+    let email = hooks::author_email().or(report.email).unwrap_or_default();
+    if account.email.eq_ignore_ascii_case(&email) {
+        return Ok(());
+    }
+    Err(GitSwitchError::CheckFailed(format!(
+        "This commit would be authored as {}, but this repository belongs to account '{}' <{}> ({}).\n   Run `git-switch use {} --local` and commit again, or skip this check once with `git commit --no-verify`.",
+        if email.is_empty() {
+            "(no email)"
+        } else {
+            &email
+        },
+        account.name,
+        account.email,
+        reason,
+        account.name
+    )))
+}
+
+/// Prints a configuration snippet for the account to stdout.
+pub fn render_account(name_or_username: &str, target: &str) -> Result<()> {
+    let acc = find_account(name_or_username)?;
+    let snippet = render::render(&acc, target).ok_or_else(|| {
+        GitSwitchError::InvalidInput(format!("Unknown render target '{}'.", target))
+    })?;
+    println!("{}", snippet.trim_end());
+    Ok(())
+}
+
+/// `git-switch direnv`: prints the account's `.envrc` exports, or with
+/// `write` puts them into the `.envrc` in that directory, in a block of
+/// their own that later writes replace.
+pub fn direnv(name_or_username: &str, write: Option<&str>) -> Result<()> {
+    let acc = find_account(name_or_username)?;
+    let Some(dir) = write else {
+        return render_account(&acc.name, "direnv");
+    };
+    let path = Path::new(&*shellexpand::tilde(dir)).join(".envrc");
+    let existing = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context(format!("Failed to read {}", path.display())),
+    };
+    files::write_with_backup(&path, &render::envrc_with_account(&existing, &acc))
+        .context(format!("Failed to write {}", path.display()))?;
+    say!(
+        "✅ {} now exports the identity of '{}'; run `direnv allow` to load it.",
+        path.display(),
+        acc.name
+    );
+    Ok(())
+}
+
+/// `git-switch config`: prints every setting or one of them, or changes
+/// one when a value is given.
+pub fn manage_settings(key: Option<&str>, value: Option<&str>) -> Result<()> {
+    let mut current = settings::read_settings().context("Failed to load settings")?;
+    let Some(key) = key else {
+        for key in settings::KEYS {
+            report!("{} = {}", key, current.get(key).unwrap_or_default());
+        }
+        return Ok(());
+    };
+    let Some(value) = value else {
+        let value = current
+            .get(key)
+            .ok_or_else(|| GitSwitchError::InvalidInput(format!("Unknown setting '{}'.", key)))?;
+        report!("{}", value);
+        return Ok(());
+    };
+    current
+        .set(key, value)
+        .map_err(GitSwitchError::InvalidInput)?;
+    settings::save_settings(&current).context("Failed to save settings")?;
+    say!("✅ {} = {}", key, current.get(key).unwrap_or_default());
+    if key == "mode" {
+        say!(
+            "ℹ️ Repositories pick up the new mode the next time you run `git-switch use` in them."
+        );
+    }
+    Ok(())
+}
+
+/// Prints the account's workspace root, for use in a shell function such as
+/// `gcd() { cd "$(git-switch cd "$1")"; }`.
+pub fn print_workspace(name_or_username: &str) -> Result<()> {
+    let acc = find_account(name_or_username)?;
+    let Some(workspace) = &acc.workspace else {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "Account '{}' has no workspace directory. Add one with `add --workspace <dir>`.",
+            acc.name
+        )));
+    };
+    println!("{}", shellexpand::tilde(workspace));
+    Ok(())
+}
+
+/// Opens the terminal dashboard (`tui` feature).
+pub fn run_dashboard() -> Result<()> {
+    #[cfg(feature = "tui")]
+    return crate::tui::run();
+    #[cfg(not(feature = "tui"))]
+    Err(GitSwitchError::Unsupported(
+        "This build of git-switch has no dashboard (cargo feature `tui`).".to_string(),
+    ))
+}
+
+/// Cargo features this binary was built with.
+pub fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "encryption") {
+        features.push("encryption");
+    }
+    if cfg!(feature = "keychain") {
+        features.push("keychain");
+    }
+    if cfg!(feature = "provider-api") {
+        features.push("provider-api");
+    }
+    if cfg!(feature = "tui") {
+        features.push("tui");
+    }
+    features
+}
+
+pub fn print_version(verbose: bool) {
+    println!("git-switch {}", env!("CARGO_PKG_VERSION"));
+    if !verbose {
+        return;
+    }
+
+    let features = enabled_features();
+    println!("commit:       {}", env!("GIT_SWITCH_COMMIT"));
+    println!("build date:   {}", env!("GIT_SWITCH_BUILD_DATE"));
+    println!(
+        "target:       {}-{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    );
+    println!(
+        "features:     {}",
+        if features.is_empty() {
+            "(none)".to_string()
+        } else {
+            features.join(", ")
+        }
+    );
+    println!("accounts:     {}", get_default_config_path().display());
+    println!("data dir:     {}", get_data_dir().display());
+    println!("ssh config:   {}", get_ssh_config_path());
+}
+
+/// Proposes accounts for the Git host entries in the SSH config and saves
+/// the ones the user confirms, keeping their keys (see [`crate::import`]).
+pub fn import_accounts(dry_run: bool, yes: bool) -> Result<()> {
+    let ssh_config = fs::read_to_string(get_ssh_config_path()).unwrap_or_default();
+    let mut accounts = load_accounts();
+    let proposals = import::propose_accounts(
+        &import::parse_host_entries(&ssh_config),
+        &import::git_identities(),
+        &accounts,
+    );
+    if proposals.is_empty() {
+        say!("ℹ️ No SSH config entries for GitHub, GitLab or other known hosts to import.");
+        return Ok(());
+    }
+
+    let ask = |label: &str, default: &str| {
+        if yes {
+            return default.to_string();
+        }
+        prompt_line(&format!("   {} [{}]: ", label, default))
+            .filter(|answer| !answer.is_empty())
+            .unwrap_or_else(|| default.to_string())
+    };
+    let mut imported = 0;
+    for (entry, mut account) in proposals {
+        say!(
+            "\n📥 Host {} ({}) with key {}",
+            entry.alias,
+            account.host,
+            account.ssh_key
+        );
+        if !Path::new(&*shellexpand::tilde(&account.ssh_key)).exists() {
+            say!("   ⚠️ The key doesn't exist; skipped.");
+            continue;
+        }
+        if dry_run {
+            say!(
+                "   Would import as '{}' ({} <{}>).",
+                account.name,
+                account.username,
+                account.email
+            );
+            continue;
+        }
+        account.name = ask("Account name", &account.name);
+        account.username = ask("Git username", &account.username);
+        account.email = ask("Email", &account.email);
+        if account.username.is_empty() || account.email.is_empty() {
+            say!("   ⚠️ An account needs a username and an email; skipped.");
+            continue;
+        }
+        if accounts.iter().any(|acc| acc.name == account.name) {
+            say!("   ⚠️ An account named '{}' exists; skipped.", account.name);
+            continue;
+        }
+        if !yes {
+            let answer = prompt_line("   Import this account? [y/N]: ").unwrap_or_default();
+            if !answer.eq_ignore_ascii_case("y") {
+                continue;
+            }
+        }
+        save_account(&account).context(format!("Failed to save account '{}'", account.name))?;
+        if let Err(e) = update_ssh_config(&account) {
+            problem!("❌ Failed to update SSH config: {}", e);
+        }
+        say!(
+            "   ✅ Imported account '{}'. Your own Host {} entry is left as it is.",
+            account.name,
+            entry.alias
+        );
+        accounts.push(account);
+        imported += 1;
+    }
+    if !dry_run {
+        say!("\n✅ Imported {} account(s).", imported);
+    }
+    Ok(())
+}
+
+/// The saved accounts with paths under the home directory written as `~/...`,
+/// for use on other machines.
+fn portable_accounts() -> Result<Vec<Account>> {
+    let home = home_dir()?.to_string_lossy().into_owned();
+    Ok(load_accounts()
+        .into_iter()
+        .map(|mut account| {
+            account.ssh_key = portable_path(&account.ssh_key, &home);
+            account.workspace = account.workspace.map(|dir| portable_path(&dir, &home));
+            account.signing_key = account.signing_key.map(|key| portable_path(&key, &home));
+            account.attestation = account.attestation.map(|path| portable_path(&path, &home));
+            account
+        })
+        .collect())
+}
+
+/// Writes all accounts and rules to `output`, or stdout, for `import` on
+/// another machine. With `include_keys` the accounts' keys are added, the
+/// private halves encrypted with a passphrase; with `encrypt` the whole
+/// file is.
+pub fn export_config(
+    format: BundleFormat,
+    include_keys: bool,
+    encrypt: bool,
+    passphrase_stdin: bool,
+    output: Option<&str>,
+) -> Result<()> {
+    let mut bundle = Bundle::new(
+        portable_accounts()?,
+        rules::load_rules().context("Failed to load rules")?,
+    );
+
+    let key = if include_keys || encrypt {
+        if !crypto::AVAILABLE {
+            // Fail before asking for the passphrase
+            return crypto::encrypt(b"", &Key::Passphrase(String::new())).map(drop);
+        }
+        let what = if encrypt {
+            "the export"
+        } else {
+            "the exported keys"
+        };
+        Some(Key::Passphrase(read_new_passphrase(
+            passphrase_stdin,
+            what,
+        )?))
+    } else {
+        None
+    };
+    if include_keys && let Some(key) = &key {
+        for account in &bundle.accounts {
+            let path = shellexpand::tilde(&account.ssh_key).into_owned();
+            let Ok(private_key) = fs::read(&path) else {
+                problem!(
+                    "⚠️ No key at {}; '{}' is exported without it.",
+                    path,
+                    account.name
+                );
+                continue;
+            };
+            bundle.keys.push(KeyMaterial {
+                account: account.name.clone(),
+                public_key: read_public_key(&account.ssh_key)?,
+                private_key: crypto::encrypt(&private_key, key)?,
+            });
+        }
+    }
+
+    let mut text = bundle
+        .to_text(format)
+        .map_err(|e| GitSwitchError::io("Failed to serialize the export", io::Error::other(e)))?;
+    if encrypt && let Some(key) = &key {
+        text = crypto::encrypt(text.as_bytes(), key)?;
+    }
+    let Some(output) = output else {
+        print!("{}", text);
+        return Ok(());
+    };
+    let path = shellexpand::tilde(output).into_owned();
+    files::write_private(Path::new(&path), text.as_bytes())
+        .context(format!("Failed to write {}", path))?;
+    say!(
+        "✅ Exported {} account(s), {} rule(s) and {} key(s) to {}.",
+        bundle.accounts.len(),
+        bundle.rules.len(),
+        bundle.keys.len(),
+        path
+    );
+    Ok(())
+}
+
+/// Adds the accounts and rules of a file written by `export`. Accounts whose
+/// name is taken are skipped; keys are written only where none exists.
+pub fn import_bundle(file: &str, passphrase_stdin: bool) -> Result<()> {
+    let path = shellexpand::tilde(file).into_owned();
+    let mut text = fs::read_to_string(&path).context(format!("Failed to read {}", path))?;
+    let mut key = None;
+    if crypto::is_encrypted(&text) {
+        let passphrase = Key::Passphrase(read_passphrase(
+            passphrase_stdin,
+            "Passphrase of the export: ",
+        )?);
+        text = String::from_utf8(crypto::decrypt(&text, &passphrase)?).map_err(|e| {
+            GitSwitchError::InvalidInput(format!("{} is not a git-switch export: {}", path, e))
+        })?;
+        key = Some(passphrase);
+    }
+    let bundle = Bundle::parse(&text).map_err(|e| {
+        GitSwitchError::InvalidInput(format!("{} is not a git-switch export: {}", path, e))
+    })?;
+    if key.is_none() && !bundle.keys.is_empty() {
+        key = Some(Key::Passphrase(read_passphrase(
+            passphrase_stdin,
+            "Passphrase of the exported keys: ",
+        )?));
+    }
+
+    let mut accounts = load_accounts();
+    let mut imported = 0;
+    for account in bundle.accounts {
+        if accounts.iter().any(|acc| acc.name == account.name) {
+            say!("⏭️ Account '{}' already exists; skipped.", account.name);
+            continue;
+        }
+        let key_path = shellexpand::tilde(&account.ssh_key).into_owned();
+        match (
+            bundle.keys.iter().find(|key| key.account == account.name),
+            &key,
+        ) {
+            _ if Path::new(&key_path).exists() => {
+                say!("ℹ️ Using the existing key {}", account.ssh_key);
+            }
+            (Some(material), Some(key)) => {
+                let private_key = crypto::decrypt(&material.private_key, key)?;
+                files::write_private(Path::new(&key_path), &private_key)
+                    .and_then(|_| fs::write(format!("{}.pub", key_path), &material.public_key))
+                    .context(format!("Failed to write {}", key_path))?;
+                say!("🔑 Restored key {}", account.ssh_key);
+            }
+            _ => say!(
+                "⚠️ No key at {} for '{}'; copy it there before using the account.",
+                account.ssh_key,
+                account.name
+            ),
+        }
+        save_account(&account).context(format!("Failed to save account '{}'", account.name))?;
+        if let Err(e) = update_ssh_config(&account) {
+            problem!("❌ Failed to update SSH config: {}", e);
+        }
+        say!("✅ Imported account '{}'.", account.name);
+        accounts.push(account);
+        imported += 1;
+    }
+
+    let mut rules = rules::load_rules().context("Failed to load rules")?;
+    let before = rules.len();
+    for rule in bundle.rules {
+        if !rules.iter().any(|r| r.pattern == rule.pattern)
+            && accounts.iter().any(|acc| acc.name == rule.account)
+        {
+            rules.push(rule);
+        }
+    }
+    if rules.len() > before {
+        rules::save_rules(&rules).context("Failed to save rules")?;
+    }
+    say!(
+        "✅ Imported {} account(s) and {} rule(s) from {}.",
+        imported,
+        rules.len() - before,
+        path
+    );
+    Ok(())
+}
+
+/// Encrypts the accounts file with a passphrase, or to the age identity in
+/// `identity`; it's decrypted whenever accounts are loaded. Run on an
+/// encrypted file, it changes the key.
+pub fn encrypt_accounts(identity: Option<&str>, passphrase_stdin: bool) -> Result<()> {
+    let path = get_default_config_path();
+    if !path.exists() {
+        return Err(GitSwitchError::InvalidInput(
+            "There is no accounts file yet; add an account first.".to_string(),
+        ));
+    }
+    if !crypto::AVAILABLE {
+        // Fail before asking for the passphrase
+        return crypto::encrypt(b"", &Key::Passphrase(String::new())).map(drop);
+    }
+    // Decrypt with the current key before asking for the new one
+    read_accounts_file(&path).context(format!("Failed to read {}", path.display()))?;
+    let (encryption, key) = match identity {
+        Some(file) if file.starts_with('~') => (
+            StoreEncryption::Identity(file.to_string()),
+            Key::Identity(shellexpand::tilde(file).into_owned().into()),
+        ),
+        Some(file) => {
+            let file = std::path::absolute(file).context(format!("Invalid path {}", file))?;
+            (
+                StoreEncryption::Identity(file.display().to_string()),
+                Key::Identity(file),
+            )
+        }
+        None => (
+            StoreEncryption::Passphrase,
+            Key::Passphrase(read_new_passphrase(passphrase_stdin, "the accounts file")?),
+        ),
+    };
+    set_store_encryption(&path, Some((encryption, key.clone())))
+        .context(format!("Failed to encrypt {}", path.display()))?;
+    say!("🔒 Encrypted {}.", path.display());
+
+    match key {
+        Key::Passphrase(passphrase) if secrets::AVAILABLE => {
+            secrets::set("", Secret::AccountsFile, &passphrase)?;
+            say!("🔐 Saved the passphrase in the OS keyring.");
+        }
+        Key::Passphrase(_) => say!(
+            "ℹ️ git-switch will ask for the passphrase whenever it reads accounts; scripts can set {}.",
+            PASSPHRASE_VAR
+        ),
+        Key::Identity(_) => {
+            secrets::delete("", Secret::AccountsFile)?;
+        }
+    }
+    Ok(())
+}
+
+/// Turns an encrypted accounts file back into plain TOML.
+pub fn decrypt_accounts() -> Result<()> {
+    let path = get_default_config_path();
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    if store_encryption(&content).is_none() {
+        say!("ℹ️ {} isn't encrypted.", path.display());
+        return Ok(());
+    }
+    set_store_encryption(&path, None).context(format!("Failed to decrypt {}", path.display()))?;
+    secrets::delete("", Secret::AccountsFile)?;
+    say!("🔓 Decrypted {}.", path.display());
+    Ok(())
+}
+
+/// Clones the private repository at `url` for `sync` and syncs with it
+/// right away: accounts it already holds are added here, and this
+/// machine's accounts are pushed.
+pub fn sync_setup(url: &str) -> Result<()> {
+    let repo = SyncRepo::setup(url)?;
+    say!("✅ Cloned {} into {}", url, sync_dir().display());
+    sync_accounts(&repo, Prefer::Local, true)
+}
+
+/// Merges remote changes in and pushes this machine's accounts. Where both
+/// changed an account, this machine's version wins.
+pub fn sync_push() -> Result<()> {
+    sync_accounts(&SyncRepo::open()?, Prefer::Local, true)
+}
+
+/// Takes in the accounts changed on other machines. Where both changed an
+/// account, the remote version wins.
+pub fn sync_pull() -> Result<()> {
+    sync_accounts(&SyncRepo::open()?, Prefer::Remote, false)
+}
+
+fn sync_accounts(repo: &SyncRepo, prefer: Prefer, push: bool) -> Result<()> {
+    let local = portable_accounts()?;
+    let (base, remote) = repo.fetch()?;
+    let merge = merge_accounts(&base, &local, &remote, prefer);
+    for name in &merge.conflicts {
+        say!(
+            "⚠️ '{}' was changed here and on another machine; kept the {} version.",
+            name,
+            if prefer == Prefer::Local {
+                "local"
+            } else {
+                "remote"
+            }
+        );
+    }
+
+    let changes = diff_accounts(&local, &merge.accounts);
+    if !changes.is_empty() {
+        print_account_changes(&changes);
+        apply_account_changes(&merge.accounts, &changes)
+            .context("Failed to apply synced changes, nothing was modified")?;
+        for change in &changes {
+            if let AccountChange::Added(account) = change
+                && !Path::new(shellexpand::tilde(&account.ssh_key).as_ref()).exists()
+            {
+                say!(
+                    "⚠️ No key at {} for '{}'; copy it from the other machine before using the account.",
+                    account.ssh_key,
+                    account.name
+                );
+            }
+        }
+    }
+
+    if push {
+        if repo.push(&merge.accounts)? {
+            say!(
+                "⬆️ Pushed {} account(s) to {}.",
+                merge.accounts.len(),
+                repo.url()
+            );
+        } else {
+            say!("✅ {} is up to date.", repo.url());
+        }
+    } else {
+        repo.reset_to_remote()?;
+        say!("⬇️ Pulled {} change(s) from {}.", changes.len(), repo.url());
+    }
+    Ok(())
+}
+
+/// Prints the last `limit` history entries, oldest first.
+pub fn print_history(limit: Option<usize>, format: OutputFormat) {
+    let entries = history::read_history(limit);
+    if format == OutputFormat::Json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&entries).unwrap_or_default()
+        );
+        return;
+    }
+    if entries.is_empty() {
+        println!("No history yet.");
+        return;
+    }
+    let home = home_dir().unwrap_or_default();
+    for entry in entries {
+        // Repositories under the home directory are shown as ~/...
+        let repo = entry
+            .repo
+            .map(|repo| match Path::new(&repo).strip_prefix(&home) {
+                Ok(inner) if !home.as_os_str().is_empty() => format!("~/{}", inner.display()),
+                _ => repo,
+            });
+        let context: Vec<String> = repo.into_iter().chain(entry.detail).collect();
+        println!(
+            "{} UTC  {:<7} {:<15} {}",
+            format_timestamp(entry.ts),
+            entry.action,
+            entry.account,
+            context.join("  ")
+        );
+    }
+}
+
+pub fn list_accounts(format: OutputFormat, tag: Option<&str>, keys: bool) {
+    crate::config::list_accounts(format, tag, keys);
+}
+
+/// The accounts carrying `tag`; failing if there are none, so bulk commands
+/// don't silently do nothing.
+fn tagged_accounts(tag: &str) -> Result<Vec<Account>> {
+    let accounts: Vec<Account> = load_accounts()
+        .into_iter()
+        .filter(|acc| acc.has_tag(tag))
+        .collect();
+    if accounts.is_empty() {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "No accounts are tagged '{}'.",
+            tag
+        )));
+    }
+    Ok(accounts)
+}
+
+/// Runs `action` on every account tagged `tag`, going on past failures and
+/// failing at the end if any account did.
+pub fn for_each_tagged(
+    tag: &str,
+    what: &str,
+    mut action: impl FnMut(&str) -> Result<()>,
+) -> Result<()> {
+    let accounts = tagged_accounts(tag)?;
+    let mut failed = Vec::new();
+    for acc in &accounts {
+        report!("▶️ {}", acc.name);
+        if let Err(e) = action(&acc.name) {
+            problem!("❌ {}", e);
+            failed.push(acc.name.as_str());
+        }
+    }
+    if !failed.is_empty() {
+        return Err(GitSwitchError::CheckFailed(format!(
+            "Could not {} {} of the {} accounts tagged '{}': {}.",
+            what,
+            failed.len(),
+            accounts.len(),
+            tag,
+            failed.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Shows everything git-switch knows about one account: its key and whether
+/// the agent holds it, the SSH host aliases, signing setup, and the rules and
+/// directories that pick it.
+pub fn show_account(name_or_username: &str, format: OutputFormat) -> Result<()> {
+    let accounts = load_accounts();
+    let acc = crate::config::find_account(&accounts, name_or_username)
+        .ok_or_else(|| GitSwitchError::AccountNotFound(name_or_username.to_string()))?;
+    let fingerprint = if acc.uses_https() {
+        None
+    } else {
+        key_fingerprint(&acc.ssh_key)
+    };
+    let in_agent = fingerprint
+        .as_ref()
+        .and_then(|fingerprint| agent_fingerprints().map(|loaded| loaded.contains(fingerprint)));
+    let aliases: Vec<String> = if acc.uses_https() {
+        Vec::new()
+    } else {
+        acc.hosts()
+            .iter()
+            .map(|host| host_alias(host, &acc.name))
+            .collect()
+    };
+    let rules: Vec<String> = rules::load_rules()
+        .context("Failed to load rules")?
+        .into_iter()
+        .filter(|rule| rule.account == acc.name)
+        .map(|rule| rule.pattern)
+        .collect();
+    let dirs: Vec<String> = directory_mappings(&accounts)
+        .into_iter()
+        .filter(|(_, mapped)| mapped.name == acc.name)
+        .map(|(dir, _)| dir)
+        .collect();
+
+    if format == OutputFormat::Json {
+        let details = json!({
+            "name": acc.name,
+            "username": acc.username,
+            "email": acc.email,
+            "auth": if acc.uses_https() { "https" } else { "ssh" },
+            "hosts": acc.hosts(),
+            "host_aliases": aliases,
+            "ssh_key": (!acc.uses_https()).then_some(&acc.ssh_key),
+            "fingerprint": fingerprint,
+            "in_agent": in_agent,
+            "signing_key": acc.signing_key,
+            "signing_format": acc.signing_format,
+            "created": acc.created,
+            "default": acc.default,
+            "exclusive_key": acc.exclusive_key,
+            "key_ttl": acc.key_ttl,
+            "key_options": acc.key_options,
+            "tags": acc.tags,
+            "orgs": acc.orgs,
+            "provider": acc.provider,
+            "hostname": acc.hostname,
+            "port": acc.port,
+            "ssh_user": acc.ssh_user,
+            "proxy_jump": acc.proxy_jump,
+            "git_config": acc.git_config,
+            "workspace": acc.workspace,
+            "rules": rules,
+            "directories": dirs,
+        });
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&details).unwrap_or_default()
+        );
+        return Ok(());
+    }
+
+    let field = |label: &str, value: &str| println!("  {:<13} {}", label, value);
+    report!(
+        "👤 {}{}",
+        acc.name,
+        if acc.default { " (default)" } else { "" }
+    );
+    field("Username", &acc.username);
+    field("Email", &acc.email);
+    field("Hosts", &acc.hosts().join(", "));
+    if acc.uses_https() {
+        field("Auth", "HTTPS with a personal access token");
+    } else {
+        field("Host aliases", &aliases.join(", "));
+        field("SSH key", &acc.ssh_key);
+        field(
+            "Fingerprint",
+            fingerprint.as_deref().unwrap_or("(key file missing)"),
+        );
+        field(
+            "In agent",
+            match in_agent {
+                Some(true) => "yes",
+                Some(false) => "no",
+                None => "unknown",
+            },
+        );
+        if let Some(ttl) = acc.key_ttl {
+            field("Agent TTL", &format_duration(ttl));
+        }
+        if !acc.key_options.is_empty() {
+            field("Key options", &acc.key_options.join(", "));
+        }
+        if acc.exclusive_key {
+            field(
+                "Exclusive",
+                "yes; `use` removes other accounts' keys from the agent",
+            );
+        }
+    }
+    match &acc.signing_key {
+        Some(key) => field(
+            "Signing key",
+            &format!(
+                "{} ({})",
+                key,
+                acc.signing_format.as_deref().unwrap_or("gpg")
+            ),
+        ),
+        None => field("Signing key", "(none)"),
+    }
+    if !acc.tags.is_empty() {
+        field("Tags", &acc.tags.join(", "));
+    }
+    if !acc.orgs.is_empty() {
+        field("Owners", &acc.orgs.join(", "));
+    }
+    if let Some(provider) = &acc.provider {
+        field("Provider", provider);
+    }
+    if acc.hostname.is_some() || acc.port.is_some() || acc.ssh_user.is_some() {
+        let (server, port) = acc.ssh_endpoint(&acc.host);
+        field(
+            "SSH endpoint",
+            &format!(
+                "{}@{}:{}",
+                acc.ssh_user_for(&acc.host),
+                server,
+                port.unwrap_or(22)
+            ),
+        );
+    }
+    if let Some(jump) = &acc.proxy_jump {
+        field("Jump host", jump);
+    }
+    for (index, (key, value)) in acc.git_config.iter().enumerate() {
+        field(
+            if index == 0 { "Git config" } else { "" },
+            &format!("{} = {}", key, value),
+        );
+    }
+    field("Created", acc.created.as_deref().unwrap_or("unknown"));
+    if let Some(workspace) = &acc.workspace {
+        field("Workspace", workspace);
+    }
+    field(
+        "Rules",
+        &if rules.is_empty() {
+            "(none)".to_string()
+        } else {
+            rules.join(", ")
+        },
+    );
+    field(
+        "Directories",
+        &if dirs.is_empty() {
+            "(none)".to_string()
+        } else {
+            dirs.join(", ")
+        },
+    );
+    Ok(())
+}
+
+/// Prints saved account names one per line, for shell completion scripts.
+pub fn list_account_names() {
+    for acc in load_accounts() {
+        println!("{}", acc.name);
+    }
+}
+
+pub fn generate_completions(shell: &str, cmd: &mut Command) {
+    let mut stdout = io::stdout();
+    completions::generate_completions(shell, cmd, &mut stdout);
+}
+
+/// Implements the git credential helper protocol (`git help credential`).
+///
+/// On `get`, the request is resolved to a managed account and its username is
+/// returned, so later helpers in the chain look up the right stored secret.
+/// `store` and `erase` are accepted and ignored.
+///
+/// `account` is set for the helper `use` configures for an HTTPS account,
+/// which answers for that account only. HTTPS accounts also get the token
+/// stored for the host from the OS keyring as the password.
+pub fn credential_helper(operation: &str, account: Option<&str>) {
+    let mut input = String::new();
+    if io::stdin().read_to_string(&mut input).is_err() {
+        return;
+    }
+    if operation != "get" {
+        return;
+    }
+
+    let request = CredentialRequest::parse(&input);
+    let accounts = load_accounts();
+    let acc = match account {
+        Some(name) => accounts.iter().find(|acc| acc.name == name),
+        None => resolve_account(&accounts, &request, &read_insteadof_rules()),
+    };
+    let Some(acc) = acc else {
+        return;
+    };
+    println!("username={}", acc.username);
+    if let Some(host) = token_host(acc, &request)
+        && let Some(token) = secrets::get(&acc.name, Secret::Token(host))
+    {
+        println!("password={}", token);
+    }
+}
+
+pub fn run_doctor(token_expiry_days: u32) -> Result<()> {
+    say!(
+        "🩺 Checking git-switch setup (git-switch {}, commit {})...",
+        env!("CARGO_PKG_VERSION"),
+        env!("GIT_SWITCH_COMMIT")
+    );
+    let findings = doctor::run_checks(token_expiry_days);
+    if findings.is_empty() {
+        say!("✅ Nothing to check.");
+        return Ok(());
+    }
+
+    let mut problems = 0;
+    for finding in &findings {
+        let icon = match finding.severity {
+            Severity::Ok => "✅",
+            Severity::Warning => "⚠️",
+            Severity::Problem => {
+                problems += 1;
+                "❌"
+            }
+        };
+        report!("{} {}", icon, finding.message);
+        if let Some(hint) = &finding.hint
+            && finding.severity != Severity::Ok
+        {
+            say!("   ↳ {}", hint);
+        }
+    }
+
+    for fix in findings.iter().filter_map(|finding| finding.fix.as_ref()) {
+        let answer = prompt_line(&format!("🔧 {}? [y/N]: ", fix.description())).unwrap_or_default();
+        if answer.eq_ignore_ascii_case("y") {
+            if fix.apply() {
+                say!("✅ Done.");
+            } else {
+                say!("❌ Fix failed.");
+            }
+        }
+    }
+
+    if problems > 0 {
+        return Err(GitSwitchError::CheckFailed(format!(
+            "{} problem(s) found.",
+            problems
+        )));
+    }
+    say!("\n✅ No problems found.");
+    Ok(())
 }
@@ -0,0 +1,398 @@
+//! Shell completion scripts, extended to complete saved account names.
+
+use crate::problem;
+use clap::{Arg, Command};
+use clap_complete::{Shell, generate};
+use std::io::Write;
+
+/// Shells `git-switch completions` can generate scripts for.
+pub const SUPPORTED_SHELLS: [&str; 6] = ["bash", "zsh", "fish", "powershell", "nushell", "elvish"];
+
+/// Subcommands whose `name` argument refers to a saved account. These get
+/// dynamic completion backed by `git-switch list --names`.
+const ACCOUNT_ARG_SUBCOMMANDS: [&str; 14] = [
+    "use",
+    "remove",
+    "clone",
+    "cd",
+    "passphrase",
+    "rename",
+    "edit",
+    "upload",
+    "attest",
+    "rotate",
+    "render",
+    "direnv",
+    "verify",
+    "test",
+];
+
+/// Writes the completion script for `shell` to `out`.
+///
+/// Bash, zsh, fish and PowerShell scripts come from clap_complete and are
+/// extended below to complete saved account names. Nushell and elvish use
+/// their own generators.
+pub fn generate_completions(shell: &str, cmd: &mut Command, out: &mut dyn Write) {
+    let bin_name = cmd.get_name().to_string();
+    let clap_shell = match shell {
+        "bash" => Some(Shell::Bash),
+        "zsh" => Some(Shell::Zsh),
+        "fish" => Some(Shell::Fish),
+        "powershell" => Some(Shell::PowerShell),
+        _ => None,
+    };
+    if let Some(clap_shell) = clap_shell {
+        let mut buffer = Vec::new();
+        generate(clap_shell, cmd, bin_name.clone(), &mut buffer);
+        let script = String::from_utf8_lossy(&buffer);
+        let account_args = account_arg_paths(cmd, &[]);
+        let script = match clap_shell {
+            Shell::Bash => extend_bash(&script, &bin_name, &account_args),
+            Shell::Zsh => extend_zsh(&script, &bin_name, &account_args),
+            Shell::Fish => extend_fish(&script, &bin_name, &account_args),
+            _ => extend_powershell(&script, &bin_name, &account_args),
+        };
+        let _ = out.write_all(script.as_bytes());
+        return;
+    }
+    match shell {
+        "nushell" => {
+            cmd.build();
+            let _ = out.write_all(render_nushell(cmd).as_bytes());
+        }
+        "elvish" => {
+            cmd.build();
+            let _ = out.write_all(render_elvish(cmd).as_bytes());
+        }
+        _ => problem!("❌ Unsupported shell '{}'.", shell),
+    }
+}
+
+fn is_account_arg(subcommand: &str, arg: &Arg) -> bool {
+    arg.is_positional() && arg.get_id() == "name" && ACCOUNT_ARG_SUBCOMMANDS.contains(&subcommand)
+}
+
+/// Subcommand paths (e.g. `["key", "passphrase"]`) whose first positional
+/// argument is an account, with that argument's help text.
+fn account_arg_paths(cmd: &Command, parent: &[String]) -> Vec<(Vec<String>, String)> {
+    let mut paths = Vec::new();
+    for sub in visible_subcommands(cmd) {
+        let mut path = parent.to_vec();
+        path.push(sub.get_name().to_string());
+        if let Some(arg) = sub
+            .get_arguments()
+            .find(|arg| is_account_arg(sub.get_name(), arg))
+        {
+            paths.push((path.clone(), help_text(arg)));
+        }
+        paths.extend(account_arg_paths(sub, &path));
+    }
+    paths
+}
+
+// --- Account name completion for clap_complete scripts ---
+
+fn extend_bash(script: &str, bin: &str, account_args: &[(Vec<String>, String)]) -> String {
+    // Delegate to clap's completion function for everything else
+    let clap_function = script
+        .split("complete -F ")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or_default()
+        .to_string();
+    let function = format!("_{}_accounts", bin.replace('-', "_"));
+    let patterns: Vec<String> = account_args
+        .iter()
+        .map(|(path, _)| format!("\"{}\"", path.join(" ")))
+        .collect();
+    format!(
+        r#"{script}
+{function}() {{
+    local words=() word
+    for word in "${{COMP_WORDS[@]:1:COMP_CWORD-1}}"; do
+        [[ "$word" != -* ]] && words+=("$word")
+    done
+    case "${{words[*]}}" in
+        {patterns})
+            if [[ "${{COMP_WORDS[COMP_CWORD]}}" != -* ]]; then
+                local IFS=$'\n'
+                COMPREPLY=( $(compgen -W "$({bin} list --names 2>/dev/null)" -- "${{COMP_WORDS[COMP_CWORD]}}") )
+                return 0
+            fi
+            ;;
+    esac
+    {clap_function} "$@"
+}}
+complete -F {function} -o bashdefault -o default {bin}
+"#,
+        patterns = patterns.join("|"),
+    )
+}
+
+fn extend_zsh(script: &str, bin: &str, account_args: &[(Vec<String>, String)]) -> String {
+    let function = format!("_{}_accounts", bin);
+    let mut script = script.replacen(
+        &format!("#compdef {}\n", bin),
+        &format!(
+            "#compdef {bin}\n\n{function}() {{\n    local -a accounts\n    accounts=(${{(f)\"$({bin} list --names 2>/dev/null)\"}})\n    _describe 'account' accounts\n}}\n"
+        ),
+        1,
+    );
+    for (_, help) in account_args {
+        // Optional arguments are spelled `'::name -- ...'`
+        for colons in [":", "::"] {
+            let spec = format!("'{}name -- {}:", colons, escape_zsh_help(help));
+            script = script.replace(
+                &format!("{}_default'", spec),
+                &format!("{}{}'", spec, function),
+            );
+        }
+    }
+    script
+}
+
+/// Escapes help text the way clap_complete does inside zsh argument specs.
+fn escape_zsh_help(help: &str) -> String {
+    help.replace('\\', "\\\\")
+        .replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+        .replace('$', "\\$")
+        .replace('`', "\\`")
+}
+
+fn extend_fish(script: &str, bin: &str, account_args: &[(Vec<String>, String)]) -> String {
+    let mut script = script.to_string();
+    let using = format!("__fish_{}_using_subcommand", bin.replace('-', "_"));
+    for (path, help) in account_args {
+        let mut condition = format!("{} {}", using, path[0]);
+        for sub in &path[1..] {
+            condition.push_str(&format!("; and __fish_seen_subcommand_from {}", sub));
+        }
+        // Only the first positional after the subcommand is an account
+        condition.push_str(&format!(
+            "; and test (count (commandline -opc)) -eq {}",
+            path.len() + 1
+        ));
+        script.push_str(&format!(
+            "complete -c {} -n \"{}\" -f -a \"({} list --names)\" -d '{}'\n",
+            bin,
+            condition,
+            bin,
+            help.replace('\'', "\\'")
+        ));
+    }
+    script
+}
+
+fn extend_powershell(script: &str, bin: &str, account_args: &[(Vec<String>, String)]) -> String {
+    let mut script = script.to_string();
+    for (path, _) in account_args {
+        let case = format!("        '{};{}' {{\n", bin, path.join(";"));
+        script = script.replacen(
+            &case,
+            &format!(
+                "{case}            & '{bin}' list --names | ForEach-Object {{ [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, 'Saved account') }}\n"
+            ),
+            1,
+        );
+    }
+    script
+}
+
+fn help_text(arg: &Arg) -> String {
+    arg.get_help()
+        .map(|h| h.to_string().replace('\n', " "))
+        .unwrap_or_default()
+}
+
+fn visible_subcommands(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+}
+
+fn visible_args(cmd: &Command) -> impl Iterator<Item = &Arg> {
+    cmd.get_arguments().filter(|arg| !arg.is_hide_set())
+}
+
+// --- Nushell ---
+
+fn render_nushell(cmd: &Command) -> String {
+    let bin = cmd.get_name();
+    let mut script = String::new();
+    script.push_str("module completions {\n\n");
+    script.push_str(&format!(
+        "  def \"nu-complete {bin} accounts\" [] {{\n    ^{bin} list --names | lines\n  }}\n\n"
+    ));
+    render_nushell_command(cmd, bin, "", &mut script);
+    script.push_str("}\n\nexport use completions *\n");
+    script
+}
+
+fn render_nushell_command(cmd: &Command, path: &str, parent: &str, script: &mut String) {
+    let bin = path.split(' ').next().unwrap_or(path);
+
+    // Value completers for arguments with a fixed set of possible values
+    for arg in visible_args(cmd) {
+        let values: Vec<String> = arg
+            .get_possible_values()
+            .iter()
+            .filter(|v| !v.is_hide_set())
+            .map(|v| format!("\"{}\"", v.get_name()))
+            .collect();
+        if !values.is_empty() && arg.get_action().takes_values() {
+            script.push_str(&format!(
+                "  def \"nu-complete {} {}\" [] {{\n    [ {} ]\n  }}\n\n",
+                path,
+                arg.get_id(),
+                values.join(" ")
+            ));
+        }
+    }
+
+    if let Some(about) = cmd.get_about() {
+        script.push_str(&format!("  # {}\n", about));
+    }
+    script.push_str(&format!("  export extern \"{}\" [\n", path));
+    for arg in visible_args(cmd) {
+        let help = help_text(arg);
+        let completer = if is_account_arg(cmd.get_name(), arg) && !parent.is_empty() {
+            format!("@\"nu-complete {} accounts\"", bin)
+        } else if !arg.get_possible_values().is_empty() {
+            format!("@\"nu-complete {} {}\"", path, arg.get_id())
+        } else {
+            String::new()
+        };
+
+        let line = if arg.is_positional() {
+            let multiple = arg.get_num_args().is_some_and(|n| n.max_values() > 1);
+            let prefix = if multiple { "..." } else { "" };
+            let optional = if arg.is_required_set() || multiple {
+                ""
+            } else {
+                "?"
+            };
+            format!(
+                "{}{}{}: string{}",
+                prefix,
+                arg.get_id(),
+                optional,
+                completer
+            )
+        } else {
+            let mut flag = match arg.get_long() {
+                Some(long) => format!("--{}", long),
+                None => format!("-{}", arg.get_short().unwrap_or_default()),
+            };
+            if arg.get_long().is_some()
+                && let Some(short) = arg.get_short()
+            {
+                flag.push_str(&format!("(-{})", short));
+            }
+            if arg.get_action().takes_values() {
+                flag.push_str(&format!(": string{}", completer));
+            }
+            flag
+        };
+
+        if help.is_empty() {
+            script.push_str(&format!("    {}\n", line));
+        } else {
+            script.push_str(&format!("    {:<40} # {}\n", line, help));
+        }
+    }
+    script.push_str("  ]\n\n");
+
+    for sub in visible_subcommands(cmd) {
+        let sub_path = format!("{} {}", path, sub.get_name());
+        render_nushell_command(sub, &sub_path, path, script);
+    }
+}
+
+// --- Elvish ---
+
+fn render_elvish(cmd: &Command) -> String {
+    let bin = cmd.get_name();
+    let mut table = String::new();
+    render_elvish_command(cmd, bin, bin, &mut table);
+    let known = elvish_known_keys(cmd, bin);
+
+    format!(
+        "use builtin;
+use str;
+
+set edit:completion:arg-completer[{bin}] = {{|@words|
+    fn spaces {{|n|
+        builtin:repeat $n ' ' | str:join ''
+    }}
+    fn cand {{|text desc|
+        edit:complex-candidate $text &display=$text' '(spaces (- 14 (wcswidth $text)))$desc
+    }}
+    fn accounts {{
+        try {{
+            e:{bin} list --names 2>/dev/null | each {{|name| cand $name 'saved account' }}
+        }} catch {{ }}
+    }}
+    var known = [{known}]
+    var command = '{bin}'
+    for word $words[1..-1] {{
+        if (str:has-prefix $word '-') {{
+            break
+        }}
+        if (has-key $known $command';'$word) {{
+            set command = $command';'$word
+        }} else {{
+            break
+        }}
+    }}
+    var completions = [
+{table}    ]
+    $completions[$command]
+}}
+"
+    )
+}
+
+fn elvish_known_keys(cmd: &Command, path: &str) -> String {
+    let mut keys = vec![format!("&'{}'=$true", path)];
+    for sub in visible_subcommands(cmd) {
+        keys.push(elvish_known_keys(
+            sub,
+            &format!("{};{}", path, sub.get_name()),
+        ));
+    }
+    keys.join(" ")
+}
+
+fn render_elvish_command(cmd: &Command, key: &str, bin: &str, table: &mut String) {
+    table.push_str(&format!("        &'{}'= {{\n", key));
+    if ACCOUNT_ARG_SUBCOMMANDS.contains(&cmd.get_name()) && key != bin {
+        table.push_str("            accounts\n");
+    }
+    for arg in visible_args(cmd).filter(|a| !a.is_positional()) {
+        let help = help_text(arg).replace('\'', "''");
+        if let Some(short) = arg.get_short() {
+            table.push_str(&format!("            cand -{} '{}'\n", short, help));
+        }
+        if let Some(long) = arg.get_long() {
+            table.push_str(&format!("            cand --{} '{}'\n", long, help));
+        }
+    }
+    for sub in visible_subcommands(cmd) {
+        let about = sub
+            .get_about()
+            .map(|a| a.to_string().replace('\'', "''"))
+            .unwrap_or_default();
+        table.push_str(&format!(
+            "            cand {} '{}'\n",
+            sub.get_name(),
+            about
+        ));
+    }
+    table.push_str("        }\n");
+
+    for sub in visible_subcommands(cmd) {
+        render_elvish_command(sub, &format!("{};{}", key, sub.get_name()), bin, table);
+    }
+}
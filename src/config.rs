@@ -1,45 +1,372 @@
-// use crate::utils::file_exists; // Assuming file_exists takes &Path
-use std::fs::{self, OpenOptions};
-use std::io::{self, Write};
+//! The accounts file: the [`Account`] type, loading, saving and validating
+//! accounts, and the locations of git-switch's files.
+
+use crate::crypto::{self, Key};
+use crate::error::GitSwitchError;
+use crate::events;
+use crate::files;
+use crate::output::{OutputFormat, csv_field};
+use crate::provider::{self, PROVIDER_KINDS};
+use crate::secrets::{self, Secret};
+use crate::utils::prompt_password;
+use crate::{problem, report};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Current version of the accounts file schema.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Values accepted for `signing_format` (git's `gpg.format`).
+pub const SIGNING_FORMATS: [&str; 2] = ["gpg", "ssh"];
+/// Values accepted for `smtp_encryption` (git's `sendemail.smtpEncryption`).
+pub const SMTP_ENCRYPTIONS: [&str; 2] = ["tls", "ssl"];
+/// Values accepted for `auth`: how git authenticates to the account's hosts.
+pub const AUTH_METHODS: [&str; 2] = ["ssh", "https"];
+/// SSH options git-switch writes itself, which `ssh_options` may not set.
+pub const RESERVED_SSH_OPTIONS: [&str; 5] = ["Host", "Match", "HostName", "User", "IdentityFile"];
+/// Git settings `use` writes itself, which `git_config` may not set.
+pub const RESERVED_GIT_CONFIG: [&str; 7] = [
+    "user.name",
+    "user.email",
+    "user.signingKey",
+    "gpg.format",
+    "commit.gpgSign",
+    "core.sshCommand",
+    "include.path",
+];
+/// Git host used by accounts that don't specify one.
+pub const DEFAULT_HOST: &str = "github.com";
+
+fn default_host() -> String {
+    DEFAULT_HOST.to_string()
+}
+
+/// The user's home directory, which every git-switch file lives under.
+pub fn home_dir() -> Result<PathBuf, GitSwitchError> {
+    dirs::home_dir().ok_or(GitSwitchError::NoHomeDirectory)
+}
+
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Uses `path` as the accounts file for the rest of the process (`--config`).
+/// Only the first call has an effect.
+pub fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH.set(path);
+}
+
+/// The accounts file chosen with `--config` or `$GIT_SWITCH_CONFIG`.
+fn config_path_override() -> Option<PathBuf> {
+    if let Some(path) = CONFIG_PATH.get() {
+        return Some(path.clone());
+    }
+    std::env::var_os("GIT_SWITCH_CONFIG")
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).as_ref()))
+}
+
+/// git-switch's directory in `$XDG_CONFIG_HOME` (`~/.config` if unset,
+/// `%APPDATA%` on Windows).
+pub fn get_config_dir() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            if cfg!(windows) {
+                dirs::config_dir()
+            } else {
+                None
+            }
+        })
+        // The binary checks `home_dir()` before running any command
+        .unwrap_or_else(|| home_dir().unwrap_or_default().join(".config"))
+        .join("git-switch")
+}
+
+/// Where the accounts file lived before it moved to [`get_config_dir`].
+pub fn legacy_config_path() -> PathBuf {
+    home_dir().unwrap_or_default().join(".git-switch-accounts")
+}
 
-/// Returns the default path for the git-switch accounts configuration file.
+/// Location of the accounts file: the `--config` path, `$GIT_SWITCH_CONFIG`,
+/// or `accounts.toml` in [`get_config_dir`].
 pub fn get_default_config_path() -> PathBuf {
-    let home_dir = dirs::home_dir().expect("Could not determine home directory");
-    home_dir.join(".git-switch-accounts")
+    config_path_override().unwrap_or_else(|| get_config_dir().join("accounts.toml"))
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Moves `~/.git-switch-accounts` (and its `.bak` copy) to the default
+/// location, unless a file is already there or the path was overridden.
+/// Returns the new path if the file moved.
+pub fn migrate_legacy_config() -> io::Result<Option<PathBuf>> {
+    if config_path_override().is_some() {
+        return Ok(None);
+    }
+    let current = get_default_config_path();
+    if !move_config_file(&legacy_config_path(), &current)? {
+        return Ok(None);
+    }
+    let mut legacy_backup = legacy_config_path().into_os_string();
+    legacy_backup.push(".bak");
+    let mut backup = current.clone().into_os_string();
+    backup.push(".bak");
+    move_config_file(Path::new(&legacy_backup), Path::new(&backup))?;
+    Ok(Some(current))
+}
+
+/// Moves `from` to `to` if only `from` exists, copying when a rename across
+/// file systems isn't possible. Returns whether it moved.
+pub(crate) fn move_config_file(from: &Path, to: &Path) -> io::Result<bool> {
+    if to.exists() || !from.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(true)
+}
+
+/// Returns the directory holding git-switch's generated files (e.g. gitconfig fragments).
+pub fn get_data_dir() -> PathBuf {
+    home_dir().unwrap_or_default().join(".git-switch")
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Account {
     pub name: String,
     pub username: String,
     pub email: String,
+    /// Private SSH key; empty for HTTPS accounts.
+    #[serde(default)]
     pub ssh_key: String,
+    /// `ssh` (the default) or `https`, which authenticates with a personal
+    /// access token from the OS keyring through `git-switch credential`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<String>,
+    /// Primary Git host (e.g. `github.com`, `gitlab.com`, `git.corp.com`).
+    #[serde(default = "default_host")]
+    pub host: String,
+    /// Further hosts the same identity and key are used with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_hosts: Vec<String>,
+    /// Kind of server (`github`, `gitlab` or `gitea`) behind hosts git-switch
+    /// doesn't know, such as a self-hosted GitLab, for key uploads and checks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Server the primary host's SSH alias connects to, when it isn't
+    /// `host` itself (e.g. `ssh.github.com` to get through on port 443).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// SSH port of the primary host, when it isn't 22.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// SSH user of the primary host, when it isn't the provider's (`git`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_user: Option<String>,
+    /// Jump host(s) the primary host is reached through, as ssh's
+    /// `ProxyJump` takes them (`bastion.corp`, `me@bastion:2222,inner`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_jump: Option<String>,
+    /// HTTPS base URL (e.g. `https://git.corp.com:8443/gitlab/`) used to match
+    /// credential helper requests to this account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_url: Option<String>,
+    /// Git LFS endpoint used by this account's repositories.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lfs_url: Option<String>,
+    /// Timeout in seconds for `git credential-cache` while this account is active.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_cache_timeout: Option<u64>,
+    /// Root directory for this account's checkouts (e.g. `~/work/src`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    /// Commit signing key: a GPG key ID, or a public key path for SSH signing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_key: Option<String>,
+    /// Signing format, `gpg` or `ssh`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing_format: Option<String>,
+    /// Attestation proving the key was generated on a hardware authenticator.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<String>,
+    /// The key was registered with `add --ssh-key` rather than generated, so
+    /// `remove` leaves its files alone.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub external_key: bool,
+    /// `use` removes the other accounts' keys from ssh-agent, so hosts are
+    /// offered only this account's key.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclusive_key: bool,
+    /// Seconds the key stays in ssh-agent after `use` loads it
+    /// (`ssh-add -t`); unset keeps it until the agent stops.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_ttl: Option<u64>,
+    /// FIDO2 options the security key was generated with (`resident`,
+    /// `verify-required`), used again when it's rotated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_options: Vec<String>,
+    /// Labels for working on groups of accounts (`list --tag client-x`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Extra options for the account's SSH config blocks (e.g. `Port`,
+    /// `ProxyJump`); an `IdentitiesOnly` entry overrides the default `yes`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ssh_options: BTreeMap<String, String>,
+    /// Further git settings (e.g. `pull.rebase`, `core.editor`) that `use`
+    /// applies with the account's fragment and drops on switching away.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub git_config: BTreeMap<String, String>,
+    /// Owners (users, organizations or groups) whose SSH URLs a global `use`
+    /// sends through the account's host alias with `url.*.insteadOf` rules.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub orgs: Vec<String>,
+    /// SMTP settings for `git send-email`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sendemail: Option<SendEmail>,
+    /// The account `use` falls back to without a name outside a terminal,
+    /// new shells start with, and repositories no rule matches get. At most
+    /// one account has it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub default: bool,
+    /// Day the account was added (`YYYY-MM-DD`); unset for accounts added
+    /// before git-switch recorded it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
 }
 
-// --- Worker functions that operate on a specific path ---
-// These are now pub(crate) to be accessible by tests within the same crate
+/// The account's outgoing mail server, written as git's `sendemail.*` keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SendEmail {
+    pub smtp_server: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smtp_port: Option<u16>,
+    /// Login name; git send-email prompts for the password or asks the
+    /// credential helper.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smtp_user: Option<String>,
+    /// `tls` (STARTTLS) or `ssl`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smtp_encryption: Option<String>,
+}
 
-/// Loads accounts from a specified configuration file path.
-pub(crate) fn load_accounts_from_path(config_file_path: &Path) -> Vec<Account> {
-    // println!("[LOAD_ACCOUNTS_FROM_PATH] Attempting to load from: {}", config_file_path.display());
-    if !config_file_path.exists() {
-        // println!("[LOAD_ACCOUNTS_FROM_PATH] File not found: {}. Returning empty Vec.", config_file_path.display());
-        return Vec::new();
+impl Default for Account {
+    fn default() -> Self {
+        Account {
+            name: String::new(),
+            username: String::new(),
+            email: String::new(),
+            ssh_key: String::new(),
+            auth: None,
+            host: default_host(),
+            extra_hosts: Vec::new(),
+            provider: None,
+            hostname: None,
+            port: None,
+            ssh_user: None,
+            proxy_jump: None,
+            credential_url: None,
+            lfs_url: None,
+            credential_cache_timeout: None,
+            workspace: None,
+            signing_key: None,
+            signing_format: None,
+            attestation: None,
+            external_key: false,
+            exclusive_key: false,
+            key_ttl: None,
+            key_options: Vec::new(),
+            tags: Vec::new(),
+            ssh_options: BTreeMap::new(),
+            git_config: BTreeMap::new(),
+            orgs: Vec::new(),
+            sendemail: None,
+            default: false,
+            created: None,
+        }
     }
+}
 
-    let file_content = match fs::read_to_string(config_file_path) {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!(
-                "[LOAD_ACCOUNTS_FROM_PATH] Error reading file {} for loading: {}. Returning empty.",
-                config_file_path.display(),
-                e
-            );
-            return Vec::new();
+impl Account {
+    /// All hosts this account is used with, primary host first.
+    pub fn hosts(&self) -> Vec<&str> {
+        std::iter::once(self.host.as_str())
+            .chain(self.extra_hosts.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Whether git reaches the account's hosts over HTTPS with a token
+    /// instead of SSH with its key.
+    pub fn uses_https(&self) -> bool {
+        self.auth.as_deref() == Some("https")
+    }
+
+    /// The SSH user for one of the account's hosts.
+    pub fn ssh_user_for(&self, host: &str) -> &str {
+        match &self.ssh_user {
+            Some(user) if host == self.host => user,
+            _ => provider::ssh_user(host),
         }
-    };
+    }
+
+    /// The server SSH connects to for one of the account's hosts, and its
+    /// port if it isn't 22.
+    pub fn ssh_endpoint<'a>(&'a self, host: &'a str) -> (&'a str, Option<u16>) {
+        if host != self.host {
+            return (host, None);
+        }
+        (self.hostname.as_deref().unwrap_or(host), self.port)
+    }
+
+    /// Whether the account carries `tag`, ignoring case.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// On-disk layout of the accounts file.
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountStore {
+    version: u32,
+    #[serde(default)]
+    accounts: Vec<Account>,
+}
+
+// --- Legacy pipe format (`name|username|email|ssh_key[|key=value...]`) ---
+
+/// Applies an optional `key=value` field from a legacy accounts line.
+fn apply_legacy_field(account: &mut Account, field: &str) -> bool {
+    match field.split_once('=') {
+        Some(("credential_url", value)) => {
+            account.credential_url = Some(value.trim().to_string());
+            true
+        }
+        Some(("lfs_url", value)) => {
+            account.lfs_url = Some(value.trim().to_string());
+            true
+        }
+        Some(("workspace", value)) => {
+            account.workspace = Some(value.trim().to_string());
+            true
+        }
+        Some(("credential_cache_timeout", value)) => match value.trim().parse() {
+            Ok(timeout) => {
+                account.credential_cache_timeout = Some(timeout);
+                true
+            }
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
 
+/// Parses the pipe-delimited format used before the TOML schema.
+fn parse_legacy_accounts(file_content: &str, config_file_path: &Path) -> Vec<Account> {
     file_content
         .lines()
         .filter_map(|line| {
@@ -48,16 +375,27 @@ pub(crate) fn load_accounts_from_path(config_file_path: &Path) -> Vec<Account> {
                 return None;
             }
             let parts: Vec<&str> = trimmed_line.split('|').collect();
-            if parts.len() == 4 {
-                Some(Account {
+            if parts.len() >= 4 {
+                let mut account = Account {
                     name: parts[0].trim().to_string(),
                     username: parts[1].trim().to_string(),
                     email: parts[2].trim().to_string(),
                     ssh_key: parts[3].trim().to_string(),
-                })
+                    ..Default::default()
+                };
+                for field in &parts[4..] {
+                    if !apply_legacy_field(&mut account, field.trim()) {
+                        problem!(
+                            "[LOAD_ACCOUNTS_FROM_PATH] Ignoring unknown field '{}' for account '{}'.",
+                            field.trim(),
+                            account.name
+                        );
+                    }
+                }
+                Some(account)
             } else {
-                eprintln!(
-                    "[LOAD_ACCOUNTS_FROM_PATH] Malformed line (parts count {} not 4): '{}'. In file: {}",
+                problem!(
+                    "[LOAD_ACCOUNTS_FROM_PATH] Malformed line (parts count {} less than 4): '{}'. In file: {}",
                     parts.len(),
                     trimmed_line,
                     config_file_path.display()
@@ -68,65 +406,771 @@ pub(crate) fn load_accounts_from_path(config_file_path: &Path) -> Vec<Account> {
         .collect()
 }
 
-/// Saves an account to the specified configuration file path.
-pub(crate) fn save_account_to_path(account: &Account, config_file_path: &Path) -> io::Result<()> {
-    if let Some(parent_dir) = config_file_path.parent() {
-        if !parent_dir.exists() {
-            fs::create_dir_all(parent_dir)?;
+/// Returns true if the file looks like the legacy pipe format rather than TOML.
+fn is_legacy_format(file_content: &str) -> bool {
+    file_content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .all(|line| line.contains('|') && !line.starts_with('[') && !line.contains(" = "))
+}
+
+/// Rewrites a legacy accounts file as TOML, keeping a `.bak` copy of the original.
+fn migrate_legacy_file(file_content: &str, config_file_path: &Path) -> Vec<Account> {
+    let accounts = parse_legacy_accounts(file_content, config_file_path);
+    let mut backup_path = config_file_path.as_os_str().to_owned();
+    backup_path.push(".bak");
+
+    let result = fs::write(&backup_path, file_content)
+        .and_then(|_| write_accounts_to_path(&accounts, config_file_path));
+    match result {
+        Ok(_) => problem!(
+            "🔄 Migrated {} to the TOML format (backup: {}).",
+            config_file_path.display(),
+            Path::new(&backup_path).display()
+        ),
+        Err(e) => problem!(
+            "⚠️ Could not migrate {} to the TOML format: {}",
+            config_file_path.display(),
+            e
+        ),
+    }
+    accounts
+}
+
+/// Finds an account by name, or failing that by username.
+pub fn find_account<'a>(accounts: &'a [Account], name_or_username: &str) -> Option<&'a Account> {
+    accounts
+        .iter()
+        .find(|acc| acc.name == name_or_username)
+        .or_else(|| accounts.iter().find(|acc| acc.username == name_or_username))
+}
+
+/// Finds the account a git identity belongs to: the account with this email
+/// and username, or failing that the only account with this email.
+pub fn match_identity<'a>(accounts: &'a [Account], name: &str, email: &str) -> Option<&'a Account> {
+    accounts
+        .iter()
+        .find(|acc| acc.email.eq_ignore_ascii_case(email) && acc.username == name)
+        .or_else(|| {
+            let mut by_email = accounts
+                .iter()
+                .filter(|acc| acc.email.eq_ignore_ascii_case(email));
+            match (by_email.next(), by_email.next()) {
+                (Some(acc), None) => Some(acc),
+                _ => None,
+            }
+        })
+}
+
+// --- TOML (de)serialization, validation and diffing ---
+
+/// Serializes accounts in the accounts file format.
+pub fn accounts_to_toml(accounts: &[Account]) -> Result<String, String> {
+    let store = AccountStore {
+        version: CONFIG_VERSION,
+        accounts: accounts.to_vec(),
+    };
+    toml::to_string_pretty(&store).map_err(|e| e.to_string())
+}
+
+/// Parses accounts from the accounts file format.
+pub fn accounts_from_toml(content: &str) -> Result<Vec<Account>, String> {
+    let store: AccountStore = toml::from_str(content).map_err(|e| e.to_string())?;
+    if store.version > CONFIG_VERSION {
+        return Err(format!(
+            "schema version {} is newer than supported ({})",
+            store.version, CONFIG_VERSION
+        ));
+    }
+    Ok(store.accounts)
+}
+
+/// Checks a full set of accounts for problems: required fields, unique
+/// names, plausible emails and existing key files. Returns one message per problem.
+pub fn validate_accounts(accounts: &[Account]) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (index, acc) in accounts.iter().enumerate() {
+        let label = if acc.name.is_empty() {
+            format!("account #{}", index + 1)
+        } else {
+            format!("account '{}'", acc.name)
+        };
+        for (field, value) in [
+            ("name", &acc.name),
+            ("username", &acc.username),
+            ("email", &acc.email),
+            ("ssh_key", &acc.ssh_key),
+            ("host", &acc.host),
+        ] {
+            if value.trim().is_empty() && !(field == "ssh_key" && acc.uses_https()) {
+                errors.push(format!("{}: '{}' must not be empty", label, field));
+            }
+        }
+        if !acc.name.is_empty()
+            && let Err(e) = check_account_name(&acc.name)
+        {
+            errors.push(format!("{}: {}", label, e));
+        }
+        if !acc.email.is_empty() && !is_valid_email(&acc.email) {
+            errors.push(format!(
+                "{}: '{}' is not an email address",
+                label, acc.email
+            ));
+        }
+        if let Some(format) = &acc.signing_format
+            && !SIGNING_FORMATS.contains(&format.as_str())
+        {
+            errors.push(format!(
+                "{}: signing_format must be one of {}",
+                label,
+                SIGNING_FORMATS.join(", ")
+            ));
+        }
+        if let Some(auth) = &acc.auth
+            && !AUTH_METHODS.contains(&auth.as_str())
+        {
+            errors.push(format!(
+                "{}: auth must be one of {}",
+                label,
+                AUTH_METHODS.join(", ")
+            ));
+        }
+        if let Some(sendemail) = &acc.sendemail {
+            if sendemail.smtp_server.trim().is_empty() {
+                errors.push(format!("{}: sendemail needs an smtp_server", label));
+            }
+            if let Some(encryption) = &sendemail.smtp_encryption
+                && !SMTP_ENCRYPTIONS.contains(&encryption.as_str())
+            {
+                errors.push(format!(
+                    "{}: smtp_encryption must be one of {}",
+                    label,
+                    SMTP_ENCRYPTIONS.join(", ")
+                ));
+            }
+        }
+        if let Some(hostname) = &acc.hostname
+            && (hostname.is_empty() || hostname.contains(|c: char| c.is_whitespace() || c == '/'))
+        {
+            errors.push(format!("{}: '{}' is not a host name", label, hostname));
+        }
+        if acc.port == Some(0) {
+            errors.push(format!("{}: port must not be 0", label));
+        }
+        if let Some(user) = &acc.ssh_user
+            && (user.is_empty() || user.contains(|c: char| c.is_whitespace() || "@:/".contains(c)))
+        {
+            errors.push(format!("{}: '{}' is not an SSH user", label, user));
+        }
+        for option in &acc.key_options {
+            if !crate::ssh::SECURITY_KEY_OPTIONS.contains(&option.as_str()) {
+                errors.push(format!(
+                    "{}: key option '{}' must be one of {}",
+                    label,
+                    option,
+                    crate::ssh::SECURITY_KEY_OPTIONS.join(", ")
+                ));
+            }
+        }
+        if let Some(jump) = &acc.proxy_jump
+            && (jump.is_empty() || jump.contains(|c: char| c.is_whitespace() || c.is_control()))
+        {
+            errors.push(format!("{}: '{}' is not a jump host", label, jump));
+        }
+        if let Some(kind) = &acc.provider
+            && !PROVIDER_KINDS.contains(&kind.as_str())
+        {
+            errors.push(format!(
+                "{}: provider must be one of {}",
+                label,
+                PROVIDER_KINDS.join(", ")
+            ));
+        }
+        for tag in &acc.tags {
+            if let Err(e) = check_tag(tag) {
+                errors.push(format!("{}: {}", label, e));
+            }
+        }
+        for (option, value) in &acc.ssh_options {
+            if let Err(e) = check_ssh_option(option, value) {
+                errors.push(format!("{}: {}", label, e));
+            }
+        }
+        for (key, value) in &acc.git_config {
+            if let Err(e) = check_git_config(key, value) {
+                errors.push(format!("{}: {}", label, e));
+            }
+        }
+        for org in &acc.orgs {
+            if let Err(e) = check_org(org) {
+                errors.push(format!("{}: {}", label, e));
+            }
+        }
+        if accounts[..index]
+            .iter()
+            .any(|other| other.name.eq_ignore_ascii_case(&acc.name))
+        {
+            errors.push(format!("{}: duplicate account name", label));
+        }
+        if acc.default && accounts[..index].iter().any(|other| other.default) {
+            errors.push(format!("{}: only one account can be the default", label));
+        }
+        if !acc.ssh_key.is_empty() && !Path::new(&*shellexpand::tilde(&acc.ssh_key)).exists() {
+            errors.push(format!("{}: SSH key {} does not exist", label, acc.ssh_key));
         }
     }
+    errors
+}
 
-    let entry = format!(
-        "{}|{}|{}|{}\n",
-        account.name, account.username, account.email, account.ssh_key
-    );
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(config_file_path)?;
-    file.write_all(entry.as_bytes())?;
+/// Checks that a name can be used for an account. It ends up in key file
+/// names and SSH host aliases, and `|` separated fields in the legacy file.
+pub fn check_account_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("the account name must not be empty".to_string());
+    }
+    if name.contains(['|', '/', '\\']) || name.chars().any(char::is_control) {
+        return Err(format!(
+            "'{}' can't be an account name; it must not contain '|', '/', '\\' or control characters",
+            name
+        ));
+    }
     Ok(())
 }
 
-/// Deletes an account from the specified configuration file path.
-pub(crate) fn delete_account_from_path(
-    name_to_delete: &str,
+/// Checks an owner for `orgs`: the path in front of the repository name,
+/// which may hold GitLab subgroups (`acme/platform`).
+pub fn check_org(org: &str) -> Result<(), String> {
+    if org.is_empty()
+        || org.starts_with('/')
+        || org.ends_with('/')
+        || org.contains([':', '@'])
+        || org.chars().any(|c| c.is_whitespace() || c.is_control())
+    {
+        return Err(format!(
+            "'{}' can't be an owner; use the user, organization or group as it appears in repository URLs",
+            org
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a tag: one word, without commas, so it reads the same in
+/// `list` and on the command line.
+pub fn check_tag(tag: &str) -> Result<(), String> {
+    if tag.is_empty()
+        || tag.contains(',')
+        || tag.chars().any(|c| c.is_whitespace() || c.is_control())
+    {
+        return Err(format!(
+            "'{}' can't be a tag; it must be a single word without commas",
+            tag
+        ));
+    }
+    Ok(())
+}
+
+/// A plausible `local@domain` address: one `@`, something on both sides,
+/// no whitespace.
+pub fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.is_empty()
+                && !domain.contains('@')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+/// Checks that an extra SSH option is a single `Keyword value` line that
+/// doesn't replace one git-switch writes itself.
+pub fn check_ssh_option(option: &str, value: &str) -> Result<(), String> {
+    check_ssh_option_name(option)?;
+    if value.trim().is_empty() || value.contains(['\n', '\r']) {
+        return Err(format!("SSH option {} needs a single-line value", option));
+    }
+    Ok(())
+}
+
+pub fn check_ssh_option_name(option: &str) -> Result<(), String> {
+    if option.is_empty() || !option.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("'{}' is not an SSH option name", option));
+    }
+    if let Some(reserved) = RESERVED_SSH_OPTIONS
+        .iter()
+        .find(|reserved| reserved.eq_ignore_ascii_case(option))
+    {
+        return Err(format!(
+            "SSH option {} is set by git-switch and cannot be overridden",
+            reserved
+        ));
+    }
+    Ok(())
+}
+
+/// Checks an extra git setting: a `section[.subsection].name` key that
+/// git-switch doesn't set itself, and a single-line value.
+pub fn check_git_config(key: &str, value: &str) -> Result<(), String> {
+    check_git_config_key(key)?;
+    if value.contains(['\n', '\r']) {
+        return Err(format!("git setting {} needs a single-line value", key));
+    }
+    Ok(())
+}
+
+pub fn check_git_config_key(key: &str) -> Result<(), String> {
+    let invalid = || format!("'{}' is not a git config key like pull.rebase", key);
+    let (section, rest) = key.split_once('.').ok_or_else(invalid)?;
+    let (subsection, name) = rest.rsplit_once('.').unwrap_or(("", rest));
+    let is_word = |part: &str| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    if !is_word(section)
+        || !is_word(name)
+        || !name.starts_with(|c: char| c.is_ascii_alphabetic())
+        || subsection.contains(['"', '\n', '\r'])
+    {
+        return Err(invalid());
+    }
+    // Section and name are case-insensitive; the subsection isn't
+    if let Some(reserved) = RESERVED_GIT_CONFIG
+        .iter()
+        .find(|reserved| reserved.eq_ignore_ascii_case(key))
+    {
+        return Err(format!(
+            "git setting {} is set by git-switch and cannot be overridden",
+            reserved
+        ));
+    }
+    Ok(())
+}
+
+/// A change between two versions of the account list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountChange {
+    Added(Account),
+    Removed(Account),
+    /// Old and new version plus the names of the fields that differ.
+    Modified(Box<Account>, Box<Account>, Vec<&'static str>),
+}
+
+/// Compares two account lists by account name.
+pub fn diff_accounts(before: &[Account], after: &[Account]) -> Vec<AccountChange> {
+    let mut changes = Vec::new();
+    for old in before {
+        match after.iter().find(|acc| acc.name == old.name) {
+            None => changes.push(AccountChange::Removed(old.clone())),
+            Some(new) if new != old => {
+                changes.push(AccountChange::Modified(
+                    Box::new(old.clone()),
+                    Box::new(new.clone()),
+                    changed_fields(old, new),
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    for new in after {
+        if !before.iter().any(|acc| acc.name == new.name) {
+            changes.push(AccountChange::Added(new.clone()));
+        }
+    }
+    changes
+}
+
+fn changed_fields(old: &Account, new: &Account) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    if old.username != new.username {
+        fields.push("username");
+    }
+    if old.email != new.email {
+        fields.push("email");
+    }
+    if old.ssh_key != new.ssh_key {
+        fields.push("ssh_key");
+    }
+    if old.auth != new.auth {
+        fields.push("auth");
+    }
+    if old.hosts() != new.hosts() {
+        fields.push("hosts");
+    }
+    if old.provider != new.provider {
+        fields.push("provider");
+    }
+    if old.hostname != new.hostname || old.port != new.port || old.ssh_user != new.ssh_user {
+        fields.push("ssh endpoint");
+    }
+    if old.proxy_jump != new.proxy_jump {
+        fields.push("proxy_jump");
+    }
+    if old.ssh_options != new.ssh_options {
+        fields.push("ssh_options");
+    }
+    if old.git_config != new.git_config {
+        fields.push("git_config");
+    }
+    if old.orgs != new.orgs {
+        fields.push("orgs");
+    }
+    if old.credential_url != new.credential_url {
+        fields.push("credential_url");
+    }
+    if old.lfs_url != new.lfs_url {
+        fields.push("lfs_url");
+    }
+    if old.credential_cache_timeout != new.credential_cache_timeout {
+        fields.push("credential_cache_timeout");
+    }
+    if old.workspace != new.workspace {
+        fields.push("workspace");
+    }
+    if old.signing_key != new.signing_key || old.signing_format != new.signing_format {
+        fields.push("signing");
+    }
+    if old.default != new.default {
+        fields.push("default");
+    }
+    if old.exclusive_key != new.exclusive_key {
+        fields.push("exclusive_key");
+    }
+    if old.key_ttl != new.key_ttl {
+        fields.push("key_ttl");
+    }
+    if old.tags != new.tags {
+        fields.push("tags");
+    }
+    if old.sendemail != new.sendemail {
+        fields.push("sendemail");
+    }
+    fields
+}
+
+// --- Encrypted accounts files ---
+
+/// Environment variable holding the passphrase of an encrypted accounts file.
+pub const PASSPHRASE_VAR: &str = "GIT_SWITCH_PASSPHRASE";
+
+const ENCRYPTED_WITH_PASSPHRASE: &str = "# Encrypted by git-switch with a passphrase";
+const ENCRYPTED_WITH_IDENTITY: &str = "# Encrypted by git-switch with the age identity in ";
+
+/// How an accounts file is encrypted. The first line of the file says so;
+/// the rest is an armored age file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreEncryption {
+    Passphrase,
+    /// Encrypted to the identity file at this path, as written (`~` allowed).
+    Identity(String),
+}
+
+impl StoreEncryption {
+    fn header(&self) -> String {
+        match self {
+            StoreEncryption::Passphrase => ENCRYPTED_WITH_PASSPHRASE.to_string(),
+            StoreEncryption::Identity(path) => format!("{}{}", ENCRYPTED_WITH_IDENTITY, path),
+        }
+    }
+}
+
+/// How the accounts file `content` is encrypted; `None` if it's plain TOML.
+pub fn store_encryption(content: &str) -> Option<StoreEncryption> {
+    let (first, rest) = content.split_once('\n').unwrap_or((content, ""));
+    if let Some(path) = first.strip_prefix(ENCRYPTED_WITH_IDENTITY) {
+        Some(StoreEncryption::Identity(path.trim().to_string()))
+    } else if crypto::is_encrypted(content) || crypto::is_encrypted(rest) {
+        Some(StoreEncryption::Passphrase)
+    } else {
+        None
+    }
+}
+
+/// The last accounts file decrypted, so a command that loads and saves
+/// accounts several times asks for the passphrase and runs scrypt once.
+#[derive(Default)]
+struct StoreCache {
+    passphrase: Option<String>,
+    encrypted: String,
+    plain: String,
+}
+
+static STORE_CACHE: Mutex<Option<StoreCache>> = Mutex::new(None);
+
+/// The key for an accounts file encrypted as `encryption`. A passphrase
+/// comes from `GIT_SWITCH_PASSPHRASE`, the OS keyring or a prompt.
+fn store_key(encryption: &StoreEncryption) -> io::Result<Key> {
+    let passphrase = match encryption {
+        StoreEncryption::Identity(path) => {
+            return Ok(Key::Identity(shellexpand::tilde(path).into_owned().into()));
+        }
+        StoreEncryption::Passphrase => STORE_CACHE
+            .lock()
+            .ok()
+            .and_then(|cache| cache.as_ref()?.passphrase.clone()),
+    };
+    passphrase
+        .or_else(|| std::env::var(PASSPHRASE_VAR).ok())
+        .or_else(|| secrets::get("", Secret::AccountsFile))
+        .or_else(|| prompt_password("Passphrase of the accounts file: "))
+        .map(Key::Passphrase)
+        .ok_or_else(|| {
+            io::Error::other(format!(
+                "The accounts file is encrypted; set {} or run git-switch in a terminal",
+                PASSPHRASE_VAR
+            ))
+        })
+}
+
+fn decrypt_store(content: &str, encryption: &StoreEncryption) -> io::Result<String> {
+    if let Ok(cache) = STORE_CACHE.lock()
+        && let Some(cache) = cache.as_ref()
+        && cache.encrypted == content
+    {
+        return Ok(cache.plain.clone());
+    }
+    let key = store_key(encryption)?;
+    let armored = &content[content.find(crypto::ARMOR_BEGIN).unwrap_or(0)..];
+    let plain = crypto::decrypt(armored, &key)
+        .and_then(|plain| {
+            String::from_utf8(plain).map_err(|e| GitSwitchError::InvalidInput(e.to_string()))
+        })
+        .map_err(io::Error::other)?;
+    remember_store(&key, content, &plain);
+    Ok(plain)
+}
+
+fn encrypt_store(plain: &str, encryption: &StoreEncryption, key: &Key) -> io::Result<String> {
+    let armored = crypto::encrypt(plain.as_bytes(), key).map_err(io::Error::other)?;
+    let content = format!("{}\n{}", encryption.header(), armored);
+    remember_store(key, &content, plain);
+    Ok(content)
+}
+
+fn remember_store(key: &Key, encrypted: &str, plain: &str) {
+    if let Ok(mut cache) = STORE_CACHE.lock() {
+        *cache = Some(StoreCache {
+            passphrase: match key {
+                Key::Passphrase(passphrase) => Some(passphrase.clone()),
+                Key::Identity(_) => None,
+            },
+            encrypted: encrypted.to_string(),
+            plain: plain.to_string(),
+        });
+    }
+}
+
+/// Reads an accounts file, decrypting it if it's encrypted.
+pub fn read_accounts_file(config_file_path: &Path) -> io::Result<String> {
+    let content = fs::read_to_string(config_file_path)?;
+    match store_encryption(&content) {
+        Some(encryption) => decrypt_store(&content, &encryption),
+        None => Ok(content),
+    }
+}
+
+/// Rewrites the accounts file encrypted with `key`, or as plain TOML with
+/// `None`. Backups are deleted when encrypting, as they hold plain text.
+pub fn set_store_encryption(
     config_file_path: &Path,
+    encryption: Option<(StoreEncryption, Key)>,
 ) -> io::Result<()> {
-    let accounts_before_delete = load_accounts_from_path(config_file_path);
+    let _lock = files::lock(config_file_path)?;
+    let plain = read_accounts_file(config_file_path)?;
+    let content = match &encryption {
+        Some((encryption, key)) => encrypt_store(&plain, encryption, key)?,
+        None => plain,
+    };
+    files::write_atomic(config_file_path, &content)?;
+    if encryption.is_some()
+        && let (Some(dir), Some(name)) = (config_file_path.parent(), config_file_path.file_name())
+    {
+        for backup in files::list_backups(dir, &name.to_string_lossy())? {
+            fs::remove_file(backup)?;
+        }
+    }
+    Ok(())
+}
 
-    let updated_accounts: Vec<Account> = accounts_before_delete
-        .into_iter()
-        .filter(|acc| acc.name != name_to_delete)
-        .collect();
+// --- Worker functions that operate on a specific path ---
+
+/// Reads and parses an accounts file; `None` if it's missing or empty.
+/// Legacy pipe-format files are migrated to TOML on first load.
+fn read_account_store(config_file_path: &Path) -> io::Result<Option<AccountStore>> {
+    if !config_file_path.exists() {
+        return Ok(None);
+    }
+    let file_content = read_accounts_file(config_file_path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Error reading {}: {}", config_file_path.display(), e),
+        )
+    })?;
+    if file_content.trim().is_empty() {
+        return Ok(None);
+    }
+
+    match toml::from_str::<AccountStore>(&file_content) {
+        Ok(store) => Ok(Some(store)),
+        Err(_) if is_legacy_format(&file_content) => Ok(Some(AccountStore {
+            version: CONFIG_VERSION,
+            accounts: migrate_legacy_file(&file_content, config_file_path),
+        })),
+        Err(e) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse {}: {}", config_file_path.display(), e),
+        )),
+    }
+}
 
-    if let Some(parent_dir) = config_file_path.parent() {
-        if !parent_dir.exists() {
-            fs::create_dir_all(parent_dir)?;
+/// Loads accounts from a specified configuration file path, for reading
+/// only: a file that can't be read or parsed gives a warning and no accounts.
+pub fn load_accounts_from_path(config_file_path: &Path) -> Vec<Account> {
+    match read_account_store(config_file_path) {
+        Ok(Some(store)) => {
+            if store.version > CONFIG_VERSION {
+                problem!(
+                    "⚠️ {} uses schema version {}, newer than this git-switch supports ({}). Some settings may be ignored.",
+                    config_file_path.display(),
+                    store.version,
+                    CONFIG_VERSION
+                );
+            }
+            store.accounts
         }
+        Ok(None) => Vec::new(),
+        Err(e) => {
+            problem!("[LOAD_ACCOUNTS_FROM_PATH] {}. Returning empty.", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Loads accounts that are about to be changed and written back. Fails
+/// instead of letting the write replace a file that couldn't be read, or
+/// drop the fields of a newer schema version.
+pub fn load_accounts_for_update(config_file_path: &Path) -> io::Result<Vec<Account>> {
+    let Some(store) = read_account_store(config_file_path)? else {
+        return Ok(Vec::new());
+    };
+    if store.version > CONFIG_VERSION {
+        return Err(io::Error::other(format!(
+            "{} uses schema version {}, newer than this git-switch supports ({}); upgrade git-switch to change it",
+            config_file_path.display(),
+            store.version,
+            CONFIG_VERSION
+        )));
     }
+    Ok(store.accounts)
+}
 
-    let mut file = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(config_file_path)?;
+/// Writes the full list of accounts to the specified configuration file path.
+/// Doesn't lock the file; the `*_to_path` and `*_in_path` helpers do.
+pub fn write_accounts_to_path(accounts: &[Account], config_file_path: &Path) -> io::Result<()> {
+    if let Some(parent_dir) = config_file_path.parent()
+        && !parent_dir.exists()
+    {
+        fs::create_dir_all(parent_dir)?;
+    }
 
-    for account_to_write in &updated_accounts {
-        let entry = format!(
-            "{}|{}|{}|{}\n",
-            account_to_write.name,
-            account_to_write.username,
-            account_to_write.email,
-            account_to_write.ssh_key
-        );
-        file.write_all(entry.as_bytes())?;
+    let content = accounts_to_toml(accounts).map_err(io::Error::other)?;
+    let raw = fs::read_to_string(config_file_path).ok();
+    let encryption = raw.as_deref().and_then(store_encryption);
+    let previous = match (&raw, &encryption) {
+        (Some(raw), Some(encryption)) => Some(decrypt_store(raw, encryption)?),
+        _ => raw,
+    };
+    if previous.as_deref() == Some(content.as_str()) {
+        return Ok(());
+    }
+    match &encryption {
+        Some(encryption) => {
+            let encrypted = encrypt_store(&content, encryption, &store_key(encryption)?)?;
+            files::write_with_backup(config_file_path, &encrypted)?;
+        }
+        None => {
+            files::write_with_backup(config_file_path, &content)?;
+        }
     }
-    file.flush()?;
-    drop(file);
+    events::file_modified(config_file_path, previous.as_deref(), Some(&content));
     Ok(())
 }
 
+/// Saves an account to the specified configuration file path.
+pub fn save_account_to_path(account: &Account, config_file_path: &Path) -> io::Result<()> {
+    let _lock = files::lock(config_file_path)?;
+    let mut accounts = load_accounts_for_update(config_file_path)?;
+    accounts.push(account.clone());
+    write_accounts_to_path(&accounts, config_file_path)
+}
+
+/// Replaces the saved account with the same name.
+pub fn update_account_in_path(account: &Account, config_file_path: &Path) -> io::Result<()> {
+    let _lock = files::lock(config_file_path)?;
+    let accounts: Vec<Account> = load_accounts_for_update(config_file_path)?
+        .into_iter()
+        .map(|acc| {
+            if acc.name == account.name {
+                account.clone()
+            } else {
+                acc
+            }
+        })
+        .collect();
+    write_accounts_to_path(&accounts, config_file_path)
+}
+
+/// Puts `account` in place of the saved account named `old_name`.
+pub fn replace_account_in_path(
+    old_name: &str,
+    account: &Account,
+    config_file_path: &Path,
+) -> io::Result<()> {
+    let _lock = files::lock(config_file_path)?;
+    let accounts: Vec<Account> = load_accounts_for_update(config_file_path)?
+        .into_iter()
+        .map(|acc| {
+            if acc.name == old_name {
+                account.clone()
+            } else {
+                acc
+            }
+        })
+        .collect();
+    write_accounts_to_path(&accounts, config_file_path)
+}
+
+/// Makes the named account the only default one, or clears the default
+/// with `None`.
+pub fn set_default_account_in_path(name: Option<&str>, config_file_path: &Path) -> io::Result<()> {
+    let _lock = files::lock(config_file_path)?;
+    let accounts: Vec<Account> = load_accounts_for_update(config_file_path)?
+        .into_iter()
+        .map(|acc| Account {
+            default: Some(acc.name.as_str()) == name,
+            ..acc
+        })
+        .collect();
+    write_accounts_to_path(&accounts, config_file_path)
+}
+
+/// The account marked as default, if any.
+pub fn default_account(accounts: &[Account]) -> Option<&Account> {
+    accounts.iter().find(|acc| acc.default)
+}
+
+/// Deletes an account from the specified configuration file path.
+pub fn delete_account_from_path(name_to_delete: &str, config_file_path: &Path) -> io::Result<()> {
+    let _lock = files::lock(config_file_path)?;
+    let updated_accounts: Vec<Account> = load_accounts_for_update(config_file_path)?
+        .into_iter()
+        .filter(|acc| acc.name != name_to_delete)
+        .collect();
+    write_accounts_to_path(&updated_accounts, config_file_path)
+}
+
 // --- Public wrapper functions using the default path ---
 
 /// Loads all saved Git accounts from the default configuration file.
@@ -135,53 +1179,219 @@ pub fn load_accounts() -> Vec<Account> {
     load_accounts_from_path(&default_path)
 }
 
+/// Replaces all saved accounts in the default configuration file, unless
+/// the file there can't be read back (see [`load_accounts_for_update`]).
+pub fn write_accounts(accounts: &[Account]) -> io::Result<()> {
+    let path = get_default_config_path();
+    let _lock = files::lock(&path)?;
+    load_accounts_for_update(&path)?;
+    write_accounts_to_path(accounts, &path)
+}
+
 /// Saves a new Git account to the default configuration file.
-pub fn save_account(account: &Account) {
-    let default_path = get_default_config_path();
-    match save_account_to_path(account, &default_path) {
-        Ok(_) => println!("✅ Account '{}' saved.", account.name),
-        Err(e) => eprintln!("❌ Failed to save account '{}': {}", account.name, e),
-    }
+pub fn save_account(account: &Account) -> io::Result<()> {
+    save_account_to_path(account, &get_default_config_path())
+}
+
+/// Replaces the saved account named `old_name` in the default configuration file.
+pub fn replace_account(old_name: &str, account: &Account) -> io::Result<()> {
+    replace_account_in_path(old_name, account, &get_default_config_path())
+}
+
+/// Makes an account the default in the default configuration file.
+pub fn set_default_account(name: Option<&str>) -> io::Result<()> {
+    set_default_account_in_path(name, &get_default_config_path())
+}
+
+/// Replaces a saved account (matched by name) in the default configuration file.
+pub fn update_account(account: &Account) -> io::Result<()> {
+    update_account_in_path(account, &get_default_config_path())
 }
 
 /// Removes a saved Git account from the default configuration file.
 pub fn delete_account(name_to_delete: &str) -> io::Result<()> {
-    let default_path = get_default_config_path();
-    match delete_account_from_path(name_to_delete, &default_path) {
-        Ok(_) => {
-            println!("🗑️ Account '{}' removed from config.", name_to_delete);
-            Ok(())
+    delete_account_from_path(name_to_delete, &get_default_config_path())
+}
+
+/// Renders accounts as a JSON array (the stored fields) or as CSV with one
+/// row per account and hosts joined by `;`.
+pub fn format_accounts(accounts: &[Account], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(accounts).unwrap_or_default(),
+        _ => {
+            let mut csv = String::from("name,username,email,ssh_key,hosts,workspace,tags\n");
+            for acc in accounts {
+                let fields = [
+                    acc.name.as_str(),
+                    &acc.username,
+                    &acc.email,
+                    &acc.ssh_key,
+                    &acc.hosts().join(";"),
+                    acc.workspace.as_deref().unwrap_or_default(),
+                    &acc.tags.join(";"),
+                ];
+                let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                csv.push_str(&row.join(","));
+                csv.push('\n');
+            }
+            csv.trim_end().to_string()
         }
-        Err(e) => {
-            eprintln!(
-                "❌ Failed to remove account '{}' from config: {}",
-                name_to_delete, e
-            );
-            Err(e)
+    }
+}
+
+/// Fingerprint of an account's key and whether ssh-agent holds it; `None`
+/// for HTTPS accounts, unreadable keys, or when the agent can't be reached.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyState {
+    pub fingerprint: Option<String>,
+    pub in_agent: Option<bool>,
+}
+
+/// The key state of each account, asking the agent only once.
+pub fn key_states(accounts: &[Account]) -> Vec<KeyState> {
+    let loaded = crate::ssh::agent_fingerprints();
+    accounts
+        .iter()
+        .map(|acc| {
+            let fingerprint = Some(&acc.ssh_key)
+                .filter(|_| !acc.uses_https())
+                .and_then(|key| crate::ssh::key_fingerprint(key));
+            let in_agent = fingerprint
+                .as_ref()
+                .and_then(|fingerprint| loaded.as_ref().map(|loaded| loaded.contains(fingerprint)));
+            KeyState {
+                fingerprint,
+                in_agent,
+            }
+        })
+        .collect()
+}
+
+/// [`format_accounts`] with `fingerprint` and `in_agent` added to each
+/// account.
+pub fn format_accounts_with_keys(
+    accounts: &[Account],
+    states: &[KeyState],
+    format: OutputFormat,
+) -> String {
+    match format {
+        OutputFormat::Json => {
+            let entries: Vec<serde_json::Value> = accounts
+                .iter()
+                .zip(states)
+                .map(|(acc, state)| {
+                    let mut entry = serde_json::to_value(acc).unwrap_or_default();
+                    if let Some(entry) = entry.as_object_mut() {
+                        entry.insert("fingerprint".into(), state.fingerprint.clone().into());
+                        entry.insert("in_agent".into(), state.in_agent.into());
+                    }
+                    entry
+                })
+                .collect();
+            serde_json::to_string_pretty(&entries).unwrap_or_default()
+        }
+        _ => {
+            let csv = format_accounts(accounts, format);
+            let mut lines = csv.lines();
+            let mut out = format!("{},fingerprint,in_agent", lines.next().unwrap_or_default());
+            for (line, state) in lines.zip(states) {
+                out.push_str(&format!(
+                    "\n{},{},{}",
+                    line,
+                    state.fingerprint.as_deref().unwrap_or_default(),
+                    state
+                        .in_agent
+                        .map(|held| held.to_string())
+                        .unwrap_or_default()
+                ));
+            }
+            out
         }
     }
 }
 
-/// Lists all saved Git accounts from the default configuration file.
-pub fn list_accounts() {
-    let accounts = load_accounts();
+/// The `--keys` line under an account in `list`.
+fn key_state_line(acc: &Account, state: &KeyState) -> String {
+    let Some(fingerprint) = &state.fingerprint else {
+        return if acc.uses_https() {
+            "no SSH key (HTTPS)".to_string()
+        } else {
+            format!("can't read {}.pub", acc.ssh_key)
+        };
+    };
+    let held = match state.in_agent {
+        Some(true) => "loaded in ssh-agent",
+        Some(false) => "not loaded",
+        None => "ssh-agent not reachable",
+    };
+    format!("{}  {}", fingerprint, held)
+}
+
+/// Lists all saved Git accounts from the default configuration file, or only
+/// those carrying `tag`. With `keys`, also each key's fingerprint and
+/// whether ssh-agent holds it.
+pub fn list_accounts(format: OutputFormat, tag: Option<&str>, keys: bool) {
+    let accounts: Vec<Account> = load_accounts()
+        .into_iter()
+        .filter(|acc| tag.is_none_or(|tag| acc.has_tag(tag)))
+        .collect();
+    let states = if keys {
+        key_states(&accounts)
+    } else {
+        Vec::new()
+    };
+    if format != OutputFormat::Table {
+        match keys {
+            true => println!("{}", format_accounts_with_keys(&accounts, &states, format)),
+            false => println!("{}", format_accounts(&accounts, format)),
+        }
+        return;
+    }
     if accounts.is_empty() {
-        println!("No saved accounts.");
+        match tag {
+            Some(tag) => println!("No accounts are tagged '{}'.", tag),
+            None => println!("No saved accounts."),
+        }
         return;
     }
 
-    println!("🔹 Saved Git Accounts:");
-    println!("------------------------------------------------------------");
+    match tag {
+        Some(tag) => report!("🔹 Saved Git Accounts tagged '{}':", tag),
+        None => report!("🔹 Saved Git Accounts:"),
+    }
+    println!(
+        "-------------------------------------------------------------------------------------------"
+    );
+    println!(
+        "{:<20} | {:<25} | {:<30} | {:<20}",
+        "Account Name", "Git Username", "Email", "Host"
+    );
     println!(
-        "{:<20} | {:<25} | {:<30}",
-        "Account Name", "Git Username", "Email"
+        "-------------------------------------------------------------------------------------------"
     );
-    println!("------------------------------------------------------------");
-    for acc in &accounts {
+    for (index, acc) in accounts.iter().enumerate() {
+        let name = if acc.default {
+            format!("{} (default)", acc.name)
+        } else {
+            acc.name.clone()
+        };
         println!(
-            "{:<20} | {:<25} | {:<30}",
-            acc.name, acc.username, acc.email
+            "{:<20} | {:<25} | {:<30} | {:<20}{}",
+            name,
+            acc.username,
+            acc.email,
+            acc.hosts().join(", "),
+            if acc.tags.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", acc.tags.join(", "))
+            }
         );
+        if let Some(state) = states.get(index) {
+            report!("{:<20}   🔑 {}", "", key_state_line(acc, state));
+        }
     }
-    println!("------------------------------------------------------------");
+    println!(
+        "-------------------------------------------------------------------------------------------"
+    );
 }
@@ -0,0 +1,244 @@
+//! The git credential helper protocol and picking the account a credential
+//! request is for.
+
+use crate::config::Account;
+use crate::ssh::host_alias;
+use crate::utils::command_output;
+
+/// The attributes git sends to a credential helper, as described in
+/// `git help credential`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CredentialRequest {
+    pub protocol: String,
+    pub host: String,
+    pub port: Option<u16>,
+    pub path: Option<String>,
+    pub username: Option<String>,
+}
+
+impl CredentialRequest {
+    /// Parses the `key=value` lines git writes to the helper's stdin.
+    pub fn parse(input: &str) -> CredentialRequest {
+        let mut request = CredentialRequest::default();
+        for line in input.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                break;
+            }
+            match line.split_once('=') {
+                Some(("protocol", value)) => request.protocol = value.to_string(),
+                Some(("host", value)) => {
+                    let (host, port) = split_host_port(value);
+                    request.host = host;
+                    request.port = port;
+                }
+                Some(("path", value)) => request.path = Some(value.to_string()),
+                Some(("username", value)) => request.username = Some(value.to_string()),
+                Some(("url", value)) => {
+                    if let Some(parsed) = CredentialRequest::from_url(value) {
+                        request = CredentialRequest {
+                            username: request.username.or(parsed.username.clone()),
+                            ..parsed
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+        request
+    }
+
+    /// Parses a URL such as `https://user@git.corp.com:8443/gitlab/team/repo.git`.
+    pub fn from_url(url: &str) -> Option<CredentialRequest> {
+        let (protocol, rest) = url.split_once("://")?;
+        let (authority, path) = match rest.split_once('/') {
+            Some((authority, path)) => (authority, Some(path.to_string())),
+            None => (rest, None),
+        };
+        let (username, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (Some(user.to_string()), host_port),
+            None => (None, authority),
+        };
+        let (host, port) = split_host_port(host_port);
+        Some(CredentialRequest {
+            protocol: protocol.to_string(),
+            host,
+            port,
+            path: path.filter(|p| !p.is_empty()),
+            username,
+        })
+    }
+
+    /// Reconstructs the request as a URL, the form `insteadOf` rules operate on.
+    pub fn to_url(&self) -> String {
+        let mut url = format!("{}://{}", self.protocol, self.host);
+        if let Some(port) = self.port {
+            url.push_str(&format!(":{}", port));
+        }
+        url.push('/');
+        if let Some(path) = &self.path {
+            url.push_str(path.trim_start_matches('/'));
+        }
+        url
+    }
+
+    fn effective_port(&self) -> Option<u16> {
+        self.port.or(match self.protocol.as_str() {
+            "https" => Some(443),
+            "http" => Some(80),
+            "ssh" => Some(22),
+            _ => None,
+        })
+    }
+
+    fn path_segments(&self) -> Vec<&str> {
+        self.path
+            .as_deref()
+            .unwrap_or("")
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+}
+
+fn split_host_port(value: &str) -> (String, Option<u16>) {
+    match value.rsplit_once(':') {
+        Some((host, port)) if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => {
+            (host.to_string(), port.parse().ok())
+        }
+        _ => (value.to_string(), None),
+    }
+}
+
+/// Reads `url.<base>.insteadOf <prefix>` rules from the effective git config.
+pub fn read_insteadof_rules() -> Vec<(String, String)> {
+    let output = command_output("git", &["config", "--get-regexp", r"^url\..*\.insteadof$"]);
+    match output {
+        Ok(out) if out.status.success() => {
+            parse_insteadof_rules(&String::from_utf8_lossy(&out.stdout))
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Parses `git config --get-regexp` output into `(base, prefix)` pairs.
+pub fn parse_insteadof_rules(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, prefix) = line.split_once(' ')?;
+            let base = key.strip_prefix("url.")?.strip_suffix(".insteadof")?;
+            Some((base.to_string(), prefix.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Finds the account owning a credential request.
+///
+/// Besides the request itself, every URL it could have been rewritten from by
+/// an `insteadOf` rule is considered. Matches are ranked: a managed host alias
+/// (`github-work`) beats an account's `credential_url` (longest path prefix
+/// wins, so enterprise servers hosted under subpaths resolve correctly), which
+/// beats an explicit username, which beats the repository owner on one of the
+/// account's hosts.
+pub fn resolve_account<'a>(
+    accounts: &'a [Account],
+    request: &CredentialRequest,
+    insteadof_rules: &[(String, String)],
+) -> Option<&'a Account> {
+    let mut candidates = vec![request.clone()];
+    let url = request.to_url();
+    for (base, prefix) in insteadof_rules {
+        if let Some(rest) = url.strip_prefix(base.as_str())
+            && let Some(mut original) = CredentialRequest::from_url(&format!("{}{}", prefix, rest))
+        {
+            original.username = original.username.or(request.username.clone());
+            candidates.push(original);
+        }
+    }
+
+    accounts
+        .iter()
+        .filter_map(|acc| {
+            candidates
+                .iter()
+                .filter_map(|candidate| match_score(acc, candidate))
+                .max()
+                .map(|score| (score, acc))
+        })
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, acc)| acc)
+}
+
+fn match_score(account: &Account, request: &CredentialRequest) -> Option<usize> {
+    if account.hosts().iter().any(|host| {
+        request
+            .host
+            .eq_ignore_ascii_case(&host_alias(host, &account.name))
+    }) {
+        return Some(1000);
+    }
+
+    if let Some(base) = account
+        .credential_url
+        .as_deref()
+        .and_then(CredentialRequest::from_url)
+        && base.protocol == request.protocol
+        && base.host.eq_ignore_ascii_case(&request.host)
+        && base.effective_port() == request.effective_port()
+    {
+        let prefix = base.path_segments();
+        let segments = request.path_segments();
+        if segments.len() >= prefix.len() && segments[..prefix.len()] == prefix[..] {
+            return Some(100 + prefix.len());
+        }
+    }
+
+    if request
+        .username
+        .as_deref()
+        .is_some_and(|user| user == account.username)
+    {
+        return Some(50);
+    }
+
+    if account
+        .hosts()
+        .iter()
+        .any(|host| request.host.eq_ignore_ascii_case(host))
+        && request
+            .path_segments()
+            .first()
+            .is_some_and(|owner| owner.eq_ignore_ascii_case(&account.username))
+    {
+        return Some(10);
+    }
+
+    None
+}
+
+/// The host whose stored token answers a request for an HTTPS account: one
+/// of the account's hosts, or the primary host for requests matching its
+/// `credential_url`. `None` for SSH accounts and for anything else, so the
+/// token is only ever sent to the host it was made for.
+pub fn token_host<'a>(account: &'a Account, request: &CredentialRequest) -> Option<&'a str> {
+    if !account.uses_https() || request.protocol != "https" {
+        return None;
+    }
+    if let Some(host) = account
+        .hosts()
+        .into_iter()
+        .find(|host| request.host.eq_ignore_ascii_case(host))
+    {
+        return Some(host);
+    }
+    account
+        .credential_url
+        .as_deref()
+        .and_then(CredentialRequest::from_url)
+        .filter(|base| {
+            base.host.eq_ignore_ascii_case(&request.host)
+                && base.effective_port() == request.effective_port()
+        })
+        .map(|_| account.host.as_str())
+}
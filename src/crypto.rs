@@ -0,0 +1,135 @@
+//! Encryption with [age](https://age-encryption.org), for secrets that
+//! leave the machine or sit on disk: exported private keys and bundles, and
+//! the accounts file in encrypted mode.
+//!
+//! The output is ASCII-armored, so it fits in TOML and JSON and can be
+//! decrypted with the `age` CLI as well. Builds without the `encryption`
+//! feature fail with [`GitSwitchError::Unsupported`].
+
+use crate::error::{GitSwitchError, Result};
+use std::path::PathBuf;
+
+/// Whether this build can encrypt.
+pub const AVAILABLE: bool = cfg!(feature = "encryption");
+
+/// First line of an armored age file.
+pub const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// What a file is encrypted with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Key {
+    Passphrase(String),
+    /// An age identity file (`age-keygen -o FILE`); files are encrypted to
+    /// its public key.
+    Identity(PathBuf),
+}
+
+/// Whether `text` is an armored age file.
+pub fn is_encrypted(text: &str) -> bool {
+    text.trim_start().starts_with(ARMOR_BEGIN)
+}
+
+#[cfg(feature = "encryption")]
+fn crypto_error(e: impl std::fmt::Display) -> GitSwitchError {
+    GitSwitchError::InvalidInput(format!("Encryption failed: {}", e))
+}
+
+/// Reads the X25519 identities of an age identity file.
+#[cfg(feature = "encryption")]
+fn read_identities(path: &std::path::Path) -> Result<Vec<age::x25519::Identity>> {
+    use crate::error::IoContext;
+
+    let content = std::fs::read_to_string(path)
+        .context(format!("Failed to read identity file {}", path.display()))?;
+    let identities: Vec<age::x25519::Identity> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.parse().ok())
+        .collect();
+    if identities.is_empty() {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "{} holds no age identity (AGE-SECRET-KEY-1...)",
+            path.display()
+        )));
+    }
+    Ok(identities)
+}
+
+/// Encrypts `plain` with `key`, returning an armored age file.
+#[cfg(feature = "encryption")]
+pub fn encrypt(plain: &[u8], key: &Key) -> Result<String> {
+    use age::armor::{ArmoredWriter, Format};
+    use age::secrecy::SecretString;
+    use std::io::Write;
+
+    let encryptor = match key {
+        Key::Passphrase(passphrase) => {
+            age::Encryptor::with_user_passphrase(SecretString::from(passphrase.clone()))
+        }
+        Key::Identity(path) => {
+            let recipients: Vec<age::x25519::Recipient> = read_identities(path)?
+                .iter()
+                .map(age::x25519::Identity::to_public)
+                .collect();
+            age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+                .map_err(crypto_error)?
+        }
+    };
+    let mut armored = Vec::new();
+    let output =
+        ArmoredWriter::wrap_output(&mut armored, Format::AsciiArmor).map_err(crypto_error)?;
+    let mut writer = encryptor.wrap_output(output).map_err(crypto_error)?;
+    writer.write_all(plain).map_err(crypto_error)?;
+    writer
+        .finish()
+        .and_then(|output| output.finish())
+        .map_err(crypto_error)?;
+    String::from_utf8(armored).map_err(crypto_error)
+}
+
+/// Decrypts an armored age file made by [`encrypt`]. A wrong key fails with
+/// [`GitSwitchError::InvalidInput`].
+#[cfg(feature = "encryption")]
+pub fn decrypt(armored: &str, key: &Key) -> Result<Vec<u8>> {
+    use age::armor::ArmoredReader;
+    use age::secrecy::SecretString;
+    use std::io::Read;
+
+    let decryptor = age::Decryptor::new(ArmoredReader::new(armored.trim_start().as_bytes()))
+        .map_err(crypto_error)?;
+    let identities: Vec<Box<dyn age::Identity>> = match key {
+        Key::Passphrase(passphrase) => vec![Box::new(age::scrypt::Identity::new(
+            SecretString::from(passphrase.clone()),
+        ))],
+        Key::Identity(path) => read_identities(path)?
+            .into_iter()
+            .map(|identity| Box::new(identity) as Box<dyn age::Identity>)
+            .collect(),
+    };
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|identity| identity.as_ref()))
+        .map_err(|e| {
+            GitSwitchError::InvalidInput(format!("Can't decrypt ({}); is the key right?", e))
+        })?;
+    let mut plain = Vec::new();
+    reader.read_to_end(&mut plain).map_err(crypto_error)?;
+    Ok(plain)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn unsupported() -> GitSwitchError {
+    GitSwitchError::Unsupported(
+        "This build of git-switch can't encrypt (cargo feature `encryption`).".to_string(),
+    )
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn encrypt(_plain: &[u8], _key: &Key) -> Result<String> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn decrypt(_armored: &str, _key: &Key) -> Result<Vec<u8>> {
+    Err(unsupported())
+}
@@ -0,0 +1,908 @@
+//! Health checks behind `git-switch doctor`, with fixes for the problems
+//! that can be repaired automatically.
+
+use crate::config::{
+    Account, accounts_from_toml, get_default_config_path, load_accounts, match_identity,
+    read_accounts_file, validate_accounts,
+};
+use crate::events;
+use crate::policy::{load_policy, policy_path};
+#[cfg(feature = "provider-api")]
+use crate::problem;
+use crate::provider::account_provider;
+#[cfg(feature = "provider-api")]
+use crate::provider::{KeyApi, api_token, inspect_token, key_api_for};
+use crate::settings::{SwitchMode, load_settings};
+use crate::ssh::{
+    WINDOWS_AGENT_HINT, agent_fingerprints, block_markers, get_ssh_config_path, host_alias,
+    key_fingerprint, update_ssh_config, upsert_account_entries,
+};
+#[cfg(feature = "provider-api")]
+use crate::tokens::{self, TokenInfo, TokenIssue};
+use crate::utils::command_output;
+#[cfg(feature = "provider-api")]
+use crate::utils::now_secs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Severity {
+    Ok,
+    Warning,
+    Problem,
+}
+
+/// A single result reported by `git-switch doctor`.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+    pub hint: Option<String>,
+    /// An automatic fix doctor can offer to apply.
+    pub fix: Option<Fix>,
+}
+
+/// A change doctor can make on the user's behalf after confirmation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fix {
+    /// Removes keys from a git config file (`None` for the repository's config).
+    UnsetConfig {
+        file: Option<String>,
+        keys: Vec<String>,
+    },
+    /// Rewrites the account's managed block in `~/.ssh/config`.
+    WriteSshBlock(Box<Account>),
+}
+
+impl Fix {
+    pub fn description(&self) -> String {
+        match self {
+            Fix::UnsetConfig { file, keys } => format!(
+                "Remove {} from {}",
+                keys.join(" and "),
+                file.as_deref().unwrap_or("the repository config")
+            ),
+            Fix::WriteSshBlock(account) => format!(
+                "Rewrite the SSH config entries of account '{}'",
+                account.name
+            ),
+        }
+    }
+
+    pub fn apply(&self) -> bool {
+        match self {
+            Fix::UnsetConfig { file, keys } => keys.iter().all(|key| {
+                let mut args = vec!["config"];
+                match file {
+                    Some(file) => args.extend(["--file", file.as_str()]),
+                    None => args.push("--local"),
+                }
+                args.extend(["--unset-all", key.as_str()]);
+                command_output("git", &args).is_ok_and(|out| out.status.success())
+            }),
+            Fix::WriteSshBlock(account) => update_ssh_config(account).is_ok(),
+        }
+    }
+}
+
+/// One `user.*` value and the config file it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigEntry {
+    pub scope: String,
+    pub file: String,
+    pub key: String,
+    pub value: String,
+}
+
+impl Finding {
+    fn ok(message: String) -> Finding {
+        Finding {
+            severity: Severity::Ok,
+            message,
+            hint: None,
+            fix: None,
+        }
+    }
+
+    fn warning(message: String, hint: &str) -> Finding {
+        Finding {
+            severity: Severity::Warning,
+            message,
+            hint: Some(hint.to_string()),
+            fix: None,
+        }
+    }
+
+    fn problem(message: String, hint: &str) -> Finding {
+        Finding {
+            severity: Severity::Problem,
+            message,
+            hint: Some(hint.to_string()),
+            fix: None,
+        }
+    }
+
+    fn with_fix(mut self, fix: Fix) -> Finding {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+/// Runs all checks and returns their findings. Provider tokens expiring
+/// within `token_expiry_days` are flagged.
+#[cfg_attr(not(feature = "provider-api"), allow(unused_variables))]
+pub fn run_checks(token_expiry_days: u32) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let git_version = command_output("git", &["--version"])
+        .ok()
+        .filter(|out| out.status.success());
+    match &git_version {
+        Some(out) => findings.push(Finding::ok(
+            String::from_utf8_lossy(&out.stdout).trim().to_string(),
+        )),
+        None => {
+            findings.push(Finding::problem(
+                "git is not installed or not on PATH".to_string(),
+                "Install git; git-switch only edits its configuration.",
+            ));
+            return findings;
+        }
+    }
+    findings.extend(check_accounts_file());
+    let accounts = load_accounts();
+    findings.extend(check_key_files(&accounts));
+    // In the sshcommand mode accounts have no blocks to check
+    if load_settings().mode == SwitchMode::Alias
+        && let Ok(ssh_config) = std::fs::read_to_string(get_ssh_config_path())
+    {
+        findings.extend(check_ssh_config(&ssh_config, &accounts));
+    }
+    findings.push(check_ssh_agent());
+    findings.extend(check_global_identity(&accounts));
+    if let Ok(output) = command_output(
+        "git",
+        &[
+            "config",
+            "--show-origin",
+            "--show-scope",
+            "--get-regexp",
+            r"^user\.(name|email)$",
+        ],
+    ) {
+        let entries = parse_config_entries(&String::from_utf8_lossy(&output.stdout));
+        findings.extend(check_identity_shadowing(
+            &entries,
+            &global_config_file(),
+            &accounts,
+        ));
+    }
+    let policy = load_policy();
+    for account in &accounts {
+        for rule in policy.evaluate(account) {
+            findings.push(Finding::warning(
+                format!("[{}] Policy: {}", account.name, rule.message),
+                &format!(
+                    "Rule from the machine policy at {}",
+                    policy_path().display()
+                ),
+            ));
+        }
+    }
+    findings.extend(check_ssh_key_order(&accounts));
+    for account in accounts.iter().filter(|acc| acc.lfs_url.is_some()) {
+        findings.push(check_lfs_auth(account));
+    }
+    #[cfg(feature = "provider-api")]
+    findings.extend(check_tokens(&accounts, token_expiry_days));
+    findings
+}
+
+/// Checks that the accounts file parses and its accounts are complete.
+fn check_accounts_file() -> Vec<Finding> {
+    let path = get_default_config_path();
+    let content = match read_accounts_file(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return vec![Finding::warning(
+                format!("No accounts file at {}", path.display()),
+                "Add an account with `git-switch add`.",
+            )];
+        }
+        Err(e) => {
+            return vec![Finding::problem(
+                format!("{} can't be read: {}", path.display(), e),
+                "Check the key of the encrypted accounts file, or its permissions.",
+            )];
+        }
+    };
+    if content.trim().is_empty() {
+        return Vec::new();
+    }
+    let accounts = match accounts_from_toml(&content) {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            return vec![Finding::problem(
+                format!("{} does not parse: {}", path.display(), e),
+                "Fix the file by hand or with `git-switch edit --all`; until then git-switch sees no accounts.",
+            )];
+        }
+    };
+    let errors = validate_accounts(&accounts);
+    if errors.is_empty() {
+        return vec![Finding::ok(format!(
+            "{} holds {} valid account(s)",
+            path.display(),
+            accounts.len()
+        ))];
+    }
+    errors
+        .into_iter()
+        .map(|error| Finding::problem(error, "Correct the account with `git-switch edit`."))
+        .collect()
+}
+
+/// Reports accounts whose public key is missing (a missing private key is
+/// reported by the accounts file check).
+fn check_key_files(accounts: &[Account]) -> Vec<Finding> {
+    accounts
+        .iter()
+        .filter(|acc| std::path::Path::new(&*shellexpand::tilde(&acc.ssh_key)).exists())
+        .filter(|acc| {
+            !std::path::Path::new(&format!("{}.pub", shellexpand::tilde(&acc.ssh_key))).exists()
+        })
+        .map(|acc| {
+            Finding::warning(
+                format!("[{}] Public key {}.pub is missing", acc.name, acc.ssh_key),
+                &format!(
+                    "Recreate it with `ssh-keygen -y -f {} > {}.pub`.",
+                    acc.ssh_key, acc.ssh_key
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Checks that every account has an up-to-date managed block in the SSH
+/// config and that no `Host` alias is defined twice.
+pub fn check_ssh_config(content: &str, accounts: &[Account]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for account in accounts.iter().filter(|acc| !acc.uses_https()) {
+        if upsert_account_entries(content, account) == content {
+            continue;
+        }
+        let (start, _) = block_markers(&account.name);
+        let message = if content.lines().any(|line| line.trim() == start) {
+            format!(
+                "[{}] SSH config entries differ from the account settings",
+                account.name
+            )
+        } else {
+            format!(
+                "[{}] SSH config has no entries for the account",
+                account.name
+            )
+        };
+        findings.push(
+            Finding::problem(
+                message,
+                "Git operations through the account's host alias will not use its key.",
+            )
+            .with_fix(Fix::WriteSshBlock(Box::new(account.clone()))),
+        );
+    }
+    for alias in duplicate_host_aliases(content) {
+        findings.push(Finding::problem(
+            format!("Host {} is defined more than once in the SSH config", alias),
+            "ssh uses the first value of each option, so the later block is partly ignored; remove one of them.",
+        ));
+    }
+    if findings.is_empty() && !accounts.is_empty() {
+        findings.push(Finding::ok(
+            "SSH config entries match all accounts".to_string(),
+        ));
+    }
+    findings
+}
+
+/// Host patterns (other than wildcards) that appear in more than one `Host` line.
+pub fn duplicate_host_aliases(content: &str) -> Vec<String> {
+    let mut seen: Vec<String> = Vec::new();
+    let mut duplicates: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let mut words = line.split_whitespace();
+        if !words
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("host"))
+        {
+            continue;
+        }
+        for pattern in words.filter(|pattern| !pattern.contains(['*', '?', '!'])) {
+            if seen.iter().any(|known| known == pattern) {
+                if !duplicates.iter().any(|known| known == pattern) {
+                    duplicates.push(pattern.to_string());
+                }
+            } else {
+                seen.push(pattern.to_string());
+            }
+        }
+    }
+    duplicates
+}
+
+/// Checks that `ssh-add` can talk to an agent.
+fn check_ssh_agent() -> Finding {
+    // ssh-add -l exits 1 for an agent without keys and 2 without an agent
+    match command_output("ssh-add", &["-l"]).map(|out| out.status.code()) {
+        Ok(Some(0 | 1)) => Finding::ok("ssh-agent is reachable".to_string()),
+        Ok(_) if cfg!(windows) => {
+            Finding::warning("ssh-agent is not reachable".to_string(), WINDOWS_AGENT_HINT)
+        }
+        Ok(_) => Finding::warning(
+            "ssh-agent is not reachable".to_string(),
+            "Start one with `eval \"$(ssh-agent -s)\"`; without it, keys with a passphrase ask for it on every git operation.",
+        ),
+        Err(_) => Finding::warning(
+            "ssh-add is not installed".to_string(),
+            "Install OpenSSH; git-switch uses it to generate and load keys.",
+        ),
+    }
+}
+
+/// Checks that the global identity belongs to a saved account.
+fn check_global_identity(accounts: &[Account]) -> Option<Finding> {
+    let value = |key: &str| {
+        command_output("git", &["config", "--global", key])
+            .ok()
+            .filter(|out| out.status.success())
+            .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+    };
+    let name = value("user.name").unwrap_or_default();
+    let email = value("user.email")?;
+    Some(match match_identity(accounts, &name, &email) {
+        Some(account) => Finding::ok(format!(
+            "Global identity {} <{}> is account '{}'",
+            name, email, account.name
+        )),
+        None => Finding::warning(
+            format!(
+                "Global identity {} <{}> matches no saved account",
+                name, email
+            ),
+            "Switch to one of your accounts with `git-switch use <name> --global`.",
+        ),
+    })
+}
+
+/// Validates the API token each account would use for key uploads. A check
+/// is reused from `~/.git-switch/tokens.toml` until it is a day old, so
+/// expiry is still reported offline.
+#[cfg(feature = "provider-api")]
+fn check_tokens(accounts: &[Account], window_days: u32) -> Vec<Finding> {
+    let known = tokens::load_tokens();
+    let now = now_secs();
+    let mut checked: Vec<(&str, String)> = Vec::new();
+    let mut findings = Vec::new();
+    for account in accounts {
+        let Some((provider, api)) = key_api_for(account) else {
+            continue;
+        };
+        let Some((token, source)) = api_token(provider, account) else {
+            continue;
+        };
+        let fingerprint = tokens::fingerprint(&token);
+        if checked.contains(&(provider.host, fingerprint.clone())) {
+            continue;
+        }
+        checked.push((provider.host, fingerprint.clone()));
+
+        let cached = known.iter().find(|info| {
+            info.host == provider.host
+                && info.fingerprint == fingerprint
+                && now.saturating_sub(info.checked_at) < tokens::RECHECK_AFTER_SECS
+        });
+        let info = match cached {
+            Some(info) => info.clone(),
+            None => match inspect_token(provider, api, &token, &source) {
+                Ok(info) => {
+                    if let Err(e) = tokens::record(&info) {
+                        problem!("⚠️ Failed to save the token check: {}", e);
+                    }
+                    info
+                }
+                Err(e) => {
+                    findings.push(Finding::problem(
+                        format!(
+                            "[{}] {} token from {}: {}",
+                            account.name, provider.name, source, e
+                        ),
+                        "Replace the token; `git-switch key upload` cannot work with it.",
+                    ));
+                    continue;
+                }
+            },
+        };
+        findings.extend(token_findings(
+            &info,
+            api,
+            provider.name,
+            (now / 86400) as i64,
+            window_days,
+        ));
+    }
+    findings
+}
+
+/// Turns the issues found with a token into findings.
+#[cfg(feature = "provider-api")]
+pub fn token_findings(
+    info: &TokenInfo,
+    api: KeyApi,
+    provider_name: &str,
+    today: i64,
+    window_days: u32,
+) -> Vec<Finding> {
+    let label = format!(
+        "{} token from {} (user {})",
+        provider_name, info.source, info.login
+    );
+    let issues = tokens::review_token(info, api, today, window_days);
+    if issues.is_empty() {
+        return vec![Finding::ok(format!(
+            "{} is valid{}",
+            label,
+            info.expires_on
+                .as_ref()
+                .map(|date| format!(" until {}", date))
+                .unwrap_or_default()
+        ))];
+    }
+    issues
+        .iter()
+        .map(|issue| {
+            let message = format!("{} {}", label, issue.describe());
+            match issue {
+                TokenIssue::MissingScope(_) => Finding::warning(
+                    message,
+                    "Key uploads will fail; create a token with that scope (see the README's token table).",
+                ),
+                TokenIssue::BroadScopes(_) => Finding::warning(
+                    message,
+                    "A leaked token exposes all of that; create one limited to SSH keys.",
+                ),
+                TokenIssue::Expired(_) => {
+                    Finding::problem(message, "Create a new token and update its source.")
+                }
+                TokenIssue::ExpiresSoon(..) => Finding::warning(
+                    message,
+                    "Create a new token before then and update its source.",
+                ),
+            }
+        })
+        .collect()
+}
+
+/// The parts of `ssh -G <alias>` output that decide which key is offered.
+#[derive(Debug, Default, PartialEq)]
+pub struct EffectiveSshConfig {
+    /// Identity files in the order ssh tries them.
+    pub identity_files: Vec<String>,
+    pub identities_only: bool,
+}
+
+/// Parses the effective configuration printed by `ssh -G`.
+pub fn parse_effective_ssh_config(output: &str) -> EffectiveSshConfig {
+    let mut config = EffectiveSshConfig::default();
+    for line in output.lines() {
+        match line.split_once(' ') {
+            Some(("identityfile", path)) => config.identity_files.push(path.trim().to_string()),
+            Some(("identitiesonly", value)) => config.identities_only = value.trim() == "yes",
+            _ => {}
+        }
+    }
+    config
+}
+
+/// Checks that `alias` offers the account's key before any other.
+///
+/// ssh tries agent keys first unless `IdentitiesOnly` is set, then the
+/// `IdentityFile` entries in the order their `Host` blocks appear, and the
+/// server accepts the first key it knows, so any key of another account
+/// offered earlier authenticates as that account.
+pub fn check_alias_key_order(
+    account: &Account,
+    alias: &str,
+    effective: &EffectiveSshConfig,
+    other_agent_accounts: &[&str],
+) -> Finding {
+    let expected = shellexpand::tilde(&account.ssh_key).to_string();
+    let files: Vec<String> = effective
+        .identity_files
+        .iter()
+        .map(|file| shellexpand::tilde(file).to_string())
+        .collect();
+    match files.iter().position(|file| *file == expected) {
+        None => {
+            return Finding::problem(
+                format!(
+                    "[{}] `ssh {}` does not use the account key {}",
+                    account.name, alias, account.ssh_key
+                ),
+                "The Host block for this alias is missing or is overridden; re-run `git-switch add` or check ~/.ssh/config.",
+            );
+        }
+        Some(0) => {}
+        Some(_) => {
+            return Finding::problem(
+                format!(
+                    "[{}] `ssh {}` offers {} before the account key",
+                    account.name, alias, effective.identity_files[0]
+                ),
+                "IdentityFile entries accumulate across matching Host blocks in file order, so a `Host *` (or other wildcard) block above this alias is tried first. Move it below the git-switch blocks or scope it to other hosts.",
+            );
+        }
+    }
+    if !effective.identities_only && !other_agent_accounts.is_empty() {
+        return Finding::warning(
+            format!(
+                "[{}] `ssh {}` may authenticate as {}: the agent offers its keys before IdentityFile",
+                account.name,
+                alias,
+                other_agent_accounts.join(", ")
+            ),
+            "Add `IdentitiesOnly yes` to the Host block so only the account key is offered.",
+        );
+    }
+    Finding::ok(format!(
+        "[{}] `ssh {}` offers the account key first",
+        account.name, alias
+    ))
+}
+
+/// Resolves each managed alias with `ssh -G` and checks its key order.
+fn check_ssh_key_order(accounts: &[Account]) -> Vec<Finding> {
+    let agent = agent_fingerprints().unwrap_or_default();
+    let in_agent: Vec<(&str, bool)> = accounts
+        .iter()
+        .map(|acc| {
+            let loaded = key_fingerprint(&acc.ssh_key).is_some_and(|fp| agent.contains(&fp));
+            (acc.name.as_str(), loaded)
+        })
+        .collect();
+
+    let mut findings = Vec::new();
+    for account in accounts.iter().filter(|acc| !acc.uses_https()) {
+        let others: Vec<&str> = in_agent
+            .iter()
+            .filter(|(name, loaded)| *loaded && *name != account.name)
+            .map(|(name, _)| *name)
+            .collect();
+        for host in account.hosts() {
+            let alias = host_alias(host, &account.name);
+            let Ok(output) = command_output("ssh", &["-G", &alias]) else {
+                return findings;
+            };
+            if !output.status.success() {
+                continue;
+            }
+            let effective = parse_effective_ssh_config(&String::from_utf8_lossy(&output.stdout));
+            findings.push(check_alias_key_order(account, &alias, &effective, &others));
+            findings.extend(check_ssh_auth(account, host, &alias));
+        }
+    }
+    findings
+}
+
+/// Connects to a known provider through the alias and checks that its
+/// greeting names the account's user.
+fn check_ssh_auth(account: &Account, host: &str, alias: &str) -> Option<Finding> {
+    let provider = account_provider(account, host)?;
+    let text = ssh_greeting(alias)?;
+    Some(match provider.greeting_user(&text) {
+        Some(user) if user.eq_ignore_ascii_case(&account.username) => Finding::ok(format!(
+            "[{}] {} authenticates `{}` as {}",
+            account.name, provider.name, alias, user
+        )),
+        Some(user) => Finding::problem(
+            format!(
+                "[{}] {} authenticates `{}` as '{}', not '{}'",
+                account.name, provider.name, alias, user, account.username
+            ),
+            &format!(
+                "The key is registered to another {} user; add {}.pub to the right account.",
+                provider.name, account.ssh_key
+            ),
+        ),
+        None => Finding::warning(
+            format!(
+                "[{}] Could not authenticate to {} through `{}`",
+                account.name, provider.name, alias
+            ),
+            &format!(
+                "Add the public key at {}, then run `ssh -T {}`.",
+                provider.key_settings_url, alias
+            ),
+        ),
+    })
+}
+
+/// Output of `ssh -T <alias>`, without prompting for passwords or host keys.
+pub fn ssh_greeting(alias: &str) -> Option<String> {
+    ssh_probe(alias, &[])
+}
+
+/// Whether `ssh` output says the server couldn't be reached at all, as
+/// opposed to reaching it and being turned away.
+pub fn ssh_unreachable(output: &str) -> bool {
+    const MARKERS: [&str; 8] = [
+        "Connection timed out",
+        "Operation timed out",
+        "Connection refused",
+        "Network is unreachable",
+        "No route to host",
+        "Could not resolve hostname",
+        "kex_exchange_identification",
+        "Connection closed by",
+    ];
+    MARKERS.iter().any(|marker| output.contains(marker))
+}
+
+/// Like [`ssh_greeting`], with extra `-o` options (`HostName=...`) that
+/// take precedence over the alias's config.
+pub fn ssh_probe(alias: &str, options: &[&str]) -> Option<String> {
+    let mut args = vec!["-T", "-o", "BatchMode=yes", "-o", "ConnectTimeout=5"];
+    for option in options {
+        args.extend(["-o", option]);
+    }
+    args.push(alias);
+    let output = command_output("ssh", &args).ok()?;
+    // Providers print the greeting on stderr and exit non-zero
+    Some(format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stderr),
+        String::from_utf8_lossy(&output.stdout)
+    ))
+}
+
+/// The file `git config --global` writes to: `~/.gitconfig`, unless only the
+/// XDG config file exists.
+fn global_config_file() -> String {
+    let home_config = dirs::home_dir().unwrap_or_default().join(".gitconfig");
+    let xdg_config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".config"))
+        .join("git")
+        .join("config");
+    let file = if !home_config.exists() && xdg_config.exists() {
+        xdg_config
+    } else {
+        home_config
+    };
+    file.to_string_lossy().into_owned()
+}
+
+/// Parses `git config --show-scope --show-origin --get-regexp` output.
+pub fn parse_config_entries(output: &str) -> Vec<ConfigEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let scope = fields.next()?;
+            let origin = fields.next()?;
+            let (key, value) = fields.next()?.split_once(' ').unwrap_or((line, ""));
+            Some(ConfigEntry {
+                scope: scope.to_string(),
+                file: origin.strip_prefix("file:").unwrap_or(origin).to_string(),
+                key: key.to_string(),
+                value: value.to_string(),
+            })
+        })
+        .collect()
+}
+
+const PRECEDENCE_HINT: &str = "Git reads /etc/gitconfig, then ~/.config/git/config, then ~/.gitconfig, then the repository's .git/config; the last value wins, so switches written to ~/.gitconfig are hidden by later files.";
+
+/// Finds `user.name`/`user.email` values that shadow the ones git-switch
+/// manages: global identities set in more than one file, and repository
+/// identities that don't belong to any saved account.
+///
+/// `entries` must be in git's reading order, lowest precedence first.
+pub fn check_identity_shadowing(
+    entries: &[ConfigEntry],
+    managed_file: &str,
+    accounts: &[Account],
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let global: Vec<&ConfigEntry> = entries
+        .iter()
+        .filter(|entry| matches!(entry.scope.as_str(), "system" | "global"))
+        .collect();
+    let mut files: Vec<&str> = Vec::new();
+    for entry in &global {
+        if !files.contains(&entry.file.as_str()) {
+            files.push(&entry.file);
+        }
+    }
+    if files.len() > 1 {
+        for file in files.iter().filter(|file| **file != managed_file) {
+            let keys: Vec<String> = global
+                .iter()
+                .filter(|entry| entry.file == *file)
+                .map(|entry| entry.key.clone())
+                .collect();
+            let values: Vec<String> = global
+                .iter()
+                .filter(|entry| entry.file == *file)
+                .map(|entry| format!("{} = {}", entry.key, entry.value))
+                .collect();
+            let finding = Finding::warning(
+                format!(
+                    "{} also sets {}, shadowing or shadowed by other config files",
+                    file,
+                    values.join(", ")
+                ),
+                PRECEDENCE_HINT,
+            );
+            // System config needs root; leave it to the user
+            let is_system = global
+                .iter()
+                .any(|entry| entry.file == *file && entry.scope == "system");
+            findings.push(if is_system {
+                finding
+            } else {
+                finding.with_fix(Fix::UnsetConfig {
+                    file: Some(file.to_string()),
+                    keys,
+                })
+            });
+        }
+    }
+
+    let local: Vec<&ConfigEntry> = entries
+        .iter()
+        .filter(|entry| entry.scope == "local")
+        .collect();
+    if !local.is_empty() {
+        let value = |key: &str| {
+            local
+                .iter()
+                .rev()
+                .find(|entry| entry.key == key)
+                .map(|entry| entry.value.as_str())
+                .unwrap_or_default()
+        };
+        let (name, email) = (value("user.name"), value("user.email"));
+        if match_identity(accounts, name, email).is_none() {
+            let keys = local.iter().map(|entry| entry.key.clone()).collect();
+            findings.push(
+                Finding::warning(
+                    format!(
+                        "This repository's config sets its own identity ({} <{}>) that matches no saved account",
+                        name, email
+                    ),
+                    "The repository config overrides the global identity, so `git-switch use --global` has no effect here.",
+                )
+                .with_fix(Fix::UnsetConfig { file: None, keys }),
+            );
+        }
+    }
+
+    if findings.is_empty() && !entries.is_empty() {
+        findings.push(Finding::ok(
+            "No conflicting user.name/user.email settings".to_string(),
+        ));
+    }
+    findings
+}
+
+/// Verifies that the account can authenticate against its Git LFS endpoint,
+/// using the credentials git would use and an empty LFS batch request.
+fn check_lfs_auth(account: &Account) -> Finding {
+    let lfs_url = account.lfs_url.as_deref().unwrap_or_default();
+    let Some(password) = fill_credential(lfs_url, &account.username) else {
+        return Finding::problem(
+            format!(
+                "[{}] No stored credentials for LFS endpoint {}",
+                account.name, lfs_url
+            ),
+            "Run a `git lfs pull` in one of the account's repositories to store credentials.",
+        );
+    };
+
+    let batch_url = format!("{}/objects/batch", lfs_url.trim_end_matches('/'));
+    let child = Command::new("curl")
+        .args([
+            "-s",
+            "-o",
+            "/dev/null",
+            "-w",
+            "%{http_code}",
+            "-K",
+            "-",
+            "-X",
+            "POST",
+            "-H",
+            "Accept: application/vnd.git-lfs+json",
+            "-H",
+            "Content-Type: application/vnd.git-lfs+json",
+            "-d",
+            r#"{"operation":"download","objects":[]}"#,
+            &batch_url,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return Finding::warning(
+            format!(
+                "[{}] Could not check LFS endpoint {}",
+                account.name, lfs_url
+            ),
+            "Install curl to let doctor verify LFS authentication.",
+        );
+    };
+    // Pass credentials through curl's config on stdin so they don't show up in `ps`
+    if let Some(mut stdin) = child.stdin.take() {
+        let user = format!("{}:{}", account.username, password).replace('"', "\\\"");
+        let _ = writeln!(stdin, "user = \"{}\"", user);
+    }
+    let output = child.wait_with_output();
+    events::subprocess(
+        "curl",
+        &[&batch_url],
+        output.as_ref().ok().and_then(|out| out.status.code()),
+    );
+    let status = output
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .unwrap_or_default();
+
+    match status.as_str() {
+        "401" | "403" => Finding::problem(
+            format!(
+                "[{}] LFS endpoint {} rejected the stored credentials (HTTP {})",
+                account.name, lfs_url, status
+            ),
+            "Update the token stored for this endpoint in your credential helper.",
+        ),
+        code if code.starts_with('2') || code == "422" => Finding::ok(format!(
+            "[{}] LFS authentication succeeded for {}",
+            account.name, lfs_url
+        )),
+        "000" | "" => Finding::warning(
+            format!("[{}] LFS endpoint {} is unreachable", account.name, lfs_url),
+            "Check the URL and your network connection.",
+        ),
+        code => Finding::warning(
+            format!(
+                "[{}] LFS endpoint {} answered with unexpected HTTP {}",
+                account.name, lfs_url, code
+            ),
+            "Check that the URL points at the LFS API (usually ending in `/info/lfs`).",
+        ),
+    }
+}
+
+/// Asks git's configured credential helpers for a password without prompting.
+fn fill_credential(url: &str, username: &str) -> Option<String> {
+    let mut child = Command::new("git")
+        .args(["credential", "fill"])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    {
+        let mut stdin = child.stdin.take()?;
+        writeln!(stdin, "url={}\nusername={}\n", url, username).ok()?;
+    }
+    let output = child.wait_with_output().ok()?;
+    events::subprocess("git", &["credential", "fill"], output.status.code());
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("password=").map(str::to_string))
+}
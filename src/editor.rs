@@ -0,0 +1,50 @@
+//! Editing text in the user's `$VISUAL`/`$EDITOR`.
+
+use std::fs;
+use std::io;
+use std::process::Command;
+
+/// Returns the user's preferred editor command from `$VISUAL` or `$EDITOR`.
+pub fn editor_command() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        })
+}
+
+/// Opens `content` in the user's editor and returns the saved text.
+///
+/// The text is written to a temporary file named with `suffix` (so editors
+/// pick the right syntax highlighting), which is removed afterwards.
+pub fn edit_text(content: &str, suffix: &str) -> io::Result<String> {
+    let path =
+        std::env::temp_dir().join(format!("git-switch-edit-{}{}", std::process::id(), suffix));
+    fs::write(&path, content)?;
+
+    // Editors are often configured with arguments, e.g. `code --wait`
+    let editor = editor_command();
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = Command::new(program).args(parts).arg(&path).status();
+
+    let result = match status {
+        Ok(status) if status.success() => fs::read_to_string(&path),
+        Ok(status) => Err(io::Error::other(format!(
+            "editor '{}' exited with {}",
+            editor, status
+        ))),
+        Err(e) => Err(io::Error::other(format!(
+            "failed to launch editor '{}': {}",
+            editor, e
+        ))),
+    };
+    let _ = fs::remove_file(&path);
+    result
+}
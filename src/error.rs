@@ -0,0 +1,100 @@
+//! Errors of git-switch commands and the exit codes they map to.
+//!
+//! Commands report progress themselves and return a [`GitSwitchError`] when
+//! they can't finish; the binary prints it and exits with
+//! [`GitSwitchError::exit_code`], so scripts can tell failures apart.
+
+use std::io;
+
+/// Every way a git-switch command can fail.
+#[derive(Debug, thiserror::Error)]
+pub enum GitSwitchError {
+    /// A check (`check`, `hook check`, `auto`) ran and didn't pass.
+    #[error("{0}")]
+    CheckFailed(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Account with name or username '{0}' not found.")]
+    AccountNotFound(String),
+    #[error("Not inside a Git repository.")]
+    NotInRepository,
+    #[error("Could not determine the home directory.")]
+    NoHomeDirectory,
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to execute '{command}': {source}")]
+    CommandNotFound {
+        command: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("`{command}` {}", failure_detail(*code, stderr))]
+    CommandFailed {
+        command: String,
+        code: Option<i32>,
+        /// What the command wrote to stderr, trimmed.
+        stderr: String,
+    },
+    #[error("{0}")]
+    PolicyDenied(String),
+    #[error("{0}")]
+    Cancelled(String),
+    /// The build or platform lacks what the command needs.
+    #[error("{0}")]
+    Unsupported(String),
+}
+
+pub type Result<T, E = GitSwitchError> = std::result::Result<T, E>;
+
+/// `failed (exit status 1): <stderr>` for `CommandFailed`.
+fn failure_detail(code: Option<i32>, stderr: &str) -> String {
+    let status = match code {
+        Some(code) => format!("failed (exit status {})", code),
+        None => "was killed by a signal".to_string(),
+    };
+    if stderr.is_empty() {
+        status
+    } else {
+        format!("{}: {}", status, stderr)
+    }
+}
+
+impl GitSwitchError {
+    /// The process exit code for the error. 2 matches clap's usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GitSwitchError::CheckFailed(_) => 1,
+            GitSwitchError::InvalidInput(_) => 2,
+            GitSwitchError::AccountNotFound(_) => 3,
+            GitSwitchError::NotInRepository => 4,
+            GitSwitchError::NoHomeDirectory | GitSwitchError::Io { .. } => 5,
+            GitSwitchError::CommandNotFound { .. } | GitSwitchError::CommandFailed { .. } => 6,
+            GitSwitchError::PolicyDenied(_) => 7,
+            GitSwitchError::Cancelled(_) => 8,
+            GitSwitchError::Unsupported(_) => 9,
+        }
+    }
+
+    pub fn io(context: impl Into<String>, source: io::Error) -> GitSwitchError {
+        GitSwitchError::Io {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+/// Adds what was being done to an I/O error, e.g.
+/// `save_rules(&rules).context("Failed to save rules")?`.
+pub trait IoContext<T> {
+    fn context(self, context: impl Into<String>) -> Result<T>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        self.map_err(|e| GitSwitchError::io(context, e))
+    }
+}
@@ -0,0 +1,117 @@
+//! Machine-readable event stream enabled by `--log-json`.
+//!
+//! Every event is a single JSON object on its own stderr line, independent of
+//! the human-readable stdout output. The `v` field versions the schema.
+
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SCHEMA_VERSION: u32 = 1;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns on JSON event output for the rest of the process.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Writes one event line to stderr if `--log-json` is active.
+pub fn emit(event: &str, fields: Value) {
+    if !is_enabled() {
+        return;
+    }
+    let ts_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let mut line = json!({ "v": SCHEMA_VERSION, "ts_ms": ts_ms, "event": event });
+    if let (Some(line_map), Value::Object(extra)) = (line.as_object_mut(), fields) {
+        line_map.extend(extra);
+    }
+    let mut stderr = std::io::stderr().lock();
+    let _ = writeln!(stderr, "{}", line);
+}
+
+pub fn command_started(command: &str, args: &[String]) {
+    emit(
+        "command_started",
+        json!({ "command": command, "args": args }),
+    );
+}
+
+pub fn command_finished(command: &str, success: bool) {
+    emit(
+        "command_finished",
+        json!({ "command": command, "success": success }),
+    );
+}
+
+pub fn subprocess(program: &str, args: &[&str], exit_code: Option<i32>) {
+    emit(
+        "subprocess_executed",
+        json!({
+            "program": program,
+            "args": args,
+            "exit_code": exit_code,
+            "success": exit_code == Some(0),
+        }),
+    );
+}
+
+/// Records a file change. `before`/`after` are `None` when the file did not
+/// exist before or was deleted. `-vv` lists it on stderr as well.
+pub fn file_modified(path: &Path, before: Option<&str>, after: Option<&str>) {
+    let action = match (before, after) {
+        (None, Some(_)) => "created",
+        (Some(_), None) => "deleted",
+        _ => "modified",
+    };
+    crate::debug!("{} {}", action, path.display());
+    if !is_enabled() {
+        return;
+    }
+    emit(
+        "file_modified",
+        json!({
+            "path": path.display().to_string(),
+            "action": action,
+            "diff_sha256": diff_hash(before.unwrap_or(""), after.unwrap_or("")),
+        }),
+    );
+}
+
+/// Hashes the changed region between two versions of a file: the common
+/// leading and trailing lines are trimmed and the rest is hashed as a
+/// `-`/`+` line diff, so identical edits produce identical hashes.
+pub fn diff_hash(before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut hasher = Sha256::new();
+    for line in &old[prefix..old.len() - suffix] {
+        hasher.update(format!("-{}\n", line));
+    }
+    for line in &new[prefix..new.len() - suffix] {
+        hasher.update(format!("+{}\n", line));
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
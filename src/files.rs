@@ -0,0 +1,253 @@
+//! Crash-safe writes of the files git-switch shares with the user: the
+//! accounts file and `~/.ssh/config`.
+//!
+//! New content goes to a temporary file next to the target, which is then
+//! renamed over it, so a crash leaves either the old or the new file, never
+//! a truncated one. The previous version is kept as a timestamped backup
+//! (`config.20261017-093000.bak`); only the newest [`MAX_BACKUPS`] are kept.
+//!
+//! Read-modify-write sequences hold a [`FileLock`] so concurrent git-switch
+//! processes don't lose each other's changes.
+//!
+//! Each file written with a backup is also noted in a per-process journal
+//! with the backup of its state before the first change, which `undo` uses
+//! to put the files back.
+
+use crate::problem;
+use crate::utils::{civil_from_days, now_secs};
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Backups kept per file; older ones are deleted.
+pub const MAX_BACKUPS: usize = 5;
+
+/// Files this process changed, each with the backup taken before its first
+/// change (`None` if it didn't exist yet).
+static JOURNAL: Mutex<Vec<(PathBuf, Option<PathBuf>)>> = Mutex::new(Vec::new());
+
+fn note_in_journal(path: &Path, backup: Option<PathBuf>) {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut journal = JOURNAL.lock().unwrap_or_else(|e| e.into_inner());
+    if !journal.iter().any(|(noted, _)| *noted == path) {
+        journal.push((path, backup));
+    }
+}
+
+/// Returns and clears the files changed so far, in the order they were
+/// first changed.
+pub fn take_journal() -> Vec<(PathBuf, Option<PathBuf>)> {
+    std::mem::take(&mut *JOURNAL.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// An exclusive advisory lock on a file, released when dropped.
+///
+/// The lock is taken on a `<name>.lock` file next to the target rather than
+/// the target itself, because [`write_atomic`] replaces the target's inode.
+/// Locks aren't reentrant: taking a second lock on the same file from the
+/// same process waits forever.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Locks `path` for a read-modify-write, waiting while another process
+/// holds the lock.
+pub fn lock(path: &Path) -> io::Result<FileLock> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if let Some(dir) = target.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        fs::create_dir_all(dir)?;
+    }
+    let lock_path = target.with_file_name(format!("{}.lock", file_name(&target)));
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)?;
+    if file.try_lock_exclusive().is_err() {
+        problem!(
+            "⏳ Waiting for another git-switch process to finish with {}...",
+            target.display()
+        );
+        file.lock_exclusive()?;
+    }
+    Ok(FileLock { file })
+}
+
+/// Replaces `path` with `content` through a temporary file and a rename. The
+/// file keeps its permissions, and a symlink is written through rather than
+/// replaced.
+pub fn write_atomic(path: &Path, content: &str) -> io::Result<()> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    fs::create_dir_all(&dir)?;
+    let name = file_name(&target);
+    let temp = dir.join(format!(".{}.tmp-{}", name, std::process::id()));
+
+    let result = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(content.as_bytes())?;
+        if let Ok(metadata) = fs::metadata(&target) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, &target)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Creates a file only its owner can read, for private keys and exports
+/// that contain them. Fails if the file exists.
+pub fn write_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+/// Copies the current `path` to a timestamped backup, then writes `content`
+/// atomically. Returns the backup's path, or `None` if there was no file.
+pub fn write_with_backup(path: &Path, content: &str) -> io::Result<Option<PathBuf>> {
+    let backup = backup(path)?;
+    write_atomic(path, content)?;
+    note_in_journal(path, backup.clone());
+    Ok(backup)
+}
+
+/// Deletes `path` after copying it to a timestamped backup, which is
+/// returned.
+pub fn remove_with_backup(path: &Path) -> io::Result<Option<PathBuf>> {
+    let backup = backup(path)?;
+    fs::remove_file(path)?;
+    note_in_journal(path, backup.clone());
+    Ok(backup)
+}
+
+/// Copies `path` to `<name>.<YYYYMMDD-HHMMSS>.bak` (UTC) next to it and
+/// deletes all but the newest [`MAX_BACKUPS`] backups.
+pub fn backup(path: &Path) -> io::Result<Option<PathBuf>> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if !target.is_file() {
+        return Ok(None);
+    }
+    let dir = target.parent().unwrap_or(Path::new("."));
+    let name = file_name(&target);
+    let stamp = timestamp(now_secs());
+    // Number later backups within the same second after the newest one, even
+    // if older ones of that second were already pruned
+    let last = list_backups(dir, &name)?
+        .iter()
+        .filter_map(|path| backup_key(path, &name))
+        .filter(|(other, _)| *other == stamp)
+        .map(|(_, n)| n)
+        .max();
+    let backup = match last {
+        None => dir.join(format!("{}.{}.bak", name, stamp)),
+        Some(n) => dir.join(format!("{}.{}-{}.bak", name, stamp, n + 1)),
+    };
+    fs::copy(&target, &backup)?;
+    prune_backups(dir, &name)?;
+    Ok(Some(backup))
+}
+
+/// Backups of the file `name` in `dir`, oldest first.
+pub fn list_backups(dir: &Path, name: &str) -> io::Result<Vec<PathBuf>> {
+    let mut backups: Vec<((String, u32), PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| Some((backup_key(&path, name)?, path)))
+        .collect();
+    backups.sort();
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
+
+/// The timestamp and sequence number of a backup of `name`.
+fn backup_key(path: &Path, name: &str) -> Option<(String, u32)> {
+    file_name(path)
+        .strip_prefix(name)?
+        .strip_prefix('.')?
+        .strip_suffix(".bak")
+        .and_then(parse_timestamp)
+}
+
+/// Deletes the oldest backups beyond [`MAX_BACKUPS`], sparing the ones in
+/// the journal, which `undo` needs however often the file is written.
+fn prune_backups(dir: &Path, name: &str) -> io::Result<()> {
+    let journaled: Vec<PathBuf> = JOURNAL
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter_map(|(_, backup)| backup.clone())
+        .collect();
+    let backups = list_backups(dir, name)?;
+    let excess = backups.len().saturating_sub(MAX_BACKUPS);
+    for old in backups
+        .iter()
+        .filter(|backup| !journaled.contains(backup))
+        .take(excess)
+    {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Splits `YYYYMMDD-HHMMSS[-N]` into the timestamp and N (1 if absent), so
+/// backups made in the same second sort in the order they were made.
+fn parse_timestamp(text: &str) -> Option<(String, u32)> {
+    let stamp = text.get(..15)?;
+    let valid = stamp
+        .char_indices()
+        .all(|(i, c)| if i == 8 { c == '-' } else { c.is_ascii_digit() });
+    if !valid {
+        return None;
+    }
+    let n = match &text[15..] {
+        "" => 1,
+        rest => rest.strip_prefix('-')?.parse().ok()?,
+    };
+    Some((stamp.to_string(), n))
+}
+
+/// Formats Unix time as `YYYYMMDD-HHMMSS` in UTC.
+pub fn timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
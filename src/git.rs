@@ -1,50 +1,960 @@
-use crate::utils::run_command;
-use std::process::Command;
-
-pub fn update_git_remote(username: &str, repo_url_input: &str) {
-    let repo_name = if repo_url_input.contains('/') {
-        // Handle full repo path like "username/repo.git" or "username/repo"
-        // Clippy fix: use next_back() for DoubleEndedIterator
-        repo_url_input
-            .split('/')
-            .next_back() // Get the last part after splitting by '/'
-            .unwrap_or("") // Handle cases where split might be empty (though unlikely for valid repo URLs)
-            .trim_end_matches(".git") // Remove .git suffix if present
-            .to_string()
-    } else {
-        // Handle just repo name like "repo.git" or "repo"
-        repo_url_input.trim_end_matches(".git").to_string()
-    };
-
-    // Create remote URL using the host alias from SSH config
-    // The host alias in SSH config is `github-{account_name_lowercase_underscored}`
-    // However, the actual remote URL should be `git@github-{account_name_lowercase_underscored}:{username}/{repo_name}.git`
-    // OR, if not using custom host aliases in the remote URL (more common): `git@github.com:{username}/{repo_name}.git`
-    // The current SSH config setup implies the latter is intended for git remote.
-    // The `github-{name}` host alias is for SSH to pick the right key.
-    let remote_url = format!("git@github.com:{}/{}.git", username, repo_name);
-    // If you intend to use the SSH host alias in the git remote URL itself, it would be:
-    // let remote_url = format!("git@github-{}:{}/{}.git", account_name_for_ssh_host_alias, username, repo_name);
-    // This requires passing `account_name_for_ssh_host_alias` to this function.
-    // For now, sticking to the standard `git@github.com:...` which relies on SSH config to resolve the key.
-
-    println!("🔄 Updating Git remote URL to: {}", remote_url);
-
-    // Check if origin remote exists
-    let output = Command::new("git")
-        .args(["remote"])
-        .output()
-        .expect("Failed to execute git remote command");
-
-    let remotes = String::from_utf8_lossy(&output.stdout);
-
-    if remotes.lines().any(|line| line.trim() == "origin") {
-        println!("Removing existing 'origin' remote...");
-        run_command("git", &["remote", "remove", "origin"]);
-    }
-
-    println!("Adding new 'origin' remote...");
-    run_command("git", &["remote", "add", "origin", &remote_url]);
-
-    println!("✅ Git remote URL updated successfully!");
+//! Git configuration and remotes: applying an account's identity to a
+//! gitconfig scope or repository and building its remote URLs.
+
+use crate::config::{Account, check_git_config_key, get_data_dir};
+use crate::error::Result;
+use crate::events;
+use crate::provider::account_provider;
+use crate::render::{shell_quote, ssh_command};
+use crate::say;
+use crate::ssh::host_alias;
+use crate::utils::{command_output, run_command};
+use crate::{files, problem};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Which git config file `use` writes the identity to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigScope {
+    Global,
+    Local,
+}
+
+impl ConfigScope {
+    /// Local inside a repository, global everywhere else.
+    pub fn default_for_cwd() -> ConfigScope {
+        if is_inside_work_tree() {
+            ConfigScope::Local
+        } else {
+            ConfigScope::Global
+        }
+    }
+
+    pub fn flag(&self) -> &'static str {
+        match self {
+            ConfigScope::Global => "--global",
+            ConfigScope::Local => "--local",
+        }
+    }
+}
+
+/// Returns whether the current directory is inside a git work tree.
+pub fn is_inside_work_tree() -> bool {
+    command_output("git", &["rev-parse", "--is-inside-work-tree"])
+        .map(|out| out.status.success() && String::from_utf8_lossy(&out.stdout).trim() == "true")
+        .unwrap_or(false)
+}
+
+/// Reads a git config value, from one scope or (with `None`) the effective value.
+pub fn config_value(scope: Option<ConfigScope>, key: &str) -> Option<String> {
+    let mut args = vec!["config"];
+    if let Some(scope) = scope {
+        args.push(scope.flag());
+    }
+    args.extend(["--get", key]);
+    let output = command_output("git", &args).ok()?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then_some(value)
+}
+
+/// The effective `user.name` and `user.email`, read with a single `git
+/// config` call for commands run on every prompt.
+pub fn effective_identity() -> (Option<String>, Option<String>) {
+    let Ok(output) = command_output("git", &["config", "--get-regexp", r"^user\.(name|email)$"])
+    else {
+        return (None, None);
+    };
+    parse_identity(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Name and email from `git config --get-regexp` output, where later
+/// scopes come last and win.
+pub fn parse_identity(output: &str) -> (Option<String>, Option<String>) {
+    let (mut name, mut email) = (None, None);
+    for line in output.lines() {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let value = Some(value.trim().to_string()).filter(|value| !value.is_empty());
+        match key {
+            "user.name" => name = value,
+            "user.email" => email = value,
+            _ => {}
+        }
+    }
+    (name, email)
+}
+
+/// Host and repository path of a remote URL, in SSH (`git@host:owner/repo`)
+/// or URL (`ssh://`, `https://`) form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteUrl {
+    pub host: String,
+    pub path: String,
+}
+
+impl RemoteUrl {
+    pub fn parse(url: &str) -> Option<RemoteUrl> {
+        let (authority, path) = match url.split_once("://") {
+            Some((_, rest)) => rest.split_once('/')?,
+            None => {
+                let (authority, path) = url.split_once(':')?;
+                if authority.contains('/') {
+                    return None; // A local path, not scp-like syntax
+                }
+                (authority, path)
+            }
+        };
+        let host = authority.rsplit('@').next().unwrap_or(authority);
+        let host = host.split(':').next().unwrap_or(host);
+        if host.is_empty() {
+            return None;
+        }
+        Some(RemoteUrl {
+            host: host.to_string(),
+            path: path.trim_start_matches('/').to_string(),
+        })
+    }
+
+    /// The first path segment: the user or organization owning the repository,
+    /// without SourceHut's `~` user prefix.
+    pub fn owner(&self) -> &str {
+        let owner = self.path.split('/').next().unwrap_or_default();
+        owner.strip_prefix('~').unwrap_or(owner)
+    }
+}
+
+/// Rewrites a remote to go through the account's SSH host alias, keeping the
+/// remote's host if the account uses it and the account's primary host otherwise.
+pub fn alias_remote_url(account: &Account, remote: &RemoteUrl) -> String {
+    let host = account
+        .hosts()
+        .into_iter()
+        .find(|host| *host == remote.host)
+        .unwrap_or(&account.host);
+    if account.uses_https() {
+        return https_url(account, host, &remote.path);
+    }
+    ssh_url(account, host, &remote.path, true)
+}
+
+/// SSH URL of `path` on one of the account's hosts, through its host alias
+/// or straight to its SSH server, in `ssh://` form when the port isn't 22.
+pub fn ssh_url(account: &Account, host: &str, path: &str, via_alias: bool) -> String {
+    let user = account.ssh_user_for(host);
+    if via_alias {
+        return format!("{}@{}:{}", user, host_alias(host, &account.name), path);
+    }
+    match account.ssh_endpoint(host) {
+        (server, Some(port)) => format!("ssh://{}@{}:{}/{}", user, server, port, path),
+        (server, None) => format!("{}@{}:{}", user, server, path),
+    }
+}
+
+/// Points `origin` of the current repository at `repo` (`owner/repo` or
+/// `repo`) on the account's host. With `via_alias` the URL goes through the
+/// account's SSH host alias, so ssh always offers the account's key; without
+/// it the plain host is used and the key depends on the rest of the SSH config.
+///
+/// An existing `origin` keeps its other settings; separate push URLs on the
+/// account's hosts are moved to the account too, keeping their paths. Returns the new URL.
+pub fn update_git_remote(
+    account: &Account,
+    accounts: &[Account],
+    repo_url_input: &str,
+    via_alias: bool,
+) -> Result<String> {
+    let remote_url = repo_ssh_url(account, repo_url_input, via_alias);
+
+    say!("🔄 Updating Git remote URL to: {}", remote_url);
+
+    let here = Path::new(".");
+    if !remote_names(here).iter().any(|name| name == "origin") {
+        say!("Adding new 'origin' remote...");
+        run_command("git", &["remote", "add", "origin", &remote_url])?;
+        say!("✅ Git remote URL updated successfully!");
+        return Ok(remote_url);
+    }
+
+    run_command("git", &["remote", "set-url", "origin", &remote_url])?;
+    for push_url in remote_config(here, "origin", "pushurl") {
+        if let Some(new_url) = rewritten_remote_url(account, accounts, &push_url, via_alias) {
+            say!("🔄 Updating push URL of 'origin' to: {}", new_url);
+            set_remote_url(here, "origin", &new_url, &push_url, true)?;
+        }
+    }
+
+    say!("✅ Git remote URL updated successfully!");
+    Ok(remote_url)
+}
+
+/// Where a URL found by [`plan_url_rewrites`] is configured.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UrlSetting {
+    /// `remote.<name>.url`, or `remote.<name>.pushurl` when `push`.
+    Remote { name: String, push: bool },
+    /// `submodule.<name>.url` in the local config, which git uses instead of
+    /// `.gitmodules` once the submodule is initialized.
+    Submodule { name: String, path: String },
+}
+
+/// A URL of a repository or one of its submodules to point at an account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlRewrite {
+    /// Repository the setting belongs to; a submodule's own remotes are
+    /// rewritten in its checkout.
+    pub repo: PathBuf,
+    pub setting: UrlSetting,
+    pub old_url: String,
+    pub new_url: String,
+}
+
+impl UrlRewrite {
+    /// Names the setting for messages, e.g. `Remote 'upstream'` or
+    /// `Remote 'origin' in vendor/lib`.
+    pub fn describe(&self, top: &Path) -> String {
+        let subject = match &self.setting {
+            UrlSetting::Remote { name, .. } => format!("Remote '{}'", name),
+            UrlSetting::Submodule { path, .. } => format!("Submodule '{}'", path),
+        };
+        match self.repo.strip_prefix(top) {
+            Ok(inner) if !inner.as_os_str().is_empty() => {
+                format!("{} in {}", subject, inner.display())
+            }
+            _ => subject,
+        }
+    }
+
+    pub fn apply(&self) -> Result<()> {
+        match &self.setting {
+            UrlSetting::Remote { name, push } => {
+                set_remote_url(&self.repo, name, &self.new_url, &self.old_url, *push)
+            }
+            UrlSetting::Submodule { name, .. } => {
+                let key = format!("submodule.{}.url", name);
+                run_git_in(&self.repo, &["config", "--local", &key, &self.new_url])
+            }
+        }
+    }
+}
+
+/// Finds every remote URL (fetch and push) of the repository at `repo` that
+/// is on one of the account's hosts, directly or through any account's SSH
+/// host alias, and the URL it gets through this account. Submodules are
+/// included, along with the remotes of those that are checked out,
+/// recursively.
+pub fn plan_url_rewrites(
+    repo: &Path,
+    account: &Account,
+    accounts: &[Account],
+    via_alias: bool,
+) -> Vec<UrlRewrite> {
+    let rewrite = |url: &str| {
+        rewritten_remote_url(account, accounts, url, via_alias).filter(|new_url| new_url != url)
+    };
+    let mut rewrites = Vec::new();
+    for name in remote_names(repo) {
+        for (key, push) in [("url", false), ("pushurl", true)] {
+            for url in remote_config(repo, &name, key) {
+                if let Some(new_url) = rewrite(&url) {
+                    rewrites.push(UrlRewrite {
+                        repo: repo.to_path_buf(),
+                        setting: UrlSetting::Remote {
+                            name: name.clone(),
+                            push,
+                        },
+                        old_url: url,
+                        new_url,
+                    });
+                }
+            }
+        }
+    }
+    for (name, path, declared) in submodules(repo) {
+        let key = format!("submodule.{}.url", name);
+        let url = git_in(repo, &["config", "--local", &key]).unwrap_or(declared);
+        if let Some(new_url) = rewrite(&url) {
+            rewrites.push(UrlRewrite {
+                repo: repo.to_path_buf(),
+                setting: UrlSetting::Submodule {
+                    name,
+                    path: path.clone(),
+                },
+                old_url: url,
+                new_url,
+            });
+        }
+        let checkout = repo.join(&path);
+        if checkout.join(".git").exists() {
+            rewrites.extend(plan_url_rewrites(&checkout, account, accounts, via_alias));
+        }
+    }
+    rewrites
+}
+
+/// Points the current repository's remotes and submodules at the account,
+/// as found by [`plan_url_rewrites`]. Returns what was changed.
+pub fn rewrite_remotes(
+    account: &Account,
+    accounts: &[Account],
+    via_alias: bool,
+) -> Result<Vec<UrlRewrite>> {
+    let rewrites = plan_url_rewrites(Path::new("."), account, accounts, via_alias);
+    for rewrite in &rewrites {
+        rewrite.apply()?;
+    }
+    Ok(rewrites)
+}
+
+/// Name, path and URL of each submodule declared in the repository's
+/// `.gitmodules`.
+fn submodules(repo: &Path) -> Vec<(String, String, String)> {
+    let file = repo.join(".gitmodules");
+    if !file.is_file() {
+        return Vec::new();
+    }
+    let Some(output) = git_in(
+        repo,
+        &[
+            "config",
+            "--file",
+            ".gitmodules",
+            "--get-regexp",
+            r"^submodule\..*\.(path|url)$",
+        ],
+    ) else {
+        return Vec::new();
+    };
+    let mut found: Vec<(String, Option<String>, Option<String>)> = Vec::new();
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        let Some(rest) = key.strip_prefix("submodule.") else {
+            continue;
+        };
+        let Some((name, field)) = rest.rsplit_once('.') else {
+            continue;
+        };
+        let index = match found.iter().position(|(known, _, _)| known == name) {
+            Some(index) => index,
+            None => {
+                found.push((name.to_string(), None, None));
+                found.len() - 1
+            }
+        };
+        match field {
+            "path" => found[index].1 = Some(value.to_string()),
+            _ => found[index].2 = Some(value.to_string()),
+        }
+    }
+    found
+        .into_iter()
+        .filter_map(|(name, path, url)| Some((name, path?, url?)))
+        .collect()
+}
+
+/// Reads from git in `repo`: its trimmed stdout, or `None` if git fails.
+fn git_in(repo: &Path, args: &[&str]) -> Option<String> {
+    let repo = repo.to_string_lossy();
+    let mut full = vec!["-C", repo.as_ref()];
+    full.extend(args);
+    let output = command_output("git", &full).ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Changes something with git in `repo`, echoing the command like other writes.
+fn run_git_in(repo: &Path, args: &[&str]) -> Result<()> {
+    let repo = repo.to_string_lossy();
+    let mut full = vec!["-C", repo.as_ref()];
+    full.extend(args);
+    run_command("git", &full)?;
+    Ok(())
+}
+
+/// The URL `url` gets when rewritten for the account, or `None` if it isn't
+/// on one of the account's hosts.
+pub fn rewritten_remote_url(
+    account: &Account,
+    accounts: &[Account],
+    url: &str,
+    via_alias: bool,
+) -> Option<String> {
+    let remote = RemoteUrl::parse(url)?;
+    let host = account.hosts().into_iter().find(|host| {
+        *host == remote.host
+            || accounts
+                .iter()
+                .any(|other| host_alias(host, &other.name) == remote.host)
+    })?;
+    if account.uses_https() {
+        return Some(https_url(account, host, &remote.path));
+    }
+    Some(ssh_url(account, host, &remote.path, via_alias))
+}
+
+fn remote_names(repo: &Path) -> Vec<String> {
+    git_in(repo, &["remote"])
+        .map(|output| {
+            output
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// All values of `remote.<name>.<key>`.
+fn remote_config(repo: &Path, name: &str, key: &str) -> Vec<String> {
+    git_in(
+        repo,
+        &["config", "--get-all", &format!("remote.{}.{}", name, key)],
+    )
+    .map(|output| output.lines().map(str::to_string).collect())
+    .unwrap_or_default()
+}
+
+/// Replaces one URL of a remote with `git remote set-url`, which keeps the
+/// remote's other URLs and settings.
+fn set_remote_url(repo: &Path, name: &str, new_url: &str, old_url: &str, push: bool) -> Result<()> {
+    // set-url takes the old URL as a regular expression
+    let pattern = format!("^{}$", regex_escape(old_url));
+    let mut args = vec!["remote", "set-url"];
+    if push {
+        args.push("--push");
+    }
+    args.extend([name, new_url, &pattern]);
+    run_git_in(repo, &args)
+}
+
+fn regex_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.^$|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Builds the SSH clone URL for `repo` (`owner/repo` or just `repo`, in which
+/// case the account's username is the owner) using the account's host alias.
+pub fn clone_url(account: &Account, repo: &str) -> String {
+    repo_ssh_url(account, repo, true)
+}
+
+/// An HTTPS URL carrying the account's username, which git passes on to
+/// the credential helper.
+fn https_url(account: &Account, host: &str, path: &str) -> String {
+    format!("https://{}@{}/{}", account.username, host, path)
+}
+
+/// The SSH URL of `repo` on the account's primary host, through its host
+/// alias or (with `via_alias` false) the host itself. HTTPS accounts get an
+/// HTTPS URL instead.
+pub fn repo_ssh_url(account: &Account, repo: &str, via_alias: bool) -> String {
+    let provider = account_provider(account, &account.host);
+    let (owner_prefix, suffix) = provider.map_or(("", ".git"), |provider| {
+        (provider.owner_prefix, provider.repo_suffix)
+    });
+    let repo = repo.trim_end_matches(".git");
+    let (owner, name) = match repo.split_once('/') {
+        Some((owner, name)) => (owner.to_string(), name),
+        None => (format!("{}{}", owner_prefix, account.username), repo),
+    };
+    if account.uses_https() {
+        let path = format!("{}/{}{}", owner, name, suffix);
+        return https_url(account, &account.host, &path);
+    }
+    let path = format!("{}/{}{}", owner, name, suffix);
+    ssh_url(account, &account.host, &path, via_alias)
+}
+
+/// Picks where `clone` puts a repository: the explicit directory if given,
+/// otherwise `<workspace>/<repo name>` for accounts with a workspace root.
+pub fn clone_destination(account: &Account, repo: &str, dir: Option<&str>) -> Option<String> {
+    if let Some(dir) = dir {
+        return Some(dir.to_string());
+    }
+    let workspace = account.workspace.as_deref()?;
+    let name = repo
+        .trim_end_matches(".git")
+        .rsplit('/')
+        .next()
+        .unwrap_or(repo);
+    Some(
+        Path::new(&*shellexpand::tilde(workspace))
+            .join(name)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+/// Runs `git config <scope flag> args...`, failing with git's message.
+fn set_config(scope: ConfigScope, args: &[&str]) -> io::Result<()> {
+    let mut full_args = vec!["config", scope.flag()];
+    full_args.extend_from_slice(args);
+    let output = command_output("git", &full_args)?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Switches the gitconfig of `scope` to the account: user name and email,
+/// signing settings and the account's settings fragment.
+pub fn apply_identity(account: &Account, scope: ConfigScope) -> io::Result<()> {
+    set_config(scope, &["user.name", &account.username])?;
+    set_config(scope, &["user.email", &account.email])?;
+    apply_signing_config(account, scope)?;
+    apply_account_fragment(account, scope)
+}
+
+/// Points `core.sshCommand` in `scope` at the account's key, for the
+/// `sshcommand` switching mode.
+pub fn apply_ssh_command(account: &Account, scope: ConfigScope) -> io::Result<()> {
+    set_config(scope, &["core.sshCommand", &ssh_command(account)])
+}
+
+/// Removes a `core.sshCommand` in `scope` that the `sshcommand` mode set for
+/// one of the accounts, so it doesn't override host aliases after switching
+/// back. Commands the user set are left alone.
+pub fn clear_ssh_command(accounts: &[Account], scope: ConfigScope) -> io::Result<()> {
+    let Some(current) = config_value(Some(scope), "core.sshCommand") else {
+        return Ok(());
+    };
+    if accounts.iter().any(|acc| ssh_command(acc) == current) {
+        set_config(scope, &["--unset", "core.sshCommand"])?;
+    }
+    Ok(())
+}
+
+/// The `url.<base>.insteadOf = <prefix>` rules that send SSH URLs of the
+/// account's `orgs` on each of its hosts through its host alias, as
+/// `(base, prefix)` pairs. HTTPS accounts have none.
+pub fn org_url_rewrites(account: &Account) -> Vec<(String, String)> {
+    if account.uses_https() {
+        return Vec::new();
+    }
+    let mut rules = Vec::new();
+    for host in account.hosts() {
+        let user = account.ssh_user_for(host);
+        let alias = host_alias(host, &account.name);
+        for org in &account.orgs {
+            let base = format!("{}@{}:{}/", user, alias, org);
+            rules.push((base.clone(), format!("{}@{}:{}/", user, host, org)));
+            rules.push((base.clone(), format!("ssh://{}@{}/{}/", user, host, org)));
+            if let (server, Some(port)) = account.ssh_endpoint(host) {
+                rules.push((base, format!("ssh://{}@{}:{}/{}/", user, server, port, org)));
+            }
+        }
+    }
+    rules
+}
+
+/// The global `insteadOf` rules git-switch installed, recorded in
+/// `~/.git-switch/url-rewrites.toml` so that only those are ever removed,
+/// even after the account's `orgs` changed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct InstalledRewrites {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<InstalledRewrite>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct InstalledRewrite {
+    pub account: String,
+    pub base: String,
+    pub prefix: String,
+}
+
+fn installed_rewrites_path() -> PathBuf {
+    get_data_dir().join("url-rewrites.toml")
+}
+
+pub(crate) fn load_installed_rewrites(path: &Path) -> InstalledRewrites {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub(crate) fn save_installed_rewrites(rewrites: &InstalledRewrites, path: &Path) -> io::Result<()> {
+    let content = toml::to_string_pretty(rewrites).map_err(io::Error::other)?;
+    files::write_atomic(path, &content)
+}
+
+/// The rules to remove for `accounts`: the ones recorded for them, plus the
+/// ones their current `orgs` produce (installed before rules were recorded).
+pub(crate) fn rewrites_to_clear(
+    accounts: &[Account],
+    installed: &InstalledRewrites,
+) -> Vec<(String, String)> {
+    let mut rules: Vec<(String, String)> = installed
+        .rules
+        .iter()
+        .filter(|rule| accounts.iter().any(|acc| acc.name == rule.account))
+        .map(|rule| (rule.base.clone(), rule.prefix.clone()))
+        .chain(accounts.iter().flat_map(org_url_rewrites))
+        .collect();
+    rules.sort();
+    rules.dedup();
+    rules
+}
+
+/// Removes the global `insteadOf` rules git-switch installed for any of
+/// `accounts`. Rules written by hand are left alone, even when they point
+/// at an account's host alias.
+pub fn clear_org_url_rewrites(accounts: &[Account]) -> io::Result<()> {
+    let path = installed_rewrites_path();
+    let mut installed = load_installed_rewrites(&path);
+    for (base, prefix) in rewrites_to_clear(accounts, &installed) {
+        let output = command_output(
+            "git",
+            &[
+                "config",
+                "--global",
+                "--unset-all",
+                &format!("url.{}.insteadOf", base),
+                &format!("^{}$", regex_escape(&prefix)),
+            ],
+        )?;
+        // 5 means there was no such rule
+        if !output.status.success() && output.status.code() != Some(5) {
+            return Err(io::Error::other(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+    }
+    let before = installed.rules.len();
+    installed
+        .rules
+        .retain(|rule| accounts.iter().all(|acc| acc.name != rule.account));
+    if installed.rules.len() != before {
+        save_installed_rewrites(&installed, &path)?;
+    }
+    Ok(())
+}
+
+/// Replaces the global `insteadOf` rules of `accounts` with the account's.
+pub fn apply_org_url_rewrites(account: &Account, accounts: &[Account]) -> io::Result<()> {
+    clear_org_url_rewrites(accounts)?;
+    let rules = org_url_rewrites(account);
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let path = installed_rewrites_path();
+    let mut installed = load_installed_rewrites(&path);
+    for (base, prefix) in rules {
+        set_config(
+            ConfigScope::Global,
+            &["--add", &format!("url.{}.insteadOf", base), &prefix],
+        )?;
+        installed.rules.push(InstalledRewrite {
+            account: account.name.clone(),
+            base,
+            prefix,
+        });
+    }
+    if let Err(e) = save_installed_rewrites(&installed, &path) {
+        problem!(
+            "⚠️ Could not record the url.*.insteadOf rules; remove them by hand when you drop the account: {}",
+            e
+        );
+    }
+    Ok(())
+}
+
+/// Moves the rules installed for `old` over to the renamed account's alias.
+pub fn rename_org_url_rewrites(old: &Account, renamed: &Account) -> io::Result<()> {
+    let installed = load_installed_rewrites(&installed_rewrites_path());
+    if !installed.rules.iter().any(|rule| rule.account == old.name) {
+        return Ok(());
+    }
+    clear_org_url_rewrites(std::slice::from_ref(old))?;
+    apply_org_url_rewrites(renamed, &[])
+}
+
+/// Configures commit signing for the account, or turns it off for accounts
+/// without a signing key so the previous account's key isn't used.
+pub fn apply_signing_config(account: &Account, scope: ConfigScope) -> io::Result<()> {
+    match &account.signing_key {
+        Some(key) => {
+            let format = account.signing_format.as_deref().unwrap_or("gpg");
+            set_config(scope, &["user.signingkey", key])?;
+            set_config(scope, &["gpg.format", format])?;
+            set_config(scope, &["commit.gpgsign", "true"])?;
+        }
+        None => {
+            for key in ["user.signingkey", "gpg.format", "commit.gpgsign"] {
+                let _ = command_output("git", &["config", scope.flag(), "--unset", key]);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path of the gitconfig fragment holding an account's extra settings.
+pub fn account_fragment_path(name: &str) -> PathBuf {
+    get_data_dir().join("fragments").join(format!(
+        "{}.gitconfig",
+        name.replace(' ', "_").to_lowercase()
+    ))
+}
+
+/// Renders the account-scoped gitconfig fragment (credential cache, Git LFS,
+/// send-email, and the credential helper of HTTPS accounts). Returns `None`
+/// when the account has no settings that need a fragment.
+pub fn render_account_fragment(account: &Account) -> Option<String> {
+    if !account.uses_https()
+        && account.credential_cache_timeout.is_none()
+        && account.lfs_url.is_none()
+        && account.sendemail.is_none()
+        && account.git_config.is_empty()
+    {
+        return None;
+    }
+
+    let mut fragment = format!(
+        "# Managed by git-switch for account '{}'. Changes will be overwritten.\n",
+        account.name
+    );
+    if let Some(timeout) = account.credential_cache_timeout {
+        fragment.push_str(&format!(
+            "[credential]\n\thelper = cache --timeout={}\n",
+            timeout
+        ));
+    }
+    if account.uses_https() {
+        // The empty value drops helpers configured elsewhere for the host, so
+        // their stored credentials of another account aren't used
+        let helper = credential_helper_command(&account.name);
+        for host in account.hosts() {
+            fragment.push_str(&format!(
+                "[credential \"https://{}\"]\n\thelper =\n\thelper = {}\n\tusername = {}\n",
+                host, helper, account.username
+            ));
+        }
+    }
+    if let Some(lfs_url) = &account.lfs_url {
+        fragment.push_str(&format!("[lfs]\n\turl = {}\n", lfs_url));
+        // Scope the LFS server's credentials to this account's username
+        fragment.push_str(&format!(
+            "[credential \"{}\"]\n\tusername = {}\n",
+            url_origin(lfs_url),
+            account.username
+        ));
+    }
+    if let Some(sendemail) = &account.sendemail {
+        fragment.push_str(&format!(
+            "[sendemail]\n\tfrom = {} <{}>\n\tsmtpServer = {}\n",
+            account.username, account.email, sendemail.smtp_server
+        ));
+        if let Some(port) = sendemail.smtp_port {
+            fragment.push_str(&format!("\tsmtpServerPort = {}\n", port));
+        }
+        if let Some(user) = &sendemail.smtp_user {
+            fragment.push_str(&format!("\tsmtpUser = {}\n", user));
+        }
+        if let Some(encryption) = &sendemail.smtp_encryption {
+            fragment.push_str(&format!("\tsmtpEncryption = {}\n", encryption));
+        }
+    }
+    let mut header = String::new();
+    for (key, value) in &account.git_config {
+        let (section_header, name) = git_config_section(key);
+        if section_header != header {
+            fragment.push_str(&format!("{}\n", section_header));
+            header = section_header;
+        }
+        fragment.push_str(&format!("\t{} = {}\n", name, gitconfig_quote(value)));
+    }
+    Some(fragment)
+}
+
+/// Splits `section[.subsection].name` into its `[section "subsection"]`
+/// header line and the name.
+fn git_config_section(key: &str) -> (String, &str) {
+    let (section, rest) = key.split_once('.').unwrap_or((key, ""));
+    match rest.rsplit_once('.') {
+        Some((subsection, name)) => (format!("[{} \"{}\"]", section, subsection), name),
+        None => (format!("[{}]", section), rest),
+    }
+}
+
+/// Quotes a value for a gitconfig file, keeping spaces, `#` and `;`.
+fn gitconfig_quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\t', "\\t")
+    )
+}
+
+/// Parses a `--git-config KEY=VALUE` argument; an empty value asks for the
+/// setting to be removed.
+pub fn parse_git_config(arg: &str) -> Result<(String, String), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", arg))?;
+    let key = key.trim();
+    check_git_config_key(key)?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// The `credential.helper` value that answers for one account, quoted for
+/// a gitconfig file. It names the running binary by absolute path, since git
+/// puts its exec-path, where `git-switch` is git's `switch`, first in `PATH`.
+fn credential_helper_command(name: &str) -> String {
+    let binary = std::env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "git-switch".to_string());
+    let helper = format!(
+        "!{} credential --account {}",
+        shell_quote(&binary),
+        shell_quote(name)
+    );
+    format!("\"{}\"", helper.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns the `scheme://host[:port]` part of a URL.
+pub fn url_origin(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) => format!("{}://{}", scheme, rest.split('/').next().unwrap_or(rest)),
+        None => url.to_string(),
+    }
+}
+
+/// Writes the account's fragment and makes it the only git-switch fragment
+/// included from the gitconfig of the given scope.
+pub fn apply_account_fragment(account: &Account, scope: ConfigScope) -> io::Result<()> {
+    deactivate_account_fragments(scope);
+
+    let Some(path) = write_account_fragment(account)? else {
+        return Ok(());
+    };
+    set_config(scope, &["--add", "include.path", &path.to_string_lossy()])
+}
+
+/// Writes the account's fragment file, if it needs one, and returns its path.
+fn write_account_fragment(account: &Account) -> io::Result<Option<PathBuf>> {
+    let Some(fragment) = render_account_fragment(account) else {
+        return Ok(None);
+    };
+    let path = account_fragment_path(&account.name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let previous = fs::read_to_string(&path).ok();
+    fs::write(&path, &fragment)?;
+    events::file_modified(&path, previous.as_deref(), Some(&fragment));
+    Ok(Some(path))
+}
+
+/// Writes the account's identity, signing settings and fragment include to
+/// the local config of the repository at `repo`, e.g. right after cloning.
+/// With `with_ssh_command`, `core.sshCommand` is pointed at the account's
+/// key too.
+pub fn configure_repository(
+    repo: &Path,
+    account: &Account,
+    with_ssh_command: bool,
+) -> io::Result<()> {
+    let repo = repo.to_string_lossy();
+    let git_config = |args: &[&str]| -> io::Result<()> {
+        let mut full = vec!["-C", repo.as_ref(), "config", "--local"];
+        full.extend(args);
+        let output = command_output("git", &full)?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ))
+        }
+    };
+    git_config(&["user.name", &account.username])?;
+    git_config(&["user.email", &account.email])?;
+    if let Some(key) = &account.signing_key {
+        git_config(&["user.signingkey", key])?;
+        git_config(&[
+            "gpg.format",
+            account.signing_format.as_deref().unwrap_or("gpg"),
+        ])?;
+        git_config(&["commit.gpgsign", "true"])?;
+    }
+    if let Some(path) = write_account_fragment(account)? {
+        git_config(&["--add", "include.path", &path.to_string_lossy()])?;
+    }
+    if with_ssh_command && !account.uses_https() {
+        git_config(&["core.sshCommand", &ssh_command(account)])?;
+    }
+    Ok(())
+}
+
+/// Every account settings fragment on disk, including those of accounts
+/// that no longer exist.
+pub fn account_fragments() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(get_data_dir().join("fragments")) else {
+        return Vec::new();
+    };
+    let mut fragments: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "gitconfig"))
+        .collect();
+    fragments.sort();
+    fragments
+}
+
+/// Removes the account's fragment file and its include from the global gitconfig.
+pub fn remove_account_fragment(name: &str) -> io::Result<()> {
+    let path = account_fragment_path(name);
+    unset_include(ConfigScope::Global, &path.to_string_lossy());
+    if path.exists() {
+        let previous = fs::read_to_string(&path).ok();
+        fs::remove_file(&path)?;
+        events::file_modified(&path, previous.as_deref().or(Some("")), None);
+        say!("🗑️ Removed account settings fragment: {}", path.display());
+    }
+    Ok(())
+}
+
+/// Drops every `include.path` of the scope that points into git-switch's fragments directory.
+fn deactivate_account_fragments(scope: ConfigScope) {
+    let fragments_dir = get_data_dir().join("fragments");
+    let output = command_output(
+        "git",
+        &["config", scope.flag(), "--get-all", "include.path"],
+    );
+    let Ok(output) = output else {
+        return;
+    };
+    for include in String::from_utf8_lossy(&output.stdout).lines() {
+        if PathBuf::from(include).starts_with(&fragments_dir) {
+            unset_include(scope, include);
+        }
+    }
+}
+
+fn unset_include(scope: ConfigScope, path: &str) {
+    let _ = command_output(
+        "git",
+        &[
+            "config",
+            scope.flag(),
+            "--fixed-value",
+            "--unset-all",
+            "include.path",
+            path,
+        ],
+    );
 }
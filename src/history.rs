@@ -0,0 +1,72 @@
+//! Append-only log of switches and account changes, one JSON object per
+//! line in `~/.git-switch/history.jsonl`, shown by `git-switch history`.
+//!
+//! Recording is best effort: a log that can't be written never fails the
+//! command being logged.
+
+use crate::config::get_data_dir;
+use crate::debug;
+use crate::utils::now_secs;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix time of the change.
+    pub ts: u64,
+    /// `use`, `add`, `remove`, `rename`, `edit` or `remote`.
+    pub action: String,
+    pub account: String,
+    /// Top of the repository the command ran in, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+pub fn history_path() -> PathBuf {
+    get_data_dir().join("history.jsonl")
+}
+
+pub(crate) fn append_to_path(entry: &HistoryEntry, path: &Path) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let line = serde_json::to_string(entry).map_err(io::Error::other)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+/// Reads the log oldest first, skipping lines that don't parse.
+pub(crate) fn read_from_path(path: &Path) -> Vec<HistoryEntry> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Appends an entry stamped with the current time.
+pub fn record(action: &str, account: &str, repo: Option<String>, detail: Option<String>) {
+    let entry = HistoryEntry {
+        ts: now_secs(),
+        action: action.to_string(),
+        account: account.to_string(),
+        repo,
+        detail,
+    };
+    if let Err(e) = append_to_path(&entry, &history_path()) {
+        debug!("could not write to the history log: {}", e);
+    }
+}
+
+/// The last `limit` entries (all with `None`), oldest first.
+pub fn read_history(limit: Option<usize>) -> Vec<HistoryEntry> {
+    let mut entries = read_from_path(&history_path());
+    if let Some(limit) = limit {
+        entries.drain(..entries.len().saturating_sub(limit));
+    }
+    entries
+}
@@ -0,0 +1,166 @@
+//! Git hooks that stop commits made with the wrong identity, and the shell
+//! hook that runs `auto` when entering a repository.
+//!
+//! The installed hook only runs `git-switch hook check`, so it picks up new
+//! accounts, rules and mappings without being reinstalled. It calls the
+//! binary by absolute path: git puts its own exec-path first in hooks'
+//! `PATH`, where `git-switch` is git's `switch` command. Hooks git-switch
+//! didn't write are never overwritten unless forced.
+
+use crate::error::{GitSwitchError, IoContext, Result};
+use crate::render::shell_quote;
+use crate::utils::command_output;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Hooks `hook install` can write.
+pub const HOOK_TYPES: [&str; 2] = ["pre-commit", "prepare-commit-msg"];
+
+/// Line identifying hooks written by git-switch.
+pub const HOOK_MARKER: &str = "# Installed by git-switch";
+
+/// The hook script, running the git-switch binary at `binary`.
+pub fn render_hook(binary: &Path) -> String {
+    let binary = binary.to_string_lossy().replace('\'', "'\\''");
+    format!(
+        r#"#!/bin/sh
+{marker}; remove with `git-switch hook uninstall`.
+# Aborts the commit if its author email isn't the one of the account this
+# repository belongs to. Skip once with `git commit --no-verify`.
+git_switch='{binary}'
+if [ -x "$git_switch" ]; then
+  exec "$git_switch" hook check
+fi
+echo "git-switch not found at $git_switch; skipping the identity check. Reinstall with \`git-switch hook install\`." >&2
+"#,
+        marker = HOOK_MARKER,
+        binary = binary
+    )
+}
+
+/// Shells `hook shell` prints the auto-switch hook for.
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// Shell code running `auto --silent` with the binary at `binary` when the
+/// shell starts and whenever it changes directory.
+pub fn render_shell_hook(shell: &str, binary: &Path) -> Option<String> {
+    let binary = shell_quote(&binary.to_string_lossy());
+    let script = match shell {
+        "bash" => format!(
+            r#"# git-switch auto-switch hook; add `eval "$(git-switch hook shell bash)"` to ~/.bashrc
+_git_switch_auto() {{
+  [ "$PWD" = "${{_GIT_SWITCH_AUTO_PWD-}}" ] && return
+  _GIT_SWITCH_AUTO_PWD="$PWD"
+  {binary} auto --silent
+}}
+PROMPT_COMMAND="_git_switch_auto${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+"#
+        ),
+        "zsh" => format!(
+            r#"# git-switch auto-switch hook; add `eval "$(git-switch hook shell zsh)"` to ~/.zshrc
+_git_switch_auto() {{
+  {binary} auto --silent
+}}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _git_switch_auto
+_git_switch_auto
+"#
+        ),
+        "fish" => format!(
+            r#"# git-switch auto-switch hook; add `git-switch hook shell fish | source` to config.fish
+function _git_switch_auto --on-variable PWD
+    {binary} auto --silent
+end
+_git_switch_auto
+"#
+        ),
+        _ => return None,
+    };
+    Some(script)
+}
+
+/// Whether a hook file's content was written by git-switch.
+pub fn is_managed(content: &str) -> bool {
+    content
+        .lines()
+        .take(3)
+        .any(|line| line.starts_with(HOOK_MARKER))
+}
+
+/// The directory git runs the current repository's hooks from, honoring
+/// `core.hooksPath`.
+pub fn hooks_dir() -> Result<PathBuf> {
+    let output = command_output("git", &["rev-parse", "--git-path", "hooks"])
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        return Err(GitSwitchError::NotInRepository);
+    }
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
+
+/// Writes the hook into `dir`. An existing hook not written by git-switch is
+/// kept (as `<hook>.git-switch-backup` with `force`, otherwise it's an error).
+/// Returns the hook's path.
+pub fn install_hook(dir: &Path, hook: &str, force: bool) -> Result<PathBuf, String> {
+    let path = dir.join(hook);
+    if let Ok(existing) = fs::read_to_string(&path)
+        && !is_managed(&existing)
+    {
+        if !force {
+            return Err(format!(
+                "{} already exists; pass --force to move it aside",
+                path.display()
+            ));
+        }
+        let backup = dir.join(format!("{}.git-switch-backup", hook));
+        fs::rename(&path, &backup)
+            .map_err(|e| format!("Failed to move {} aside: {}", path.display(), e))?;
+    }
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let binary = std::env::current_exe().map_err(|e| e.to_string())?;
+    fs::write(&path, render_hook(&binary)).map_err(|e| e.to_string())?;
+    make_executable(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Removes the hook if git-switch wrote it, restoring a hook moved aside by
+/// `install_hook`. Returns whether there was one to remove.
+pub fn uninstall_hook(dir: &Path, hook: &str) -> Result<bool, String> {
+    let path = dir.join(hook);
+    match fs::read_to_string(&path) {
+        Ok(content) if is_managed(&content) => {}
+        _ => return Ok(false),
+    }
+    fs::remove_file(&path).map_err(|e| e.to_string())?;
+    let backup = dir.join(format!("{}.git-switch-backup", hook));
+    if backup.exists() {
+        fs::rename(&backup, &path).map_err(|e| e.to_string())?;
+    }
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// The email git will author the next commit with, honoring
+/// `GIT_AUTHOR_EMAIL` (set by `use --session`) as well as config.
+pub fn author_email() -> Option<String> {
+    let output = command_output("git", &["var", "GIT_AUTHOR_IDENT"]).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let ident = String::from_utf8_lossy(&output.stdout).into_owned();
+    let (_, rest) = ident.split_once('<')?;
+    let (email, _) = rest.split_once('>')?;
+    Some(email.trim().to_string())
+}
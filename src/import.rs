@@ -0,0 +1,195 @@
+//! Finding accounts in an existing setup: `Host` entries for known Git
+//! hosts in `~/.ssh/config`, and the identities in the user's git config.
+//!
+//! Only proposes accounts; `git-switch import` confirms and saves them.
+
+use crate::config::{Account, check_ssh_option_name};
+use crate::provider::provider_for;
+use crate::utils::command_output;
+use std::collections::BTreeMap;
+
+/// A `Host` entry of an SSH config.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostEntry {
+    /// The first pattern of the `Host` line.
+    pub alias: String,
+    pub hostname: Option<String>,
+    pub identity_file: Option<String>,
+    /// Every other option, keyed by its name as written.
+    pub options: BTreeMap<String, String>,
+}
+
+impl HostEntry {
+    /// The host ssh actually connects to.
+    pub fn real_host(&self) -> &str {
+        self.hostname.as_deref().unwrap_or(&self.alias)
+    }
+}
+
+/// A git identity: `user.name` and `user.email`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    pub name: String,
+    pub email: String,
+}
+
+/// Identities in the global git config and the files it includes with
+/// `includeIf` (the usual way to use several identities without git-switch).
+pub fn git_identities() -> Vec<Identity> {
+    let read = |args: &[&str], key: &str| {
+        let mut args = args.to_vec();
+        args.extend(["--get", key]);
+        let output = command_output("git", &args).ok()?;
+        let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !value.is_empty()).then_some(value)
+    };
+    let identity = |args: &[&str]| {
+        Some(Identity {
+            name: read(args, "user.name")?,
+            email: read(args, "user.email")?,
+        })
+    };
+
+    let mut identities: Vec<Identity> = identity(&["config", "--global"]).into_iter().collect();
+    let includes = command_output(
+        "git",
+        &[
+            "config",
+            "--global",
+            "--get-regexp",
+            r"^includeif\..*\.path$",
+        ],
+    )
+    .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    .unwrap_or_default();
+    for path in includes.lines().filter_map(|line| line.split_once(' ')) {
+        let path = shellexpand::tilde(path.1.trim()).into_owned();
+        if let Some(found) = identity(&["config", "--file", &path])
+            && !identities.contains(&found)
+        {
+            identities.push(found);
+        }
+    }
+    identities
+}
+
+/// Parses the `Host` entries of an SSH config, skipping the blocks
+/// git-switch manages and `Match` sections. Options before the first
+/// `Host` line apply to every host and are ignored.
+pub fn parse_host_entries(content: &str) -> Vec<HostEntry> {
+    let mut entries = Vec::new();
+    let mut current: Option<HostEntry> = None;
+    let mut managed = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with("# >>> git-switch:") {
+            managed = true;
+        } else if line.starts_with("# <<< git-switch:") {
+            managed = false;
+            continue;
+        }
+        if managed || line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+            Some((key, value)) => (key, value.trim_start_matches([' ', '\t', '=']).trim()),
+            None => (line, ""),
+        };
+        let value = value.trim_matches('"');
+        if key.eq_ignore_ascii_case("host") || key.eq_ignore_ascii_case("match") {
+            entries.extend(current.take());
+            if key.eq_ignore_ascii_case("host") {
+                current = value.split_whitespace().next().map(|alias| HostEntry {
+                    alias: alias.to_string(),
+                    ..Default::default()
+                });
+            }
+            continue;
+        }
+        let Some(entry) = current.as_mut() else {
+            continue;
+        };
+        if key.eq_ignore_ascii_case("hostname") {
+            entry.hostname = Some(value.to_string());
+        } else if key.eq_ignore_ascii_case("identityfile") {
+            entry.identity_file.get_or_insert_with(|| value.to_string());
+        } else {
+            entry.options.insert(key.to_string(), value.to_string());
+        }
+    }
+    entries.extend(current);
+    entries
+}
+
+/// Name for an account found under `alias`: the alias without the host part
+/// (`github-work`, `work.github.com` and `github.com-work` give `work`), or
+/// the host's first label when nothing else is left.
+pub fn account_name_for(alias: &str, host: &str) -> String {
+    let stem = host.split('.').next().unwrap_or(host);
+    let rest = [host, stem]
+        .iter()
+        .find_map(|prefix| {
+            let prefix = prefix.to_ascii_lowercase();
+            let lower = alias.to_ascii_lowercase();
+            lower
+                .strip_prefix(&prefix)
+                .or_else(|| lower.strip_suffix(&prefix))
+                .map(|rest| rest.trim_matches(['-', '_', '.']).to_string())
+        })
+        .unwrap_or_else(|| alias.to_string());
+    if rest.is_empty() {
+        stem.to_string()
+    } else {
+        rest
+    }
+}
+
+/// Accounts proposed for the SSH config's entries for known Git hosts, with
+/// the identity that fits best. Entries without a key or with wildcards are
+/// skipped, as are keys `existing` accounts already use.
+pub fn propose_accounts(
+    entries: &[HostEntry],
+    identities: &[Identity],
+    existing: &[Account],
+) -> Vec<(HostEntry, Account)> {
+    let mut proposals: Vec<(HostEntry, Account)> = Vec::new();
+    for entry in entries {
+        let host = entry.real_host();
+        let (Some(provider), Some(key)) = (provider_for(host), &entry.identity_file) else {
+            continue;
+        };
+        let same_key = |acc: &Account| shellexpand::tilde(&acc.ssh_key) == shellexpand::tilde(key);
+        if entry.alias.contains(['*', '?', '!'])
+            || existing.iter().any(same_key)
+            || proposals.iter().any(|(_, acc)| same_key(acc))
+        {
+            continue;
+        }
+        let name = account_name_for(&entry.alias, provider.host);
+        let identity = identities
+            .iter()
+            .find(|identity| identity.email.to_lowercase().contains(&name.to_lowercase()))
+            .or(identities.first());
+        let ssh_options = entry
+            .options
+            .iter()
+            .filter(|(option, _)| {
+                !option.eq_ignore_ascii_case("identitiesonly")
+                    && check_ssh_option_name(option).is_ok()
+            })
+            .map(|(option, value)| (option.clone(), value.clone()))
+            .collect();
+        let account = Account {
+            name,
+            username: identity.map(|id| id.name.clone()).unwrap_or_default(),
+            email: identity.map(|id| id.email.clone()).unwrap_or_default(),
+            ssh_key: key.clone(),
+            host: provider.host.to_string(),
+            external_key: true,
+            ssh_options,
+            ..Default::default()
+        };
+        proposals.push((entry.clone(), account));
+    }
+    proposals
+}
@@ -0,0 +1,221 @@
+//! Directory-based identities via `includeIf "gitdir:..."`.
+//!
+//! Each mapped account gets an identity fragment under
+//! `~/.git-switch/identities/`, and the global gitconfig includes it for
+//! repositories below the mapped directory. Only includes that point into
+//! the identities directory are treated as managed by git-switch.
+
+use crate::config::{Account, get_data_dir};
+use crate::events;
+use crate::git::render_account_fragment;
+use crate::render::ssh_command;
+use crate::utils::command_output;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Returns the path of the identity fragment included for mapped directories.
+pub fn identity_fragment_path(name: &str) -> PathBuf {
+    get_data_dir().join("identities").join(format!(
+        "{}.gitconfig",
+        name.replace(' ', "_").to_lowercase()
+    ))
+}
+
+/// Renders the full identity of an account: user, SSH key, signing and the
+/// settings from its regular fragment.
+pub fn render_identity_fragment(account: &Account) -> String {
+    let mut fragment = format!(
+        "# Managed by git-switch for account '{}'. Changes will be overwritten.\n",
+        account.name
+    );
+    fragment.push_str(&format!(
+        "[user]\n\tname = {}\n\temail = {}\n",
+        account.username, account.email
+    ));
+    if let Some(key) = &account.signing_key {
+        fragment.push_str(&format!("\tsigningkey = {}\n", key));
+    }
+    if !account.uses_https() {
+        fragment.push_str(&format!(
+            "[core]\n\tsshCommand = {}\n",
+            config_quote(&ssh_command(account))
+        ));
+    }
+    if account.signing_key.is_some() {
+        fragment.push_str(&format!(
+            "[gpg]\n\tformat = {}\n[commit]\n\tgpgsign = true\n",
+            account.signing_format.as_deref().unwrap_or("gpg")
+        ));
+    }
+    if let Some(extra) = render_account_fragment(account) {
+        // Drop the header line of the regular fragment
+        fragment.extend(extra.lines().skip(1).map(|line| format!("{}\n", line)));
+    }
+    fragment
+}
+
+/// Quotes a gitconfig value if it has characters the config parser treats
+/// specially, such as the backslash of a shell-quoted `'`.
+fn config_quote(value: &str) -> String {
+    if !value.contains(['\\', '"', '#', ';']) {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Builds the `gitdir:` condition for a directory. The trailing slash makes
+/// git match every repository below it.
+pub fn gitdir_condition(dir: &str) -> String {
+    format!("gitdir:{}/", dir.trim_end_matches(['/', '\\']))
+}
+
+fn include_key(dir: &str) -> String {
+    format!("includeIf.{}.path", gitdir_condition(dir))
+}
+
+/// Resolves `dir` to an absolute path, keeping a leading `~/` (git expands it).
+pub fn normalize_dir(dir: &str) -> String {
+    if dir.starts_with("~/") || Path::new(dir).is_absolute() {
+        return dir.to_string();
+    }
+    fs::canonicalize(dir)
+        .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(dir)))
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| dir.to_string())
+}
+
+/// Maps `dir` to the account, replacing any existing managed mapping for it.
+pub fn map_directory(dir: &str, account: &Account) -> io::Result<()> {
+    let path = identity_fragment_path(&account.name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let fragment = render_identity_fragment(account);
+    let previous = fs::read_to_string(&path).ok();
+    fs::write(&path, &fragment)?;
+    events::file_modified(&path, previous.as_deref(), Some(&fragment));
+
+    unmap_directory(dir);
+    let key = include_key(dir);
+    let output = command_output(
+        "git",
+        &["config", "--global", "--add", &key, &path.to_string_lossy()],
+    )?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Rewrites the account's identity fragment, if it has mapped directories,
+/// so they pick up changes to the account.
+pub fn refresh_identity_fragment(account: &Account) -> io::Result<()> {
+    let path = identity_fragment_path(&account.name);
+    let Ok(previous) = fs::read_to_string(&path) else {
+        return Ok(());
+    };
+    let fragment = render_identity_fragment(account);
+    if fragment != previous {
+        fs::write(&path, &fragment)?;
+        events::file_modified(&path, Some(&previous), Some(&fragment));
+    }
+    Ok(())
+}
+
+/// Points the directories mapped to `old_name` at the renamed account.
+pub fn rename_account_mappings(old_name: &str, account: &Account) -> io::Result<()> {
+    let old_path = identity_fragment_path(old_name);
+    if old_path == identity_fragment_path(&account.name) {
+        return refresh_identity_fragment(account);
+    }
+    for (dir, mapped_path) in managed_includes() {
+        if Path::new(&mapped_path) == old_path {
+            map_directory(&dir, account)?;
+        }
+    }
+    remove_account_mappings(old_name)
+}
+
+/// Removes the managed mapping for `dir`. Returns whether one existed.
+pub fn unmap_directory(dir: &str) -> bool {
+    let condition = gitdir_condition(dir);
+    let mut removed = false;
+    for (mapped_dir, path) in managed_includes() {
+        if gitdir_condition(&mapped_dir) == condition {
+            removed |= unset_include(&include_key(&mapped_dir), &path);
+        }
+    }
+    removed
+}
+
+/// Returns `(directory, fragment path)` for every managed conditional include.
+pub fn managed_includes() -> Vec<(String, String)> {
+    let output = command_output(
+        "git",
+        &[
+            "config",
+            "--global",
+            "--get-regexp",
+            r"^includeif\.gitdir:.*\.path$",
+        ],
+    );
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+    parse_managed_includes(
+        &String::from_utf8_lossy(&output.stdout),
+        &get_data_dir().join("identities"),
+    )
+}
+
+/// Parses `git config --get-regexp` output, keeping includes that point
+/// into `identities_dir`.
+pub fn parse_managed_includes(output: &str, identities_dir: &Path) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, path) = line.split_once(' ')?;
+            let dir = key
+                .strip_prefix("includeif.gitdir:")?
+                .strip_suffix(".path")?
+                .trim_end_matches('/');
+            Path::new(path)
+                .starts_with(identities_dir)
+                .then(|| (dir.to_string(), path.to_string()))
+        })
+        .collect()
+}
+
+/// Drops every mapping to the account and deletes its identity fragment.
+pub fn remove_account_mappings(name: &str) -> io::Result<()> {
+    let path = identity_fragment_path(name);
+    for (dir, mapped_path) in managed_includes() {
+        if Path::new(&mapped_path) == path {
+            unset_include(&include_key(&dir), &mapped_path);
+        }
+    }
+    if path.exists() {
+        let previous = fs::read_to_string(&path).ok();
+        fs::remove_file(&path)?;
+        events::file_modified(&path, previous.as_deref().or(Some("")), None);
+    }
+    Ok(())
+}
+
+fn unset_include(key: &str, path: &str) -> bool {
+    command_output(
+        "git",
+        &[
+            "config",
+            "--global",
+            "--fixed-value",
+            "--unset-all",
+            key,
+            path,
+        ],
+    )
+    .is_ok_and(|out| out.status.success())
+}
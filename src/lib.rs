@@ -0,0 +1,71 @@
+//! Manage several Git identities on one machine: accounts with their own
+//! SSH keys, SSH host aliases and git settings, and switching between them.
+//!
+//! This is the library behind the `git-switch` binary. The modules most
+//! useful for embedding are:
+//!
+//! - [`config`]: the accounts file ([`Account`], loading and saving),
+//! - [`ssh`]: key generation and the managed blocks in `~/.ssh/config`,
+//! - [`git`]: applying an account's identity to git config and remotes.
+//!
+//! Functions there return `Result`s instead of printing. [`commands`] holds
+//! the binary's subcommands, which print their progress and are not meant
+//! for embedding; they fail with a [`GitSwitchError`], whose
+//! [`exit_code`](GitSwitchError::exit_code) the binary exits with.
+//!
+//! ```no_run
+//! use git_switch::config::{find_account, load_accounts};
+//! use git_switch::git::{ConfigScope, apply_identity};
+//!
+//! let accounts = load_accounts();
+//! let work = find_account(&accounts, "work").expect("no account named 'work'");
+//! apply_identity(work, ConfigScope::Global)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+pub mod bundle;
+pub mod cli;
+pub mod commands;
+pub mod completions;
+pub mod config;
+pub mod credential;
+pub mod crypto;
+pub mod doctor;
+pub mod editor;
+pub mod error;
+pub mod events;
+pub mod files;
+pub mod git;
+pub mod history;
+pub mod hooks;
+pub mod import;
+pub mod includes;
+pub mod manifest;
+pub mod output;
+pub mod pending;
+pub mod policy;
+pub mod prompt;
+pub mod provider;
+pub mod render;
+pub mod rules;
+pub mod scan;
+pub mod secrets;
+pub mod session;
+pub mod settings;
+pub mod ssh;
+pub mod sync;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+#[cfg(feature = "provider-api")]
+pub mod tokens;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod undo;
+pub mod utils;
+
+pub use config::Account;
+pub use error::GitSwitchError;
+pub use git::ConfigScope;
+
+#[cfg(test)]
+mod tests;
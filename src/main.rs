@@ -1,71 +1,585 @@
-use crate::commands::{add_account, list_accounts, remove_account, use_account};
-use clap::{Arg, Command};
+//! The `git-switch` binary: parses the command line and runs the matching
+//! subcommand from [`git_switch::commands`].
 
-mod commands;
-mod config;
-mod git;
-mod ssh;
-mod utils;
+use clap::ArgMatches;
+use git_switch::GitSwitchError;
+use git_switch::bundle::BundleFormat;
+use git_switch::cli::build_cli;
+use git_switch::commands::{
+    AccountEdit, AddOptions, FixOptions, PromptOptions, RemoteAction, RemoveOptions, RotateOptions,
+    ScanFix, UseOptions, account_to_use, add_account, add_account_wizard, add_rule, attest_key,
+    auto_use, change_passphrase, check_commit_identity, check_manifest, clone_by_rule, clone_repo,
+    copy_key, credential_helper, decrypt_accounts, direnv, edit_account, edit_all_accounts,
+    encrypt_accounts, end_shell_session, export_config, fix_repositories, for_each_tagged,
+    generate_completions, import_accounts, import_bundle, install_commit_hook, list_account_names,
+    list_accounts, list_directory_mappings, list_rules, manage_settings, map_directory,
+    pick_account, print_account_env, print_auto_switch_hook, print_current, print_history,
+    print_prompt, print_shell_hook, print_status, print_version, print_workspace, prune,
+    read_new_passphrase, remove_account, remove_rule, remove_secret, rename_account,
+    render_account, retry_pending, rotate_key, run_dashboard, run_doctor, scan_repositories,
+    set_default, set_secret, show_account, show_key, sync_pull, sync_push, sync_setup,
+    test_connection, undo, uninstall_commit_hook, unmap_directory, upload_key, use_account,
+    verify_account,
+};
+use git_switch::git::ConfigScope;
+use git_switch::output::{self, OutputFormat};
+use git_switch::problem;
+use git_switch::settings::{self, SwitchMode};
+use git_switch::{config, events, pending, rules, ssh, utils};
 
-#[cfg(test)]
-mod tests;
+fn output_format(matches: &ArgMatches) -> OutputFormat {
+    matches
+        .get_one::<String>("format")
+        .and_then(|format| OutputFormat::parse(format))
+        .unwrap_or_default()
+}
+
+fn switch_mode(matches: &ArgMatches) -> Option<SwitchMode> {
+    matches
+        .get_one::<String>("mode")
+        .and_then(|mode| SwitchMode::parse(mode))
+}
 
 fn main() {
-    let matches = Command::new("git-switch")
-        .version("1.0")
-        .about("CLI tool to switch between multiple Git accounts")
-        .subcommand(
-            Command::new("add")
-                .about("Add a new Git account")
-                .arg(
-                    Arg::new("name")
-                        .required(true)
-                        .help("Name for the account (e.g. 'Work', 'Personal')"),
-                )
-                .arg(Arg::new("username").required(true).help("Git username"))
-                .arg(Arg::new("email").required(true).help("Git email address")),
-        )
-        .subcommand(
-            Command::new("use")
-                .about("Switch to a saved Git account")
-                .arg(
-                    Arg::new("name")
-                        .required(true)
-                        .help("Name or username of the account to use"),
-                ),
-        )
-        .subcommand(Command::new("list").about("List all saved Git accounts"))
-        .subcommand(
-            Command::new("remove")
-                .about("Remove a saved Git account and its SSH key")
-                .arg(
-                    Arg::new("name")
-                        .required(true)
-                        .help("Name of the account to remove"),
-                ),
-        )
-        .get_matches();
+    // ssh-add runs us as its SSH_ASKPASS program when adding a key with a
+    // passphrase given to `add`
+    if let Ok(passphrase) = std::env::var(ssh::ASKPASS_VAR) {
+        println!("{}", passphrase);
+        return;
+    }
+    let matches = build_cli().get_matches();
+    if matches.get_flag("log-json") {
+        events::enable();
+    }
+    if matches.get_flag("non-interactive") {
+        utils::set_non_interactive();
+    }
+    if let Some(path) = matches.get_one::<String>("config") {
+        config::set_config_path(shellexpand::tilde(path).as_ref().into());
+    }
+    if let Some(path) = matches.get_one::<String>("ssh-config") {
+        ssh::set_ssh_config_path(path);
+    }
+    match matches.get_count("verbose") {
+        0 => {}
+        1 => utils::set_verbosity(utils::Verbosity::Verbose),
+        _ => utils::set_verbosity(utils::Verbosity::Debug),
+    }
+    if matches.get_flag("quiet") {
+        utils::set_verbosity(utils::Verbosity::Quiet);
+    }
+    let settings = settings::load_settings();
+    output::configure(settings.emoji(), settings.color());
+    let command_name = matches.subcommand_name().unwrap_or("").to_string();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    events::command_started(&command_name, &args);
 
+    let result = config::home_dir().and_then(|_| {
+        // Stderr, so output captured by scripts (`list --format json`, `cd`) stays clean
+        match config::migrate_legacy_config() {
+            Ok(Some(path)) => problem!(
+                "ℹ️ Moved {} to {}",
+                config::legacy_config_path().display(),
+                path.display()
+            ),
+            Ok(None) => {}
+            Err(e) => problem!(
+                "⚠️ Failed to move the accounts file to its new location: {}",
+                e
+            ),
+        }
+        run(&matches)
+    });
+    events::command_finished(&command_name, result.is_ok());
+    if result.is_ok() {
+        git_switch::undo::record_command(&args.join(" "));
+    }
+    if let Err(e) = result {
+        problem!("❌ {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+/// Runs the subcommand the command line names.
+fn run(matches: &ArgMatches) -> Result<(), GitSwitchError> {
     match matches.subcommand() {
         Some(("add", sub_m)) => {
-            let name = sub_m.get_one::<String>("name").unwrap();
-            let username = sub_m.get_one::<String>("username").unwrap();
-            let email = sub_m.get_one::<String>("email").unwrap();
-            add_account(name, username, email);
+            let options = AddOptions {
+                credential_url: sub_m.get_one::<String>("credential-url").cloned(),
+                lfs_url: sub_m.get_one::<String>("lfs-url").cloned(),
+                credential_cache_timeout: sub_m.get_one::<u64>("credential-cache").copied(),
+                key_type: sub_m
+                    .get_one::<String>("key-type")
+                    .and_then(|t| ssh::KeyType::parse(t))
+                    .unwrap_or_else(|| settings::load_settings().key_type()),
+                key_bits: sub_m.get_one::<u32>("key-bits").copied(),
+                workspace: sub_m.get_one::<String>("workspace").cloned(),
+                hosts: sub_m
+                    .get_many::<String>("host")
+                    .map(|hosts| hosts.cloned().collect())
+                    .unwrap_or_default(),
+                signing_key: sub_m.get_one::<String>("signing-key").cloned(),
+                signing_format: sub_m.get_one::<String>("signing-format").cloned(),
+                upload: sub_m.get_flag("upload"),
+                passphrase: if sub_m.get_flag("passphrase-stdin") {
+                    Some(read_new_passphrase(true, "the new key")?)
+                } else if sub_m.get_flag("passphrase") {
+                    Some(read_new_passphrase(false, "the new key")?)
+                } else {
+                    None
+                },
+                ssh_key: sub_m.get_one::<String>("ssh-key").cloned(),
+                https: sub_m
+                    .get_one::<String>("auth")
+                    .is_some_and(|auth| auth == "https"),
+                token: if sub_m.get_flag("token-stdin") {
+                    Some(utils::read_stdin_line().map_err(|e| {
+                        GitSwitchError::io("Failed to read the token from stdin", e)
+                    })?)
+                } else {
+                    None
+                },
+                ssh_options: sub_m
+                    .get_many::<(String, String)>("ssh-option")
+                    .map(|options| {
+                        options
+                            .filter(|(_, value)| !value.is_empty())
+                            .cloned()
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                git_config: sub_m
+                    .get_many::<(String, String)>("git-config")
+                    .map(|settings| {
+                        settings
+                            .filter(|(_, value)| !value.is_empty())
+                            .cloned()
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                sendemail: sub_m
+                    .get_one::<String>("smtp-server")
+                    .map(|server| config::SendEmail {
+                        smtp_server: server.clone(),
+                        smtp_port: sub_m.get_one::<u16>("smtp-port").copied(),
+                        smtp_user: sub_m.get_one::<String>("smtp-user").cloned(),
+                        smtp_encryption: sub_m.get_one::<String>("smtp-encryption").cloned(),
+                    }),
+                force: sub_m.get_flag("force"),
+                exclusive_key: sub_m.get_flag("exclusive-key"),
+                key_ttl: sub_m.get_one::<u64>("agent-ttl").copied(),
+                tags: sub_m
+                    .get_many::<String>("tag")
+                    .map(|tags| tags.cloned().collect())
+                    .unwrap_or_default(),
+                orgs: sub_m
+                    .get_many::<String>("org")
+                    .map(|orgs| orgs.cloned().collect())
+                    .unwrap_or_default(),
+                provider: sub_m.get_one::<String>("provider").cloned(),
+                hostname: sub_m.get_one::<String>("hostname").cloned(),
+                port: sub_m.get_one::<u16>("port").copied(),
+                ssh_user: sub_m.get_one::<String>("ssh-user").cloned(),
+                proxy_jump: sub_m.get_one::<String>("proxy-jump").cloned(),
+                key_options: ["resident", "verify-required"]
+                    .into_iter()
+                    .filter(|option| sub_m.get_flag(option))
+                    .map(String::from)
+                    .collect(),
+                key_dir: sub_m.get_one::<String>("key-dir").cloned(),
+            };
+            match (
+                sub_m.get_one::<String>("name"),
+                sub_m.get_one::<String>("username"),
+                sub_m.get_one::<String>("email"),
+            ) {
+                (Some(name), Some(username), Some(email)) => {
+                    add_account(name, username, email, options)
+                }
+                _ => add_account_wizard(options),
+            }
         }
+        Some(("import", sub_m)) => match sub_m.get_one::<String>("file") {
+            Some(file) => import_bundle(file, sub_m.get_flag("passphrase-stdin")),
+            None => import_accounts(sub_m.get_flag("dry-run"), sub_m.get_flag("yes")),
+        },
+        Some(("export", sub_m)) => export_config(
+            sub_m
+                .get_one::<String>("format")
+                .and_then(|format| BundleFormat::parse(format))
+                .unwrap_or_default(),
+            sub_m.get_flag("include-keys"),
+            sub_m.get_flag("encrypt"),
+            sub_m.get_flag("passphrase-stdin"),
+            sub_m.get_one::<String>("output").map(String::as_str),
+        ),
+        Some(("encrypt", sub_m)) => encrypt_accounts(
+            sub_m.get_one::<String>("identity").map(String::as_str),
+            sub_m.get_flag("passphrase-stdin"),
+        ),
+        Some(("decrypt", _)) => decrypt_accounts(),
+        Some(("sync", sub_m)) => match sub_m.subcommand() {
+            Some(("setup", setup_m)) => sync_setup(setup_m.get_one::<String>("url").unwrap()),
+            Some(("push", _)) => sync_push(),
+            Some(("pull", _)) => sync_pull(),
+            _ => Ok(()),
+        },
+        Some(("use", sub_m)) if sub_m.get_flag("end-session") => end_shell_session(),
         Some(("use", sub_m)) => {
-            let name = sub_m.get_one::<String>("name").unwrap();
-            use_account(name);
+            let name = match sub_m.get_one::<String>("name") {
+                Some(name) => name.clone(),
+                None => account_to_use()?,
+            };
+            let scope = if sub_m.get_flag("local") {
+                Some(ConfigScope::Local)
+            } else if sub_m.get_flag("global") {
+                Some(ConfigScope::Global)
+            } else {
+                None
+            };
+            let remote = if let Some(repo) = sub_m.get_one::<String>("update-remote") {
+                RemoteAction::Update(repo.clone())
+            } else if sub_m.get_flag("no-remote")
+                || sub_m.get_flag("rewrite-remotes")
+                || sub_m.get_flag("print-env")
+            {
+                RemoteAction::Skip
+            } else {
+                RemoteAction::Prompt
+            };
+            let options = UseOptions {
+                scope,
+                acknowledge: sub_m.get_flag("acknowledge"),
+                remote,
+                session: sub_m.get_flag("session"),
+                plain_remote: sub_m.get_flag("plain-remote"),
+                rewrite_remotes: sub_m.get_flag("rewrite-remotes"),
+                print_env: sub_m.get_flag("print-env"),
+                exclusive: sub_m.get_flag("exclusive"),
+                mode: switch_mode(sub_m),
+            };
+            if options.print_env {
+                output::messages_to_stderr();
+            }
+            use_account(&name, options)
+        }
+        Some(("tui", _)) => run_dashboard(),
+        Some(("history", sub_m)) => {
+            let limit = (!sub_m.get_flag("all"))
+                .then(|| sub_m.get_one::<usize>("limit").copied())
+                .flatten();
+            print_history(limit, output_format(sub_m));
+            Ok(())
+        }
+        Some(("current", sub_m)) => print_current(output_format(sub_m)),
+        Some(("prompt", sub_m)) => print_prompt(PromptOptions {
+            template: sub_m.get_one::<String>("template").unwrap().clone(),
+            mismatch_template: sub_m
+                .get_one::<String>("mismatch-template")
+                .unwrap()
+                .clone(),
+            shell: sub_m.get_one::<String>("shell").unwrap().clone(),
+            color: !sub_m.get_flag("no-color"),
+        }),
+        Some(("status", sub_m)) => {
+            print_status(output_format(sub_m));
+            Ok(())
         }
-        Some(("list", _)) => {
-            list_accounts();
+        Some(("show", sub_m)) => show_account(
+            sub_m.get_one::<String>("name").unwrap(),
+            output_format(sub_m),
+        ),
+        Some(("list", sub_m)) => {
+            if sub_m.get_flag("names") {
+                list_account_names();
+            } else {
+                list_accounts(
+                    output_format(sub_m),
+                    sub_m.get_one::<String>("tag").map(String::as_str),
+                    sub_m.get_flag("keys"),
+                );
+            }
+            Ok(())
         }
         Some(("remove", sub_m)) => {
+            let name = match sub_m.get_one::<String>("name") {
+                Some(name) => name.clone(),
+                None => pick_account("Remove which account?")?,
+            };
+            remove_account(
+                &name,
+                RemoveOptions {
+                    yes: sub_m.get_flag("yes"),
+                    keep_keys: sub_m.get_flag("keep-keys"),
+                    config_only: sub_m.get_flag("config-only"),
+                    dry_run: sub_m.get_flag("dry-run"),
+                },
+            )
+        }
+        Some(("undo", _)) => undo(),
+        Some(("prune", sub_m)) => prune(sub_m.get_flag("dry-run"), sub_m.get_flag("yes")),
+        Some(("default", sub_m)) => set_default(
+            sub_m.get_one::<String>("name").map(String::as_str),
+            sub_m.get_flag("clear"),
+        ),
+        Some(("rename", sub_m)) => {
+            let name = sub_m.get_one::<String>("name").unwrap();
+            let new_name = sub_m.get_one::<String>("new-name").unwrap();
+            rename_account(name, new_name)
+        }
+        Some(("test", sub_m)) => test_connection(
+            sub_m.get_one::<String>("name").unwrap(),
+            sub_m.get_flag("yes"),
+        ),
+        Some(("verify", sub_m)) => {
+            let api = sub_m.get_flag("api");
+            match sub_m.get_one::<String>("tag") {
+                Some(tag) => for_each_tagged(tag, "verify", |name| verify_account(name, api)),
+                None => verify_account(sub_m.get_one::<String>("name").unwrap(), api),
+            }
+        }
+        Some(("edit", sub_m)) => {
+            if sub_m.get_flag("all") {
+                edit_all_accounts()
+            } else {
+                let name = sub_m.get_one::<String>("name").unwrap();
+                let edit = AccountEdit {
+                    email: sub_m.get_one::<String>("email").cloned(),
+                    username: sub_m.get_one::<String>("username").cloned(),
+                    ssh_key: sub_m.get_one::<String>("ssh-key").cloned(),
+                    ssh_options: sub_m
+                        .get_many::<(String, String)>("ssh-option")
+                        .map(|options| options.cloned().collect())
+                        .unwrap_or_default(),
+                    git_config: sub_m
+                        .get_many::<(String, String)>("git-config")
+                        .map(|settings| settings.cloned().collect())
+                        .unwrap_or_default(),
+                    exclusive_key: sub_m.get_one::<bool>("exclusive-key").copied(),
+                    add_tags: sub_m
+                        .get_many::<String>("tag")
+                        .map(|tags| tags.cloned().collect())
+                        .unwrap_or_default(),
+                    remove_tags: sub_m
+                        .get_many::<String>("untag")
+                        .map(|tags| tags.cloned().collect())
+                        .unwrap_or_default(),
+                    add_orgs: sub_m
+                        .get_many::<String>("org")
+                        .map(|orgs| orgs.cloned().collect())
+                        .unwrap_or_default(),
+                    remove_orgs: sub_m
+                        .get_many::<String>("remove-org")
+                        .map(|orgs| orgs.cloned().collect())
+                        .unwrap_or_default(),
+                    provider: sub_m
+                        .get_one::<String>("provider")
+                        .map(|kind| if kind == "none" { "" } else { kind }.to_string()),
+                    hostname: sub_m.get_one::<String>("hostname").cloned(),
+                    port: sub_m.get_one::<u16>("port").copied(),
+                    ssh_user: sub_m.get_one::<String>("ssh-user").cloned(),
+                    proxy_jump: sub_m.get_one::<String>("proxy-jump").cloned(),
+                };
+                edit_account(name, edit)
+            }
+        }
+        Some(("key", sub_m)) => match sub_m.subcommand() {
+            Some(("passphrase", key_m)) => {
+                let name = key_m.get_one::<String>("name").unwrap();
+                change_passphrase(name)
+            }
+            Some(("show", key_m)) => show_key(key_m.get_one::<String>("name").unwrap()),
+            Some(("copy", key_m)) => copy_key(key_m.get_one::<String>("name").unwrap()),
+            Some(("upload", key_m)) => {
+                let name = key_m.get_one::<String>("name").unwrap();
+                upload_key(
+                    name,
+                    key_m.get_one::<String>("provider").map(String::as_str),
+                )
+            }
+            Some(("attest", key_m)) => {
+                let name = key_m.get_one::<String>("name").unwrap();
+                let output = key_m.get_one::<String>("output").map(String::as_str);
+                let import = key_m.get_one::<String>("import").map(String::as_str);
+                attest_key(name, output, import)
+            }
+            Some(("rotate", key_m)) => {
+                let options = RotateOptions {
+                    passphrase: if key_m.get_flag("passphrase-stdin") {
+                        Some(read_new_passphrase(true, "the new keys")?)
+                    } else if key_m.get_flag("passphrase") {
+                        Some(read_new_passphrase(false, "the new keys")?)
+                    } else {
+                        None
+                    },
+                    upload: key_m.get_flag("upload"),
+                };
+                match key_m.get_one::<String>("tag") {
+                    Some(tag) => {
+                        for_each_tagged(tag, "rotate the key of", |name| rotate_key(name, &options))
+                    }
+                    None => rotate_key(key_m.get_one::<String>("name").unwrap(), &options),
+                }
+            }
+            _ => Ok(()),
+        },
+        Some(("secret", sub_m)) => match sub_m.subcommand() {
+            Some((action, secret_m)) => {
+                let name = secret_m.get_one::<String>("name").unwrap();
+                let kind = secret_m.get_one::<String>("kind").unwrap();
+                let host = secret_m.get_one::<String>("host").map(String::as_str);
+                if action == "set" {
+                    set_secret(name, kind, host, secret_m.get_flag("stdin"))
+                } else {
+                    remove_secret(name, kind, host)
+                }
+            }
+            _ => Ok(()),
+        },
+        Some(("pending", sub_m)) => match sub_m.subcommand_name() {
+            Some("list") => {
+                pending::list_pending();
+                Ok(())
+            }
+            Some("retry") => retry_pending(),
+            _ => Ok(()),
+        },
+        Some(("map", sub_m)) => {
+            if sub_m.get_flag("list") {
+                list_directory_mappings();
+                Ok(())
+            } else {
+                let dir = sub_m.get_one::<String>("dir").unwrap();
+                match sub_m.get_one::<String>("account") {
+                    Some(account) => map_directory(dir, account),
+                    None => {
+                        unmap_directory(dir);
+                        Ok(())
+                    }
+                }
+            }
+        }
+        Some(("rule", sub_m)) => match sub_m.subcommand() {
+            Some(("add", rule_m)) => add_rule(
+                rule_m.get_one::<String>("pattern").unwrap(),
+                rule_m.get_one::<String>("account").unwrap(),
+            ),
+            Some(("remove", rule_m)) => remove_rule(rule_m.get_one::<String>("pattern").unwrap()),
+            _ => list_rules(),
+        },
+        Some(("auto", sub_m)) => auto_use(sub_m.get_flag("silent")),
+        Some(("scan", sub_m)) => {
+            let dirs: Vec<String> = sub_m
+                .get_many::<String>("dirs")
+                .map(|dirs| dirs.cloned().collect())
+                .unwrap_or_default();
+            let fix = sub_m.get_flag("fix").then_some(ScanFix {
+                rewrite_remotes: sub_m.get_flag("rewrite-remotes"),
+                interactive: sub_m.get_flag("interactive"),
+            });
+            scan_repositories(&dirs, fix)
+        }
+        Some(("fix", sub_m)) => {
+            let dirs: Vec<String> = sub_m
+                .get_many::<String>("dirs")
+                .map(|dirs| dirs.cloned().collect())
+                .unwrap_or_default();
+            fix_repositories(
+                &dirs,
+                FixOptions {
+                    rewrite_remotes: sub_m.get_flag("rewrite-remotes"),
+                    dry_run: sub_m.get_flag("dry-run"),
+                    yes: sub_m.get_flag("yes"),
+                },
+            )
+        }
+        Some(("hook", sub_m)) => match sub_m.subcommand() {
+            Some(("install", hook_m)) => install_commit_hook(
+                hook_m.get_one::<String>("type").unwrap(),
+                hook_m.get_flag("force"),
+            ),
+            Some(("uninstall", hook_m)) => {
+                uninstall_commit_hook(hook_m.get_one::<String>("type").unwrap())
+            }
+            Some(("shell", hook_m)) => {
+                print_auto_switch_hook(hook_m.get_one::<String>("shell").unwrap())
+            }
+            _ => check_commit_identity(),
+        },
+        Some(("check", _)) => check_manifest(),
+        Some(("render", sub_m)) => {
+            let name = sub_m.get_one::<String>("name").unwrap();
+            let target = sub_m.get_one::<String>("target").unwrap();
+            render_account(name, target)
+        }
+        Some(("direnv", sub_m)) => direnv(
+            sub_m.get_one::<String>("name").unwrap(),
+            sub_m.get_one::<String>("write").map(String::as_str),
+        ),
+        Some(("clone", sub_m)) => {
             let name = sub_m.get_one::<String>("name").unwrap();
-            remove_account(name);
+            let repo = sub_m.get_one::<String>("repo").map(String::as_str);
+            let dir = sub_m.get_one::<String>("dir").map(String::as_str);
+            if rules::parse_repository(name).is_some() {
+                clone_by_rule(name, repo.or(dir), switch_mode(sub_m))
+            } else if let Some(repo) = repo {
+                clone_repo(name, repo, dir, switch_mode(sub_m))
+            } else {
+                Err(GitSwitchError::InvalidInput(
+                    "Name the repository to clone, or give its URL to pick the account by rule."
+                        .to_string(),
+                ))
+            }
+        }
+        Some(("env", sub_m)) => {
+            output::messages_to_stderr();
+            print_account_env(sub_m.get_one::<String>("name").unwrap())
+        }
+        Some(("config", sub_m)) => {
+            let key = |m: &clap::ArgMatches| m.get_one::<String>("key").cloned();
+            match sub_m.subcommand() {
+                Some(("get", m)) => manage_settings(key(m).as_deref(), None),
+                Some(("set", m)) => manage_settings(
+                    key(m).as_deref(),
+                    m.get_one::<String>("value").map(String::as_str),
+                ),
+                Some(("unset", m)) => manage_settings(key(m).as_deref(), Some("")),
+                Some(("list", _)) => manage_settings(None, None),
+                _ => manage_settings(
+                    key(sub_m).as_deref(),
+                    sub_m.get_one::<String>("value").map(String::as_str),
+                ),
+            }
+        }
+        Some(("cd", sub_m)) => {
+            let name = sub_m.get_one::<String>("name").unwrap();
+            print_workspace(name)
+        }
+        Some(("version", sub_m)) => {
+            print_version(sub_m.get_count("verbose") > 0);
+            Ok(())
+        }
+        Some(("doctor", sub_m)) => run_doctor(*sub_m.get_one::<u32>("token-expiry-days").unwrap()),
+        Some(("credential", sub_m)) => {
+            let operation = sub_m.get_one::<String>("operation").unwrap();
+            credential_helper(
+                operation,
+                sub_m.get_one::<String>("account").map(String::as_str),
+            );
+            Ok(())
+        }
+        Some(("init", sub_m)) => {
+            let shell = sub_m.get_one::<String>("shell").unwrap();
+            print_shell_hook(shell)
+        }
+        Some(("completions", sub_m)) => {
+            let shell = sub_m.get_one::<String>("shell").unwrap();
+            generate_completions(shell, &mut build_cli());
+            Ok(())
         }
         _ => {
             println!("Use 'git-switch --help' to see available commands.");
+            Ok(())
         }
     }
 }
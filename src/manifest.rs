@@ -0,0 +1,115 @@
+//! Repository manifest (`.git-switch.toml`) declaring which identities may
+//! be used in a repository, without naming the identity itself:
+//!
+//! ```toml
+//! host = "github.com"
+//! org = "mycompany"
+//! email_domains = ["mycompany.com"]
+//! require_signing = true
+//! ```
+
+use crate::config::Account;
+use crate::git::RemoteUrl;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+pub const MANIFEST_FILE: &str = ".git-switch.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Git host the repository lives on; accounts must use it.
+    pub host: Option<String>,
+    /// Organization (first path segment) the remote must belong to.
+    pub org: Option<String>,
+    /// Allowed domains for the committer email.
+    #[serde(default)]
+    pub email_domains: Vec<String>,
+    /// Whether commits must be signed.
+    #[serde(default)]
+    pub require_signing: bool,
+}
+
+/// Reads the manifest at the root of `repo`. Returns `Ok(None)` when the
+/// repository has none.
+pub fn load_manifest(repo: &Path) -> Result<Option<Manifest>, String> {
+    let path = repo.join(MANIFEST_FILE);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    toml::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+impl Manifest {
+    /// Lists the ways an account doesn't meet the manifest.
+    pub fn account_violations(&self, account: &Account) -> Vec<String> {
+        let mut violations = Vec::new();
+        if let Some(host) = &self.host
+            && !account.hosts().contains(&host.as_str())
+        {
+            violations.push(format!(
+                "account '{}' is not set up for {}",
+                account.name, host
+            ));
+        }
+        if !self.email_allowed(&account.email) {
+            violations.push(format!(
+                "email {} is not in an allowed domain ({})",
+                account.email,
+                self.email_domains.join(", ")
+            ));
+        }
+        if self.require_signing && account.signing_key.is_none() {
+            violations.push(format!(
+                "commits must be signed, but account '{}' has no signing key",
+                account.name
+            ));
+        }
+        violations
+    }
+
+    /// Checks a remote URL against the manifest's org. Host aliases are
+    /// accepted in place of the host itself.
+    pub fn remote_violation(&self, url: &str) -> Option<String> {
+        let org = self.org.as_ref()?;
+        let remote = RemoteUrl::parse(url)?;
+        (!remote.owner().eq_ignore_ascii_case(org))
+            .then(|| format!("remote {} does not belong to the {} organization", url, org))
+    }
+
+    pub fn email_allowed(&self, email: &str) -> bool {
+        let domain = email.rsplit('@').next().unwrap_or_default();
+        self.email_domains.is_empty()
+            || self
+                .email_domains
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(domain))
+    }
+
+    /// Accounts that satisfy the manifest.
+    pub fn compliant_accounts<'a>(&self, accounts: &'a [Account]) -> Vec<&'a Account> {
+        accounts
+            .iter()
+            .filter(|acc| self.account_violations(acc).is_empty())
+            .collect()
+    }
+
+    /// A suggested `git-switch add` invocation for contributors without a
+    /// compliant account.
+    pub fn add_hint(&self) -> String {
+        let mut hint = String::from("git-switch add <name> <username> <email>");
+        if let Some(domain) = self.email_domains.first() {
+            hint = format!("git-switch add <name> <username> <you>@{}", domain);
+        }
+        if let Some(host) = &self.host {
+            hint.push_str(&format!(" --host {}", host));
+        }
+        if self.require_signing {
+            hint.push_str(" --signing-format ssh");
+        }
+        hint
+    }
+}
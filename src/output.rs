@@ -0,0 +1,167 @@
+//! How commands talk to the terminal: progress messages that follow
+//! `--quiet`/`--verbose` and `NO_COLOR`, and output formats for scripts.
+//!
+//! Messages start with an emoji on a terminal. When the stream is piped,
+//! `NO_COLOR` is set or the `emoji`/`color` settings are off, the emoji is
+//! dropped so scripts and logs get plain text; ⚠️ and ❌ become `warning:`
+//! and `error:`.
+
+use crate::utils::{Verbosity, verbosity};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static MESSAGES_TO_STDERR: AtomicBool = AtomicBool::new(false);
+static NO_EMOJI: AtomicBool = AtomicBool::new(false);
+static NO_COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Applies the `emoji` and `color` settings for the rest of the process.
+pub fn configure(emoji: bool, color: bool) {
+    NO_EMOJI.store(!emoji, Ordering::Relaxed);
+    NO_COLOR.store(!color, Ordering::Relaxed);
+}
+
+/// Sends [`say!`] and [`report!`] to stderr for the rest of the process,
+/// for commands whose stdout is meant for `eval` (`use --print-env`, `env`).
+pub fn messages_to_stderr() {
+    MESSAGES_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// Prints a progress message on stdout, unless `--quiet` is given.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::output::say(format_args!($($arg)*))
+    };
+}
+
+/// Prints a line of a command's results (`status`, `show`, `doctor`) on
+/// stdout. Unlike [`say!`], `--quiet` keeps it.
+#[macro_export]
+macro_rules! report {
+    ($($arg:tt)*) => {
+        $crate::output::report(format_args!($($arg)*))
+    };
+}
+
+/// Prints a warning or error on stderr; `--quiet` doesn't hide these.
+#[macro_export]
+macro_rules! problem {
+    ($($arg:tt)*) => {
+        $crate::output::problem(format_args!($($arg)*))
+    };
+}
+
+/// Prints a detail on stderr for `-vv`, such as a file written or a
+/// command run to read something.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::output::debug(format_args!($($arg)*))
+    };
+}
+
+pub fn say(message: fmt::Arguments) {
+    if verbosity() > Verbosity::Quiet {
+        report(message);
+    }
+}
+
+pub fn report(message: fmt::Arguments) {
+    if MESSAGES_TO_STDERR.load(Ordering::Relaxed) {
+        problem(message);
+    } else {
+        println!(
+            "{}",
+            decorate(&message.to_string(), io::stdout().is_terminal())
+        );
+    }
+}
+
+pub fn problem(message: fmt::Arguments) {
+    eprintln!(
+        "{}",
+        decorate(&message.to_string(), io::stderr().is_terminal())
+    );
+}
+
+pub fn debug(message: fmt::Arguments) {
+    if verbosity() >= Verbosity::Debug {
+        eprintln!("· {}", message);
+    }
+}
+
+/// Whether `NO_COLOR` (https://no-color.org) asks for undecorated output:
+/// set to anything but the empty string. The `color` setting turned off
+/// counts too.
+pub fn no_color() -> bool {
+    NO_COLOR.load(Ordering::Relaxed)
+        || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+fn decorate(message: &str, terminal: bool) -> Cow<'_, str> {
+    if terminal && !no_color() && !NO_EMOJI.load(Ordering::Relaxed) {
+        Cow::Borrowed(message)
+    } else {
+        undecorate(message)
+    }
+}
+
+/// Drops the emoji (and the space after it) a message starts with, keeping
+/// any leading line breaks and indentation. Warning and error signs turn
+/// into words, since they carry the message's meaning.
+pub fn undecorate(message: &str) -> Cow<'_, str> {
+    let body = message.trim_start_matches([' ', '\n']);
+    let indent = &message[..message.len() - body.len()];
+    let Some((first, rest)) = body.split_once(' ') else {
+        return Cow::Borrowed(message);
+    };
+    if !first.is_empty() && first.chars().all(is_symbol) {
+        let label = match first.trim_end_matches('\u{fe0f}') {
+            "⚠" => "warning: ",
+            "❌" => "error: ",
+            _ => "",
+        };
+        Cow::Owned(format!("{}{}{}", indent, label, rest))
+    } else {
+        Cow::Borrowed(message)
+    }
+}
+
+/// Emoji and the dingbats used like them; ℹ counts as a letter otherwise.
+fn is_symbol(c: char) -> bool {
+    !c.is_ascii() && (!c.is_alphanumeric() || ('\u{2100}'..='\u{2bff}').contains(&c))
+}
+
+/// How a command renders its results on stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    /// Human-readable text.
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    pub const NAMES: [&'static str; 3] = ["table", "json", "csv"];
+
+    pub fn parse(name: &str) -> Option<OutputFormat> {
+        match name {
+            "table" => Some(OutputFormat::Table),
+            "json" => Some(OutputFormat::Json),
+            "csv" => Some(OutputFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break.
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,170 @@
+//! Persistent queue of provider actions that failed and should be retried,
+//! stored in `~/.git-switch/pending.toml`.
+
+use crate::config::get_data_dir;
+use crate::report;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "provider-api")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Upper bound on provider requests `pending retry` runs at once.
+#[cfg(feature = "provider-api")]
+pub const MAX_CONCURRENT_ACTIONS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PendingKind {
+    /// Upload the account's public key to its Git host.
+    UploadKey,
+}
+
+impl PendingKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingKind::UploadKey => "upload-key",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingAction {
+    pub kind: PendingKind,
+    pub account: String,
+    /// Unix time the action was first queued.
+    pub queued_at: u64,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PendingQueue {
+    #[serde(default, rename = "action")]
+    actions: Vec<PendingAction>,
+}
+
+pub fn pending_path() -> PathBuf {
+    get_data_dir().join("pending.toml")
+}
+
+pub(crate) fn load_pending_from_path(path: &Path) -> Vec<PendingAction> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml::from_str::<PendingQueue>(&content).ok())
+        .map(|queue| queue.actions)
+        .unwrap_or_default()
+}
+
+/// Writes the queue, removing the file once it is empty.
+pub(crate) fn save_pending_to_path(actions: &[PendingAction], path: &Path) -> io::Result<()> {
+    if actions.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let queue = PendingQueue {
+        actions: actions.to_vec(),
+    };
+    let content = toml::to_string_pretty(&queue).map_err(io::Error::other)?;
+    // Write a temporary file first so an interrupted write keeps the old queue
+    let temp = path.with_extension("toml.tmp");
+    fs::write(&temp, content)?;
+    fs::rename(&temp, path)
+}
+
+/// Queues an action, or records the new error on the existing one; the
+/// queue holds at most one action of each kind per account.
+#[cfg(feature = "provider-api")]
+pub(crate) fn enqueue_in_path(
+    kind: PendingKind,
+    account: &str,
+    error: &str,
+    path: &Path,
+) -> io::Result<()> {
+    let mut actions = load_pending_from_path(path);
+    match actions
+        .iter_mut()
+        .find(|action| action.kind == kind && action.account == account)
+    {
+        Some(action) => {
+            action.attempts += 1;
+            action.last_error = Some(error.to_string());
+        }
+        None => actions.push(PendingAction {
+            kind,
+            account: account.to_string(),
+            queued_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+            attempts: 1,
+            last_error: Some(error.to_string()),
+        }),
+    }
+    save_pending_to_path(&actions, path)
+}
+
+pub fn load_pending() -> Vec<PendingAction> {
+    load_pending_from_path(&pending_path())
+}
+
+pub fn save_pending(actions: &[PendingAction]) -> io::Result<()> {
+    save_pending_to_path(actions, &pending_path())
+}
+
+#[cfg(feature = "provider-api")]
+pub fn enqueue(kind: PendingKind, account: &str, error: &str) -> io::Result<()> {
+    enqueue_in_path(kind, account, error, &pending_path())
+}
+
+/// Points queued actions of a renamed account at its new name, or drops
+/// them when `new_name` is `None` (the account was removed).
+pub fn rename_account_actions(old_name: &str, new_name: Option<&str>) -> io::Result<()> {
+    let actions = load_pending();
+    if !actions.iter().any(|action| action.account == old_name) {
+        return Ok(());
+    }
+    let updated: Vec<PendingAction> = actions
+        .into_iter()
+        .filter_map(|mut action| {
+            if action.account != old_name {
+                return Some(action);
+            }
+            action.account = new_name?.to_string();
+            Some(action)
+        })
+        .collect();
+    save_pending(&updated)
+}
+
+/// Prints the queued actions.
+pub fn list_pending() {
+    let actions = load_pending();
+    if actions.is_empty() {
+        report!("✅ No pending actions.");
+        return;
+    }
+    report!("⏳ Pending actions:");
+    for action in &actions {
+        println!(
+            "  {} {} ({} attempt(s)){}",
+            action.kind.as_str(),
+            action.account,
+            action.attempts,
+            action
+                .last_error
+                .as_ref()
+                .map(|error| format!(": {}", error))
+                .unwrap_or_default()
+        );
+    }
+    println!("Run `git-switch pending retry` to try them again.");
+}
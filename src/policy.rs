@@ -0,0 +1,145 @@
+//! Machine-level policy provisioned by an administrator.
+//!
+//! The policy file is TOML with a list of `[[rule]]` tables. A rule applies
+//! to an account when all of its conditions match:
+//!
+//! ```toml
+//! [[rule]]
+//! email_domains = ["gmail.com"]
+//! action = "acknowledge"
+//! message = "Personal accounts must not be used for company code."
+//!
+//! [[rule]]
+//! key_older_than_days = 365
+//! message = "Rotate SSH keys at least once a year."
+//!
+//! [[rule]]
+//! email_domains = ["corp.com"]
+//! require_attestation = true
+//! message = "Company keys must live on a security key."
+//! ```
+
+use crate::config::Account;
+use crate::problem;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Print the message and carry on.
+    #[default]
+    Warn,
+    /// Refuse to switch unless the user passes `--acknowledge`.
+    Acknowledge,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Rule {
+    /// Account names the rule applies to.
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// Email domains the rule applies to (e.g. `gmail.com`).
+    #[serde(default)]
+    pub email_domains: Vec<String>,
+    /// Applies when the account's SSH key is older than this many days.
+    #[serde(default)]
+    pub key_older_than_days: Option<u64>,
+    /// Applies when the account has no recorded hardware key attestation.
+    #[serde(default)]
+    pub require_attestation: bool,
+    #[serde(default)]
+    pub action: Action,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Policy {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+/// Location of the policy file: `$GIT_SWITCH_POLICY`, or the system-wide
+/// default (`/etc/git-switch/policy.toml`, `%ProgramData%\git-switch\policy.toml`).
+pub fn policy_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("GIT_SWITCH_POLICY") {
+        return PathBuf::from(path);
+    }
+    if cfg!(windows) {
+        std::env::var_os("ProgramData")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+            .join("git-switch")
+            .join("policy.toml")
+    } else {
+        PathBuf::from("/etc/git-switch/policy.toml")
+    }
+}
+
+/// Loads the machine policy. A missing file means no rules; an unreadable
+/// one is reported and ignored.
+pub fn load_policy() -> Policy {
+    let path = policy_path();
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Policy::default();
+    };
+    parse_policy(&content).unwrap_or_else(|e| {
+        problem!("⚠️ Ignoring invalid policy file {}: {}", path.display(), e);
+        Policy::default()
+    })
+}
+
+pub fn parse_policy(content: &str) -> Result<Policy, String> {
+    toml::from_str(content).map_err(|e| e.to_string())
+}
+
+/// Age of the account's SSH key in whole days, from the key file's mtime.
+pub fn key_age_days(account: &Account) -> Option<u64> {
+    let modified = fs::metadata(&*shellexpand::tilde(&account.ssh_key))
+        .and_then(|meta| meta.modified())
+        .ok()?;
+    let age = SystemTime::now().duration_since(modified).ok()?;
+    Some(age.as_secs() / 86_400)
+}
+
+impl Rule {
+    /// A rule matches when it has at least one condition and all of them hold.
+    pub fn matches(&self, account: &Account, key_age_days: Option<u64>) -> bool {
+        let mut conditions = Vec::new();
+        if !self.accounts.is_empty() {
+            conditions.push(
+                self.accounts
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(&account.name)),
+            );
+        }
+        if !self.email_domains.is_empty() {
+            let domain = account.email.rsplit('@').next().unwrap_or_default();
+            conditions.push(
+                self.email_domains
+                    .iter()
+                    .any(|d| d.eq_ignore_ascii_case(domain)),
+            );
+        }
+        if let Some(max_age) = self.key_older_than_days {
+            conditions.push(key_age_days.is_some_and(|age| age > max_age));
+        }
+        if self.require_attestation {
+            conditions.push(account.attestation.is_none());
+        }
+        !conditions.is_empty() && conditions.iter().all(|&met| met)
+    }
+}
+
+impl Policy {
+    /// Rules that apply to the account.
+    pub fn evaluate(&self, account: &Account) -> Vec<&Rule> {
+        let age = key_age_days(account);
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(account, age))
+            .collect()
+    }
+}
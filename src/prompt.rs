@@ -0,0 +1,70 @@
+//! The segment `git-switch prompt` prints for shell prompts: the active
+//! account from a template, green when it's the account the repository
+//! belongs to and red, with its own template, when it isn't.
+
+/// How the color codes are wrapped: `raw` for fish, starship and other
+/// prompts that measure escapes themselves; `bash` and `zsh` mark them as
+/// zero-width so the line editor doesn't miscount the prompt's length.
+pub const SHELLS: [&str; 3] = ["raw", "bash", "zsh"];
+
+pub const DEFAULT_TEMPLATE: &str = "{account}";
+pub const DEFAULT_MISMATCH_TEMPLATE: &str = "⚠ {account}≠{expected}";
+
+/// The placeholders templates may use.
+pub const PLACEHOLDERS: [&str; 4] = ["account", "username", "email", "expected"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PromptState {
+    /// The active account is the repository's.
+    Match,
+    /// The repository belongs to another account than the active one.
+    Mismatch,
+    /// Outside a repository, or no account could be associated with it.
+    Unmapped,
+}
+
+impl PromptState {
+    fn color(self) -> Option<&'static str> {
+        match self {
+            PromptState::Match => Some("32"),
+            PromptState::Mismatch => Some("1;31"),
+            PromptState::Unmapped => None,
+        }
+    }
+}
+
+/// Replaces `{placeholder}`s in the template; unknown ones stay as written.
+pub fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut text = template.to_string();
+    for (name, value) in values {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+/// Colors the text for the state, wrapping the escapes for `shell`. With
+/// `color` false only zsh's `%` is escaped.
+pub fn colorize(text: &str, state: PromptState, shell: &str, color: bool) -> String {
+    // zsh expands prompt escapes in command substitution output
+    let text = if shell == "zsh" {
+        text.replace('%', "%%")
+    } else {
+        text.to_string()
+    };
+    let Some(color) = state.color().filter(|_| color) else {
+        return text;
+    };
+    let (open, close) = match shell {
+        // Readline's markers, which also work in `$(...)` output
+        "bash" => ("\x01", "\x02"),
+        "zsh" => ("%{", "%}"),
+        _ => ("", ""),
+    };
+    format!(
+        "{open}\x1b[{color}m{close}{text}{open}\x1b[0m{close}",
+        open = open,
+        close = close,
+        color = color,
+        text = text
+    )
+}
@@ -0,0 +1,830 @@
+//! Conventions of well-known Git hosts, and their REST APIs (built with the
+//! `provider-api` feature).
+//!
+//! API requests go through `curl`; the token is passed in curl's config on
+//! stdin so it never shows up in `ps` or the event log.
+
+use crate::config::Account;
+#[cfg(feature = "provider-api")]
+use crate::events;
+#[cfg(feature = "provider-api")]
+use crate::secrets::{self, Secret};
+#[cfg(feature = "provider-api")]
+use crate::tokens::{self, TokenInfo};
+#[cfg(feature = "provider-api")]
+use crate::utils::{command_output, hostname, now_secs};
+#[cfg(feature = "provider-api")]
+use std::fs;
+#[cfg(feature = "provider-api")]
+use std::io::Write;
+#[cfg(feature = "provider-api")]
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// The API used to register SSH keys with a provider.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyApi {
+    GitHub,
+    GitLab,
+    /// Gitea and Forgejo (Codeberg).
+    Gitea,
+    /// SourceHut's meta.sr.ht GraphQL API.
+    SourceHut,
+    /// Bitbucket Cloud, with an app password or API token.
+    Bitbucket,
+}
+
+/// How a Git host names things and greets SSH connections.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "provider-api"), allow(dead_code))]
+pub struct Provider {
+    pub name: &'static str,
+    pub host: &'static str,
+    /// User for SSH connections.
+    pub ssh_user: &'static str,
+    /// Prefix of user namespaces in repository paths (`~` on SourceHut).
+    pub owner_prefix: &'static str,
+    /// Suffix of repository paths in clone URLs.
+    pub repo_suffix: &'static str,
+    pub key_settings_url: &'static str,
+    /// Text before the username in the `ssh -T` greeting, and the character
+    /// that ends it.
+    pub greeting: (&'static str, char),
+    /// Server that takes SSH on port 443, for networks that block port 22.
+    pub ssh_443: Option<&'static str>,
+    pub key_api: Option<KeyApi>,
+    pub api_base: &'static str,
+    /// Environment variables checked for an API token.
+    pub token_vars: &'static [&'static str],
+}
+
+pub const PROVIDERS: [Provider; 5] = [
+    Provider {
+        name: "GitHub",
+        host: "github.com",
+        ssh_user: "git",
+        owner_prefix: "",
+        repo_suffix: ".git",
+        key_settings_url: "https://github.com/settings/keys",
+        greeting: ("Hi ", '!'),
+        ssh_443: Some("ssh.github.com"),
+        key_api: Some(KeyApi::GitHub),
+        api_base: "https://api.github.com",
+        token_vars: &["GITHUB_TOKEN", "GH_TOKEN"],
+    },
+    Provider {
+        name: "GitLab",
+        host: "gitlab.com",
+        ssh_user: "git",
+        owner_prefix: "",
+        repo_suffix: ".git",
+        key_settings_url: "https://gitlab.com/-/user_settings/ssh_keys",
+        greeting: ("Welcome to GitLab, @", '!'),
+        ssh_443: Some("altssh.gitlab.com"),
+        key_api: Some(KeyApi::GitLab),
+        api_base: "https://gitlab.com/api/v4",
+        token_vars: &["GITLAB_TOKEN"],
+    },
+    Provider {
+        name: "Bitbucket",
+        host: "bitbucket.org",
+        ssh_user: "git",
+        owner_prefix: "",
+        repo_suffix: ".git",
+        key_settings_url: "https://bitbucket.org/account/settings/ssh-keys/",
+        greeting: ("logged in as ", '.'),
+        ssh_443: Some("altssh.bitbucket.org"),
+        key_api: Some(KeyApi::Bitbucket),
+        api_base: "https://api.bitbucket.org/2.0",
+        token_vars: &["BITBUCKET_TOKEN", "BITBUCKET_APP_PASSWORD"],
+    },
+    Provider {
+        name: "Codeberg",
+        host: "codeberg.org",
+        ssh_user: "git",
+        owner_prefix: "",
+        repo_suffix: ".git",
+        key_settings_url: "https://codeberg.org/user/settings/keys",
+        greeting: ("Hi there, ", '!'),
+        ssh_443: None,
+        key_api: Some(KeyApi::Gitea),
+        api_base: "https://codeberg.org/api/v1",
+        token_vars: &["CODEBERG_TOKEN", "GITEA_TOKEN"],
+    },
+    Provider {
+        name: "SourceHut",
+        host: "git.sr.ht",
+        ssh_user: "git",
+        owner_prefix: "~",
+        repo_suffix: "",
+        key_settings_url: "https://meta.sr.ht/keys",
+        greeting: ("Hi ~", '!'),
+        ssh_443: None,
+        key_api: Some(KeyApi::SourceHut),
+        api_base: "https://meta.sr.ht/query",
+        token_vars: &["SRHT_TOKEN"],
+    },
+];
+
+/// Returns the known provider for `host`, if any.
+pub fn provider_for(host: &str) -> Option<&'static Provider> {
+    PROVIDERS
+        .iter()
+        .find(|provider| provider.host.eq_ignore_ascii_case(host))
+}
+
+/// SSH user for `host`: the provider's, or `git` for unknown hosts.
+pub fn ssh_user(host: &str) -> &'static str {
+    provider_for(host).map_or("git", |provider| provider.ssh_user)
+}
+
+/// Kinds of self-hosted servers an account's `provider` can name.
+pub const PROVIDER_KINDS: [&str; 3] = ["github", "gitlab", "gitea"];
+
+/// Providers made for self-hosted hosts, kept for the rest of the process
+/// like the built-in ones so each is only made once.
+static SELF_HOSTED: Mutex<Vec<&'static Provider>> = Mutex::new(Vec::new());
+
+/// The provider for a self-hosted server of `kind` (one of
+/// [`PROVIDER_KINDS`]) at `host`: GitHub Enterprise Server, a GitLab
+/// instance, or Gitea/Forgejo.
+pub fn self_hosted(kind: &str, host: &str) -> Option<&'static Provider> {
+    let (name, api_path, settings_path, greeting, token_vars, key_api): (
+        &'static str,
+        &str,
+        &str,
+        (&'static str, char),
+        &'static [&'static str],
+        KeyApi,
+    ) = match kind {
+        "github" => (
+            "GitHub Enterprise",
+            "api/v3",
+            "settings/keys",
+            ("Hi ", '!'),
+            &["GH_ENTERPRISE_TOKEN", "GITHUB_ENTERPRISE_TOKEN"],
+            KeyApi::GitHub,
+        ),
+        "gitlab" => (
+            "GitLab",
+            "api/v4",
+            "-/user_settings/ssh_keys",
+            ("Welcome to GitLab, @", '!'),
+            &["GITLAB_TOKEN"],
+            KeyApi::GitLab,
+        ),
+        "gitea" => (
+            "Gitea",
+            "api/v1",
+            "user/settings/keys",
+            ("Hi there, ", '!'),
+            &["GITEA_TOKEN"],
+            KeyApi::Gitea,
+        ),
+        _ => return None,
+    };
+    let host = host.to_ascii_lowercase();
+    let mut known = SELF_HOSTED.lock().ok()?;
+    if let Some(provider) = known
+        .iter()
+        .find(|provider| provider.host == host && provider.name == name)
+    {
+        return Some(provider);
+    }
+    let leak = |text: String| -> &'static str { Box::leak(text.into_boxed_str()) };
+    let provider: &'static Provider = Box::leak(Box::new(Provider {
+        name,
+        host: leak(host.clone()),
+        ssh_user: "git",
+        owner_prefix: "",
+        repo_suffix: ".git",
+        key_settings_url: leak(format!("https://{}/{}", host, settings_path)),
+        greeting,
+        ssh_443: None,
+        key_api: Some(key_api),
+        api_base: leak(format!("https://{}/{}", host, api_path)),
+        token_vars,
+    }));
+    known.push(provider);
+    Some(provider)
+}
+
+/// The provider of one of the account's hosts: the well-known one, or a
+/// self-hosted server of the account's `provider` kind.
+pub fn account_provider(account: &Account, host: &str) -> Option<&'static Provider> {
+    provider_for(host).or_else(|| self_hosted(account.provider.as_deref()?, host))
+}
+
+impl Provider {
+    /// Extracts the authenticated username from `ssh -T` output.
+    pub fn greeting_user(&self, output: &str) -> Option<String> {
+        let (prefix, end) = self.greeting;
+        let start = output.find(prefix)? + prefix.len();
+        let rest = &output[start..];
+        let user = &rest[..rest.find(end)?];
+        (!user.is_empty() && !user.contains(char::is_whitespace)).then(|| user.to_string())
+    }
+}
+
+/// The user an `ssh -T` greeting from the host names. Self-hosted servers
+/// are matched against every known provider's greeting.
+pub fn authenticated_user(host: &str, output: &str) -> Option<String> {
+    match provider_for(host) {
+        Some(provider) => provider.greeting_user(output),
+        None => PROVIDERS
+            .iter()
+            .find_map(|provider| provider.greeting_user(output)),
+    }
+}
+
+/// Title of the uploaded key, e.g. `git-switch work@laptop`.
+#[cfg(feature = "provider-api")]
+pub fn key_title(account: &Account, hostname: &str) -> String {
+    format!("git-switch {}@{}", account.name, hostname)
+}
+
+/// Splits curl output written with `-w "\n%{http_code}"` into status and body.
+#[cfg(feature = "provider-api")]
+pub fn split_status(output: &str) -> (u16, &str) {
+    let (body, code) = output.rsplit_once('\n').unwrap_or(("", output));
+    (code.trim().parse().unwrap_or(0), body)
+}
+
+/// A provider's own CLI, whose login can lend its token so there's no
+/// separate token to create.
+#[derive(Debug)]
+#[cfg(feature = "provider-api")]
+pub struct AuthCli {
+    pub program: &'static str,
+    pub key_api: KeyApi,
+    /// The command that logs the CLI in.
+    pub login: &'static str,
+    /// Arguments that print the token, tried in order; `{host}` and
+    /// `{user}` are filled in.
+    attempts: &'static [&'static [&'static str]],
+}
+
+#[cfg(feature = "provider-api")]
+pub const AUTH_CLIS: [AuthCli; 2] = [
+    AuthCli {
+        program: "gh",
+        key_api: KeyApi::GitHub,
+        login: "gh auth login",
+        attempts: &[
+            &["auth", "token", "--hostname", "{host}", "--user", "{user}"],
+            &["auth", "token", "--hostname", "{host}"],
+        ],
+    },
+    AuthCli {
+        program: "glab",
+        key_api: KeyApi::GitLab,
+        login: "glab auth login",
+        attempts: &[&["config", "get", "token", "--host", "{host}"]],
+    },
+];
+
+/// The CLI that can lend tokens for the API, if any.
+#[cfg(feature = "provider-api")]
+pub fn auth_cli_for(api: KeyApi) -> Option<&'static AuthCli> {
+    AUTH_CLIS.iter().find(|cli| cli.key_api == api)
+}
+
+#[cfg(feature = "provider-api")]
+impl AuthCli {
+    /// The argument lists to try for a token for `user` on `host`.
+    pub fn token_args(&self, host: &str, user: &str) -> Vec<Vec<String>> {
+        self.attempts
+            .iter()
+            .map(|args| {
+                args.iter()
+                    .map(|arg| arg.replace("{host}", host).replace("{user}", user))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The token the CLI is logged in with, if it's installed and logged in.
+    pub fn token(&self, host: &str, user: &str) -> Option<String> {
+        self.token_args(host, user).iter().find_map(|args| {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let output = command_output(self.program, &args).ok()?;
+            let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            (output.status.success() && !token.is_empty() && !token.contains(char::is_whitespace))
+                .then_some(token)
+        })
+    }
+}
+
+/// Token for the provider: the account's token in the OS keyring, then the
+/// provider's environment variables, then the token of the provider's CLI
+/// (`gh`, `glab`) if it's logged in. Returns the token and where it came from.
+#[cfg(feature = "provider-api")]
+pub(crate) fn api_token(provider: &Provider, account: &Account) -> Option<(String, String)> {
+    if let Some(token) = secrets::get(&account.name, Secret::Token(provider.host)) {
+        return Some((token, String::from("the OS keyring")));
+    }
+    let username = account.username.as_str();
+    for var in provider.token_vars {
+        if let Ok(token) = std::env::var(var)
+            && !token.trim().is_empty()
+        {
+            return Some((token.trim().to_string(), var.to_string()));
+        }
+    }
+    let cli = auth_cli_for(provider.key_api?)?;
+    let token = cli.token(provider.host, username)?;
+    Some((token, format!("the {} CLI", cli.program)))
+}
+
+/// Splits curl output written with `-i` into response headers (names
+/// lowercased) and the rest.
+#[cfg(feature = "provider-api")]
+pub fn split_headers(output: &str) -> (Vec<(String, String)>, &str) {
+    let (head, rest) = output
+        .split_once("\r\n\r\n")
+        .or_else(|| output.split_once("\n\n"))
+        .unwrap_or(("", output));
+    let headers = head
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    (headers, rest)
+}
+
+/// Status, headers (if requested) and body of an API response.
+#[cfg(feature = "provider-api")]
+type ApiResponse = (u16, Vec<(String, String)>, String);
+
+/// Sends an authenticated API request and returns status and body.
+#[cfg(feature = "provider-api")]
+fn api_request(
+    method: &str,
+    url: &str,
+    auth_header: &str,
+    body: Option<&str>,
+) -> Result<(u16, String), String> {
+    send_request(method, url, auth_header, body, false).map(|(status, _, body)| (status, body))
+}
+
+/// Sends an authenticated GET request and also returns the response headers.
+#[cfg(feature = "provider-api")]
+pub(crate) fn api_get_with_headers(url: &str, auth_header: &str) -> Result<ApiResponse, String> {
+    send_request("GET", url, auth_header, None, true)
+}
+
+#[cfg(feature = "provider-api")]
+fn send_request(
+    method: &str,
+    url: &str,
+    auth_header: &str,
+    body: Option<&str>,
+    include_headers: bool,
+) -> Result<ApiResponse, String> {
+    let mut args = vec![
+        "-sS",
+        "-w",
+        "\n%{http_code}",
+        "-K",
+        "-",
+        "-X",
+        method,
+        "-H",
+        "Accept: application/json",
+    ];
+    if let Some(body) = body {
+        args.extend(["-H", "Content-Type: application/json", "-d", body]);
+    }
+    if include_headers {
+        args.push("-i");
+    }
+    args.push(url);
+
+    let mut child = Command::new("curl")
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = writeln!(stdin, "header = \"{}\"", auth_header);
+    }
+    let output = child.wait_with_output();
+    events::subprocess(
+        "curl",
+        &[method, url],
+        output.as_ref().ok().and_then(|out| out.status.code()),
+    );
+    let output = output.map_err(|e| format!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (status, body) = split_status(&stdout);
+    if !include_headers {
+        return Ok((status, Vec::new(), body.to_string()));
+    }
+    let (headers, body) = split_headers(body);
+    Ok((status, headers, body.to_string()))
+}
+
+/// Adds the account's public key to its user on the first of its hosts
+/// with a supported key API. Returns a description of the uploaded key.
+///
+/// Refuses to upload when the token belongs to a different user than the
+/// account, since that would let the wrong account authenticate.
+#[cfg(feature = "provider-api")]
+pub fn upload_public_key(account: &Account) -> Result<String, String> {
+    let Some((provider, api)) = key_api_for(account) else {
+        return Err(format!(
+            "Key upload is not supported for {}; add the key on the host's settings page",
+            account.hosts().join(", ")
+        ));
+    };
+    let public_key_path = format!("{}.pub", shellexpand::tilde(&account.ssh_key));
+    let public_key = fs::read_to_string(&public_key_path)
+        .map_err(|e| format!("Failed to read {}: {}", public_key_path, e))?;
+    let public_key = public_key.trim();
+    let (token, source) =
+        api_token(provider, account).ok_or_else(|| no_token(provider, account))?;
+
+    if api == KeyApi::SourceHut {
+        return upload_sourcehut_key(provider, account, &token, public_key);
+    }
+
+    let info = inspect_token(provider, api, &token, &source)?;
+    check_token_user(provider, account, &info.login)?;
+    tokens::vet_for_upload(&info, api, provider.name)?;
+    let auth_header = auth_header(api, &token);
+
+    // Uploading is retried from the pending queue, so skip keys already there
+    let keys_url = match api {
+        KeyApi::Bitbucket => bitbucket_keys_url(provider, &auth_header)?,
+        _ => format!("{}/user/keys", provider.api_base),
+    };
+    if registered_keys(api, &keys_url, &auth_header)?
+        .is_some_and(|keys| keys.iter().any(|key| same_key(key, public_key)))
+    {
+        return Ok(format!("{} key (already registered)", provider.name));
+    }
+
+    let title = key_title(account, &hostname());
+    let payload = match api {
+        KeyApi::Bitbucket => serde_json::json!({ "label": title, "key": public_key }),
+        _ => serde_json::json!({ "title": title, "key": public_key }),
+    }
+    .to_string();
+    match api_request("POST", &keys_url, &auth_header, Some(&payload))? {
+        (201, _) => Ok(format!("{} key '{}'", provider.name, title)),
+        (400 | 422, body) if body.contains("already") => Err(format!(
+            "This key is already registered on a {} account",
+            provider.name
+        )),
+        (403 | 404, _) => Err(format!(
+            "The token cannot manage SSH keys on {}; {}",
+            provider.name,
+            match api {
+                KeyApi::GitHub => "it needs the admin:public_key (or write:public_key) scope",
+                KeyApi::GitLab => "it needs the api scope",
+                KeyApi::Bitbucket => "it needs the account:write permission",
+                _ => "it needs write access to the user's keys",
+            }
+        )),
+        (status, _) => Err(format!(
+            "{} rejected the key (HTTP {})",
+            provider.name, status
+        )),
+    }
+}
+
+/// The error for an account without a token for the provider.
+#[cfg(feature = "provider-api")]
+fn no_token(provider: &Provider, account: &Account) -> String {
+    format!(
+        "No {} token found. Store one with `git-switch secret set {} token`, or set {}{}.",
+        provider.name,
+        account.name,
+        provider.token_vars.join(" or "),
+        provider
+            .key_api
+            .and_then(auth_cli_for)
+            .map(|cli| format!(" or log in with `{}`", cli.login))
+            .unwrap_or_default()
+    )
+}
+
+/// The public keys listed at `keys_url`, or `None` if the provider
+/// wouldn't list them.
+#[cfg(feature = "provider-api")]
+fn registered_keys(
+    api: KeyApi,
+    keys_url: &str,
+    auth_header: &str,
+) -> Result<Option<Vec<String>>, String> {
+    let page_size = match api {
+        KeyApi::Gitea => "limit=50",
+        KeyApi::Bitbucket => "pagelen=100",
+        _ => "per_page=100",
+    };
+    let list_url = format!("{}?{}", keys_url, page_size);
+    let (200, body) = api_request("GET", &list_url, auth_header, None)? else {
+        return Ok(None);
+    };
+    Ok(listed_keys(&body))
+}
+
+/// The public keys in a key listing response, or `None` if it isn't one.
+#[cfg(feature = "provider-api")]
+pub(crate) fn listed_keys(body: &str) -> Option<Vec<String>> {
+    let listed = serde_json::from_str::<serde_json::Value>(body).ok()?;
+    // Bitbucket pages its results in `values`
+    listed
+        .as_array()
+        .or(listed["values"].as_array())
+        .map(|keys| {
+            keys.iter()
+                .filter_map(|key| key["key"].as_str().map(str::to_string))
+                .collect()
+        })
+}
+
+/// What the provider's API says about an account, for `verify --api`.
+#[cfg(feature = "provider-api")]
+#[derive(Debug)]
+pub struct ApiIdentity {
+    pub provider: &'static str,
+    /// The user the account's token belongs to.
+    pub login: String,
+    /// Whether the account's public key is among that user's keys; `None`
+    /// when the provider wouldn't list them.
+    pub key_registered: Option<bool>,
+}
+
+/// Asks the account's provider who its token belongs to and whether the
+/// account's key is registered to that user.
+#[cfg(feature = "provider-api")]
+pub fn api_identity(account: &Account) -> Result<ApiIdentity, String> {
+    let Some((provider, api)) = key_api_for(account) else {
+        return Err(format!(
+            "{} has no supported user API; verify over SSH instead",
+            account.hosts().join(", ")
+        ));
+    };
+    let (token, source) =
+        api_token(provider, account).ok_or_else(|| no_token(provider, account))?;
+    let info = inspect_token(provider, api, &token, &source)?;
+    let key_registered = match api {
+        // meta.sr.ht lists keys through GraphQL only
+        KeyApi::SourceHut => None,
+        _ => {
+            let auth_header = auth_header(api, &token);
+            let keys_url = match api {
+                KeyApi::Bitbucket => bitbucket_keys_url(provider, &auth_header)?,
+                _ => format!("{}/user/keys", provider.api_base),
+            };
+            let public_key_path = format!("{}.pub", shellexpand::tilde(&account.ssh_key));
+            match fs::read_to_string(&public_key_path) {
+                Ok(public_key) => registered_keys(api, &keys_url, &auth_header)?
+                    .map(|keys| keys.iter().any(|key| same_key(key, &public_key))),
+                Err(_) => None,
+            }
+        }
+    };
+    Ok(ApiIdentity {
+        provider: provider.name,
+        login: info.login,
+        key_registered,
+    })
+}
+
+/// The first of the account's hosts with a key upload API.
+#[cfg(feature = "provider-api")]
+pub fn key_api_for(account: &Account) -> Option<(&'static Provider, KeyApi)> {
+    account.hosts().into_iter().find_map(|host| {
+        let provider = account_provider(account, host)?;
+        Some((provider, provider.key_api?))
+    })
+}
+
+/// Bitbucket app passwords and API tokens are stored as `user:secret` and
+/// sent with basic authentication.
+#[cfg(feature = "provider-api")]
+fn auth_header(api: KeyApi, token: &str) -> String {
+    match api {
+        KeyApi::Gitea => format!("Authorization: token {}", token),
+        KeyApi::Bitbucket if token.contains(':') => {
+            format!("Authorization: Basic {}", base64(token.as_bytes()))
+        }
+        _ => format!("Authorization: Bearer {}", token),
+    }
+}
+
+/// Standard base64 with padding, for basic authentication.
+#[cfg(feature = "provider-api")]
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Bitbucket files SSH keys under the user's account id, which its `/user`
+/// endpoint tells.
+#[cfg(feature = "provider-api")]
+fn bitbucket_keys_url(provider: &Provider, auth_header: &str) -> Result<String, String> {
+    let (status, body) = api_request(
+        "GET",
+        &format!("{}/user", provider.api_base),
+        auth_header,
+        None,
+    )?;
+    let account_id = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|user| user["account_id"].as_str().map(str::to_string))
+        .ok_or(format!(
+            "{} rejected the token (HTTP {})",
+            provider.name, status
+        ))?;
+    Ok(format!(
+        "{}/users/{}/ssh-keys",
+        provider.api_base, account_id
+    ))
+}
+
+/// Asks the provider who the token belongs to and, where it tells, which
+/// scopes it has and when it expires.
+#[cfg(feature = "provider-api")]
+pub fn inspect_token(
+    provider: &Provider,
+    api: KeyApi,
+    token: &str,
+    source: &str,
+) -> Result<TokenInfo, String> {
+    let auth_header = auth_header(api, token);
+    let mut info = TokenInfo {
+        host: provider.host.to_string(),
+        source: source.to_string(),
+        fingerprint: tokens::fingerprint(token),
+        login: String::new(),
+        scopes: None,
+        expires_on: None,
+        checked_at: now_secs(),
+    };
+    if api == KeyApi::SourceHut {
+        let payload = serde_json::json!({ "query": "{ me { username } }" }).to_string();
+        let (status, body) = api_request("POST", provider.api_base, &auth_header, Some(&payload))?;
+        let response: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|_| format!("{} rejected the token (HTTP {})", provider.name, status))?;
+        info.login = response["data"]["me"]["username"]
+            .as_str()
+            .ok_or(format!(
+                "{} rejected the token (HTTP {})",
+                provider.name, status
+            ))?
+            .to_string();
+        return Ok(info);
+    }
+
+    let (status, headers, body) =
+        api_get_with_headers(&format!("{}/user", provider.api_base), &auth_header)?;
+    if status != 200 {
+        return Err(format!(
+            "{} rejected the token (HTTP {})",
+            provider.name, status
+        ));
+    }
+    let login_field = match api {
+        KeyApi::GitLab | KeyApi::Bitbucket => "username",
+        _ => "login",
+    };
+    info.login = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|user| user[login_field].as_str().map(str::to_string))
+        .unwrap_or_default();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.clone())
+    };
+    match api {
+        KeyApi::GitHub => {
+            // Only classic tokens list their scopes; fine-grained ones omit the header
+            info.scopes = header("x-oauth-scopes").map(|scopes| {
+                scopes
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|scope| !scope.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            });
+            info.expires_on = header("github-authentication-token-expiration")
+                .and_then(|date| date.get(..10).map(str::to_string));
+        }
+        KeyApi::GitLab => {
+            let url = format!("{}/personal_access_tokens/self", provider.api_base);
+            if let (200, body) = api_request("GET", &url, &auth_header, None)?
+                && let Ok(details) = serde_json::from_str::<serde_json::Value>(&body)
+            {
+                info.scopes = details["scopes"].as_array().map(|scopes| {
+                    scopes
+                        .iter()
+                        .filter_map(|scope| scope.as_str().map(str::to_string))
+                        .collect()
+                });
+                info.expires_on = details["expires_at"].as_str().map(str::to_string);
+            }
+        }
+        KeyApi::Gitea | KeyApi::SourceHut | KeyApi::Bitbucket => {}
+    }
+    Ok(info)
+}
+
+/// Whether two public key lines hold the same key, ignoring their comments.
+#[cfg(feature = "provider-api")]
+pub fn same_key(a: &str, b: &str) -> bool {
+    fn blob(key: &str) -> Vec<&str> {
+        key.split_whitespace().take(2).collect()
+    }
+    blob(a).len() == 2 && blob(a) == blob(b)
+}
+
+/// Whether any of the account's hosts has a key upload API.
+#[cfg(feature = "provider-api")]
+pub fn supports_key_upload(account: &Account) -> bool {
+    key_api_for(account).is_some()
+}
+
+#[cfg(feature = "provider-api")]
+fn check_token_user(provider: &Provider, account: &Account, login: &str) -> Result<(), String> {
+    if login.eq_ignore_ascii_case(&account.username) {
+        return Ok(());
+    }
+    Err(format!(
+        "The token belongs to {} user '{}', not '{}'",
+        provider.name, login, account.username
+    ))
+}
+
+/// Registers a key through meta.sr.ht's GraphQL API, which has no key titles.
+#[cfg(feature = "provider-api")]
+fn upload_sourcehut_key(
+    provider: &Provider,
+    account: &Account,
+    token: &str,
+    public_key: &str,
+) -> Result<String, String> {
+    let auth_header = format!("Authorization: Bearer {}", token);
+    let query = |payload: serde_json::Value| -> Result<serde_json::Value, String> {
+        let (status, body) = api_request(
+            "POST",
+            provider.api_base,
+            &auth_header,
+            Some(&payload.to_string()),
+        )?;
+        let response: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|_| format!("{} returned HTTP {}", provider.name, status))?;
+        match response["errors"][0]["message"].as_str() {
+            Some(message) => Err(format!("{}: {}", provider.name, message)),
+            None => Ok(response),
+        }
+    };
+
+    let me = query(serde_json::json!({
+        "query": "{ me { username sshKeys { results { key } } } }"
+    }))?;
+    let login = me["data"]["me"]["username"].as_str().unwrap_or_default();
+    check_token_user(provider, account, login)?;
+    if me["data"]["me"]["sshKeys"]["results"]
+        .as_array()
+        .is_some_and(|keys| {
+            keys.iter().any(|key| {
+                key["key"]
+                    .as_str()
+                    .is_some_and(|key| same_key(key, public_key))
+            })
+        })
+    {
+        return Ok(format!("{} key (already registered)", provider.name));
+    }
+
+    query(serde_json::json!({
+        "query": "mutation($key: String!) { createSSHKey(key: $key) { fingerprint } }",
+        "variables": { "key": public_key },
+    }))?;
+    Ok(format!("{} key", provider.name))
+}
@@ -0,0 +1,147 @@
+//! Ready-to-paste configuration for an account, for users who apply config
+//! through their own dotfile tooling. Uses the same renderers as the files
+//! git-switch manages itself.
+
+use crate::config::Account;
+use crate::includes::render_identity_fragment;
+use crate::ssh::render_ssh_config;
+
+/// Targets `git-switch render` can produce.
+pub const TARGETS: [&str; 4] = ["ssh", "git", "direnv", "devcontainer"];
+
+/// Renders the snippet for `target`, one of [`TARGETS`].
+pub fn render(account: &Account, target: &str) -> Option<String> {
+    match target {
+        "ssh" => Some(render_ssh_config(account).trim_start().to_string()),
+        // Drop the "managed by git-switch" header; the user owns this copy
+        "git" => Some(
+            render_identity_fragment(account)
+                .lines()
+                .skip(1)
+                .map(|line| format!("{}\n", line))
+                .collect(),
+        ),
+        "direnv" => Some(render_direnv(account)),
+        "devcontainer" => Some(render_devcontainer(account)),
+        _ => None,
+    }
+}
+
+/// Environment variables that make git use the account's identity and key.
+pub fn identity_env(account: &Account) -> Vec<(&'static str, String)> {
+    vec![
+        ("GIT_AUTHOR_NAME", account.username.clone()),
+        ("GIT_AUTHOR_EMAIL", account.email.clone()),
+        ("GIT_COMMITTER_NAME", account.username.clone()),
+        ("GIT_COMMITTER_EMAIL", account.email.clone()),
+        ("GIT_SSH_COMMAND", ssh_command(account)),
+    ]
+}
+
+/// A `GIT_SSH_COMMAND` that offers only the account's key. git runs it
+/// through `sh`, so a key path with spaces or shell characters is quoted.
+pub fn ssh_command(account: &Account) -> String {
+    format!(
+        "ssh -i {} -o IdentitiesOnly=yes",
+        shell_path(&account.ssh_key)
+    )
+}
+
+/// Quotes a path for POSIX shells if it needs it, leaving a leading `~/`
+/// outside the quotes so the shell still expands it.
+fn shell_path(path: &str) -> String {
+    let plain = path
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-~+@%,:=".contains(c));
+    match path.strip_prefix("~/") {
+        _ if plain => path.to_string(),
+        Some(rest) => format!("~/{}", shell_quote(rest)),
+        None => shell_quote(path),
+    }
+}
+
+/// `export NAME='value';` lines for `eval` in POSIX shells and fish.
+pub fn shell_exports(vars: &[(String, String)]) -> String {
+    vars.iter()
+        .map(|(name, value)| format!("export {}={};\n", name, shell_quote(value)))
+        .collect()
+}
+
+/// Quotes a value for POSIX shells.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn render_direnv(account: &Account) -> String {
+    let mut envrc = format!("# .envrc for git-switch account '{}'\n", account.name);
+    for (name, value) in identity_env(account) {
+        // HTTPS accounts get their token from the credential helper
+        if name == "GIT_SSH_COMMAND" && account.uses_https() {
+            continue;
+        }
+        // `~` isn't expanded inside quotes, so spell out $HOME
+        let quoted = match value.strip_prefix("ssh -i ~/") {
+            Some(rest) => {
+                let escaped: String = rest
+                    .chars()
+                    .flat_map(|c| {
+                        let escape = matches!(c, '"' | '\\' | '$' | '`').then_some('\\');
+                        escape.into_iter().chain([c])
+                    })
+                    .collect();
+                format!("\"ssh -i $HOME/{}\"", escaped)
+            }
+            None => shell_quote(&value),
+        };
+        envrc.push_str(&format!("export {}={}\n", name, quoted));
+    }
+    envrc
+}
+
+/// First and last line of the block `git-switch direnv --write` owns in an
+/// `.envrc`.
+pub const ENVRC_BEGIN: &str = "# >>> git-switch";
+pub const ENVRC_END: &str = "# <<< git-switch";
+
+/// The `.envrc` content with the account's exports in git-switch's block,
+/// replacing an earlier block in place and keeping everything else.
+pub fn envrc_with_account(existing: &str, account: &Account) -> String {
+    let block = format!("{}\n{}{}\n", ENVRC_BEGIN, render_direnv(account), ENVRC_END);
+    let mut content = String::new();
+    let mut replaced = false;
+    let mut in_block = false;
+    for line in existing.lines() {
+        if line.trim() == ENVRC_BEGIN {
+            in_block = true;
+        } else if in_block {
+            if line.trim() == ENVRC_END {
+                in_block = false;
+                if !replaced {
+                    content.push_str(&block);
+                    replaced = true;
+                }
+            }
+        } else {
+            content.push_str(line);
+            content.push('\n');
+        }
+    }
+    if !replaced {
+        if !content.is_empty() && !content.ends_with("\n\n") {
+            content.push('\n');
+        }
+        content.push_str(&block);
+    }
+    content
+}
+
+fn render_devcontainer(account: &Account) -> String {
+    // The key stays on the host; the Dev Containers extension forwards the SSH agent
+    let env: serde_json::Map<String, serde_json::Value> = identity_env(account)
+        .into_iter()
+        .filter(|(name, _)| *name != "GIT_SSH_COMMAND")
+        .map(|(name, value)| (name.to_string(), value.into()))
+        .collect();
+    let snippet = serde_json::json!({ "containerEnv": env });
+    serde_json::to_string_pretty(&snippet).unwrap_or_default()
+}
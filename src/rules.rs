@@ -0,0 +1,196 @@
+//! Rules mapping repository patterns to accounts, stored in
+//! `~/.git-switch/rules.toml`:
+//!
+//! ```toml
+//! [[rule]]
+//! pattern = "github.com/mycompany/*"
+//! account = "work"
+//! ```
+//!
+//! A pattern is `host/path`, where `*` matches within one path segment and
+//! `**` matches any number of segments. Rules are tried in the order they
+//! were added and the first match wins.
+
+use crate::config::{Account, get_data_dir};
+use crate::files;
+use crate::git::RemoteUrl;
+use crate::ssh::host_alias;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub pattern: String,
+    /// Name of the account repositories matching the pattern use.
+    pub account: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RuleSet {
+    #[serde(default, rename = "rule")]
+    rules: Vec<Rule>,
+}
+
+pub fn rules_path() -> PathBuf {
+    get_data_dir().join("rules.toml")
+}
+
+/// Loads the rules at `path`; none if the file doesn't exist. A file that
+/// can't be read or parsed is an error, so nothing saves over it.
+pub(crate) fn load_rules_from_path(path: &Path) -> io::Result<Vec<Rule>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(io::Error::new(
+                e.kind(),
+                format!("Error reading {}: {}", path.display(), e),
+            ));
+        }
+    };
+    toml::from_str::<RuleSet>(&content)
+        .map(|set| set.rules)
+        .map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", path.display(), e),
+            )
+        })
+}
+
+pub(crate) fn save_rules_to_path(rules: &[Rule], path: &Path) -> io::Result<()> {
+    let set = RuleSet {
+        rules: rules.to_vec(),
+    };
+    let content = toml::to_string_pretty(&set).map_err(io::Error::other)?;
+    files::write_atomic(path, &content)
+}
+
+pub fn load_rules() -> io::Result<Vec<Rule>> {
+    load_rules_from_path(&rules_path())
+}
+
+pub fn save_rules(rules: &[Rule]) -> io::Result<()> {
+    save_rules_to_path(rules, &rules_path())
+}
+
+/// Normalizes a pattern given on the command line: no scheme, no `.git`
+/// suffix, no trailing slash. Patterns need a host and a path.
+pub fn normalize_pattern(pattern: &str) -> Result<String, String> {
+    let pattern = pattern
+        .trim()
+        .split_once("://")
+        .map_or(pattern.trim(), |(_, rest)| rest)
+        .trim_end_matches('/');
+    let pattern = pattern.strip_suffix(".git").unwrap_or(pattern);
+    match pattern.split_once('/') {
+        Some((host, path)) if !host.is_empty() && !path.is_empty() => Ok(pattern.to_string()),
+        _ => Err(format!(
+            "Pattern '{}' needs a host and a path, e.g. 'github.com/mycompany/*'",
+            pattern
+        )),
+    }
+}
+
+/// Whether `text` matches the glob `pattern`, ignoring case.
+pub fn pattern_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let text: Vec<&str> = text.split('/').collect();
+    segments_match(&pattern, &text)
+}
+
+fn segments_match(pattern: &[&str], text: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((&"**", rest)) => (0..=text.len()).any(|skip| segments_match(rest, &text[skip..])),
+        Some((segment, rest)) => match text.split_first() {
+            Some((first, remaining)) => {
+                segment_matches(&segment.to_lowercase(), &first.to_lowercase())
+                    && segments_match(rest, remaining)
+            }
+            None => false,
+        },
+    }
+}
+
+fn segment_matches(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|i| text.is_char_boundary(*i))
+                .any(|i| segment_matches(rest, &text[i..]))
+        }
+    }
+}
+
+/// The `host/path` a remote is matched as: SSH host aliases of the accounts
+/// are resolved to their host and the `.git` suffix is dropped.
+pub fn remote_key(remote: &RemoteUrl, accounts: &[Account]) -> String {
+    let host = accounts
+        .iter()
+        .flat_map(|acc| {
+            acc.hosts()
+                .into_iter()
+                .filter(|host| host_alias(host, &acc.name) == remote.host)
+        })
+        .next()
+        .unwrap_or(&remote.host);
+    let path = remote.path.trim_end_matches('/');
+    format!("{}/{}", host, path.strip_suffix(".git").unwrap_or(path))
+}
+
+/// The first rule matching the remote and the account it names.
+pub fn match_remote<'a>(
+    rules: &'a [Rule],
+    accounts: &'a [Account],
+    remote: &RemoteUrl,
+) -> Option<(&'a Rule, &'a Account)> {
+    let key = remote_key(remote, accounts);
+    rules
+        .iter()
+        .filter(|rule| pattern_matches(&rule.pattern, &key))
+        .find_map(|rule| {
+            accounts
+                .iter()
+                .find(|acc| acc.name == rule.account)
+                .map(|acc| (rule, acc))
+        })
+}
+
+/// Parses a repository given as a remote URL or as `host/owner/repo`.
+pub fn parse_repository(spec: &str) -> Option<RemoteUrl> {
+    if let Some(remote) = RemoteUrl::parse(spec) {
+        return Some(remote);
+    }
+    let (host, path) = spec.split_once('/')?;
+    (host.contains('.') && path.contains('/')).then(|| RemoteUrl {
+        host: host.to_string(),
+        path: path.to_string(),
+    })
+}
+
+/// Points rules of a renamed account at its new name, or drops them when
+/// `new_name` is `None` (the account was removed).
+pub fn rename_account_rules(old_name: &str, new_name: Option<&str>) -> io::Result<()> {
+    let rules = load_rules()?;
+    if !rules.iter().any(|rule| rule.account == old_name) {
+        return Ok(());
+    }
+    let updated: Vec<Rule> = rules
+        .into_iter()
+        .filter_map(|mut rule| {
+            if rule.account != old_name {
+                return Some(rule);
+            }
+            rule.account = new_name?.to_string();
+            Some(rule)
+        })
+        .collect();
+    save_rules(&updated)
+}
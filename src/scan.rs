@@ -0,0 +1,294 @@
+//! Finds git repositories below a directory and checks whether each one
+//! commits with the identity of the account it belongs to.
+
+use crate::config::Account;
+use crate::git::{RemoteUrl, alias_remote_url};
+use crate::rules::{self, Rule};
+use crate::ssh::host_alias;
+use crate::utils::command_output;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Directories that never contain repositories worth scanning.
+const SKIPPED_DIRS: [&str; 3] = ["node_modules", "target", "vendor"];
+
+/// How deep below the scan root repositories are looked for.
+const MAX_DEPTH: usize = 6;
+
+/// Upper bound on repositories checked at once.
+pub const MAX_SCAN_THREADS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RepoStatus {
+    /// The effective email is the expected account's.
+    Ok,
+    /// The repository commits with another identity than its account's.
+    Mismatch,
+    /// No account could be associated with the repository.
+    Unknown,
+}
+
+/// The result of checking one repository.
+#[derive(Debug, Clone)]
+pub struct RepoReport {
+    pub path: PathBuf,
+    pub email: Option<String>,
+    /// Name of the account whose email the repository commits with.
+    pub current: Option<String>,
+    /// Remote names and URLs, `origin` first.
+    pub remotes: Vec<(String, String)>,
+    /// Name of the account the repository belongs to and why.
+    pub expected: Option<(String, String)>,
+    pub status: RepoStatus,
+}
+
+/// Returns every git repository at or below `root`, without descending into
+/// repositories (submodules and nested checkouts are skipped).
+pub fn find_repositories(root: &Path) -> Vec<PathBuf> {
+    let mut repos = Vec::new();
+    walk(root, 0, &mut repos);
+    repos.sort();
+    repos
+}
+
+fn walk(dir: &Path, depth: usize, repos: &mut Vec<PathBuf>) {
+    if dir.join(".git").exists() {
+        repos.push(dir.to_path_buf());
+        return;
+    }
+    if depth >= MAX_DEPTH {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_str()) {
+            continue;
+        }
+        // Don't follow symlinks, which can loop
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            walk(&entry.path(), depth + 1, repos);
+        }
+    }
+}
+
+/// Picks the account a repository belongs to, most explicit signal first:
+/// an SSH host alias in a remote, a rule matching a remote, a `map`ped
+/// directory, a workspace containing the repository, then the remote's
+/// owner on the account's host.
+pub fn expected_account<'a>(
+    accounts: &'a [Account],
+    repo: &Path,
+    remotes: &[RemoteUrl],
+    mappings: &[(String, &'a Account)],
+    rules: &'a [Rule],
+) -> Option<(&'a Account, String)> {
+    for remote in remotes {
+        for acc in accounts {
+            if acc
+                .hosts()
+                .iter()
+                .any(|host| host_alias(host, &acc.name) == remote.host)
+            {
+                return Some((acc, format!("remote uses host alias {}", remote.host)));
+            }
+        }
+    }
+
+    for remote in remotes {
+        if let Some((rule, acc)) = rules::match_remote(rules, accounts, remote) {
+            return Some((acc, format!("rule {}", rule.pattern)));
+        }
+    }
+
+    let under = |dir: &str| repo.starts_with(&*shellexpand::tilde(dir));
+    if let Some((dir, acc)) = mappings.iter().find(|(dir, _)| under(dir)) {
+        return Some((acc, format!("directory mapped from {}", dir)));
+    }
+    for acc in accounts {
+        if let Some(workspace) = &acc.workspace
+            && under(workspace)
+        {
+            return Some((acc, format!("inside workspace {}", workspace)));
+        }
+    }
+
+    for remote in remotes {
+        let mut owners = accounts.iter().filter(|acc| {
+            acc.hosts().contains(&remote.host.as_str())
+                && acc.username.eq_ignore_ascii_case(remote.owner())
+        });
+        if let (Some(acc), None) = (owners.next(), owners.next()) {
+            return Some((
+                acc,
+                format!("remote owned by {} on {}", acc.username, remote.host),
+            ));
+        }
+    }
+    None
+}
+
+/// Reads a repository's identity and remotes and compares them with the
+/// account it belongs to.
+pub fn check_repository(
+    repo: &Path,
+    accounts: &[Account],
+    mappings: &[(String, &Account)],
+    rules: &[Rule],
+) -> RepoReport {
+    let email = git_in(repo, &["config", "user.email"]);
+    let remotes = read_remotes(repo);
+    let parsed: Vec<RemoteUrl> = remotes
+        .iter()
+        .filter_map(|(_, url)| RemoteUrl::parse(url))
+        .collect();
+    let expected = expected_account(accounts, repo, &parsed, mappings, rules);
+    let current = email.as_ref().and_then(|email| {
+        accounts
+            .iter()
+            .find(|acc| acc.email.eq_ignore_ascii_case(email))
+            .map(|acc| acc.name.clone())
+    });
+    let status = match (&expected, &email) {
+        (None, _) => RepoStatus::Unknown,
+        (Some((acc, _)), Some(email)) if acc.email.eq_ignore_ascii_case(email) => RepoStatus::Ok,
+        (Some(_), _) => RepoStatus::Mismatch,
+    };
+    RepoReport {
+        path: repo.to_path_buf(),
+        email,
+        current,
+        remotes,
+        expected: expected.map(|(acc, reason)| (acc.name.clone(), reason)),
+        status,
+    }
+}
+
+/// Checks repositories on up to [`MAX_SCAN_THREADS`] threads, since each
+/// check waits on several git processes. Reports keep the order of `repos`.
+pub fn check_repositories(
+    repos: &[PathBuf],
+    accounts: &[Account],
+    mappings: &[(String, &Account)],
+    rules: &[Rule],
+) -> Vec<RepoReport> {
+    let threads = std::thread::available_parallelism()
+        .map_or(1, |count| count.get())
+        .clamp(1, MAX_SCAN_THREADS)
+        .min(repos.len());
+    let next = AtomicUsize::new(0);
+    let mut reports: Vec<(usize, RepoReport)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut checked = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(repo) = repos.get(index) else {
+                            break;
+                        };
+                        checked.push((index, check_repository(repo, accounts, mappings, rules)));
+                    }
+                    checked
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    });
+    reports.sort_by_key(|(index, _)| *index);
+    reports.into_iter().map(|(_, report)| report).collect()
+}
+
+/// A repository's remote names and URLs, `origin` first.
+pub fn read_remotes(repo: &Path) -> Vec<(String, String)> {
+    let output =
+        git_in(repo, &["config", "--get-regexp", r"^remote\..*\.url$"]).unwrap_or_default();
+    let mut remotes: Vec<(String, String)> = output
+        .lines()
+        .filter_map(|line| {
+            let (key, url) = line.split_once(' ')?;
+            let name = key.strip_prefix("remote.")?.strip_suffix(".url")?;
+            Some((name.to_string(), url.to_string()))
+        })
+        .collect();
+    remotes.sort_by_key(|(name, _)| name != "origin");
+    remotes
+}
+
+/// Runs git in `repo` and returns its trimmed stdout if it succeeded.
+pub fn git_in(repo: &Path, args: &[&str]) -> Option<String> {
+    let repo = repo.to_string_lossy();
+    let mut full_args = vec!["-C", repo.as_ref()];
+    full_args.extend_from_slice(args);
+    let output = command_output("git", &full_args).ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !stdout.is_empty()).then_some(stdout)
+}
+
+/// Gives a mismatched repository its account's identity: local user, signing
+/// settings and, with `rewrite_remotes`, remotes on the account's hosts
+/// rewritten to its SSH host alias. Returns a description of each change;
+/// with `dry_run` nothing is changed.
+pub fn fix_repository(
+    report: &RepoReport,
+    account: &Account,
+    rewrite_remotes: bool,
+    dry_run: bool,
+) -> Result<Vec<String>, String> {
+    let set_in = |repo: &Path, args: &[&str]| if dry_run { Ok(()) } else { set_in(repo, args) };
+    let mut changes = Vec::new();
+    let mut settings = vec![
+        ("user.name", account.username.as_str()),
+        ("user.email", account.email.as_str()),
+    ];
+    if let Some(key) = &account.signing_key {
+        settings.push(("user.signingkey", key));
+        settings.push((
+            "gpg.format",
+            account.signing_format.as_deref().unwrap_or("gpg"),
+        ));
+        settings.push(("commit.gpgsign", "true"));
+    }
+    for (key, value) in settings {
+        set_in(&report.path, &["config", "--local", key, value])?;
+    }
+    changes.push(format!(
+        "set identity {} <{}>",
+        account.username, account.email
+    ));
+
+    if rewrite_remotes {
+        for (name, url) in &report.remotes {
+            let Some(remote) = RemoteUrl::parse(url) else {
+                continue;
+            };
+            if !account.hosts().contains(&remote.host.as_str()) {
+                continue;
+            }
+            let new_url = alias_remote_url(account, &remote);
+            if new_url == *url {
+                continue;
+            }
+            set_in(&report.path, &["remote", "set-url", name, &new_url])?;
+            changes.push(format!("{} → {}", name, new_url));
+        }
+    }
+    Ok(changes)
+}
+
+fn set_in(repo: &Path, args: &[&str]) -> Result<(), String> {
+    let repo_str = repo.to_string_lossy();
+    let mut full_args = vec!["-C", repo_str.as_ref()];
+    full_args.extend_from_slice(args);
+    match command_output("git", &full_args) {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
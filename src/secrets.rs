@@ -0,0 +1,128 @@
+//! Per-account secrets in the OS keyring: the macOS Keychain, the Windows
+//! Credential Manager or the Secret Service on Linux.
+//!
+//! Entries are stored under the service `git-switch`, one per account and
+//! kind (`work:passphrase`, `work:token:github.com`). Builds without the
+//! `keychain` feature store nothing: lookups find nothing and writes fail
+//! with [`GitSwitchError::Unsupported`].
+
+use crate::config::Account;
+use crate::error::{GitSwitchError, Result};
+
+/// Keyring service the entries are stored under.
+pub const SERVICE: &str = "git-switch";
+
+/// Kinds of secret, as named on the command line.
+pub const KINDS: [&str; 2] = ["passphrase", "token"];
+
+/// Whether this build can use the OS keyring.
+pub const AVAILABLE: bool = cfg!(feature = "keychain");
+
+/// A kind of secret kept for an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Secret<'a> {
+    /// Passphrase of the account's SSH key.
+    Passphrase,
+    /// Provider API token for a host, used for key uploads.
+    Token(&'a str),
+    /// Passphrase of the encrypted accounts file; not tied to an account.
+    AccountsFile,
+}
+
+impl Secret<'_> {
+    /// Name of the keyring entry holding the secret for `account`.
+    pub fn entry_name(&self, account: &str) -> String {
+        match self {
+            Secret::Passphrase => format!("{}:passphrase", account),
+            Secret::Token(host) => format!("{}:token:{}", account, host),
+            Secret::AccountsFile => "accounts-file:passphrase".to_string(),
+        }
+    }
+}
+
+/// Every secret an account can have.
+fn account_secrets(account: &Account) -> Vec<Secret<'_>> {
+    let mut secrets = vec![Secret::Passphrase];
+    secrets.extend(account.hosts().into_iter().map(Secret::Token));
+    secrets
+}
+
+#[cfg(feature = "keychain")]
+fn entry(account: &str, secret: Secret) -> Result<keyring::Entry> {
+    keyring::Entry::new(SERVICE, &secret.entry_name(account)).map_err(keyring_error)
+}
+
+#[cfg(feature = "keychain")]
+fn keyring_error(e: keyring::Error) -> GitSwitchError {
+    GitSwitchError::Unsupported(format!("OS keyring: {}", e))
+}
+
+/// Looks up a secret; `None` if there is none or the keyring can't be used.
+#[cfg(feature = "keychain")]
+pub fn get(account: &str, secret: Secret) -> Option<String> {
+    entry(account, secret).ok()?.get_password().ok()
+}
+
+/// Stores a secret, replacing an existing one.
+#[cfg(feature = "keychain")]
+pub fn set(account: &str, secret: Secret, value: &str) -> Result<()> {
+    entry(account, secret)?
+        .set_password(value)
+        .map_err(keyring_error)
+}
+
+/// Deletes a secret. Returns whether there was one.
+#[cfg(feature = "keychain")]
+pub fn delete(account: &str, secret: Secret) -> Result<bool> {
+    match entry(account, secret)?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(keyring_error(e)),
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn get(_account: &str, _secret: Secret) -> Option<String> {
+    None
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn set(_account: &str, _secret: Secret, _value: &str) -> Result<()> {
+    Err(GitSwitchError::Unsupported(
+        "This build of git-switch has no OS keyring support (cargo feature `keychain`)."
+            .to_string(),
+    ))
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn delete(_account: &str, _secret: Secret) -> Result<bool> {
+    Ok(false)
+}
+
+/// Deletes all secrets of an account, e.g. when it's removed.
+pub fn delete_account(account: &Account) -> Result<()> {
+    for secret in account_secrets(account) {
+        delete(&account.name, secret)?;
+    }
+    Ok(())
+}
+
+/// Keyring entry names of the secrets stored for an account.
+pub fn stored_entries(account: &Account) -> Vec<String> {
+    account_secrets(account)
+        .into_iter()
+        .filter(|secret| get(&account.name, *secret).is_some())
+        .map(|secret| secret.entry_name(&account.name))
+        .collect()
+}
+
+/// Moves an account's secrets to its new name.
+pub fn rename_account(account: &Account, new_name: &str) -> Result<()> {
+    for secret in account_secrets(account) {
+        if let Some(value) = get(&account.name, secret) {
+            set(new_name, secret, &value)?;
+            delete(&account.name, secret)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,191 @@
+//! Per-shell identity sessions (`use --session`).
+//!
+//! A session is a file in `~/.git-switch/sessions/` named after the shell's
+//! process id, holding `KEY=VALUE` environment lines. The hook printed by
+//! `git-switch init <shell>` exports them before each prompt, unsets them
+//! once the file is gone, and deletes the file when the shell exits.
+
+use crate::config::{Account, get_data_dir};
+use crate::render::{identity_env, shell_quote};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// Names the session's account; also how the hook knows a session is active.
+pub const SESSION_VAR: &str = "GIT_SWITCH_SESSION";
+
+pub fn sessions_dir() -> PathBuf {
+    get_data_dir().join("sessions")
+}
+
+/// The interactive shell that ran git-switch: `GIT_SWITCH_SHELL_PID` if
+/// set, otherwise the parent process.
+pub fn shell_pid() -> Option<u32> {
+    if let Some(pid) = std::env::var("GIT_SWITCH_SHELL_PID")
+        .ok()
+        .and_then(|pid| pid.parse().ok())
+    {
+        return Some(pid);
+    }
+    #[cfg(unix)]
+    {
+        Some(std::os::unix::process::parent_id())
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// The environment lines of a session for the account.
+pub fn render_session(account: &Account) -> String {
+    let mut session = String::new();
+    for (name, value) in identity_env(account) {
+        session.push_str(&format!("{}={}\n", name, value));
+    }
+    session.push_str(&format!("{}={}\n", SESSION_VAR, account.name));
+    session
+}
+
+/// Writes the session file for the shell and removes those of shells that
+/// no longer exist.
+pub fn start_session(account: &Account, pid: u32) -> io::Result<PathBuf> {
+    let dir = sessions_dir();
+    fs::create_dir_all(&dir)?;
+    prune_stale_sessions();
+    let path = dir.join(pid.to_string());
+    fs::write(&path, render_session(account))?;
+    Ok(path)
+}
+
+/// Removes the shell's session file. Returns whether there was one.
+pub fn end_session(pid: u32) -> io::Result<bool> {
+    match fs::remove_file(sessions_dir().join(pid.to_string())) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Deletes session files of shells that exited without running their exit
+/// hook (e.g. a closed terminal). Only possible where `/proc` lists processes.
+fn prune_stale_sessions() {
+    let proc = std::path::Path::new("/proc");
+    if !proc.join("self").exists() {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(sessions_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if name.to_str().is_some_and(|pid| !proc.join(pid).exists()) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// The variables a session sets, unset again when it ends.
+fn session_vars() -> Vec<&'static str> {
+    let mut vars: Vec<&str> = identity_env(&Account::default())
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    vars.push(SESSION_VAR);
+    vars
+}
+
+/// Shell code, run once when the hook is loaded, that starts a session for
+/// the default account unless the shell already has one. `binary` is the
+/// git-switch executable.
+pub fn default_session_script(shell: &str, binary: &str, account: &str) -> String {
+    let dir = sessions_dir().to_string_lossy().to_string();
+    let start = format!(
+        "{} use --session {} >/dev/null 2>&1",
+        shell_quote(binary),
+        shell_quote(account)
+    );
+    let line = match shell {
+        "fish" => format!(
+            "test -f \"{}/$fish_pid\"; or GIT_SWITCH_SHELL_PID=$fish_pid {}",
+            dir, start
+        ),
+        _ => format!("[ -f \"{}/$$\" ] || GIT_SWITCH_SHELL_PID=$$ {}", dir, start),
+    };
+    format!(
+        "# New shells start as the default account; `git-switch default --clear` turns this off\n{}\n",
+        line
+    )
+}
+
+/// Shell code that applies session files before each prompt.
+pub fn hook_script(shell: &str) -> Option<String> {
+    let dir = sessions_dir().to_string_lossy().to_string();
+    let vars = session_vars().join(" ");
+    let script = match shell {
+        "bash" | "zsh" => {
+            let register = if shell == "bash" {
+                "PROMPT_COMMAND=\"_git_switch_session${PROMPT_COMMAND:+;$PROMPT_COMMAND}\"\ntrap '_git_switch_session_exit' EXIT"
+            } else {
+                "autoload -Uz add-zsh-hook\nadd-zsh-hook precmd _git_switch_session\nadd-zsh-hook zshexit _git_switch_session_exit"
+            };
+            format!(
+                r#"# git-switch session hook; add `eval "$(git-switch init {shell})"` to your shell rc file
+_git_switch_session() {{
+  local file="{dir}/$$"
+  if [ -f "$file" ]; then
+    local state
+    state="$(cat "$file")"
+    if [ "$state" != "${{_GIT_SWITCH_SESSION_STATE-}}" ]; then
+      while IFS='=' read -r key value; do
+        [ -n "$key" ] && export "$key=$value"
+      done < "$file"
+      _GIT_SWITCH_SESSION_STATE="$state"
+    fi
+  elif [ -n "${{{var}-}}" ]; then
+    unset {vars} _GIT_SWITCH_SESSION_STATE
+  fi
+}}
+_git_switch_session_exit() {{
+  rm -f "{dir}/$$"
+}}
+{register}
+"#,
+                shell = shell,
+                dir = dir,
+                var = SESSION_VAR,
+                vars = vars,
+                register = register
+            )
+        }
+        "fish" => format!(
+            r#"# git-switch session hook; add `git-switch init fish | source` to config.fish
+function _git_switch_session --on-event fish_prompt
+    set -l file "{dir}/$fish_pid"
+    if test -f $file
+        set -l state (cat $file | string collect)
+        if test "$state" != "$_git_switch_session_state"
+            for line in (cat $file)
+                set -l pair (string split -m 1 = $line)
+                set -gx $pair[1] $pair[2]
+            end
+            set -g _git_switch_session_state $state
+        end
+    else if set -q {var}
+        set -e {vars} _git_switch_session_state
+    end
+end
+function _git_switch_session_exit --on-event fish_exit
+    rm -f "{dir}/$fish_pid"
+end
+"#,
+            dir = dir,
+            var = SESSION_VAR,
+            vars = vars
+        ),
+        _ => return None,
+    };
+    Some(script)
+}
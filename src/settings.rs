@@ -0,0 +1,287 @@
+//! Settings that apply to all accounts, stored in
+//! `~/.git-switch/settings.toml`:
+//!
+//! ```toml
+//! mode = "sshcommand"
+//! key_type = "ecdsa-sk"
+//! host = "gitlab.com"
+//! emoji = false
+//! color = false
+//! key_dir = "~/.ssh/git-switch/{account}"
+//! key_name = "id_{type}"
+//! key_comment = "{email}"
+//! ```
+//!
+//! Changed with `git-switch config set <key> <value>` (or the shorter
+//! `git-switch config <key> <value>`); an empty value or `config unset`
+//! goes back to the default.
+
+use crate::config::{DEFAULT_HOST, get_data_dir};
+use crate::files;
+use crate::problem;
+use crate::ssh::KeyType;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How git is made to use an account's key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwitchMode {
+    /// A `Host <host>-<name>` block per account in `~/.ssh/config`, with
+    /// remotes pointing at the alias.
+    #[default]
+    Alias,
+    /// `core.sshCommand` set to `ssh -i <key>` in the repository's git
+    /// config; `~/.ssh/config` is left alone and remotes use the real host.
+    SshCommand,
+}
+
+impl SwitchMode {
+    pub const NAMES: [&'static str; 2] = ["alias", "sshcommand"];
+
+    pub fn parse(name: &str) -> Option<SwitchMode> {
+        match name {
+            "alias" => Some(SwitchMode::Alias),
+            "sshcommand" => Some(SwitchMode::SshCommand),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwitchMode::Alias => "alias",
+            SwitchMode::SshCommand => "sshcommand",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub mode: SwitchMode,
+    /// Key type `add` generates without `--key-type`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_type: Option<String>,
+    /// Host of accounts added without `--host`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Start messages with an emoji on a terminal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<bool>,
+    /// Color output on a terminal; `false` is like setting `NO_COLOR`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<bool>,
+    /// Directory new keys are generated in; a template like `key_name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_dir: Option<String>,
+    /// File name template of new keys, with `{account}`, `{type}` and
+    /// `{host}` placeholders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_name: Option<String>,
+    /// Comment of new keys, with `{email}`, `{account}` and `{hostname}`
+    /// placeholders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_comment: Option<String>,
+}
+
+/// Names of the settings `git-switch config` can change.
+pub const KEYS: [&str; 8] = [
+    "mode",
+    "key_type",
+    "host",
+    "emoji",
+    "color",
+    "key_dir",
+    "key_name",
+    "key_comment",
+];
+
+pub const DEFAULT_KEY_DIR: &str = "~/.ssh";
+pub const DEFAULT_KEY_NAME: &str = "id_{type}_{account}";
+pub const DEFAULT_KEY_COMMENT: &str = "{email} ({account}@{hostname})";
+
+impl Settings {
+    /// The value of a setting, by its name in [`KEYS`].
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "mode" => Some(self.mode.as_str().to_string()),
+            "key_type" => Some(self.key_type().as_str().to_string()),
+            "host" => Some(self.host().to_string()),
+            "emoji" => Some(self.emoji().to_string()),
+            "color" => Some(self.color().to_string()),
+            "key_dir" => Some(self.key_dir().to_string()),
+            "key_name" => Some(self.key_name().to_string()),
+            "key_comment" => Some(self.key_comment().to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        self.key_type
+            .as_deref()
+            .and_then(KeyType::parse)
+            .unwrap_or_default()
+    }
+
+    pub fn host(&self) -> &str {
+        self.host.as_deref().unwrap_or(DEFAULT_HOST)
+    }
+
+    pub fn emoji(&self) -> bool {
+        self.emoji.unwrap_or(true)
+    }
+
+    pub fn color(&self) -> bool {
+        self.color.unwrap_or(true)
+    }
+
+    pub fn key_dir(&self) -> &str {
+        self.key_dir.as_deref().unwrap_or(DEFAULT_KEY_DIR)
+    }
+
+    pub fn key_name(&self) -> &str {
+        self.key_name.as_deref().unwrap_or(DEFAULT_KEY_NAME)
+    }
+
+    pub fn key_comment(&self) -> &str {
+        self.key_comment.as_deref().unwrap_or(DEFAULT_KEY_COMMENT)
+    }
+
+    /// Changes a setting; fails with a message naming the accepted values.
+    /// An empty value goes back to the default.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "mode" if value.is_empty() => {
+                self.mode = SwitchMode::default();
+                Ok(())
+            }
+            "mode" => {
+                self.mode = SwitchMode::parse(value).ok_or_else(|| {
+                    format!(
+                        "Unknown mode '{}'; use one of {}.",
+                        value,
+                        SwitchMode::NAMES.join(", ")
+                    )
+                })?;
+                Ok(())
+            }
+            "key_type" => {
+                if !value.is_empty() && KeyType::parse(value).is_none() {
+                    return Err(format!(
+                        "Unknown key type '{}'; use one of {}.",
+                        value,
+                        KeyType::NAMES.join(", ")
+                    ));
+                }
+                self.key_type = Some(value.to_string())
+                    .filter(|name| !name.is_empty() && name != KeyType::default().as_str());
+                Ok(())
+            }
+            "host" => {
+                if value.contains(['/', ':', ' ']) {
+                    return Err(format!(
+                        "'{}' is not a host name; give it like `gitlab.com`.",
+                        value
+                    ));
+                }
+                self.host = Some(value.to_lowercase())
+                    .filter(|host| !host.is_empty() && host != DEFAULT_HOST);
+                Ok(())
+            }
+            "emoji" => {
+                self.emoji = parse_switch(value)?.filter(|on| !on);
+                Ok(())
+            }
+            "color" => {
+                self.color = parse_switch(value)?.filter(|on| !on);
+                Ok(())
+            }
+            "key_dir" => {
+                self.key_dir = Some(value.trim_end_matches('/').to_string())
+                    .filter(|dir| !dir.is_empty() && dir != DEFAULT_KEY_DIR);
+                Ok(())
+            }
+            "key_name" => {
+                if value.contains(['/', '\\']) {
+                    return Err(format!(
+                        "'{}' is not a file name; put directories in key_dir.",
+                        value
+                    ));
+                }
+                self.key_name = Some(value.to_string())
+                    .filter(|name| !name.is_empty() && name != DEFAULT_KEY_NAME);
+                Ok(())
+            }
+            "key_comment" => {
+                self.key_comment = Some(value.to_string())
+                    .filter(|comment| !comment.is_empty() && comment != DEFAULT_KEY_COMMENT);
+                Ok(())
+            }
+            _ => Err(format!("Unknown setting '{}'.", key)),
+        }
+    }
+}
+
+/// Reads an on/off value; `None` for the empty value.
+fn parse_switch(value: &str) -> Result<Option<bool>, String> {
+    match value.to_lowercase().as_str() {
+        "" => Ok(None),
+        "true" | "on" | "yes" | "1" => Ok(Some(true)),
+        "false" | "off" | "no" | "0" => Ok(Some(false)),
+        _ => Err(format!("'{}' is not on or off; use true or false.", value)),
+    }
+}
+
+pub fn settings_path() -> PathBuf {
+    get_data_dir().join("settings.toml")
+}
+
+/// Loads the settings at `path`; the defaults if the file doesn't exist. A
+/// file that can't be read or parsed is an error, so nothing saves over it.
+pub(crate) fn load_settings_from_path(path: &Path) -> io::Result<Settings> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(e) => {
+            return Err(io::Error::new(
+                e.kind(),
+                format!("Error reading {}: {}", path.display(), e),
+            ));
+        }
+    };
+    toml::from_str(&content).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse {}: {}", path.display(), e),
+        )
+    })
+}
+
+pub(crate) fn save_settings_to_path(settings: &Settings, path: &Path) -> io::Result<()> {
+    let content = toml::to_string_pretty(settings).map_err(io::Error::other)?;
+    files::write_atomic(path, &content)
+}
+
+/// Loads the settings for reading only: a file that can't be read or parsed
+/// gives a warning, once per run, and the defaults.
+pub fn load_settings() -> Settings {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+    read_settings().unwrap_or_else(|e| {
+        if !WARNED.swap(true, Ordering::Relaxed) {
+            problem!("⚠️ {}; using the default settings.", e);
+        }
+        Settings::default()
+    })
+}
+
+/// Loads the settings, failing if the file can't be read or parsed.
+pub fn read_settings() -> io::Result<Settings> {
+    load_settings_from_path(&settings_path())
+}
+
+pub fn save_settings(settings: &Settings) -> io::Result<()> {
+    save_settings_to_path(settings, &settings_path())
+}
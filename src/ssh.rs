@@ -1,53 +1,517 @@
-use crate::utils::run_command;
-use std::fs::{self, File, OpenOptions};
-use std::io::{self, Read, Write};
-use std::path::Path;
+//! SSH keys and the account blocks git-switch manages in `~/.ssh/config`.
 
+use crate::config::{Account, DEFAULT_HOST, check_ssh_option_name, get_data_dir, home_dir};
+use crate::error::{GitSwitchError, IoContext, Result};
+use crate::events;
+use crate::files;
+use crate::settings::{Settings, SwitchMode, load_settings};
+use crate::utils::{Verbosity, command_output, hostname, now_secs, run_command, verbosity};
+use crate::{problem, say};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+static SSH_CONFIG_PATH: OnceLock<String> = OnceLock::new();
+
+/// Uses `path` as the SSH config for the rest of the process
+/// (`--ssh-config`). Only the first call has an effect.
+pub fn set_ssh_config_path(path: &str) {
+    let _ = SSH_CONFIG_PATH.set(shellexpand::tilde(path).into_owned());
+}
+
+/// Location of the SSH config git-switch manages: the `--ssh-config` path,
+/// `$GIT_SWITCH_SSH_CONFIG`, or `~/.ssh/config`.
 pub fn get_ssh_config_path() -> String {
-    let home = dirs::home_dir().expect("Could not determine home directory");
-    home.join(".ssh")
+    if let Some(path) = SSH_CONFIG_PATH.get() {
+        return path.clone();
+    }
+    if let Ok(path) = std::env::var("GIT_SWITCH_SSH_CONFIG")
+        && !path.is_empty()
+    {
+        return shellexpand::tilde(&path).into_owned();
+    }
+    home_dir()
+        .unwrap_or_default()
+        .join(".ssh")
         .join("config")
         .to_string_lossy()
         .into_owned()
 }
 
-pub fn generate_ssh_key(identity_file: &str) {
-    let expanded_path_str = if identity_file.starts_with('~') {
-        let home = dirs::home_dir().expect("Could not determine home directory");
-        home.join(&identity_file[2..])
-            .to_string_lossy()
-            .into_owned()
+/// Returns the SSH `Host` alias used for an account on a host, e.g.
+/// `github-work` for github.com, `gitlab-work` for gitlab.com and
+/// `git-corp-com-work` for a self-hosted `git.corp.com`.
+pub fn host_alias(host: &str, name: &str) -> String {
+    let labels: Vec<&str> = host.split('.').collect();
+    let prefix = if labels.len() == 2 {
+        labels[0].to_lowercase()
     } else {
-        identity_file.to_string()
+        host.replace('.', "-").to_lowercase()
     };
+    format!("{}-{}", prefix, name.replace(' ', "_").to_lowercase())
+}
+
+/// Header comment preceding an account's SSH config block for a host.
+fn config_entry_header(host: &str, name: &str) -> String {
+    if host == DEFAULT_HOST {
+        format!("# {} GitHub Account", name)
+    } else {
+        format!("# {} Git Account ({})", name, host)
+    }
+}
+
+/// SSH key algorithms `add` can generate.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum KeyType {
+    #[default]
+    Ed25519,
+    Ecdsa,
+    Rsa,
+    /// Ed25519 key backed by a FIDO2 security key.
+    Ed25519Sk,
+    /// ECDSA P-256 key backed by a FIDO2 security key.
+    EcdsaSk,
+}
+
+impl KeyType {
+    pub const NAMES: [&'static str; 5] = ["ed25519", "ecdsa", "rsa", "ed25519-sk", "ecdsa-sk"];
+
+    pub fn parse(name: &str) -> Option<KeyType> {
+        match name.to_lowercase().as_str() {
+            "ed25519" => Some(KeyType::Ed25519),
+            "ecdsa" => Some(KeyType::Ecdsa),
+            "rsa" => Some(KeyType::Rsa),
+            "ed25519-sk" => Some(KeyType::Ed25519Sk),
+            "ecdsa-sk" => Some(KeyType::EcdsaSk),
+            _ => None,
+        }
+    }
+
+    /// The `ssh-keygen -t` argument.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyType::Ed25519 => "ed25519",
+            KeyType::Ecdsa => "ecdsa",
+            KeyType::Rsa => "rsa",
+            KeyType::Ed25519Sk => "ed25519-sk",
+            KeyType::EcdsaSk => "ecdsa-sk",
+        }
+    }
+
+    /// The type and size of an existing key, from its public key line
+    /// (`ssh-ed25519 AAAA... comment`). RSA sizes aren't in the line, so
+    /// they get the default.
+    pub fn from_public_key(public_key: &str) -> Option<(KeyType, Option<u32>)> {
+        match public_key.split_whitespace().next()? {
+            "ssh-ed25519" => Some((KeyType::Ed25519, None)),
+            "ssh-rsa" => Some((KeyType::Rsa, None)),
+            "ecdsa-sha2-nistp256" => Some((KeyType::Ecdsa, Some(256))),
+            "ecdsa-sha2-nistp384" => Some((KeyType::Ecdsa, Some(384))),
+            "ecdsa-sha2-nistp521" => Some((KeyType::Ecdsa, Some(521))),
+            "sk-ssh-ed25519@openssh.com" => Some((KeyType::Ed25519Sk, None)),
+            "sk-ecdsa-sha2-nistp256@openssh.com" => Some((KeyType::EcdsaSk, None)),
+            _ => None,
+        }
+    }
+
+    /// The type as used in default key file names (`id_ed25519_sk_work`).
+    pub fn file_stem(&self) -> String {
+        self.as_str().replace('-', "_")
+    }
+
+    /// Whether the private key lives on a hardware authenticator.
+    pub fn is_hardware_backed(&self) -> bool {
+        matches!(self, KeyType::Ed25519Sk | KeyType::EcdsaSk)
+    }
+
+    /// Checks a requested key size, returning the size to pass to `ssh-keygen -b`.
+    /// Ed25519 keys have a fixed size, so no `-b` is passed for them.
+    pub fn resolve_bits(&self, bits: Option<u32>) -> Result<Option<u32>, String> {
+        match (self, bits) {
+            (KeyType::Ed25519, None) => Ok(None),
+            (KeyType::Ed25519 | KeyType::Ed25519Sk | KeyType::EcdsaSk, Some(_)) => Err(format!(
+                "{} keys have a fixed size; --key-bits is not supported",
+                self.as_str()
+            )),
+            (KeyType::Ed25519Sk | KeyType::EcdsaSk, None) => Ok(None),
+            (KeyType::Ecdsa, None) => Ok(Some(256)),
+            (KeyType::Ecdsa, Some(bits @ (256 | 384 | 521))) => Ok(Some(bits)),
+            (KeyType::Ecdsa, Some(bits)) => Err(format!(
+                "ecdsa keys must be 256, 384 or 521 bits (got {})",
+                bits
+            )),
+            (KeyType::Rsa, None) => Ok(Some(4096)),
+            (KeyType::Rsa, Some(bits)) if bits >= 2048 => Ok(Some(bits)),
+            (KeyType::Rsa, Some(bits)) => Err(format!(
+                "rsa keys must be at least 2048 bits (got {})",
+                bits
+            )),
+        }
+    }
+}
+
+/// FIDO2 options security keys can be generated with: `resident` keeps the
+/// key on the authenticator, so `ssh-keygen -K` can restore it on another
+/// machine; `verify-required` asks for the PIN on every use.
+pub const SECURITY_KEY_OPTIONS: [&str; 2] = ["resident", "verify-required"];
+
+/// `ssh-keygen -O` values for an account's security key options. Resident
+/// keys get a user id per account, so one account's key doesn't replace
+/// another's on the authenticator.
+pub fn security_key_args(account_name: &str, options: &[String]) -> Vec<String> {
+    let mut args = options.to_vec();
+    if options.iter().any(|option| option == "resident") {
+        args.push(format!(
+            "user=git-switch-{}",
+            account_name.replace(' ', "_").to_lowercase()
+        ));
+    }
+    args
+}
+
+/// Whether the key at `key_path` (expanded) lives on a security key, going
+/// by its public key.
+pub fn is_security_key(key_path: &str) -> bool {
+    fs::read_to_string(format!("{}.pub", key_path))
+        .ok()
+        .and_then(|public_key| KeyType::from_public_key(&public_key))
+        .is_some_and(|(key_type, _)| key_type.is_hardware_backed())
+}
+
+/// Generates a key pair at `identity_file` with ssh-keygen, which talks to
+/// the terminal directly (hardware keys ask to be touched). `sk_options`
+/// are extra `-O` options for hardware keys. Returns `Ok(false)` without
+/// doing anything if the key already exists.
+pub fn generate_ssh_key(
+    identity_file: &str,
+    key_type: KeyType,
+    bits: Option<u32>,
+    passphrase: Option<&str>,
+    sk_options: &[String],
+    comment: &str,
+) -> io::Result<bool> {
+    let expanded_path_str = shellexpand::tilde(identity_file).into_owned();
     let expanded_path = Path::new(&expanded_path_str);
 
     if expanded_path.exists() {
-        println!("✅ SSH key already exists: {}", identity_file);
-        return;
+        return Ok(false);
     }
 
     // Ensure the directory exists
-    if let Some(parent) = expanded_path.parent() {
-        if !parent.exists() {
-            fs::create_dir_all(parent).expect("Failed to create SSH directory");
-        }
+    if let Some(parent) = expanded_path.parent()
+        && !parent.exists()
+    {
+        fs::create_dir_all(parent)?;
     }
 
-    println!("🔑 Generating SSH key: {}", identity_file);
-    run_command(
-        "ssh-keygen",
-        &[
-            "-t",
-            "rsa",
-            "-b",
-            "4096",
-            "-f",
-            expanded_path.to_str().unwrap(),
-            "-N",
-            "",
-        ],
+    say!(
+        "🔑 Generating {} SSH key: {}",
+        key_type.as_str(),
+        identity_file
+    );
+    let bits_arg = bits.map(|b| b.to_string());
+    let mut args = vec!["-t", key_type.as_str(), "-C", comment];
+    if let Some(bits_arg) = &bits_arg {
+        args.extend(["-b", bits_arg.as_str()]);
+    }
+    // Record the authenticator's attestation so it can be verified later
+    let attestation_args = if key_type.is_hardware_backed() {
+        let mut options = vec![format!(
+            "write-attestation={}",
+            attestation_path(&expanded_path_str)
+        )];
+        if write_challenge(&challenge_path(&expanded_path_str)) {
+            options.push(format!("challenge={}", challenge_path(&expanded_path_str)));
+        }
+        options.extend(sk_options.iter().cloned());
+        // ssh-keygen's own prompt is easy to miss among the output
+        say!("👆 Touch your security key when it blinks (enter its PIN first if asked).");
+        options
+    } else {
+        Vec::new()
+    };
+    for option in &attestation_args {
+        args.extend(["-O", option.as_str()]);
+    }
+    if verbosity() == Verbosity::Quiet {
+        args.push("-q");
+    }
+    args.extend(["-f", &expanded_path_str, "-N", passphrase.unwrap_or("")]);
+    let status = Command::new("ssh-keygen").args(&args).status()?;
+    // Never record the passphrase in the event stream
+    let logged: Vec<&str> = match passphrase {
+        Some(_) => args[..args.len() - 1]
+            .iter()
+            .copied()
+            .chain(["<redacted>"])
+            .collect(),
+        None => args.clone(),
+    };
+    events::subprocess("ssh-keygen", &logged, status.code());
+    if !status.success() && key_type.is_hardware_backed() {
+        return Err(io::Error::other(format!(
+            "ssh-keygen exited with {}; check that the security key is plugged in, was touched in time, and that OpenSSH was built with FIDO2 support",
+            status
+        )));
+    }
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "ssh-keygen exited with {}",
+            status
+        )));
+    }
+    // Key material is never hashed into the event stream
+    events::file_modified(expanded_path, None, Some(""));
+    events::file_modified(
+        Path::new(&format!("{}.pub", expanded_path_str)),
+        None,
+        Some(""),
     );
+    Ok(true)
+}
+
+/// Returns the fingerprints (`SHA256:...`) of the keys loaded in the agent,
+/// or `None` if the agent can't be reached.
+pub fn agent_fingerprints() -> Option<Vec<String>> {
+    let output = command_output("ssh-add", &["-l"]).ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match output.status.code() {
+        Some(0) => Some(stdout.lines().filter_map(fingerprint_field).collect()),
+        // Exit code 1 means the agent is running but holds no identities
+        Some(1) => Some(Vec::new()),
+        _ => None,
+    }
+}
+
+/// Variables that tell `ssh-add` and `ssh` how to reach the agent.
+pub const AGENT_VARS: [&str; 2] = ["SSH_AUTH_SOCK", "SSH_AGENT_PID"];
+
+/// Reads the agent variables from `ssh-agent -s` output, which looks like
+/// `SSH_AUTH_SOCK=/tmp/ssh-XXX/agent.1; export SSH_AUTH_SOCK;`.
+pub fn parse_agent_output(output: &str) -> Vec<(String, String)> {
+    output
+        .split([';', '\n'])
+        .filter_map(|part| {
+            let (name, value) = part.trim().split_once('=')?;
+            AGENT_VARS
+                .contains(&name)
+                .then(|| (name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Makes sure an agent is reachable and returns its variables. When the
+/// current one can't be reached, starts one with `ssh-agent -s` and points
+/// the commands git-switch runs next at it; the caller's shell only sees it
+/// if it evaluates the returned variables. On Windows the OpenSSH
+/// Authentication Agent service is started instead when it is installed;
+/// otherwise `ssh-agent -s` starts Git for Windows' agent.
+pub fn ensure_agent() -> Result<Vec<(String, String)>> {
+    if agent_fingerprints().is_some() || (cfg!(windows) && start_agent_service()?) {
+        return Ok(AGENT_VARS
+            .iter()
+            .filter_map(|name| Some((name.to_string(), std::env::var(name).ok()?)))
+            .collect());
+    }
+    let output =
+        command_output("ssh-agent", &["-s"]).map_err(|source| GitSwitchError::CommandNotFound {
+            command: "ssh-agent".to_string(),
+            source,
+        })?;
+    let vars = parse_agent_output(&String::from_utf8_lossy(&output.stdout));
+    if !output.status.success() || vars.is_empty() {
+        return Err(GitSwitchError::CommandFailed {
+            command: "ssh-agent -s".to_string(),
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    for (name, value) in &vars {
+        // SAFETY: switching runs on the main thread; the only threads
+        // git-switch starts are scoped to scans and done by now.
+        unsafe { std::env::set_var(name, value) };
+    }
+    Ok(vars)
+}
+
+/// Name of the OpenSSH Authentication Agent service on Windows.
+const AGENT_SERVICE: &str = "ssh-agent";
+
+/// How to get an agent running on Windows, for when git-switch can't.
+pub const WINDOWS_AGENT_HINT: &str = "In an administrator PowerShell, run `Get-Service ssh-agent | Set-Service -StartupType Manual; Start-Service ssh-agent`, or run `start-ssh-agent` from Git for Windows.";
+
+/// Reads the state (`RUNNING`, `STOPPED`, ...) from `sc query` output; `None`
+/// if the service isn't installed.
+pub fn parse_service_state(output: &str) -> Option<&str> {
+    output.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        if key.trim() == "STATE" {
+            value.split_whitespace().nth(1)
+        } else {
+            None
+        }
+    })
+}
+
+/// Starts Windows' agent service if it is installed but stopped. Returns
+/// whether the agent can be reached through it; fails with guidance when
+/// the service won't start, usually because it is disabled, as it is out
+/// of the box.
+fn start_agent_service() -> Result<bool> {
+    let Ok(query) = command_output("sc.exe", &["query", AGENT_SERVICE]) else {
+        return Ok(false);
+    };
+    match parse_service_state(&String::from_utf8_lossy(&query.stdout)) {
+        None => return Ok(false),
+        Some("RUNNING") => {}
+        Some(_) => {
+            say!("🔄 Starting the OpenSSH Authentication Agent service...");
+            let start = command_output("sc.exe", &["start", AGENT_SERVICE]).map_err(|source| {
+                GitSwitchError::CommandNotFound {
+                    command: "sc.exe".to_string(),
+                    source,
+                }
+            })?;
+            if !start.status.success() {
+                problem!("ℹ️ {}", WINDOWS_AGENT_HINT);
+                // sc reports errors on stdout
+                return Err(GitSwitchError::CommandFailed {
+                    command: format!("sc.exe start {}", AGENT_SERVICE),
+                    code: start.status.code(),
+                    stderr: String::from_utf8_lossy(&start.stdout).trim().to_string(),
+                });
+            }
+        }
+    }
+    // The service takes a moment to open its pipe
+    for _ in 0..10 {
+        if agent_fingerprints().is_some() {
+            return Ok(true);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    Ok(false)
+}
+
+/// Removes the other accounts' keys from the agent, keeping `keep`'s, so
+/// hosts are offered only the active account's key. Keys that aren't
+/// loaded, or that `keep` shares, are left alone. Returns the names of the
+/// accounts whose keys were removed.
+pub fn unload_other_keys(keep: &Account, accounts: &[Account]) -> Vec<String> {
+    let Some(mut loaded) = agent_fingerprints() else {
+        return Vec::new();
+    };
+    let kept = key_fingerprint(&keep.ssh_key);
+    let mut unloaded = Vec::new();
+    for acc in accounts
+        .iter()
+        .filter(|acc| acc.name != keep.name && !acc.uses_https())
+    {
+        let Some(fingerprint) = key_fingerprint(&acc.ssh_key) else {
+            continue;
+        };
+        if kept.as_ref() == Some(&fingerprint) || !loaded.contains(&fingerprint) {
+            continue;
+        }
+        if unload_key(&acc.ssh_key) {
+            loaded.retain(|loaded| *loaded != fingerprint);
+            unloaded.push(acc.name.clone());
+        } else {
+            problem!("⚠️ Couldn't remove {}'s key from ssh-agent.", acc.name);
+        }
+    }
+    unloaded
+}
+
+/// Whether a private key is protected by a passphrase, i.e. ssh-keygen
+/// can't read it with an empty one.
+pub fn key_is_encrypted(identity_file: &str) -> bool {
+    let path = shellexpand::tilde(identity_file);
+    command_output("ssh-keygen", &["-y", "-P", "", "-f", &path])
+        .is_ok_and(|out| !out.status.success())
+}
+
+/// Removes a key from ssh-agent by its public key file.
+pub fn unload_key(identity_file: &str) -> bool {
+    let public_key = format!("{}.pub", shellexpand::tilde(identity_file));
+    command_output("ssh-add", &["-d", &public_key]).is_ok_and(|out| out.status.success())
+}
+
+/// Returns the fingerprint of a key, read from its public key file.
+pub fn key_fingerprint(identity_file: &str) -> Option<String> {
+    let public_key = format!("{}.pub", shellexpand::tilde(identity_file));
+    let output = command_output("ssh-keygen", &["-lf", &public_key]).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    fingerprint_field(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Extracts the fingerprint from `ssh-add -l`/`ssh-keygen -l` output
+/// (`<bits> <fingerprint> <comment> (<type>)`).
+fn fingerprint_field(line: &str) -> Option<String> {
+    line.split_whitespace().nth(1).map(str::to_string)
+}
+
+/// Path of the FIDO attestation recorded when a hardware-backed key was generated.
+pub fn attestation_path(identity_file: &str) -> String {
+    format!("{}.attestation", identity_file)
+}
+
+/// Path of the challenge the attestation was generated with.
+pub fn challenge_path(identity_file: &str) -> String {
+    format!("{}.challenge", identity_file)
+}
+
+/// Writes a random 32-byte attestation challenge. Returns false where no
+/// system randomness source is available, in which case ssh-keygen picks
+/// its own (unrecorded) challenge.
+fn write_challenge(path: &str) -> bool {
+    let mut challenge = [0u8; 32];
+    let read = File::open("/dev/urandom").and_then(|mut random| random.read_exact(&mut challenge));
+    read.is_ok() && fs::write(path, challenge).is_ok()
+}
+
+/// Reads the public half of a key (`<key>.pub`), trimmed.
+pub fn read_public_key(identity_file: &str) -> Result<String> {
+    let path = format!("{}.pub", shellexpand::tilde(identity_file));
+    match fs::read_to_string(&path) {
+        Ok(key) => Ok(key.trim().to_string()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(GitSwitchError::InvalidInput(
+            format!("Public key not found: {}", path),
+        )),
+        Err(e) => Err(GitSwitchError::io(format!("Failed to read {}", path), e)),
+    }
+}
+
+/// Private keys in `dir` (those with a `.pub` next to them) that no saved
+/// account uses, sorted by path; candidates for reuse by a new account.
+pub fn unused_keys(dir: &Path, accounts: &[Account]) -> Vec<PathBuf> {
+    let used: Vec<PathBuf> = accounts
+        .iter()
+        .map(|acc| PathBuf::from(&*shellexpand::tilde(&acc.ssh_key)))
+        .collect();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut keys: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension().is_none_or(|ext| ext != "pub")
+                && path.is_file()
+                && path
+                    .with_file_name(format!(
+                        "{}.pub",
+                        path.file_name().unwrap_or_default().to_string_lossy()
+                    ))
+                    .is_file()
+                && !used.contains(path)
+        })
+        .collect();
+    keys.sort();
+    keys
 }
 
 pub fn display_public_key(identity_file: &str) {
@@ -60,14 +524,14 @@ pub fn display_public_key(identity_file: &str) {
             if file.read_to_string(&mut contents).is_ok() {
                 println!("{}", contents.trim());
             } else {
-                println!(
+                say!(
                     "❌ Failed to read public key file. Please check the file at: {}",
                     public_key_path.display()
                 );
             }
         }
         Err(_) => {
-            println!(
+            say!(
                 "❌ Public key file not found at: {}",
                 public_key_path.display()
             );
@@ -75,114 +539,321 @@ pub fn display_public_key(identity_file: &str) {
     }
 }
 
-pub fn update_ssh_config(name: &str, identity_file: &str) -> io::Result<()> {
-    let host_alias_name = name.replace(' ', "_").to_lowercase(); // Consistent host alias
-    let config_entry = format!(
-        "\n# {} GitHub Account\nHost github-{}\n    HostName github.com\n    User git\n    IdentityFile {}\n",
-        name, host_alias_name, identity_file
-    );
-
-    let expanded_path_str = get_ssh_config_path();
-    let path = Path::new(&expanded_path_str);
+/// Options every SSH config block gets unless the account's `ssh_options`
+/// set them. Without IdentitiesOnly the agent offers its keys first, and the
+/// host authenticates whichever account it recognizes. On macOS, keys are
+/// loaded into the agent on first use with their passphrase from the login
+/// keychain; `IgnoreUnknown` keeps OpenSSH builds other than Apple's (e.g.
+/// Homebrew's) from rejecting `UseKeychain`.
+pub fn default_ssh_options(macos: bool) -> Vec<(&'static str, &'static str)> {
+    let mut options = vec![("IdentitiesOnly", "yes")];
+    if macos {
+        options.extend([
+            ("AddKeysToAgent", "yes"),
+            ("IgnoreUnknown", "UseKeychain"),
+            ("UseKeychain", "yes"),
+        ]);
+    }
+    options
+}
 
-    // Create directory if it doesn't exist
-    if let Some(parent) = path.parent() {
-        if !parent.exists() {
-            fs::create_dir_all(parent)?;
+/// Renders the SSH config blocks for every host of an account.
+pub fn render_ssh_config(account: &Account) -> String {
+    let defaults = default_ssh_options(cfg!(target_os = "macos"));
+    let mut config_entry = String::new();
+    for host in account.hosts() {
+        let (hostname, port) = account.ssh_endpoint(host);
+        config_entry.push_str(&format!(
+            "\n{}\nHost {}\n    HostName {}\n    User {}\n    IdentityFile {}\n",
+            config_entry_header(host, &account.name),
+            host_alias(host, &account.name),
+            hostname,
+            account.ssh_user_for(host),
+            account.ssh_key
+        ));
+        if let Some(port) = port {
+            config_entry.push_str(&format!("    Port {}\n", port));
+        }
+        if let Some(jump) = account.proxy_jump.as_ref().filter(|_| host == account.host) {
+            config_entry.push_str(&format!("    ProxyJump {}\n", jump));
+        }
+        for (option, value) in &defaults {
+            if !account
+                .ssh_options
+                .keys()
+                .any(|set| set.eq_ignore_ascii_case(option))
+            {
+                config_entry.push_str(&format!("    {} {}\n", option, value));
+            }
+        }
+        for (option, value) in &account.ssh_options {
+            config_entry.push_str(&format!("    {} {}\n", option, value));
         }
     }
+    config_entry
+}
 
-    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+/// Parses a `--ssh-option KEY=VALUE` argument. An empty value is allowed so
+/// `edit` can remove an option.
+pub fn parse_ssh_option(arg: &str) -> Result<(String, String), String> {
+    let (option, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", arg))?;
+    let (option, value) = (option.trim(), value.trim());
+    check_ssh_option_name(option)?;
+    Ok((option.to_string(), value.to_string()))
+}
 
-    file.write_all(config_entry.as_bytes())?;
-    println!("✅ Updated SSH config for account: {}", name);
-    Ok(())
+/// Start and end marker lines around the entries git-switch manages for an account.
+pub fn block_markers(name: &str) -> (String, String) {
+    (
+        format!("# >>> git-switch:{}", name),
+        format!("# <<< git-switch:{}", name),
+    )
 }
 
-pub fn remove_ssh_config_entry(name: &str) -> io::Result<()> {
-    let config_path_str = get_ssh_config_path();
-    let path = Path::new(&config_path_str);
+/// The account's SSH config blocks wrapped in its markers, as appended by
+/// `update_ssh_config`.
+pub fn managed_block(account: &Account) -> String {
+    let (start, end) = block_markers(&account.name);
+    format!(
+        "\n{}\n{}\n{}\n",
+        start,
+        render_ssh_config(account).trim(),
+        end
+    )
+}
 
-    if !path.exists() {
-        println!(
-            "ℹ️ SSH config file not found, nothing to remove for account '{}'.",
-            name
-        );
-        return Ok(());
+/// Removes the account's marked block, along with the blank line written
+/// before it, and returns the line where it started. Returns `None` if the
+/// config has no complete block for it.
+pub fn remove_managed_block(content: &str, name: &str) -> Option<(String, usize)> {
+    let (start_marker, end_marker) = block_markers(name);
+    let lines: Vec<&str> = content.lines().collect();
+    let start = lines.iter().position(|line| line.trim() == start_marker)?;
+    let end = start
+        + lines[start..]
+            .iter()
+            .position(|line| line.trim() == end_marker)?;
+    let start = if start > 0 && lines[start - 1].trim().is_empty() {
+        start - 1
+    } else {
+        start
+    };
+    Some((
+        join_lines(lines[..start].iter().chain(&lines[end + 1..])),
+        start,
+    ))
+}
+
+/// The blocks git-switch marked in SSH config text: the account name in
+/// each block's markers and the `IdentityFile` paths inside it.
+pub fn managed_blocks(content: &str) -> Vec<(String, Vec<String>)> {
+    let mut blocks = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let Some(name) = line.trim().strip_prefix("# >>> git-switch:") else {
+            continue;
+        };
+        let (_, end_marker) = block_markers(name);
+        let body: Vec<&str> = lines
+            .by_ref()
+            .take_while(|line| line.trim() != end_marker)
+            .collect();
+        blocks.push((name.to_string(), identity_files(&body.join("\n"))));
     }
+    blocks
+}
 
-    let file_content = fs::read_to_string(path)?;
-    let mut new_content = String::new();
-    let mut lines = file_content.lines().peekable();
-    let entry_header_check = format!("# {} GitHub Account", name);
-    // Ensure host_check matches the format used in update_ssh_config
-    let host_check = format!("Host github-{}", name.replace(' ', "_").to_lowercase());
+/// Every `IdentityFile` path in SSH config text, as written.
+pub fn identity_files(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            words
+                .next()
+                .is_some_and(|word| word.eq_ignore_ascii_case("IdentityFile"))
+                .then(|| words.next())
+                .flatten()
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+/// Removes an unmarked entry written by older versions: the header comment,
+/// the `Host <alias>` line and its options up to the next blank line, `Host`
+/// or `Match` line, or unindented comment. Returns the line where it started.
+pub fn remove_legacy_entry(content: &str, header: &str, alias: &str) -> Option<(String, usize)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let is_host_line = |line: &str| {
+        let mut words = line.split_whitespace();
+        words
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("host"))
+            && words.next() == Some(alias)
+            && words.next().is_none()
+    };
+    let start = lines
+        .windows(2)
+        .position(|pair| pair[0].trim() == header && is_host_line(pair[1]))?;
+    let mut end = start + 2;
+    while let Some(line) = lines.get(end) {
+        let keyword = line.split_whitespace().next().unwrap_or_default();
+        if line.trim().is_empty()
+            || keyword.eq_ignore_ascii_case("host")
+            || keyword.eq_ignore_ascii_case("match")
+            || line.starts_with('#')
+        {
+            break;
+        }
+        end += 1;
+    }
+    let start = if start > 0 && lines[start - 1].trim().is_empty() {
+        start - 1
+    } else {
+        start
+    };
+    Some((
+        join_lines(lines[..start].iter().chain(&lines[end..])),
+        start,
+    ))
+}
 
-    let mut skip_block = false;
+fn join_lines<'a>(lines: impl Iterator<Item = &'a &'a str>) -> String {
+    let mut joined = String::new();
+    for line in lines {
+        joined.push_str(line);
+        joined.push('\n');
+    }
+    joined
+}
 
-    while let Some(line) = lines.next() {
-        if line.trim() == entry_header_check {
-            // Clippy fix: unnecessary_map_or
-            if lines
-                .peek()
-                .is_some_and(|next_line| next_line.trim().starts_with(&host_check))
-            {
-                skip_block = true;
-                // Skip the header line and the next 3 lines of the config block
-                for _ in 0..3 {
-                    lines.next();
-                }
-                continue;
-            }
+/// Removes every entry of the account from SSH config text: its marked
+/// block and any unmarked entries from older versions. Also returns the line
+/// where the first removed entry was, so it can be replaced in place.
+pub fn remove_account_entries(content: &str, account: &Account) -> (String, Option<usize>) {
+    let mut content = content.to_string();
+    let mut position: Option<usize> = None;
+    // An entry removed before `position` starts earlier; one removed after it doesn't move it
+    let mut track = |start: usize| position = Some(position.map_or(start, |pos| pos.min(start)));
+    while let Some((updated, start)) = remove_managed_block(&content, &account.name) {
+        content = updated;
+        track(start);
+    }
+    for host in account.hosts() {
+        let header = config_entry_header(host, &account.name);
+        let alias = host_alias(host, &account.name);
+        while let Some((updated, start)) = remove_legacy_entry(&content, &header, &alias) {
+            content = updated;
+            track(start);
         }
+    }
+    (content, position)
+}
 
-        if !skip_block {
-            new_content.push_str(line);
-            new_content.push('\n');
-        } else {
-            // If we were skipping, and the current line is not empty or not a comment,
-            // it means the block ended.
-            if !line.trim().is_empty() && !line.trim().starts_with('#') {
-                skip_block = false;
-                new_content.push_str(line);
-                new_content.push('\n');
-            } else if line.trim().is_empty() || line.trim().starts_with('#') {
-                // If it's an empty line or a new comment (potentially a new block's header)
-                skip_block = false;
-                // Clippy fix: unnecessary_map_or
-                if !(line.trim() == entry_header_check
-                    && lines
-                        .peek()
-                        .is_some_and(|next_line| next_line.trim().starts_with(&host_check)))
-                {
-                    new_content.push_str(line);
-                    new_content.push('\n');
-                }
-            }
+/// Replaces the account's entries with its current managed block, where
+/// the first of them was, or appends the block if there were none.
+pub fn upsert_account_entries(content: &str, account: &Account) -> String {
+    let (content, position) = remove_account_entries(content, account);
+    let block = managed_block(account);
+    match position {
+        Some(position) => {
+            let lines: Vec<&str> = content.lines().collect();
+            let (before, after) = lines.split_at(position.min(lines.len()));
+            format!(
+                "{}{}{}",
+                join_lines(before.iter()),
+                block,
+                join_lines(after.iter())
+            )
         }
+        None => format!("{}{}", content, block),
     }
+}
 
-    // Clean up excessive newlines at the end
-    while new_content.ends_with("\n\n\n") {
-        // Handles cases with multiple blank lines from deleted blocks
-        new_content.pop();
+/// Writes the account's SSH config entries, replacing existing ones in place.
+/// In the `sshcommand` mode nothing is written: git reaches the hosts with
+/// `core.sshCommand` instead.
+pub fn update_ssh_config(account: &Account) -> io::Result<()> {
+    if account.uses_https() {
+        // No key, so no entries; drop any left from when it used SSH
+        return remove_ssh_config_entries(account);
+    }
+    if load_settings().mode == SwitchMode::SshCommand {
+        return Ok(());
     }
-    if new_content.ends_with("\n\n") && new_content.trim().is_empty() {
-        // If only two newlines left and content is otherwise empty
-        new_content.clear();
-    } else if new_content.ends_with("\n\n") {
-        new_content.pop(); // Reduce to a single trailing newline if content exists
+    let expanded_path_str = get_ssh_config_path();
+    let path = Path::new(&expanded_path_str);
+
+    // Create directory if it doesn't exist
+    if let Some(parent) = path.parent()
+        && !parent.exists()
+    {
+        fs::create_dir_all(parent)?;
     }
 
-    if new_content == "\n" && file_content.lines().count() > 1 { // Avoid clearing if it was a single line file to begin with
-        // Only clear if it was meant to be empty after removal
-    } else if new_content.trim().is_empty() && !file_content.trim().is_empty() {
-        new_content.clear(); // If all content was removed, make it fully empty
+    let _lock = files::lock(path)?;
+    let previous = fs::read_to_string(path).ok();
+    let updated = upsert_account_entries(previous.as_deref().unwrap_or(""), account);
+    if previous.as_deref() == Some(updated.as_str()) {
+        say!(
+            "✅ SSH config for account '{}' is up to date.",
+            account.name
+        );
+        return Ok(());
+    }
+    files::write_with_backup(path, &updated)?;
+    events::file_modified(path, previous.as_deref(), Some(&updated));
+    say!("✅ Updated SSH config for account: {}", account.name);
+    Ok(())
+}
+
+/// Removes the SSH config entries of an account, keeping everything else.
+pub fn remove_ssh_config_entries(account: &Account) -> io::Result<()> {
+    let config_path_str = get_ssh_config_path();
+    let path = Path::new(&config_path_str);
+    let _lock = files::lock(path)?;
+    let Ok(file_content) = fs::read_to_string(path) else {
+        say!(
+            "ℹ️ SSH config file not found, nothing to remove for account '{}'.",
+            account.name
+        );
+        return Ok(());
+    };
+
+    let (new_content, _) = remove_account_entries(&file_content, account);
+    if new_content == file_content {
+        return Ok(());
     }
+    files::write_with_backup(path, &new_content)?;
+    events::file_modified(path, Some(&file_content), Some(&new_content));
+    say!("🗑️ SSH config entry for '{}' removed.", account.name);
+    Ok(())
+}
 
-    let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
-    file.write_all(new_content.as_bytes())?;
-    println!("🗑️ SSH config entry for '{}' removed.", name);
+/// Removes the marked blocks of accounts that no longer exist.
+pub fn remove_managed_blocks(names: &[String]) -> io::Result<()> {
+    let config_path_str = get_ssh_config_path();
+    let path = Path::new(&config_path_str);
+    let _lock = files::lock(path)?;
+    let Ok(file_content) = fs::read_to_string(path) else {
+        return Ok(());
+    };
+    let mut new_content = file_content.clone();
+    for name in names {
+        while let Some((updated, _)) = remove_managed_block(&new_content, name) {
+            new_content = updated;
+        }
+    }
+    if new_content == file_content {
+        return Ok(());
+    }
+    files::write_with_backup(path, &new_content)?;
+    events::file_modified(path, Some(&file_content), Some(&new_content));
+    for name in names {
+        say!("🗑️ SSH config entry for '{}' removed.", name);
+    }
     Ok(())
 }
 
@@ -194,55 +865,334 @@ pub fn delete_ssh_key_files(identity_file_base: &str) -> io::Result<()> {
     let public_key_path_str = format!("{}.pub", base_path_str);
     let public_key_path = Path::new(&public_key_path_str);
 
+    // Key material is never hashed into the event stream
     if private_key_path.exists() {
         fs::remove_file(private_key_path)?;
-        println!("🗑️ Deleted private SSH key: {}", private_key_path.display());
+        events::file_modified(private_key_path, Some(""), None);
+        say!("🗑️ Deleted private SSH key: {}", private_key_path.display());
     }
 
     if public_key_path.exists() {
         fs::remove_file(public_key_path)?;
-        println!("🗑️ Deleted public SSH key: {}", public_key_path.display());
+        events::file_modified(public_key_path, Some(""), None);
+        say!("🗑️ Deleted public SSH key: {}", public_key_path.display());
+    }
+
+    for extra in [
+        attestation_path(&base_path_str),
+        challenge_path(&base_path_str),
+    ] {
+        let path = Path::new(&extra);
+        if path.exists() {
+            fs::remove_file(path)?;
+            events::file_modified(path, Some(""), None);
+        }
     }
 
     Ok(())
 }
 
-pub fn add_ssh_key(key_path: &str) -> bool {
-    let home = dirs::home_dir().expect("Could not determine home directory");
-    let expanded_path_str = if key_path.starts_with('~') {
-        home.join(&key_path[2..]).to_string_lossy().into_owned()
-    } else {
-        key_path.to_string()
+/// Path of a new account's key: `dir` (or the `key_dir` setting) joined with
+/// the `key_name` template, filling in `{account}`, `{type}` and `{host}`.
+pub fn derived_key_path(
+    settings: &Settings,
+    dir: Option<&str>,
+    account_name: &str,
+    key_type: KeyType,
+    host: &str,
+) -> String {
+    let fill = |template: &str| {
+        template
+            .replace("{account}", &account_name.replace(' ', "_").to_lowercase())
+            .replace("{type}", &key_type.file_stem())
+            .replace("{host}", host)
     };
+    format!(
+        "{}/{}",
+        fill(dir.unwrap_or(settings.key_dir()).trim_end_matches('/')),
+        fill(settings.key_name())
+    )
+}
+
+/// Comment of a new account key from the `key_comment` template, so the key
+/// can be told apart on the provider's key page and in `ssh-add -l`.
+pub fn key_comment(settings: &Settings, account_name: &str, email: &str) -> String {
+    let template = settings.key_comment();
+    let comment = template
+        .replace("{email}", email)
+        .replace("{account}", account_name);
+    // Only ask for the hostname when the template uses it
+    if template.contains("{hostname}") {
+        comment.replace("{hostname}", &hostname())
+    } else {
+        comment
+    }
+}
+
+/// Returns the key path for a renamed account, if the key was named after
+/// the account (`~/.ssh/id_ed25519_<name>`).
+pub fn renamed_key_path(identity_file: &str, old_name: &str, new_name: &str) -> Option<String> {
+    let slug = |name: &str| name.replace(' ', "_").to_lowercase();
+    identity_file
+        .strip_suffix(&format!("_{}", slug(old_name)))
+        .map(|base| format!("{}_{}", base, slug(new_name)))
+}
+
+/// Renames a key and its companion files (public key, attestation and
+/// challenge). Fails without renaming anything if a target already exists.
+pub fn rename_ssh_key_files(old_base: &str, new_base: &str) -> io::Result<()> {
+    let suffixes = ["", ".pub", ".attestation", ".challenge"];
+    let pairs: Vec<(String, String)> = suffixes
+        .iter()
+        .map(|suffix| {
+            (
+                format!("{}{}", shellexpand::tilde(old_base), suffix),
+                format!("{}{}", shellexpand::tilde(new_base), suffix),
+            )
+        })
+        .filter(|(old, _)| Path::new(old).exists())
+        .collect();
+    if let Some((_, existing)) = pairs.iter().find(|(_, new)| Path::new(new).exists()) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", existing),
+        ));
+    }
+    for (old, new) in &pairs {
+        fs::rename(old, new)?;
+        say!("🔑 Renamed {} to {}", old, new);
+    }
+    Ok(())
+}
+
+/// Whether `ssh-add` is Apple's, which takes `--apple-use-keychain`;
+/// Homebrew's and MacPorts' OpenSSH reject it. Probed once per process.
+fn apple_keychain_supported() -> bool {
+    static SUPPORTED: OnceLock<bool> = OnceLock::new();
+    *SUPPORTED.get_or_init(|| {
+        cfg!(target_os = "macos")
+            && command_output("ssh-add", &["--apple-use-keychain", "-l"])
+                .is_ok_and(|out| !rejects_option(&String::from_utf8_lossy(&out.stderr)))
+    })
+}
+
+/// Whether `ssh-add` stderr is a complaint about an unknown option.
+pub(crate) fn rejects_option(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    [
+        "usage:",
+        "illegal option",
+        "unknown option",
+        "unrecognized option",
+        "invalid option",
+    ]
+    .iter()
+    .any(|complaint| stderr.contains(complaint))
+}
+
+/// `ssh-add` arguments that add `path`, for `lifetime` seconds if given.
+/// With Apple's ssh-add (`apple_keychain`) the key's passphrase is also
+/// stored in (or read from) the login keychain, so it is asked for once.
+pub(crate) fn ssh_add_args(path: &str, lifetime: Option<u64>, apple_keychain: bool) -> Vec<String> {
+    let mut args = Vec::new();
+    if apple_keychain {
+        args.push("--apple-use-keychain".to_string());
+    }
+    if let Some(lifetime) = lifetime {
+        args.extend(["-t".to_string(), lifetime.to_string()]);
+    }
+    args.push(path.to_string());
+    args
+}
+
+/// Adds a key to the agent, for `lifetime` seconds if given (`ssh-add -t`).
+pub fn add_ssh_key(key_path: &str, lifetime: Option<u64>) -> Result<()> {
+    let expanded_path_str = shellexpand::tilde(key_path).into_owned();
     let expanded_path = Path::new(&expanded_path_str);
 
     if !expanded_path.exists() {
-        println!("❌ SSH key not found: {}", expanded_path.display());
-        return false;
+        return Err(GitSwitchError::InvalidInput(format!(
+            "SSH key not found: {}",
+            expanded_path.display()
+        )));
     }
 
-    println!("🔑 Adding SSH key to agent: {}", expanded_path.display());
+    say!("🔑 Adding SSH key to agent: {}", expanded_path.display());
+    let hardware_backed = is_security_key(&expanded_path_str);
+    if hardware_backed {
+        say!("👆 The key lives on a security key: touch it when git connects.");
+    }
     // On Windows, ssh-add might require the agent to be running.
     // `start-ssh-agent.cmd` is often used, or it's part of Git for Windows.
     // For cross-platform simplicity, directly calling ssh-add.
     // Users on Windows might need to ensure their agent is active.
-    let status = run_command(
-        "ssh-add",
-        &[expanded_path.to_str().expect("Invalid path for SSH key")],
-    );
-    if !status {
-        eprintln!(
-            "❌ Failed to add SSH key. Ensure ssh-agent is running and the key is not password protected or password was entered if prompted."
+    let args = ssh_add_args(&expanded_path_str, lifetime, apple_keychain_supported());
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = run_command("ssh-add", &args);
+    if result.is_ok() {
+        record_key_lifetime(key_path, lifetime);
+    } else {
+        problem!(
+            "⚠️ Failed to add SSH key. Ensure ssh-agent is running and the key is not password protected or password was entered if prompted."
         );
-        if cfg!(windows) {
-            eprintln!(
-                "Hint: On Windows, you might need to run `start-ssh-agent` or ensure the OpenSSH Authentication Agent service is running."
+        if hardware_backed {
+            problem!(
+                "Hint: the agent needs OpenSSH 8.2 or later with security key support; for a key with verify-required, enter the PIN when asked."
             );
+        } else if cfg!(windows) {
+            problem!("Hint: {}", WINDOWS_AGENT_HINT);
         } else {
-            eprintln!(
+            problem!(
                 "Hint: On Unix-like systems, try `eval $(ssh-agent -s)` then `ssh-add your_key_path` manually to debug."
             );
         }
     }
-    status
+    result.map(drop)
+}
+
+/// Environment variable that makes the git-switch binary act as an
+/// `SSH_ASKPASS` program: it prints the variable's value and exits.
+pub const ASKPASS_VAR: &str = "GIT_SWITCH_ASKPASS_PASSPHRASE";
+
+/// Adds a passphrase-protected key to the agent without prompting, by
+/// running `ssh-add` with the git-switch binary as its `SSH_ASKPASS`.
+pub fn add_ssh_key_with_passphrase(
+    key_path: &str,
+    passphrase: &str,
+    lifetime: Option<u64>,
+) -> Result<()> {
+    let expanded_path_str = shellexpand::tilde(key_path).into_owned();
+    let exe = std::env::current_exe().context("Failed to locate the git-switch binary")?;
+    say!("🔑 Adding SSH key to agent: {}", expanded_path_str);
+    let args = ssh_add_args(&expanded_path_str, lifetime, apple_keychain_supported());
+    let output = Command::new("ssh-add")
+        .args(&args)
+        .env("SSH_ASKPASS", exe)
+        .env("SSH_ASKPASS_REQUIRE", "force")
+        // Older OpenSSH only uses SSH_ASKPASS when DISPLAY is set
+        .env(
+            "DISPLAY",
+            std::env::var("DISPLAY").unwrap_or_else(|_| ":0".into()),
+        )
+        .env(ASKPASS_VAR, passphrase)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|source| GitSwitchError::CommandNotFound {
+            command: "ssh-add".to_string(),
+            source,
+        })?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    events::subprocess("ssh-add", &args, output.status.code());
+    if !output.status.success() {
+        return Err(GitSwitchError::CommandFailed {
+            command: format!("ssh-add {}", args.join(" ")),
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    record_key_lifetime(key_path, lifetime);
+    Ok(())
+}
+
+/// Where the expiry of keys added with a lifetime is kept, since the agent
+/// doesn't report it: `<fingerprint> <expiry>` lines, in Unix seconds.
+fn key_expiries_path() -> PathBuf {
+    get_data_dir().join("agent-expiry")
+}
+
+fn read_key_expiries() -> Vec<(String, u64)> {
+    fs::read_to_string(key_expiries_path())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (fingerprint, expiry) = line.split_once(' ')?;
+            Some((fingerprint.to_string(), expiry.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Remembers when a key just added leaves the agent. Adding a key again
+/// replaces its lifetime, so `None` forgets an earlier one.
+fn record_key_lifetime(identity_file: &str, lifetime: Option<u64>) {
+    let Some(fingerprint) = key_fingerprint(identity_file) else {
+        return;
+    };
+    let now = now_secs();
+    let mut expiries: Vec<(String, u64)> = read_key_expiries()
+        .into_iter()
+        .filter(|(known, expiry)| *known != fingerprint && *expiry > now)
+        .collect();
+    if let Some(lifetime) = lifetime {
+        expiries.push((fingerprint, now + lifetime));
+    }
+    let content: String = expiries
+        .iter()
+        .map(|(fingerprint, expiry)| format!("{} {}\n", fingerprint, expiry))
+        .collect();
+    if let Err(e) = files::write_atomic(&key_expiries_path(), &content) {
+        crate::debug!("Couldn't record the key's lifetime: {}", e);
+    }
+}
+
+/// Seconds until a loaded key leaves the agent, if it was added with a
+/// lifetime.
+pub fn key_time_left(fingerprint: &str) -> Option<u64> {
+    let now = now_secs();
+    read_key_expiries()
+        .into_iter()
+        .find(|(known, _)| known == fingerprint)
+        .and_then(|(_, expiry)| expiry.checked_sub(now))
+        .filter(|left| *left > 0)
+}
+
+/// Changes a key's passphrase interactively with `ssh-keygen -p`.
+pub fn change_key_passphrase(identity_file: &str) -> Result<()> {
+    let path = shellexpand::tilde(identity_file).into_owned();
+    if !Path::new(&path).exists() {
+        return Err(GitSwitchError::InvalidInput(format!(
+            "SSH key not found: {}",
+            path
+        )));
+    }
+    run_command("ssh-keygen", &["-p", "-f", &path])?;
+    Ok(())
+}
+
+/// Changes a key's passphrase from `old` to `new` without prompting, for
+/// keys whose passphrase is stored in the OS keyring.
+pub fn replace_key_passphrase(identity_file: &str, old: &str, new: &str) -> Result<()> {
+    let path = shellexpand::tilde(identity_file).into_owned();
+    let args = ["-p", "-f", &path, "-P", old, "-N", new];
+    let output = Command::new("ssh-keygen")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|source| GitSwitchError::CommandNotFound {
+            command: "ssh-keygen".to_string(),
+            source,
+        })?;
+    // Never record the passphrases in the event stream
+    events::subprocess(
+        "ssh-keygen",
+        &["-p", "-f", &path, "-P", "<redacted>", "-N", "<redacted>"],
+        output.status.code(),
+    );
+    if !output.status.success() {
+        return Err(GitSwitchError::CommandFailed {
+            command: "ssh-keygen -p".to_string(),
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Replaces a key in the agent after its passphrase changed. With Apple's
+/// ssh-add the key is re-added with `--apple-use-keychain`, which overwrites the
+/// passphrase stored in the login keychain.
+pub fn readd_ssh_key(identity_file: &str, lifetime: Option<u64>) -> Result<()> {
+    let path = shellexpand::tilde(identity_file).into_owned();
+    // Not loaded is fine; the agent just reports an error we don't need
+    let _ = command_output("ssh-add", &["-d", &path]);
+    add_ssh_key(identity_file, lifetime)
 }
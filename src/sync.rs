@@ -0,0 +1,199 @@
+//! Keeping accounts in step across machines through a private git
+//! repository. `sync setup` clones it into `~/.git-switch/sync`; the
+//! repository holds one file, `accounts.toml`, in the accounts file format.
+//! Private keys are never synced.
+//!
+//! Changes are merged per account name against the version this machine
+//! synced last (kept in the local ref `refs/git-switch/synced`), so edits to
+//! different accounts on different machines both survive.
+
+use crate::config::{Account, accounts_from_toml, accounts_to_toml, get_data_dir};
+use crate::error::{GitSwitchError, IoContext, Result};
+use crate::utils::{command_output, hostname};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File in the sync repository that holds the accounts.
+pub const SYNC_FILE: &str = "accounts.toml";
+
+/// Local ref marking the commit this machine last synced with.
+const SYNCED_REF: &str = "refs/git-switch/synced";
+
+/// Where the sync repository is cloned.
+pub fn sync_dir() -> PathBuf {
+    get_data_dir().join("sync")
+}
+
+/// Which side wins when both changed the same account.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Prefer {
+    Local,
+    Remote,
+}
+
+/// Result of [`merge_accounts`].
+#[derive(Debug, Default, PartialEq)]
+pub struct Merge {
+    pub accounts: Vec<Account>,
+    /// Names of accounts both sides changed differently.
+    pub conflicts: Vec<String>,
+}
+
+/// Three-way merge of account lists by name: a side's change (including
+/// adding or removing an account) is taken unless the other side changed
+/// the same account too, in which case `prefer` decides. Local order is
+/// kept; accounts only the remote has are appended.
+pub fn merge_accounts(
+    base: &[Account],
+    local: &[Account],
+    remote: &[Account],
+    prefer: Prefer,
+) -> Merge {
+    let find = |list: &[Account], name: &str| list.iter().find(|acc| acc.name == name).cloned();
+    let mut names: Vec<&str> = local.iter().map(|acc| acc.name.as_str()).collect();
+    for account in remote {
+        if !names.contains(&account.name.as_str()) {
+            names.push(&account.name);
+        }
+    }
+
+    let mut merge = Merge::default();
+    for name in names {
+        let (base, local, remote) = (find(base, name), find(local, name), find(remote, name));
+        let merged = if local == remote || remote == base {
+            local
+        } else if local == base {
+            remote
+        } else {
+            merge.conflicts.push(name.to_string());
+            match prefer {
+                Prefer::Local => local,
+                Prefer::Remote => remote,
+            }
+        };
+        merge.accounts.extend(merged);
+    }
+    merge
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let dir = dir.to_string_lossy();
+    let mut full_args = vec!["-C", dir.as_ref()];
+    full_args.extend(args);
+    let output =
+        command_output("git", &full_args).map_err(|source| GitSwitchError::CommandNotFound {
+            command: "git".to_string(),
+            source,
+        })?;
+    if !output.status.success() {
+        return Err(GitSwitchError::CommandFailed {
+            command: format!("git {}", args.join(" ")),
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The sync repository, once `sync setup` has cloned it.
+pub struct SyncRepo {
+    dir: PathBuf,
+}
+
+impl SyncRepo {
+    /// Clones `url` into [`sync_dir`]. The repository may be empty.
+    pub fn setup(url: &str) -> Result<SyncRepo> {
+        let dir = sync_dir();
+        if dir.exists() {
+            return Err(GitSwitchError::InvalidInput(format!(
+                "Sync is already set up in {}; delete it to use another repository.",
+                dir.display()
+            )));
+        }
+        let parent = dir.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+        git(parent, &["clone", "--quiet", url, &dir.to_string_lossy()])?;
+        Ok(SyncRepo { dir })
+    }
+
+    /// Opens the repository `sync setup` cloned.
+    pub fn open() -> Result<SyncRepo> {
+        let dir = sync_dir();
+        if !dir.join(".git").exists() {
+            return Err(GitSwitchError::InvalidInput(
+                "Sync isn't set up; run `git-switch sync setup <git-url>` first.".to_string(),
+            ));
+        }
+        Ok(SyncRepo { dir })
+    }
+
+    pub fn url(&self) -> String {
+        git(&self.dir, &["remote", "get-url", "origin"]).unwrap_or_default()
+    }
+
+    fn branch(&self) -> Result<String> {
+        git(&self.dir, &["symbolic-ref", "--short", "HEAD"])
+    }
+
+    /// Accounts in `SYNC_FILE` at `rev`; none if the revision or file doesn't exist.
+    fn accounts_at(&self, rev: &str) -> Result<Vec<Account>> {
+        let Ok(content) = git(&self.dir, &["show", &format!("{}:{}", rev, SYNC_FILE)]) else {
+            return Ok(Vec::new());
+        };
+        accounts_from_toml(&content).map_err(|e| {
+            GitSwitchError::InvalidInput(format!("{} in the sync repository: {}", SYNC_FILE, e))
+        })
+    }
+
+    /// Fetches and returns the accounts as this machine last synced them
+    /// (none before the first sync) and as they are on the remote now.
+    pub fn fetch(&self) -> Result<(Vec<Account>, Vec<Account>)> {
+        git(&self.dir, &["fetch", "--quiet", "origin"])?;
+        let remote = format!("origin/{}", self.branch()?);
+        Ok((self.accounts_at(SYNCED_REF)?, self.accounts_at(&remote)?))
+    }
+
+    /// Moves the clone to the fetched remote state and marks it synced.
+    pub fn reset_to_remote(&self) -> Result<()> {
+        let remote = format!("origin/{}", self.branch()?);
+        if git(&self.dir, &["rev-parse", "--verify", "--quiet", &remote]).is_ok() {
+            git(&self.dir, &["reset", "--quiet", "--hard", &remote])?;
+            git(&self.dir, &["update-ref", SYNCED_REF, "HEAD"])?;
+        }
+        Ok(())
+    }
+
+    /// Commits `accounts` on top of the fetched remote state and pushes.
+    /// Returns false if there was nothing to push.
+    pub fn push(&self, accounts: &[Account]) -> Result<bool> {
+        self.reset_to_remote()?;
+        let content = accounts_to_toml(accounts).map_err(GitSwitchError::InvalidInput)?;
+        let path = self.dir.join(SYNC_FILE);
+        if fs::read_to_string(&path).ok().as_deref() == Some(content.as_str()) {
+            return Ok(false);
+        }
+        fs::write(&path, content).context(format!("Failed to write {}", path.display()))?;
+        let host = hostname();
+        git(&self.dir, &["add", SYNC_FILE])?;
+        git(
+            &self.dir,
+            &[
+                "-c",
+                "user.name=git-switch",
+                "-c",
+                &format!("user.email=git-switch@{}", host),
+                "commit",
+                "--quiet",
+                "-m",
+                &format!("Update accounts from {}", host),
+            ],
+        )?;
+        let branch = self.branch()?;
+        git(
+            &self.dir,
+            &["push", "--quiet", "origin", &format!("HEAD:{}", branch)],
+        )?;
+        git(&self.dir, &["update-ref", SYNCED_REF, "HEAD"])?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,294 @@
+//! Hermetic harness for running the git-switch binary in tests.
+//!
+//! Every [`Sandbox`] gets its own home directory, an empty environment (only
+//! `PATH` is kept), a private ssh-agent and a git that ignores the system
+//! config, so tests run in parallel and never touch the developer's accounts,
+//! `~/.ssh` or global git config.
+//!
+//! The binary under test is the path in `GIT_SWITCH_BIN` or else the one
+//! Cargo built for this package — packagers can point `GIT_SWITCH_BIN` at an
+//! installed binary and run `cargo test --test integration_tests` as a smoke
+//! test. Other crates get this module with the `testkit` feature.
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+/// Path of the git-switch binary under test: `GIT_SWITCH_BIN`, the binary
+/// Cargo built for this package's integration tests, or `git_switch` next to
+/// the running test's `deps` directory.
+pub fn binary() -> PathBuf {
+    if let Some(path) = std::env::var_os("GIT_SWITCH_BIN") {
+        return PathBuf::from(path);
+    }
+    if let Some(path) = option_env!("CARGO_BIN_EXE_git_switch") {
+        return PathBuf::from(path);
+    }
+    let exe = std::env::current_exe().expect("Failed to locate the test binary");
+    let dir = exe.parent().expect("Test binary has no parent directory");
+    let dir = if dir.ends_with("deps") {
+        dir.parent().unwrap_or(dir)
+    } else {
+        dir
+    };
+    dir.join(format!("git_switch{}", std::env::consts::EXE_SUFFIX))
+}
+
+/// An isolated home directory and environment for git-switch and git.
+pub struct Sandbox {
+    dir: TempDir,
+    env: Vec<(String, String)>,
+    agent_pid: Option<String>,
+}
+
+impl Sandbox {
+    /// Creates a home with an empty `~/.ssh/config` and starts an ssh-agent
+    /// for it when one is installed.
+    pub fn new() -> Sandbox {
+        let dir = TempDir::new().expect("Failed to create sandbox directory");
+        let ssh_dir = dir.path().join(".ssh");
+        fs::create_dir_all(&ssh_dir).expect("Failed to create sandbox .ssh directory");
+        fs::write(ssh_dir.join("config"), "").expect("Failed to create sandbox SSH config");
+
+        let home = dir.path().to_string_lossy().into_owned();
+        let mut env = vec![
+            ("HOME".to_string(), home.clone()),
+            ("USERPROFILE".to_string(), home.clone()),
+            ("XDG_CONFIG_HOME".to_string(), format!("{}/.config", home)),
+            ("GIT_CONFIG_NOSYSTEM".to_string(), "1".to_string()),
+            ("RUST_BACKTRACE".to_string(), "1".to_string()),
+        ];
+        if let Some(path) = std::env::var_os("PATH") {
+            env.push(("PATH".to_string(), path.to_string_lossy().into_owned()));
+        }
+
+        let mut sandbox = Sandbox {
+            dir,
+            env,
+            agent_pid: None,
+        };
+        sandbox.start_agent();
+        sandbox
+    }
+
+    fn start_agent(&mut self) {
+        let socket = self.dir.path().join("agent.sock");
+        let Ok(output) = Command::new("ssh-agent")
+            .args(["-s", "-a"])
+            .arg(&socket)
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .output()
+        else {
+            return;
+        };
+        // Output looks like `SSH_AGENT_PID=1234; export SSH_AGENT_PID;`
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        self.agent_pid = stdout
+            .split(';')
+            .find_map(|part| part.trim().strip_prefix("SSH_AGENT_PID="))
+            .map(str::to_string);
+        if self.agent_pid.is_some() {
+            self.env.push((
+                "SSH_AUTH_SOCK".to_string(),
+                socket.to_string_lossy().into_owned(),
+            ));
+        }
+    }
+
+    /// The sandbox's home directory.
+    pub fn home(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// A path inside the home directory.
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.dir.path().join(relative)
+    }
+
+    /// Reads a file inside the home directory, or an empty string.
+    pub fn read(&self, relative: &str) -> String {
+        fs::read_to_string(self.path(relative)).unwrap_or_default()
+    }
+
+    /// Sets an environment variable for every command run in the sandbox.
+    pub fn env(mut self, key: &str, value: &str) -> Sandbox {
+        self.env.retain(|(k, _)| k != key);
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Builds a git-switch invocation.
+    pub fn cmd(&self, args: &[&str]) -> Cmd {
+        self.program(binary(), args)
+    }
+
+    /// Builds an invocation of another program, e.g. `git`.
+    pub fn program(&self, program: impl AsRef<std::ffi::OsStr>, args: &[&str]) -> Cmd {
+        let mut command = Command::new(program.as_ref());
+        command
+            .args(args)
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .current_dir(self.dir.path());
+        Cmd {
+            command,
+            label: format!("{} {}", program.as_ref().to_string_lossy(), args.join(" ")),
+            stdin: None,
+        }
+    }
+
+    /// Runs git-switch with no input.
+    pub fn run(&self, args: &[&str]) -> Outcome {
+        self.cmd(args).run()
+    }
+
+    /// Runs git inside the sandbox.
+    pub fn git(&self, args: &[&str]) -> Outcome {
+        self.program("git", args).run()
+    }
+}
+
+impl Default for Sandbox {
+    fn default() -> Sandbox {
+        Sandbox::new()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        if let Some(pid) = &self.agent_pid {
+            let _ = Command::new("kill")
+                .arg(pid)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+    }
+}
+
+/// A command to run in a sandbox.
+pub struct Cmd {
+    command: Command,
+    label: String,
+    stdin: Option<String>,
+}
+
+impl Cmd {
+    pub fn env(mut self, key: &str, value: &str) -> Cmd {
+        self.command.env(key, value);
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl AsRef<Path>) -> Cmd {
+        self.command.current_dir(dir);
+        self
+    }
+
+    /// Text written to the command's stdin (stdin is empty otherwise, so
+    /// prompts read end-of-file instead of hanging).
+    pub fn stdin(mut self, input: &str) -> Cmd {
+        self.stdin = Some(input.to_string());
+        self
+    }
+
+    pub fn run(mut self) -> Outcome {
+        self.command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = self
+            .command
+            .spawn()
+            .unwrap_or_else(|e| panic!("Failed to run `{}`: {}", self.label, e));
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(self.stdin.unwrap_or_default().as_bytes());
+        }
+        let output = child
+            .wait_with_output()
+            .unwrap_or_else(|e| panic!("Failed to wait for `{}`: {}", self.label, e));
+        Outcome {
+            label: self.label,
+            output,
+        }
+    }
+}
+
+/// The result of a command, with assertions that print its full output on
+/// failure.
+pub struct Outcome {
+    pub label: String,
+    pub output: Output,
+}
+
+impl Outcome {
+    pub fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.output.stdout).into_owned()
+    }
+
+    pub fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.output.stderr).into_owned()
+    }
+
+    fn report(&self, expectation: &str) -> String {
+        format!(
+            "`{}` {}\nstatus: {}\nstdout:\n{}\nstderr:\n{}",
+            self.label,
+            expectation,
+            self.output.status,
+            self.stdout(),
+            self.stderr()
+        )
+    }
+
+    #[track_caller]
+    pub fn success(&self) -> &Outcome {
+        assert!(self.output.status.success(), "{}", self.report("failed"));
+        self
+    }
+
+    /// Asserts the exit code, e.g. one of `GitSwitchError::exit_code`.
+    #[track_caller]
+    pub fn code(&self, expected: i32) -> &Outcome {
+        assert_eq!(
+            self.output.status.code(),
+            Some(expected),
+            "{}",
+            self.report(&format!("did not exit with {}", expected))
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn failure(&self) -> &Outcome {
+        assert!(
+            !self.output.status.success(),
+            "{}",
+            self.report("succeeded unexpectedly")
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn stdout_contains(&self, text: &str) -> &Outcome {
+        assert!(
+            self.stdout().contains(text),
+            "{}",
+            self.report(&format!("did not print '{}'", text))
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn stderr_contains(&self, text: &str) -> &Outcome {
+        assert!(
+            self.stderr().contains(text),
+            "{}",
+            self.report(&format!("did not print '{}' on stderr", text))
+        );
+        self
+    }
+}
@@ -0,0 +1,77 @@
+use crate::bundle::{Bundle, BundleFormat, KeyMaterial, portable_path};
+use crate::config::Account;
+use crate::rules::Rule;
+
+fn bundle() -> Bundle {
+    let account = Account {
+        name: "work".to_string(),
+        username: "Jane Doe".to_string(),
+        email: "jane@example.com".to_string(),
+        ssh_key: "~/.ssh/id_work".to_string(),
+        host: "github.com".to_string(),
+        ..Default::default()
+    };
+    let rule = Rule {
+        pattern: "github.com/mycompany/*".to_string(),
+        account: "work".to_string(),
+    };
+    let mut bundle = Bundle::new(vec![account], vec![rule]);
+    bundle.keys.push(KeyMaterial {
+        account: "work".to_string(),
+        public_key: "ssh-ed25519 AAAA jane@example.com".to_string(),
+        private_key: "-----BEGIN AGE ENCRYPTED FILE-----\n...\n".to_string(),
+    });
+    bundle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_round_trips_in_both_formats() {
+        let bundle = bundle();
+        for format in [BundleFormat::Toml, BundleFormat::Json] {
+            let text = bundle.to_text(format).unwrap();
+            assert_eq!(Bundle::parse(&text).unwrap(), bundle);
+        }
+        assert!(
+            bundle
+                .to_text(BundleFormat::Toml)
+                .unwrap()
+                .contains("[[account]]")
+        );
+
+        let newer = "version = 2\n";
+        assert!(Bundle::parse(newer).unwrap_err().contains("newer"));
+    }
+
+    #[test]
+    fn test_portable_path() {
+        assert_eq!(
+            portable_path("/home/jane/.ssh/id_work", "/home/jane"),
+            "~/.ssh/id_work"
+        );
+        assert_eq!(
+            portable_path("/home/janet/.ssh/id_work", "/home/jane"),
+            "/home/janet/.ssh/id_work"
+        );
+        assert_eq!(
+            portable_path("~/.ssh/id_work", "/home/jane"),
+            "~/.ssh/id_work"
+        );
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypt_round_trip() {
+        use crate::crypto::{self, Key};
+
+        let key = Key::Passphrase("correct horse".to_string());
+        let armored = crypto::encrypt(b"secret key", &key).unwrap();
+        assert!(crypto::is_encrypted(&armored));
+        assert_eq!(crypto::decrypt(&armored, &key).unwrap(), b"secret key");
+        let wrong = Key::Passphrase("wrong".to_string());
+        assert!(crypto::decrypt(&armored, &wrong).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use crate::cli::build_cli;
+use crate::completions::generate_completions;
+
+/// Helper function to render a completion script into a String
+fn render(shell: &str) -> String {
+    let mut buffer: Vec<u8> = Vec::new();
+    generate_completions(shell, &mut build_cli(), &mut buffer);
+    String::from_utf8(buffer).expect("Completion script is not valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nushell_completions_include_account_completer() {
+        let script = render("nushell");
+        assert!(script.contains("export extern \"git-switch use\""));
+        assert!(script.contains("name: string@\"nu-complete git-switch accounts\""));
+        assert!(script.contains("^git-switch list --names | lines"));
+        // Hidden arguments must not leak into completions
+        assert!(!script.contains("--names("));
+    }
+
+    #[test]
+    fn test_elvish_completions_cover_subcommands() {
+        let script = render("elvish");
+        assert!(script.contains("set edit:completion:arg-completer[git-switch]"));
+        assert!(script.contains("&'git-switch;use'= {\n            accounts"));
+        assert!(script.contains("cand completions 'Generate shell completion scripts'"));
+    }
+
+    #[test]
+    fn test_clap_shells_complete_account_names() {
+        let bash = render("bash");
+        assert!(bash.contains(
+            "\"use\"|\"remove\"|\"rename\"|\"test\"|\"verify\"|\"edit\"|\"key passphrase\""
+        ));
+        assert!(bash.contains("complete -F _git_switch_accounts"));
+        assert!(bash.contains("_git__switch \"$@\""));
+
+        let zsh = render("zsh");
+        assert!(
+            zsh.contains("'::name -- Name or username of the account to use:_git-switch_accounts'")
+        );
+        // The name of a new account in `add` is not completed
+        assert!(!zsh.contains("'Personal'\\'')\\:_git-switch_accounts"));
+        assert!(zsh.contains(":_default'"));
+
+        let fish = render("fish");
+        assert!(fish.contains(
+            "__fish_git_switch_using_subcommand remove; and test (count (commandline -opc)) -eq 2\" -f -a \"(git-switch list --names)\""
+        ));
+
+        let powershell = render("powershell");
+        assert!(powershell.contains("'git-switch;use' {\n            & 'git-switch' list --names"));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::config::Account;
+use crate::credential::{CredentialRequest, parse_insteadof_rules, resolve_account, token_host};
+use crate::git::{render_account_fragment, rewritten_remote_url};
+
+/// Helper function to build a test account
+fn account(name: &str, username: &str, credential_url: Option<&str>) -> Account {
+    Account {
+        name: name.to_string(),
+        username: username.to_string(),
+        email: format!("{}@example.com", username),
+        ssh_key: format!("~/.ssh/id_rsa_{}", name),
+        credential_url: credential_url.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request_with_port() {
+        let request = CredentialRequest::parse(
+            "protocol=https\nhost=git.corp.com:8443\npath=gitlab/team/repo.git\n\n",
+        );
+        assert_eq!(request.protocol, "https");
+        assert_eq!(request.host, "git.corp.com");
+        assert_eq!(request.port, Some(8443));
+        assert_eq!(request.path.as_deref(), Some("gitlab/team/repo.git"));
+    }
+
+    #[test]
+    fn test_resolve_managed_alias() {
+        let accounts = vec![
+            account("personal", "me", None),
+            account("work", "me-corp", None),
+        ];
+        let request = CredentialRequest::parse("protocol=https\nhost=github-work\n");
+        let resolved = resolve_account(&accounts, &request, &[]).expect("No account resolved");
+        assert_eq!(resolved.name, "work");
+    }
+
+    #[test]
+    fn test_resolve_enterprise_subpath_longest_prefix() {
+        let accounts = vec![
+            account("corp", "jdoe", Some("https://git.corp.com:8443/gitlab/")),
+            account(
+                "corp-infra",
+                "jdoe-infra",
+                Some("https://git.corp.com:8443/gitlab/infra"),
+            ),
+        ];
+        let request = CredentialRequest::parse(
+            "protocol=https\nhost=git.corp.com:8443\npath=gitlab/infra/tools.git\n",
+        );
+        let resolved = resolve_account(&accounts, &request, &[]).expect("No account resolved");
+        assert_eq!(resolved.name, "corp-infra");
+
+        // A different port is a different server
+        let other_port =
+            CredentialRequest::parse("protocol=https\nhost=git.corp.com\npath=gitlab/x.git\n");
+        assert!(resolve_account(&accounts, &other_port, &[]).is_none());
+    }
+
+    #[test]
+    fn test_resolve_insteadof_rewritten_url() {
+        let accounts = vec![account("work", "me-corp", None)];
+        let rules =
+            parse_insteadof_rules("url.https://mirror.corp.com/.insteadof https://github-work/\n");
+        assert_eq!(
+            rules,
+            vec![(
+                "https://mirror.corp.com/".to_string(),
+                "https://github-work/".to_string()
+            )]
+        );
+        let request =
+            CredentialRequest::parse("protocol=https\nhost=mirror.corp.com\npath=org/repo.git\n");
+        let resolved = resolve_account(&accounts, &request, &rules).expect("No account resolved");
+        assert_eq!(resolved.name, "work");
+    }
+
+    #[test]
+    fn test_resolve_alias_and_owner_on_non_default_host() {
+        let mut gitlab = account("lab", "labuser", None);
+        gitlab.host = "gitlab.com".to_string();
+        let accounts = vec![account("hub", "labuser", None), gitlab];
+
+        let alias = CredentialRequest::parse("protocol=https\nhost=gitlab-lab\n");
+        let resolved = resolve_account(&accounts, &alias, &[]).expect("No account resolved");
+        assert_eq!(resolved.name, "lab");
+
+        let owner =
+            CredentialRequest::parse("protocol=https\nhost=gitlab.com\npath=labuser/repo.git\n");
+        let resolved = resolve_account(&accounts, &owner, &[]).expect("No account resolved");
+        assert_eq!(resolved.name, "lab");
+    }
+
+    #[test]
+    fn test_https_account_token_only_goes_to_its_hosts() {
+        let mut corp = account("corp", "jdoe", Some("https://git.corp.com:8443/gitlab/"));
+        corp.extra_hosts = vec!["gitlab.com".to_string()];
+        let request = |input: &str| CredentialRequest::parse(input);
+
+        // SSH accounts never get a token
+        assert_eq!(
+            token_host(&corp, &request("protocol=https\nhost=github.com\n")),
+            None
+        );
+
+        corp.auth = Some("https".to_string());
+        let on_host = request("protocol=https\nhost=GitLab.com\npath=org/repo.git\n");
+        assert_eq!(token_host(&corp, &on_host), Some("gitlab.com"));
+        let enterprise = request("protocol=https\nhost=git.corp.com:8443\n");
+        assert_eq!(token_host(&corp, &enterprise), Some("github.com"));
+        assert_eq!(
+            token_host(&corp, &request("protocol=http\nhost=github.com\n")),
+            None
+        );
+        assert_eq!(
+            token_host(&corp, &request("protocol=https\nhost=evil.com\n")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_https_account_keeps_https_remotes_and_sets_its_helper() {
+        let mut hub = account("hub", "octo", None);
+        hub.auth = Some("https".to_string());
+        let accounts = vec![hub.clone()];
+        assert_eq!(
+            rewritten_remote_url(&hub, &accounts, "git@github.com:org/repo.git", true).as_deref(),
+            Some("https://octo@github.com/org/repo.git")
+        );
+
+        let fragment = render_account_fragment(&hub).expect("No fragment rendered");
+        assert!(fragment.contains("[credential \"https://github.com\"]\n\thelper =\n"));
+        assert!(fragment.contains("credential --account 'hub'\"\n\tusername = octo\n"));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::config::Account;
+use crate::doctor::{
+    Fix, Severity, check_alias_key_order, check_identity_shadowing, check_ssh_config,
+    duplicate_host_aliases, parse_config_entries, parse_effective_ssh_config, ssh_unreachable,
+};
+use crate::ssh::managed_block;
+
+fn account(name: &str, username: &str, email: &str) -> Account {
+    Account {
+        name: name.to_string(),
+        username: username.to_string(),
+        email: email.to_string(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_identity_in_two_files_offers_to_clean_the_unmanaged_one() {
+        let entries = parse_config_entries(
+            "global\tfile:/home/me/.config/git/config\tuser.email old@example.com\n\
+             global\tfile:/home/me/.gitconfig\tuser.name jdoe\n\
+             global\tfile:/home/me/.gitconfig\tuser.email jdoe@example.com\n",
+        );
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].file, "/home/me/.config/git/config");
+        assert_eq!(entries[0].value, "old@example.com");
+
+        let accounts = vec![account("personal", "jdoe", "jdoe@example.com")];
+        let findings = check_identity_shadowing(&entries, "/home/me/.gitconfig", &accounts);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(
+            findings[0].fix,
+            Some(Fix::UnsetConfig {
+                file: Some("/home/me/.config/git/config".to_string()),
+                keys: vec!["user.email".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_local_identity_is_only_flagged_without_a_matching_account() {
+        let entries = parse_config_entries(
+            "global\tfile:/home/me/.gitconfig\tuser.email jdoe@example.com\n\
+             local\tfile:.git/config\tuser.name ci\n\
+             local\tfile:.git/config\tuser.email ci@build.local\n",
+        );
+        let mut accounts = vec![account("personal", "jdoe", "jdoe@example.com")];
+        let findings = check_identity_shadowing(&entries, "/home/me/.gitconfig", &accounts);
+        assert!(matches!(
+            &findings[0].fix,
+            Some(Fix::UnsetConfig { file: None, .. })
+        ));
+
+        accounts.push(account("ci", "ci", "ci@build.local"));
+        let findings = check_identity_shadowing(&entries, "/home/me/.gitconfig", &accounts);
+        assert!(findings.iter().all(|finding| finding.fix.is_none()));
+    }
+
+    #[test]
+    fn test_wildcard_identity_file_and_agent_keys_are_flagged() {
+        let mut work = account("work", "workuser", "me@corp.com");
+        work.ssh_key = String::from("~/.ssh/id_ed25519_work");
+
+        let effective = parse_effective_ssh_config(
+            "host github-work\nidentitiesonly no\nidentityfile ~/.ssh/id_ed25519\nidentityfile ~/.ssh/id_ed25519_work\n",
+        );
+        assert_eq!(effective.identity_files.len(), 2);
+        let finding = check_alias_key_order(&work, "github-work", &effective, &[]);
+        assert_eq!(finding.severity, Severity::Problem);
+        assert!(finding.message.contains("~/.ssh/id_ed25519 before"));
+
+        let effective =
+            parse_effective_ssh_config("identitiesonly no\nidentityfile ~/.ssh/id_ed25519_work\n");
+        let finding = check_alias_key_order(&work, "github-work", &effective, &["personal"]);
+        assert_eq!(finding.severity, Severity::Warning);
+
+        let effective =
+            parse_effective_ssh_config("identitiesonly yes\nidentityfile ~/.ssh/id_ed25519_work\n");
+        let finding = check_alias_key_order(&work, "github-work", &effective, &["personal"]);
+        assert_eq!(finding.severity, Severity::Ok);
+    }
+
+    #[test]
+    fn test_ssh_config_blocks_are_checked_against_accounts() {
+        let mut work = account("work", "jdoe-corp", "jdoe@corp.com");
+        work.ssh_key = String::from("~/.ssh/id_ed25519_work");
+        let personal = account("personal", "jdoe", "jdoe@example.com");
+        let config = format!(
+            "Host box\n    User me\n{}\nHost box\n    Port 2222\n",
+            managed_block(&work)
+        );
+
+        let findings = check_ssh_config(&config, &[work.clone(), personal.clone()]);
+        assert_eq!(findings.len(), 2, "{:?}", findings);
+        assert!(
+            findings[0]
+                .message
+                .contains("[personal] SSH config has no entries")
+        );
+        assert_eq!(
+            findings[0].fix,
+            Some(Fix::WriteSshBlock(Box::new(personal)))
+        );
+        assert!(
+            findings[1]
+                .message
+                .contains("Host box is defined more than once")
+        );
+
+        work.ssh_options
+            .insert(String::from("Port"), String::from("2222"));
+        let findings = check_ssh_config(&config, &[work]);
+        assert!(
+            findings[0]
+                .message
+                .contains("[work] SSH config entries differ from the account settings")
+        );
+
+        assert_eq!(
+            duplicate_host_aliases("Host a b\nHost *\n  Host B\nHost b *\nhost a\n"),
+            vec!["b", "a"]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_hosts_are_told_from_rejected_keys() {
+        assert!(ssh_unreachable(
+            "ssh: connect to host github.com port 22: Connection timed out\n"
+        ));
+        assert!(ssh_unreachable(
+            "kex_exchange_identification: Connection closed by remote host\n"
+        ));
+        assert!(!ssh_unreachable(
+            "git@github.com: Permission denied (publickey).\n"
+        ));
+        assert!(!ssh_unreachable(
+            "Hi jdoe! You've successfully authenticated, but GitHub does not provide shell access.\n"
+        ));
+    }
+}
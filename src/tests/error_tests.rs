@@ -0,0 +1,62 @@
+use crate::error::{GitSwitchError, IoContext};
+use crate::utils::run_command;
+use std::io;
+
+fn all_errors() -> Vec<GitSwitchError> {
+    vec![
+        GitSwitchError::CheckFailed(String::new()),
+        GitSwitchError::InvalidInput(String::new()),
+        GitSwitchError::AccountNotFound(String::new()),
+        GitSwitchError::NotInRepository,
+        GitSwitchError::io("", io::Error::other("")),
+        GitSwitchError::CommandFailed {
+            command: String::new(),
+            code: Some(1),
+            stderr: String::new(),
+        },
+        GitSwitchError::PolicyDenied(String::new()),
+        GitSwitchError::Cancelled(String::new()),
+        GitSwitchError::Unsupported(String::new()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kinds_have_distinct_nonzero_exit_codes() {
+        let mut codes: Vec<i32> = all_errors().iter().map(|e| e.exit_code()).collect();
+        assert!(codes.iter().all(|code| *code != 0));
+        let count = codes.len();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), count);
+    }
+
+    #[test]
+    fn test_command_failures_are_returned_not_exited() {
+        let missing = run_command("git-switch-no-such-command", &[]).unwrap_err();
+        assert!(matches!(missing, GitSwitchError::CommandNotFound { .. }));
+        assert_eq!(missing.exit_code(), 6);
+
+        let failed = run_command("git", &["no-such-subcommand"]).unwrap_err();
+        assert!(matches!(
+            failed,
+            GitSwitchError::CommandFailed { code: Some(_), .. }
+        ));
+        assert!(failed.to_string().contains("not a git command"));
+
+        let version = run_command("git", &["--version"]).unwrap();
+        assert!(version.stdout.starts_with("git version"));
+
+        let io_error: io::Result<()> = Err(io::Error::other("disk full"));
+        assert_eq!(
+            io_error
+                .context("Failed to save rules")
+                .unwrap_err()
+                .to_string(),
+            "Failed to save rules: disk full"
+        );
+    }
+}
@@ -0,0 +1,21 @@
+use crate::events::diff_hash;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_hash_ignores_unchanged_context() {
+        // The same edit surrounded by different unchanged lines hashes identically
+        let a = diff_hash("one\ntwo\nthree\n", "one\nTWO\nthree\n");
+        let b = diff_hash("zero\ntwo\nfour\n", "zero\nTWO\nfour\n");
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 64);
+    }
+
+    #[test]
+    fn test_diff_hash_distinguishes_direction() {
+        assert_ne!(diff_hash("a\n", "b\n"), diff_hash("b\n", "a\n"));
+        assert_ne!(diff_hash("", "a\n"), diff_hash("a\n", ""));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::files::{self, MAX_BACKUPS};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn backup_count(dir: &Path, name: &str) -> usize {
+    files::list_backups(dir, name).unwrap().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_formats_utc() {
+        assert_eq!(files::timestamp(0), "19700101-000000");
+        assert_eq!(files::timestamp(1_709_210_096), "20240229-123456");
+    }
+
+    #[test]
+    fn test_write_with_backup_keeps_previous_versions() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config");
+
+        // No backup of a file that doesn't exist yet
+        assert_eq!(files::write_with_backup(&path, "v0").unwrap(), None);
+        for version in 1..=MAX_BACKUPS + 2 {
+            let backup = files::write_with_backup(&path, &format!("v{}", version))
+                .unwrap()
+                .expect("No backup made");
+            assert_eq!(
+                fs::read_to_string(backup).unwrap(),
+                format!("v{}", version - 1)
+            );
+        }
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("v{}", MAX_BACKUPS + 2)
+        );
+        assert_eq!(backup_count(dir.path(), "config"), MAX_BACKUPS);
+        // Only the target and its backups; no temporary file is left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), MAX_BACKUPS + 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_keeps_mode_and_symlink() {
+        use std::os::unix::fs::{PermissionsExt, symlink};
+
+        let dir = TempDir::new().unwrap();
+        let real = dir.path().join("dotfiles-config");
+        fs::write(&real, "old").unwrap();
+        fs::set_permissions(&real, fs::Permissions::from_mode(0o600)).unwrap();
+        let link = dir.path().join("config");
+        symlink(&real, &link).unwrap();
+
+        files::write_with_backup(&link, "new").unwrap();
+        assert!(
+            fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(fs::read_to_string(&real).unwrap(), "new");
+        let mode = fs::metadata(&real).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(backup_count(dir.path(), "dotfiles-config"), 1);
+    }
+}
@@ -0,0 +1,37 @@
+use crate::history::{HistoryEntry, append_to_path, read_from_path};
+use crate::utils::format_timestamp;
+use tempfile::TempDir;
+
+fn entry(ts: u64, action: &str) -> HistoryEntry {
+    HistoryEntry {
+        ts,
+        action: action.to_string(),
+        account: String::from("work"),
+        repo: Some(String::from("/home/jdoe/src/api")),
+        detail: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_appends_and_skips_damaged_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("history.jsonl");
+        assert!(read_from_path(&path).is_empty());
+
+        append_to_path(&entry(1, "add"), &path).unwrap();
+        let mut content = std::fs::read_to_string(&path).unwrap();
+        content.push_str("{\"ts\": \n");
+        std::fs::write(&path, content).unwrap();
+        append_to_path(&entry(2, "use"), &path).unwrap();
+
+        assert_eq!(
+            read_from_path(&path),
+            vec![entry(1, "add"), entry(2, "use")]
+        );
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13");
+    }
+}
@@ -0,0 +1,55 @@
+use crate::hooks::{install_hook, is_managed, render_hook, render_shell_hook, uninstall_hook};
+use std::fs;
+use tempfile::TempDir;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_keeps_foreign_hooks_unless_forced() {
+        let dir = TempDir::new().unwrap();
+        let hook = dir.path().join("pre-commit");
+        fs::write(&hook, "#!/bin/sh\nmake lint\n").unwrap();
+
+        assert!(install_hook(dir.path(), "pre-commit", false).is_err());
+        assert_eq!(fs::read_to_string(&hook).unwrap(), "#!/bin/sh\nmake lint\n");
+
+        install_hook(dir.path(), "pre-commit", true).unwrap();
+        assert!(is_managed(&fs::read_to_string(&hook).unwrap()));
+        // Reinstalling over our own hook needs no force
+        install_hook(dir.path(), "pre-commit", false).unwrap();
+
+        assert!(uninstall_hook(dir.path(), "pre-commit").unwrap());
+        assert_eq!(fs::read_to_string(&hook).unwrap(), "#!/bin/sh\nmake lint\n");
+        assert!(!uninstall_hook(dir.path(), "pre-commit").unwrap());
+    }
+
+    #[test]
+    fn test_hook_runs_check_only_when_installed() {
+        let script = render_hook(std::path::Path::new("/opt/it's/git-switch"));
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("git_switch='/opt/it'\\''s/git-switch'"));
+        assert!(script.contains("exec \"$git_switch\" hook check"));
+        assert!(!is_managed("#!/bin/sh\necho git-switch\n"));
+    }
+
+    #[test]
+    fn test_shell_hooks_run_auto_on_directory_change() {
+        let binary = std::path::Path::new("/usr/bin/git-switch");
+        let bash = render_shell_hook("bash", binary).unwrap();
+        assert!(bash.contains("'/usr/bin/git-switch' auto --silent"));
+        assert!(bash.contains("PROMPT_COMMAND=\"_git_switch_auto"));
+        assert!(
+            render_shell_hook("zsh", binary)
+                .unwrap()
+                .contains("add-zsh-hook chpwd _git_switch_auto")
+        );
+        assert!(
+            render_shell_hook("fish", binary)
+                .unwrap()
+                .contains("--on-variable PWD")
+        );
+        assert!(render_shell_hook("tcsh", binary).is_none());
+    }
+}
@@ -0,0 +1,88 @@
+use crate::config::Account;
+use crate::import::{self, Identity};
+
+const SSH_CONFIG: &str = "\
+Host *
+    AddKeysToAgent yes
+
+Host github-work
+    HostName github.com
+    User git
+    IdentityFile ~/.ssh/id_work
+    Port 443
+
+Host gitlab.com
+    IdentityFile=\"~/.ssh/id_lab\"
+
+Host myserver
+    HostName 10.0.0.5
+    IdentityFile ~/.ssh/id_server
+
+# >>> git-switch:old
+Host github-old
+    HostName github.com
+    IdentityFile ~/.ssh/id_old
+# <<< git-switch:old
+";
+
+fn identities() -> Vec<Identity> {
+    vec![
+        Identity {
+            name: String::from("Jane Doe"),
+            email: String::from("jane@example.com"),
+        },
+        Identity {
+            name: String::from("Jane Doe"),
+            email: String::from("jane@work.example"),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_host_entries_skips_managed_blocks() {
+        let entries = import::parse_host_entries(SSH_CONFIG);
+        let aliases: Vec<&str> = entries.iter().map(|e| e.alias.as_str()).collect();
+        assert_eq!(aliases, ["*", "github-work", "gitlab.com", "myserver"]);
+        assert_eq!(entries[1].real_host(), "github.com");
+        assert_eq!(entries[2].identity_file.as_deref(), Some("~/.ssh/id_lab"));
+
+        assert_eq!(
+            import::account_name_for("github-work", "github.com"),
+            "work"
+        );
+        assert_eq!(
+            import::account_name_for("work.github.com", "github.com"),
+            "work"
+        );
+        assert_eq!(
+            import::account_name_for("gitlab.com", "gitlab.com"),
+            "gitlab"
+        );
+    }
+
+    #[test]
+    fn test_propose_accounts_for_known_hosts_only() {
+        let entries = import::parse_host_entries(SSH_CONFIG);
+        let existing = vec![Account {
+            name: String::from("lab"),
+            ssh_key: String::from("~/.ssh/id_lab"),
+            ..Default::default()
+        }];
+        let proposals = import::propose_accounts(&entries, &identities(), &existing);
+        assert_eq!(proposals.len(), 1, "{:?}", proposals);
+
+        let (entry, account) = &proposals[0];
+        assert_eq!(entry.alias, "github-work");
+        assert_eq!(account.name, "work");
+        // The identity whose email mentions the account wins over the first
+        assert_eq!(account.email, "jane@work.example");
+        assert_eq!(account.host, "github.com");
+        assert!(account.external_key);
+        // User is managed by git-switch and not carried over
+        assert_eq!(account.ssh_options.keys().collect::<Vec<_>>(), vec!["Port"]);
+    }
+}
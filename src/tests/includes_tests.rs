@@ -0,0 +1,116 @@
+use crate::config::{Account, SendEmail, validate_accounts};
+use crate::includes::{gitdir_condition, parse_managed_includes, render_identity_fragment};
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// The `core.sshCommand` git reads from a rendered fragment.
+fn fragment_ssh_command(fragment: &str) -> String {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("fragment.gitconfig");
+    std::fs::write(&path, fragment).unwrap();
+    let output = Command::new("git")
+        .args(["config", "--file"])
+        .arg(&path)
+        .args(["core.sshCommand"])
+        .output()
+        .unwrap();
+    String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gitdir_condition_matches_subdirectories() {
+        assert_eq!(gitdir_condition("~/work"), "gitdir:~/work/");
+        assert_eq!(gitdir_condition("/src/corp/"), "gitdir:/src/corp/");
+    }
+
+    #[test]
+    fn test_only_includes_into_identities_dir_are_managed() {
+        let output = "includeif.gitdir:/home/me/work/.path /home/me/.git-switch/identities/work.gitconfig\n\
+                      includeif.gitdir:/home/me/oss/.path /home/me/.gitconfig-oss\n";
+        let managed = parse_managed_includes(output, Path::new("/home/me/.git-switch/identities"));
+        assert_eq!(
+            managed,
+            vec![(
+                "/home/me/work".to_string(),
+                "/home/me/.git-switch/identities/work.gitconfig".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_identity_fragment_includes_key_and_signing() {
+        let account = Account {
+            name: String::from("work"),
+            username: String::from("jdoe-corp"),
+            email: String::from("jdoe@corp.com"),
+            ssh_key: String::from("~/.ssh/id_ed25519_work"),
+            signing_key: Some(String::from("~/.ssh/id_ed25519_work.pub")),
+            signing_format: Some(String::from("ssh")),
+            ..Default::default()
+        };
+        let fragment = render_identity_fragment(&account);
+        assert!(fragment.contains("\temail = jdoe@corp.com\n"));
+        assert!(
+            fragment.contains("sshCommand = ssh -i ~/.ssh/id_ed25519_work -o IdentitiesOnly=yes")
+        );
+        assert!(fragment.contains("[gpg]\n\tformat = ssh\n"));
+    }
+
+    #[test]
+    fn test_identity_fragment_quotes_key_paths() {
+        let mut account = Account {
+            name: String::from("work"),
+            username: String::from("jdoe-corp"),
+            email: String::from("jdoe@corp.com"),
+            ssh_key: String::from("~/My Keys/id_ed25519_work"),
+            ..Default::default()
+        };
+        assert_eq!(
+            fragment_ssh_command(&render_identity_fragment(&account)),
+            "ssh -i ~/'My Keys/id_ed25519_work' -o IdentitiesOnly=yes"
+        );
+        account.ssh_key = String::from("/keys/jane's key");
+        assert_eq!(
+            fragment_ssh_command(&render_identity_fragment(&account)),
+            "ssh -i '/keys/jane'\\''s key' -o IdentitiesOnly=yes"
+        );
+
+        account.ssh_key.clear();
+        account.auth = Some(String::from("https"));
+        assert!(!render_identity_fragment(&account).contains("[core]"));
+    }
+
+    #[test]
+    fn test_fragments_carry_sendemail_settings() {
+        let mut account = Account {
+            name: String::from("kernel"),
+            username: String::from("Jane Doe"),
+            email: String::from("jane@kernel.org"),
+            ssh_key: String::from("~/.ssh/id_ed25519_kernel"),
+            sendemail: Some(SendEmail {
+                smtp_server: String::from("mail.kernel.org"),
+                smtp_port: Some(587),
+                smtp_user: Some(String::from("jane")),
+                smtp_encryption: Some(String::from("tls")),
+            }),
+            ..Default::default()
+        };
+        assert!(render_identity_fragment(&account).contains(
+            "[sendemail]\n\tfrom = Jane Doe <jane@kernel.org>\n\tsmtpServer = mail.kernel.org\n\tsmtpServerPort = 587\n\tsmtpUser = jane\n\tsmtpEncryption = tls\n"
+        ));
+
+        account.sendemail.as_mut().unwrap().smtp_encryption = Some(String::from("starttls"));
+        assert!(
+            validate_accounts(&[account])
+                .iter()
+                .any(|e| e.contains("smtp_encryption must be one of tls, ssl"))
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use crate::config::Account;
+use crate::manifest::load_manifest;
+use std::fs;
+use tempfile::TempDir;
+
+fn account(name: &str, email: &str, host: &str, signing: bool) -> Account {
+    Account {
+        name: name.to_string(),
+        username: name.to_string(),
+        email: email.to_string(),
+        host: host.to_string(),
+        signing_key: signing.then(|| String::from("ABCDEF")),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_constraints() {
+        let repo = TempDir::new().unwrap();
+        assert!(load_manifest(repo.path()).unwrap().is_none());
+
+        fs::write(
+            repo.path().join(".git-switch.toml"),
+            "host = \"gitlab.com\"\norg = \"corp\"\nemail_domains = [\"corp.com\"]\nrequire_signing = true\n",
+        )
+        .unwrap();
+        let manifest = load_manifest(repo.path()).unwrap().unwrap();
+
+        let accounts = vec![
+            account("personal", "me@gmail.com", "gitlab.com", true),
+            account("work", "me@Corp.com", "gitlab.com", true),
+            account("unsigned", "me@corp.com", "gitlab.com", false),
+            account("github", "me@corp.com", "github.com", true),
+        ];
+        let compliant: Vec<&str> = manifest
+            .compliant_accounts(&accounts)
+            .iter()
+            .map(|acc| acc.name.as_str())
+            .collect();
+        assert_eq!(compliant, vec!["work"]);
+        assert_eq!(manifest.account_violations(&accounts[0]).len(), 1);
+
+        assert!(
+            manifest
+                .remote_violation("git@gitlab-work:corp/api.git")
+                .is_none()
+        );
+        assert!(
+            manifest
+                .remote_violation("https://gitlab.com/other/api")
+                .is_some()
+        );
+        assert_eq!(
+            manifest.add_hint(),
+            "git-switch add <name> <username> <you>@corp.com --host gitlab.com --signing-format ssh"
+        );
+    }
+
+    #[test]
+    fn test_unknown_manifest_keys_are_rejected() {
+        let repo = TempDir::new().unwrap();
+        fs::write(
+            repo.path().join(".git-switch.toml"),
+            "email = \"me@corp.com\"\n",
+        )
+        .unwrap();
+        assert!(load_manifest(repo.path()).is_err());
+    }
+}
@@ -1,5 +1,37 @@
+mod bundle_tests;
+mod completions_tests;
+mod credential_tests;
+mod doctor_tests;
+mod error_tests;
+mod events_tests;
+mod files_tests;
+mod history_tests;
+mod hooks_tests;
+mod import_tests;
+mod includes_tests;
+mod manifest_tests;
+mod output_tests;
+mod pending_tests;
+mod policy_tests;
+mod prompt_tests;
+mod provider_tests;
+mod render_tests;
+mod rules_tests;
+mod scan_tests;
+mod secrets_tests;
+mod session_tests;
+mod settings_tests;
+mod ssh_tests;
+mod sync_tests;
+#[cfg(feature = "provider-api")]
+mod tokens_tests;
+#[cfg(feature = "tui")]
+mod tui_tests;
+mod undo_tests;
+
 use crate::config::{self, Account}; // Use config:: for public functions
-use crate::utils::file_exists; // Keep if used by test_file_exists
+use crate::output::OutputFormat;
+use crate::utils::{file_exists, format_duration, parse_duration}; // Keep if used by test_file_exists
 use std::fs::{self, File}; // fs needed for reading in debug helper
 // use std::io::Write; // For File::create if needed, not directly used in test logic now
 use std::path::PathBuf;
@@ -14,6 +46,7 @@ fn setup_temp_config_env() -> (TempDir, PathBuf) {
 }
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use super::*; // To get setup_temp_config_env
 
@@ -48,6 +81,7 @@ mod tests {
             username: String::from("testuser_sl_temp"),
             email: String::from("test_sl_temp@example.com"),
             ssh_key: String::from("~/.ssh/id_rsa_test_sl_temp"),
+            ..Default::default()
         };
 
         // Use the internal worker function with the temp path
@@ -83,12 +117,14 @@ mod tests {
             username: String::from("userdel1_temp"),
             email: String::from("testdel1_temp@example.com"),
             ssh_key: String::from("~/.ssh/id_rsa_testdel1_temp"),
+            ..Default::default()
         };
         let acc2 = Account {
             name: String::from("testdel2_temp"),
             username: String::from("userdel2_temp"),
             email: String::from("testdel2_temp@example.com"),
             ssh_key: String::from("~/.ssh/id_rsa_testdel2_temp"),
+            ..Default::default()
         };
 
         config::save_account_to_path(&acc1, &temp_config_path).expect("Save acc1 to temp failed");
@@ -129,6 +165,346 @@ mod tests {
         // _temp_dir goes out of scope here, cleaning up.
     }
 
+    #[test]
+    fn test_concurrent_saves_keep_every_account() {
+        let (_temp_dir, temp_config_path) = setup_temp_config_env();
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let path = &temp_config_path;
+                scope.spawn(move || {
+                    let account = Account {
+                        name: format!("parallel{}", i),
+                        username: format!("user{}", i),
+                        email: format!("user{}@example.com", i),
+                        ssh_key: format!("~/.ssh/id_ed25519_parallel{}", i),
+                        ..Default::default()
+                    };
+                    config::save_account_to_path(&account, path).expect("Save failed");
+                });
+            }
+        });
+        assert_eq!(config::load_accounts_from_path(&temp_config_path).len(), 8);
+    }
+
+    #[test]
+    fn test_legacy_pipe_file_is_migrated_to_toml() {
+        let (_temp_dir, temp_config_path) = setup_temp_config_env();
+        fs::write(
+            &temp_config_path,
+            "legacy|legacyuser|legacy@example.com|~/.ssh/id_rsa_legacy|workspace=~/src\n",
+        )
+        .expect("Failed to write legacy config");
+
+        let accounts = config::load_accounts_from_path(&temp_config_path);
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].username, "legacyuser");
+        assert_eq!(accounts[0].workspace.as_deref(), Some("~/src"));
+
+        let migrated = fs::read_to_string(&temp_config_path).expect("Failed to read config");
+        assert!(
+            migrated.contains("version = 1"),
+            "Not migrated: {}",
+            migrated
+        );
+        let mut backup_path = temp_config_path.as_os_str().to_owned();
+        backup_path.push(".bak");
+        assert!(PathBuf::from(backup_path).exists(), "Backup not created");
+
+        // Loading again reads the TOML file and yields the same account
+        assert_eq!(config::load_accounts_from_path(&temp_config_path), accounts);
+    }
+
+    #[test]
+    fn test_key_path_conflicts_and_answers() {
+        use crate::commands::{
+            KeyPathChoice, KeyPathConflict, alternative_key_path, key_path_choice,
+            key_path_conflict,
+        };
+
+        let taken = ["/k/id_work", "/k/id_stray", "/k/id_stray_2"];
+        let exists = |path: &str| taken.contains(&path);
+        let accounts = [Account {
+            name: String::from("work"),
+            ssh_key: String::from("/k/id_work"),
+            ..Default::default()
+        }];
+        assert_eq!(
+            key_path_conflict("/k/id_new", &accounts, exists),
+            KeyPathConflict::Free
+        );
+        assert_eq!(
+            key_path_conflict("/k/id_work", &accounts, exists),
+            KeyPathConflict::UsedBy(String::from("work"))
+        );
+        assert_eq!(
+            key_path_conflict("/k/id_stray", &accounts, exists),
+            KeyPathConflict::Unmanaged
+        );
+
+        // Reuse, keep separately or abort; a closed stdin aborts
+        assert_eq!(key_path_choice(Some("A")), KeyPathChoice::Adopt);
+        assert_eq!(key_path_choice(Some("g")), KeyPathChoice::Separate);
+        assert_eq!(key_path_choice(Some("q")), KeyPathChoice::Abort);
+        assert_eq!(key_path_choice(Some("")), KeyPathChoice::Abort);
+        assert_eq!(key_path_choice(None), KeyPathChoice::Abort);
+
+        assert_eq!(
+            alternative_key_path("/k/id_stray_3", Some(""), exists).as_deref(),
+            Some("/k/id_stray_3")
+        );
+        assert_eq!(
+            alternative_key_path("/k/id_stray_3", Some("/k/id_mine"), exists).as_deref(),
+            Some("/k/id_mine")
+        );
+        assert_eq!(
+            alternative_key_path("/k/id_stray_3", Some("/k/id_stray_2"), exists),
+            None
+        );
+        assert_eq!(alternative_key_path("/k/id_stray_3", None, exists), None);
+    }
+
+    #[test]
+    fn test_unreadable_or_newer_file_is_not_overwritten() {
+        let (_temp_dir, temp_config_path) = setup_temp_config_env();
+        let account = Account {
+            name: String::from("work"),
+            username: String::from("jdoe"),
+            email: String::from("jdoe@corp.com"),
+            ssh_key: String::from("~/.ssh/id_ed25519_work"),
+            ..Default::default()
+        };
+        for content in [
+            "version = 1\n[[accounts]\nname = \"broken\"\n",
+            "version = 99\n\n[[accounts]]\nname = \"future\"\nusername = \"f\"\nemail = \"f@x.com\"\nssh_key = \"~/.ssh/f\"\nnew_field = true\n",
+        ] {
+            fs::write(&temp_config_path, content).unwrap();
+            assert!(config::save_account_to_path(&account, &temp_config_path).is_err());
+            assert!(config::delete_account_from_path("future", &temp_config_path).is_err());
+            assert_eq!(fs::read_to_string(&temp_config_path).unwrap(), content);
+        }
+        // Reading still works, with a warning
+        assert_eq!(config::load_accounts_from_path(&temp_config_path).len(), 1);
+    }
+
+    #[test]
+    fn test_store_encryption_is_read_from_the_header() {
+        use crate::config::{StoreEncryption, store_encryption};
+
+        assert_eq!(store_encryption("version = 1\n"), None);
+        assert_eq!(
+            store_encryption(
+                "# Encrypted by git-switch with a passphrase\n-----BEGIN AGE ENCRYPTED FILE-----\n"
+            ),
+            Some(StoreEncryption::Passphrase)
+        );
+        assert_eq!(
+            store_encryption(
+                "# Encrypted by git-switch with the age identity in ~/.age/key.txt\n-----BEGIN AGE ENCRYPTED FILE-----\n"
+            ),
+            Some(StoreEncryption::Identity("~/.age/key.txt".to_string()))
+        );
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_store_is_decrypted_on_load() {
+        use crate::config::StoreEncryption;
+        use crate::crypto::Key;
+        use age::secrecy::ExposeSecret;
+
+        let (temp_dir, temp_config_path) = setup_temp_config_env();
+        let identity_path = temp_dir.path().join("key.txt");
+        let identity = age::x25519::Identity::generate();
+        fs::write(&identity_path, identity.to_string().expose_secret()).unwrap();
+        let account = |name: &str| Account {
+            name: name.to_string(),
+            username: format!("{}user", name),
+            email: format!("{}@example.com", name),
+            ssh_key: format!("~/.ssh/id_ed25519_{}", name),
+            ..Default::default()
+        };
+        config::save_account_to_path(&account("work"), &temp_config_path).unwrap();
+
+        let encryption = StoreEncryption::Identity(identity_path.display().to_string());
+        config::set_store_encryption(
+            &temp_config_path,
+            Some((encryption, Key::Identity(identity_path.clone()))),
+        )
+        .unwrap();
+        config::save_account_to_path(&account("home"), &temp_config_path).unwrap();
+        let content = fs::read_to_string(&temp_config_path).unwrap();
+        assert!(!content.contains("work@example.com"));
+        assert!(config::store_encryption(&content).is_some());
+        let names: Vec<String> = config::load_accounts_from_path(&temp_config_path)
+            .into_iter()
+            .map(|acc| acc.name)
+            .collect();
+        assert_eq!(names, ["work", "home"]);
+
+        config::set_store_encryption(&temp_config_path, None).unwrap();
+        assert!(
+            fs::read_to_string(&temp_config_path)
+                .unwrap()
+                .contains("home@example.com")
+        );
+    }
+
+    #[test]
+    fn test_pipe_character_in_fields_round_trips() {
+        let (_temp_dir, temp_config_path) = setup_temp_config_env();
+        let account = Account {
+            name: String::from("pipe|name"),
+            username: String::from("pipeuser"),
+            email: String::from("a|b@example.com"),
+            ssh_key: String::from("~/.ssh/id_ed25519_pipe"),
+            ..Default::default()
+        };
+        config::save_account_to_path(&account, &temp_config_path).expect("Save failed");
+        let accounts = config::load_accounts_from_path(&temp_config_path);
+        assert_eq!(accounts, vec![account]);
+    }
+
+    #[test]
+    fn test_edited_accounts_are_validated_and_diffed() {
+        let (temp_dir, _) = setup_temp_config_env();
+        let key = temp_dir.path().join("id_ed25519_work");
+        File::create(&key).unwrap();
+        let work = Account {
+            name: String::from("work"),
+            username: String::from("work-user"),
+            email: String::from("work@example.com"),
+            ssh_key: key.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+
+        let text = config::accounts_to_toml(std::slice::from_ref(&work)).unwrap();
+        let parsed = config::accounts_from_toml(&text).unwrap();
+        assert_eq!(parsed, vec![work.clone()]);
+        assert!(config::validate_accounts(&parsed).is_empty());
+
+        let mut renamed_email = work.clone();
+        renamed_email.email = String::from("not-an-email");
+        let duplicate = work.clone();
+        let errors = config::validate_accounts(&[renamed_email.clone(), duplicate]);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(config::check_account_name("work|old").is_err());
+        assert!(!config::is_valid_email("jdoe@corp@example.com"));
+        assert!(config::is_valid_email("jdoe+git@corp.example.com"));
+
+        let changes = config::diff_accounts(std::slice::from_ref(&work), &[renamed_email]);
+        assert!(matches!(
+            changes.as_slice(),
+            [config::AccountChange::Modified(_, _, fields)] if fields == &vec!["email"]
+        ));
+    }
+
+    #[test]
+    fn test_git_config_settings_render_into_the_fragment() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut account = Account {
+            name: String::from("work"),
+            ..Default::default()
+        };
+        for (key, value) in [
+            ("pull.rebase", "true"),
+            ("core.editor", "code --wait # \"new\""),
+            ("url.git@github-work:.insteadOf", "git@github.com:acme/"),
+        ] {
+            assert!(config::check_git_config(key, value).is_ok());
+            account
+                .git_config
+                .insert(key.to_string(), value.to_string());
+        }
+        assert!(config::check_git_config_key("User.Email").is_err());
+        assert!(config::check_git_config_key("editor").is_err());
+
+        // git reads back exactly what was configured
+        let path = temp_dir.path().join("work.gitconfig");
+        fs::write(
+            &path,
+            crate::git::render_account_fragment(&account).unwrap(),
+        )
+        .unwrap();
+        for (key, value) in &account.git_config {
+            let output = std::process::Command::new("git")
+                .args(["config", "--file"])
+                .arg(&path)
+                .args(["--get", key])
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), value);
+        }
+    }
+
+    #[test]
+    fn test_match_identity_prefers_username_and_email() {
+        let account = |name: &str, username: &str, email: &str| Account {
+            name: name.to_string(),
+            username: username.to_string(),
+            email: email.to_string(),
+            ..Default::default()
+        };
+        let accounts = vec![
+            account("work", "jdoe-corp", "jdoe@corp.com"),
+            account("bot", "corp-bot", "jdoe@corp.com"),
+            account("personal", "jdoe", "jdoe@example.com"),
+        ];
+
+        let matched = config::match_identity(&accounts, "corp-bot", "JDOE@corp.com");
+        assert_eq!(matched.map(|acc| acc.name.as_str()), Some("bot"));
+        // Unique email is enough on its own
+        let matched = config::match_identity(&accounts, "John", "jdoe@example.com");
+        assert_eq!(matched.map(|acc| acc.name.as_str()), Some("personal"));
+        // Shared email with an unknown username is ambiguous
+        assert!(config::match_identity(&accounts, "John", "jdoe@corp.com").is_none());
+    }
+
+    #[test]
+    fn test_format_accounts_as_csv_and_json() {
+        let account = Account {
+            name: String::from("Work, Inc"),
+            username: String::from("jdoe"),
+            email: String::from("jdoe@corp.com"),
+            ssh_key: String::from("~/.ssh/id_ed25519_work"),
+            extra_hosts: vec![String::from("gitlab.com")],
+            tags: vec![String::from("corp"), String::from("client-x")],
+            ..Default::default()
+        };
+        let csv = config::format_accounts(std::slice::from_ref(&account), OutputFormat::Csv);
+        assert_eq!(
+            csv.lines().nth(1),
+            Some(
+                "\"Work, Inc\",jdoe,jdoe@corp.com,~/.ssh/id_ed25519_work,github.com;gitlab.com,,corp;client-x"
+            )
+        );
+
+        let states = [config::KeyState {
+            fingerprint: Some(String::from("SHA256:abc")),
+            in_agent: Some(true),
+        }];
+        let csv = config::format_accounts_with_keys(
+            std::slice::from_ref(&account),
+            &states,
+            OutputFormat::Csv,
+        );
+        assert!(
+            csv.lines()
+                .next()
+                .unwrap()
+                .ends_with(",tags,fingerprint,in_agent")
+        );
+        assert!(
+            csv.lines()
+                .nth(1)
+                .unwrap()
+                .ends_with(",corp;client-x,SHA256:abc,true")
+        );
+
+        let json = config::format_accounts(&[account], OutputFormat::Json);
+        let parsed: Vec<Account> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0].extra_hosts, vec!["gitlab.com"]);
+    }
+
     #[test]
     fn test_file_exists() {
         // This test is inherently isolated if it creates its own temp file.
@@ -141,4 +517,21 @@ mod tests {
 
         assert!(file_exists(&test_file_path));
     }
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(parse_duration("90"), Ok(90));
+        assert_eq!(parse_duration("30m"), Ok(1800));
+        assert_eq!(parse_duration("8h"), Ok(28_800));
+        assert_eq!(parse_duration("1d"), Ok(86_400));
+        assert!(parse_duration("0h").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("8 hours").is_err());
+
+        assert_eq!(format_duration(28_800), "8h");
+        assert_eq!(format_duration(25_920), "7h 12m");
+        assert_eq!(format_duration(90_061), "1d 1h");
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(0), "0s");
+    }
 }
@@ -0,0 +1,26 @@
+use crate::output::undecorate;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undecorate_drops_leading_emoji() {
+        assert_eq!(
+            undecorate("✅ Account 'work' saved."),
+            "Account 'work' saved."
+        );
+        assert_eq!(
+            undecorate("\n🔑 Here is your public SSH key:"),
+            "\nHere is your public SSH key:"
+        );
+        assert_eq!(
+            undecorate("   ⚠️ The key doesn't exist; skipped."),
+            "   warning: The key doesn't exist; skipped."
+        );
+        assert_eq!(undecorate("❌ No rule matches"), "error: No rule matches");
+        assert_eq!(undecorate("ℹ️ Moved the file"), "Moved the file");
+        assert_eq!(undecorate("work → personal"), "work → personal");
+        assert_eq!(undecorate("Zoë's key"), "Zoë's key");
+    }
+}
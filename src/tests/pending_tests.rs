@@ -0,0 +1,52 @@
+#[cfg(feature = "provider-api")]
+use crate::pending::enqueue_in_path;
+use crate::pending::{PendingAction, PendingKind, load_pending_from_path, save_pending_to_path};
+use tempfile::TempDir;
+
+fn upload(account: &str) -> PendingAction {
+    PendingAction {
+        kind: PendingKind::UploadKey,
+        account: account.to_string(),
+        queued_at: 1_700_000_000,
+        attempts: 1,
+        last_error: Some(String::from("offline")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_round_trips_and_empty_queue_removes_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("pending.toml");
+        let actions = vec![upload("work"), upload("personal")];
+
+        save_pending_to_path(&actions, &path).unwrap();
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("kind = \"upload-key\"")
+        );
+        assert_eq!(load_pending_from_path(&path), actions);
+
+        save_pending_to_path(&[], &path).unwrap();
+        assert!(!path.exists());
+        assert!(load_pending_from_path(&path).is_empty());
+    }
+
+    #[cfg(feature = "provider-api")]
+    #[test]
+    fn test_enqueue_keeps_one_action_per_account() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("pending.toml");
+        enqueue_in_path(PendingKind::UploadKey, "work", "offline", &path).unwrap();
+        enqueue_in_path(PendingKind::UploadKey, "work", "HTTP 401", &path).unwrap();
+
+        let actions = load_pending_from_path(&path);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].attempts, 2);
+        assert_eq!(actions[0].last_error.as_deref(), Some("HTTP 401"));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::config::Account;
+use crate::policy::{Action, parse_policy};
+
+fn personal_account() -> Account {
+    Account {
+        name: String::from("personal"),
+        username: String::from("jdoe"),
+        email: String::from("jdoe@Gmail.com"),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_match_when_all_conditions_hold() {
+        let policy = parse_policy(
+            r#"
+            [[rule]]
+            email_domains = ["gmail.com"]
+            action = "acknowledge"
+            message = "No personal accounts"
+
+            [[rule]]
+            accounts = ["personal"]
+            key_older_than_days = 365
+            message = "Rotate your key"
+            "#,
+        )
+        .unwrap();
+        let account = personal_account();
+
+        assert!(policy.rules[0].matches(&account, None));
+        assert_eq!(policy.rules[0].action, Action::Acknowledge);
+        assert_eq!(policy.rules[1].action, Action::Warn);
+        assert!(!policy.rules[1].matches(&account, Some(30)));
+        assert!(policy.rules[1].matches(&account, Some(400)));
+        assert!(!policy.rules[1].matches(&account, None));
+    }
+
+    #[test]
+    fn test_rule_without_conditions_never_matches() {
+        let policy = parse_policy("[[rule]]\nmessage = \"always?\"\n").unwrap();
+        assert!(!policy.rules[0].matches(&personal_account(), Some(1000)));
+        assert!(parse_policy("[[rule]]\naction = \"deny\"\nmessage = \"x\"\n").is_err());
+    }
+
+    #[test]
+    fn test_require_attestation_matches_unattested_keys() {
+        let policy = parse_policy(
+            "[[rule]]\nemail_domains = [\"gmail.com\"]\nrequire_attestation = true\nmessage = \"x\"\n",
+        )
+        .unwrap();
+        let mut account = personal_account();
+        assert!(policy.rules[0].matches(&account, None));
+
+        account.attestation = Some(String::from("~/.ssh/id_ed25519_sk_personal.attestation"));
+        assert!(!policy.rules[0].matches(&account, None));
+    }
+}
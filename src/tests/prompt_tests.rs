@@ -0,0 +1,37 @@
+use crate::prompt::{DEFAULT_MISMATCH_TEMPLATE, PromptState, colorize, fill_template};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt_segment_templates_and_colors() {
+        let values = [("account", "personal"), ("expected", "work")];
+        assert_eq!(
+            fill_template(DEFAULT_MISMATCH_TEMPLATE, &values),
+            "⚠ personal≠work"
+        );
+        assert_eq!(
+            fill_template("{account} {unknown}", &values),
+            "personal {unknown}"
+        );
+
+        assert_eq!(
+            colorize("work", PromptState::Match, "raw", true),
+            "\x1b[32mwork\x1b[0m"
+        );
+        assert_eq!(
+            colorize("work", PromptState::Mismatch, "bash", true),
+            "\x01\x1b[1;31m\x02work\x01\x1b[0m\x02"
+        );
+        assert_eq!(
+            colorize("50%", PromptState::Match, "zsh", true),
+            "%{\x1b[32m%}50%%%{\x1b[0m%}"
+        );
+        assert_eq!(
+            colorize("work", PromptState::Unmapped, "bash", true),
+            "work"
+        );
+        assert_eq!(colorize("work", PromptState::Match, "raw", false), "work");
+    }
+}
@@ -0,0 +1,214 @@
+use crate::config::Account;
+use crate::git::{RemoteUrl, clone_url, org_url_rewrites, repo_ssh_url};
+#[cfg(feature = "provider-api")]
+use crate::provider::{
+    ApiIdentity, KeyApi, auth_cli_for, base64, key_title, listed_keys, split_status,
+};
+use crate::provider::{account_provider, authenticated_user, provider_for};
+
+fn sourcehut_account() -> Account {
+    Account {
+        name: String::from("hut"),
+        username: String::from("jdoe"),
+        host: String::from("git.sr.ht"),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_greetings_name_the_authenticated_user() {
+        let github = provider_for("GitHub.com").unwrap();
+        assert_eq!(
+            github.greeting_user(
+                "Hi jdoe! You've successfully authenticated, but GitHub does not provide shell access.\n"
+            ),
+            Some(String::from("jdoe"))
+        );
+        assert_eq!(
+            github.greeting_user("Permission denied (publickey).\n"),
+            None
+        );
+
+        let codeberg = provider_for("codeberg.org").unwrap();
+        assert_eq!(
+            codeberg.greeting_user(
+                "Hi there, jdoe! You've successfully authenticated with the key named laptop, but Forgejo does not provide shell access."
+            ),
+            Some(String::from("jdoe"))
+        );
+
+        let sourcehut = provider_for("git.sr.ht").unwrap();
+        assert_eq!(
+            sourcehut.greeting_user("Hi ~jdoe! You've successfully authenticated, but I do not provide an interactive shell. Bye!"),
+            Some(String::from("jdoe"))
+        );
+        assert!(provider_for("git.example.com").is_none());
+        // Self-hosted servers are recognized by any known greeting
+        assert_eq!(
+            authenticated_user("git.example.com", "Welcome to GitLab, @jdoe!\n"),
+            Some(String::from("jdoe"))
+        );
+        assert_eq!(
+            authenticated_user("git.example.com", "Permission denied (publickey).\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sourcehut_clone_urls_use_tilde_owners() {
+        let account = sourcehut_account();
+        assert_eq!(
+            clone_url(&account, "dotfiles"),
+            "git@git-sr-ht-hut:~jdoe/dotfiles"
+        );
+        assert_eq!(
+            RemoteUrl::parse("git@git.sr.ht:~jdoe/dotfiles")
+                .unwrap()
+                .owner(),
+            "jdoe"
+        );
+    }
+
+    #[test]
+    fn test_enterprise_endpoint_urls() {
+        let account = Account {
+            name: String::from("corp"),
+            username: String::from("jdoe"),
+            host: String::from("bitbucket.corp.com"),
+            hostname: Some(String::from("git.internal.corp")),
+            port: Some(7999),
+            ssh_user: Some(String::from("git2")),
+            orgs: vec![String::from("PROJ")],
+            ..Default::default()
+        };
+        assert_eq!(
+            clone_url(&account, "PROJ/app"),
+            "git2@bitbucket-corp-com-corp:PROJ/app.git"
+        );
+        assert_eq!(
+            repo_ssh_url(&account, "PROJ/app", false),
+            "ssh://git2@git.internal.corp:7999/PROJ/app.git"
+        );
+        assert!(org_url_rewrites(&account).contains(&(
+            String::from("git2@bitbucket-corp-com-corp:PROJ/"),
+            String::from("ssh://git2@git.internal.corp:7999/PROJ/")
+        )));
+    }
+
+    #[test]
+    fn test_self_hosted_providers_follow_the_account_kind() {
+        let mut account = Account {
+            name: String::from("corp"),
+            host: String::from("GitLab.Corp.example"),
+            ..Default::default()
+        };
+        assert!(account_provider(&account, &account.host).is_none());
+
+        account.provider = Some(String::from("gitlab"));
+        let gitlab = account_provider(&account, &account.host).unwrap();
+        assert_eq!(gitlab.name, "GitLab");
+        assert_eq!(gitlab.api_base, "https://gitlab.corp.example/api/v4");
+        assert_eq!(
+            gitlab.key_settings_url,
+            "https://gitlab.corp.example/-/user_settings/ssh_keys"
+        );
+        // Interned, so repeated lookups don't leak another copy
+        assert!(std::ptr::eq(
+            gitlab,
+            account_provider(&account, "gitlab.corp.example").unwrap()
+        ));
+
+        // Well-known hosts keep their own provider
+        account.provider = Some(String::from("gitea"));
+        assert_eq!(
+            account_provider(&account, "github.com").unwrap().name,
+            "GitHub"
+        );
+    }
+
+    #[cfg(feature = "provider-api")]
+    #[test]
+    fn test_key_title_and_curl_status_parsing() {
+        let account = Account {
+            name: String::from("work"),
+            ..Default::default()
+        };
+        assert_eq!(key_title(&account, "laptop"), "git-switch work@laptop");
+
+        assert_eq!(split_status("{\"id\":1}\n201"), (201, "{\"id\":1}"));
+        assert_eq!(split_status("404"), (404, ""));
+        assert_eq!(split_status("garbage"), (0, ""));
+
+        assert_eq!(base64(b"jdoe:secret"), "amRvZTpzZWNyZXQ=");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b""), "");
+    }
+
+    #[cfg(feature = "provider-api")]
+    #[test]
+    fn test_provider_clis_lend_tokens_for_the_account() {
+        let gh = auth_cli_for(KeyApi::GitHub).unwrap();
+        assert_eq!(
+            gh.token_args("github.example.com", "jdoe")[0],
+            [
+                "auth",
+                "token",
+                "--hostname",
+                "github.example.com",
+                "--user",
+                "jdoe"
+            ]
+        );
+        let glab = auth_cli_for(KeyApi::GitLab).unwrap();
+        assert_eq!(glab.program, "glab");
+        assert_eq!(
+            glab.token_args("gitlab.com", "jdoe"),
+            [["config", "get", "token", "--host", "gitlab.com"]]
+        );
+        assert!(auth_cli_for(KeyApi::Bitbucket).is_none());
+    }
+
+    #[cfg(feature = "provider-api")]
+    #[test]
+    fn test_key_listings_and_api_verdicts() {
+        use crate::commands::report_api_identity;
+        use crate::error::GitSwitchError;
+
+        assert_eq!(
+            listed_keys(r#"[{"id":1,"key":"ssh-ed25519 AAAA1"},{"id":2}]"#),
+            Some(vec![String::from("ssh-ed25519 AAAA1")])
+        );
+        assert_eq!(
+            listed_keys(r#"{"pagelen":100,"values":[{"key":"ssh-rsa BBBB"}]}"#),
+            Some(vec![String::from("ssh-rsa BBBB")])
+        );
+        assert_eq!(listed_keys(r#"{"message":"Bad credentials"}"#), None);
+        assert_eq!(listed_keys("<html>502 Bad Gateway</html>"), None);
+
+        let account = Account {
+            name: String::from("work"),
+            username: String::from("jdoe"),
+            ..Default::default()
+        };
+        let identity = |login: &str, key_registered| ApiIdentity {
+            provider: "GitHub",
+            login: String::from(login),
+            key_registered,
+        };
+        assert!(report_api_identity(&account, &identity("JDoe", Some(true))).is_ok());
+        assert!(report_api_identity(&account, &identity("jdoe", None)).is_ok());
+        for mismatch in [
+            identity("someone", Some(true)),
+            identity("jdoe", Some(false)),
+        ] {
+            assert!(matches!(
+                report_api_identity(&account, &mismatch),
+                Err(GitSwitchError::CheckFailed(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::config::Account;
+use crate::render::{ENVRC_BEGIN, envrc_with_account, render, ssh_command};
+
+fn account() -> Account {
+    Account {
+        name: String::from("work"),
+        username: String::from("Jo O'Neil"),
+        email: String::from("jo@corp.com"),
+        ssh_key: String::from("~/.ssh/id_ed25519_work"),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_targets() {
+        let ssh = render(&account(), "ssh").unwrap();
+        assert!(ssh.starts_with("# work GitHub Account\nHost github-work\n"));
+
+        let git = render(&account(), "git").unwrap();
+        assert!(git.starts_with("[user]\n"));
+
+        let direnv = render(&account(), "direnv").unwrap();
+        assert!(direnv.contains("export GIT_AUTHOR_NAME='Jo O'\\''Neil'\n"));
+        assert!(direnv.contains(
+            "export GIT_SSH_COMMAND=\"ssh -i $HOME/.ssh/id_ed25519_work -o IdentitiesOnly=yes\"\n"
+        ));
+
+        let devcontainer: serde_json::Value =
+            serde_json::from_str(&render(&account(), "devcontainer").unwrap()).unwrap();
+        assert_eq!(
+            devcontainer["containerEnv"]["GIT_COMMITTER_EMAIL"],
+            "jo@corp.com"
+        );
+        assert!(render(&account(), "nix").is_none());
+    }
+
+    #[test]
+    fn test_ssh_command_quotes_key_paths() {
+        assert_eq!(
+            ssh_command(&account()),
+            "ssh -i ~/.ssh/id_ed25519_work -o IdentitiesOnly=yes"
+        );
+        let spaced = Account {
+            ssh_key: String::from("~/Library/Application Support/keys/id_work"),
+            ..account()
+        };
+        assert_eq!(
+            ssh_command(&spaced),
+            "ssh -i ~/'Library/Application Support/keys/id_work' -o IdentitiesOnly=yes"
+        );
+        let absolute = Account {
+            ssh_key: String::from("/keys/jo's key"),
+            ..account()
+        };
+        assert_eq!(
+            ssh_command(&absolute),
+            "ssh -i '/keys/jo'\\''s key' -o IdentitiesOnly=yes"
+        );
+        assert!(render(&spaced, "direnv").unwrap().contains(
+            "export GIT_SSH_COMMAND=\"ssh -i $HOME/'Library/Application Support/keys/id_work' -o IdentitiesOnly=yes\"\n"
+        ));
+    }
+
+    #[test]
+    fn test_envrc_block_is_replaced_in_place() {
+        let envrc = envrc_with_account("use nix\n", &account());
+        assert!(envrc.starts_with("use nix\n\n# >>> git-switch\n"));
+        assert!(envrc.contains("export GIT_AUTHOR_EMAIL='jo@corp.com'\n"));
+
+        let personal = Account {
+            name: String::from("personal"),
+            email: String::from("jo@home.org"),
+            ..account()
+        };
+        let edited = format!("{}layout python\n", envrc);
+        let rewritten = envrc_with_account(&edited, &personal);
+        assert_eq!(rewritten.matches(ENVRC_BEGIN).count(), 1);
+        assert!(rewritten.contains("jo@home.org"));
+        assert!(!rewritten.contains("jo@corp.com"));
+        assert!(rewritten.starts_with("use nix\n"));
+        assert!(rewritten.ends_with("# <<< git-switch\nlayout python\n"));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::config::Account;
+use crate::git::RemoteUrl;
+use crate::rules::{
+    Rule, load_rules_from_path, match_remote, normalize_pattern, parse_repository, pattern_matches,
+    save_rules_to_path,
+};
+use tempfile::TempDir;
+
+fn rule(pattern: &str, account: &str) -> Rule {
+    Rule {
+        pattern: pattern.to_string(),
+        account: account.to_string(),
+    }
+}
+
+fn account(name: &str) -> Account {
+    Account {
+        name: name.to_string(),
+        username: format!("{}user", name),
+        email: format!("{}@example.com", name),
+        ..Account::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns_match_segments() {
+        assert!(pattern_matches(
+            "github.com/mycompany/*",
+            "github.com/MyCompany/api"
+        ));
+        assert!(!pattern_matches(
+            "github.com/mycompany/*",
+            "github.com/mycompany/a/b"
+        ));
+        assert!(!pattern_matches(
+            "github.com/mycompany/*",
+            "github.com/other/api"
+        ));
+        assert!(pattern_matches(
+            "github.com/mycompany/**",
+            "github.com/mycompany/a/b"
+        ));
+        assert!(pattern_matches(
+            "*.example.com/**",
+            "git.example.com/team/api"
+        ));
+        assert!(pattern_matches(
+            "github.com/*/api-*",
+            "github.com/acme/api-server"
+        ));
+        assert!(!pattern_matches(
+            "github.com/*/api-*",
+            "github.com/acme/web"
+        ));
+    }
+
+    #[test]
+    fn test_normalize_pattern() {
+        assert_eq!(
+            normalize_pattern("https://github.com/mycompany/*/").unwrap(),
+            "github.com/mycompany/*"
+        );
+        assert_eq!(
+            normalize_pattern("github.com/mycompany/api.git").unwrap(),
+            "github.com/mycompany/api"
+        );
+        assert!(normalize_pattern("github.com").is_err());
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins_and_aliases_resolve() {
+        let accounts = vec![account("work"), account("personal")];
+        let rules = vec![
+            rule("github.com/mycompany/secret", "personal"),
+            rule("github.com/mycompany/*", "work"),
+            rule("github.com/**", "missing"),
+        ];
+        let matched = |url: &str| {
+            match_remote(&rules, &accounts, &RemoteUrl::parse(url).unwrap())
+                .map(|(_, acc)| acc.name.clone())
+        };
+
+        assert_eq!(
+            matched("git@github.com:mycompany/api.git").as_deref(),
+            Some("work")
+        );
+        assert_eq!(
+            matched("git@github-personal:mycompany/api.git").as_deref(),
+            Some("work")
+        );
+        assert_eq!(
+            matched("https://github.com/mycompany/secret").as_deref(),
+            Some("personal")
+        );
+        // Rules naming unknown accounts are skipped
+        assert_eq!(matched("git@github.com:someone/else.git"), None);
+    }
+
+    #[test]
+    fn test_parse_repository_and_round_trip() {
+        let remote = parse_repository("github.com/mycompany/api").unwrap();
+        assert_eq!(
+            (remote.host.as_str(), remote.path.as_str()),
+            ("github.com", "mycompany/api")
+        );
+        assert!(parse_repository("work").is_none());
+        assert!(parse_repository("mycompany/api").is_none());
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("rules.toml");
+        let rules = vec![rule("github.com/mycompany/*", "work")];
+        save_rules_to_path(&rules, &path).unwrap();
+        assert_eq!(load_rules_from_path(&path).unwrap(), rules);
+    }
+
+    #[test]
+    fn test_unparseable_rules_are_an_error() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("rules.toml");
+        assert!(load_rules_from_path(&path).unwrap().is_empty());
+
+        let content = "[[rule]]\npattern = github.com/a/*\naccount = \"work\"\n";
+        std::fs::write(&path, content).unwrap();
+        let err = load_rules_from_path(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("rules.toml"));
+    }
+}
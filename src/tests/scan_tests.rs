@@ -0,0 +1,158 @@
+use crate::config::Account;
+use crate::git::{RemoteUrl, alias_remote_url};
+use crate::rules::Rule;
+use crate::scan::{check_repositories, expected_account, find_repositories};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn accounts() -> Vec<Account> {
+    vec![
+        Account {
+            name: String::from("work"),
+            username: String::from("jdoe-corp"),
+            email: String::from("jdoe@corp.com"),
+            workspace: Some(String::from("/src/work")),
+            ..Default::default()
+        },
+        Account {
+            name: String::from("personal"),
+            username: String::from("jdoe"),
+            email: String::from("jdoe@example.com"),
+            ..Default::default()
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_url_forms() {
+        let scp = RemoteUrl::parse("git@github-work:corp/api.git").unwrap();
+        assert_eq!((scp.host.as_str(), scp.owner()), ("github-work", "corp"));
+        let https = RemoteUrl::parse("https://user@gitlab.com:443/group/sub/repo").unwrap();
+        assert_eq!(https.host, "gitlab.com");
+        assert_eq!(https.path, "group/sub/repo");
+        assert!(RemoteUrl::parse("/srv/git/repo.git").is_none());
+        assert!(RemoteUrl::parse("../relative:path/repo").is_none());
+    }
+
+    #[test]
+    fn test_expected_account_prefers_host_alias_over_workspace() {
+        let accounts = accounts();
+        let in_workspace = Path::new("/src/work/api");
+        let alias = RemoteUrl::parse("git@github-personal:jdoe/api.git").unwrap();
+        let (acc, _) = expected_account(&accounts, in_workspace, &[alias], &[], &[]).unwrap();
+        assert_eq!(acc.name, "personal");
+
+        let plain = RemoteUrl::parse("git@github.com:someone/api.git").unwrap();
+        let (acc, reason) = expected_account(
+            &accounts,
+            in_workspace,
+            std::slice::from_ref(&plain),
+            &[],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(acc.name, "work");
+        assert!(reason.contains("workspace"));
+
+        let owned = RemoteUrl::parse("https://github.com/JDOE/dotfiles").unwrap();
+        let (acc, _) =
+            expected_account(&accounts, Path::new("/tmp/x"), &[owned], &[], &[]).unwrap();
+        assert_eq!(acc.name, "personal");
+        assert!(expected_account(&accounts, Path::new("/tmp/x"), &[plain], &[], &[]).is_none());
+    }
+
+    #[test]
+    fn test_rules_pick_account_before_workspace() {
+        let accounts = accounts();
+        let rules = vec![Rule {
+            pattern: String::from("github.com/jdoe/*"),
+            account: String::from("personal"),
+        }];
+        let remote = RemoteUrl::parse("git@github.com:jdoe/notes.git").unwrap();
+        let (acc, reason) = expected_account(
+            &accounts,
+            Path::new("/src/work/notes"),
+            &[remote],
+            &[],
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(acc.name, "personal");
+        assert_eq!(reason, "rule github.com/jdoe/*");
+    }
+
+    #[test]
+    fn test_check_repositories_keeps_order_and_names_current_account() {
+        let root = TempDir::new().unwrap();
+        let accounts = accounts();
+        let mut repos = Vec::new();
+        for (name, email) in [
+            ("a", "jdoe@example.com"),
+            ("b", "jdoe@corp.com"),
+            ("c", "x@y.z"),
+        ] {
+            let repo = root.path().join(name);
+            fs::create_dir_all(&repo).unwrap();
+            let repo_str = repo.to_str().unwrap();
+            let git = |args: &[&str]| {
+                let status = std::process::Command::new("git")
+                    .args(["-C", repo_str])
+                    .args(args)
+                    .status()
+                    .unwrap();
+                assert!(status.success());
+            };
+            git(&["init", "--quiet"]);
+            git(&["config", "user.email", email]);
+            git(&["remote", "add", "origin", "git@github-work:corp/api.git"]);
+            repos.push(repo);
+        }
+
+        let reports = check_repositories(&repos, &accounts, &[], &[]);
+        let paths: Vec<_> = reports.iter().map(|report| report.path.clone()).collect();
+        assert_eq!(paths, repos);
+        let current: Vec<_> = reports
+            .iter()
+            .map(|report| report.current.as_deref())
+            .collect();
+        assert_eq!(current, [Some("personal"), Some("work"), None]);
+        assert!(
+            reports
+                .iter()
+                .all(|report| report.expected.as_ref().unwrap().0 == "work")
+        );
+    }
+
+    #[test]
+    fn test_alias_remote_url_keeps_path() {
+        let remote = RemoteUrl::parse("https://github.com/corp/api.git").unwrap();
+        assert_eq!(
+            alias_remote_url(&accounts()[0], &remote),
+            "git@github-work:corp/api.git"
+        );
+    }
+
+    #[test]
+    fn test_find_repositories_stops_at_repo_roots() {
+        let root = TempDir::new().unwrap();
+        for dir in [
+            "a/.git",
+            "a/nested/.git",
+            "b/c/.git",
+            "node_modules/x/.git",
+            ".hidden/.git",
+        ] {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        let repos = find_repositories(root.path());
+        assert_eq!(
+            repos,
+            vec![root.path().join("a"), root.path().join("b").join("c")]
+        );
+    }
+}
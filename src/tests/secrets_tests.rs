@@ -0,0 +1,42 @@
+use crate::config::Account;
+use crate::secrets::Secret;
+
+fn account() -> Account {
+    Account {
+        name: String::from("work"),
+        host: String::from("github.com"),
+        extra_hosts: vec![String::from("gitlab.com")],
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_names_are_per_account_and_host() {
+        let account = account();
+        assert_eq!(
+            Secret::Passphrase.entry_name(&account.name),
+            "work:passphrase"
+        );
+        let names: Vec<String> = account
+            .hosts()
+            .into_iter()
+            .map(|host| Secret::Token(host).entry_name(&account.name))
+            .collect();
+        assert_eq!(names, ["work:token:github.com", "work:token:gitlab.com"]);
+    }
+
+    #[cfg(not(feature = "keychain"))]
+    #[test]
+    fn test_builds_without_keyring_store_nothing() {
+        let account = account();
+        assert_eq!(crate::secrets::get(&account.name, Secret::Passphrase), None);
+        let err = crate::secrets::set(&account.name, Secret::Passphrase, "hunter2").unwrap_err();
+        assert_eq!(err.exit_code(), 9);
+        // Removing an account doesn't fail for lack of a keyring
+        crate::secrets::delete_account(&account).unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+use crate::config::Account;
+use crate::session::{SESSION_VAR, default_session_script, hook_script, render_session};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_file_and_hooks() {
+        let account = Account {
+            name: String::from("work"),
+            username: String::from("jdoe-corp"),
+            email: String::from("jdoe@corp.com"),
+            ssh_key: String::from("~/.ssh/id_ed25519_work"),
+            ..Default::default()
+        };
+        let session = render_session(&account);
+        assert!(session.contains("GIT_AUTHOR_EMAIL=jdoe@corp.com\n"));
+        assert!(session.ends_with(&format!("{}=work\n", SESSION_VAR)));
+
+        let bash = hook_script("bash").unwrap();
+        assert!(bash.contains("PROMPT_COMMAND=\"_git_switch_session"));
+        assert!(bash.contains("unset GIT_AUTHOR_NAME"));
+        assert!(hook_script("zsh").unwrap().contains("add-zsh-hook precmd"));
+        assert!(
+            hook_script("fish")
+                .unwrap()
+                .contains("--on-event fish_exit")
+        );
+        assert!(hook_script("tcsh").is_none());
+
+        let start = default_session_script("bash", "/opt/git switch", "Bob's");
+        assert!(
+            start.contains("GIT_SWITCH_SHELL_PID=$$ '/opt/git switch' use --session 'Bob'\\''s'")
+        );
+        assert!(
+            default_session_script("fish", "git-switch", "work")
+                .contains("; or GIT_SWITCH_SHELL_PID=$fish_pid")
+        );
+    }
+}
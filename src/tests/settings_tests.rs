@@ -0,0 +1,77 @@
+use crate::settings::{Settings, SwitchMode, load_settings_from_path, save_settings_to_path};
+use crate::ssh::{KeyType, derived_key_path, key_comment};
+use tempfile::TempDir;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_round_trip_and_reject_unknown_values() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("settings.toml");
+        assert_eq!(
+            load_settings_from_path(&path).unwrap().mode,
+            SwitchMode::Alias
+        );
+
+        let mut settings = Settings::default();
+        settings.set("mode", "sshcommand").unwrap();
+        save_settings_to_path(&settings, &path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "mode = \"sshcommand\"\n"
+        );
+        assert_eq!(load_settings_from_path(&path).unwrap(), settings);
+
+        std::fs::write(&path, "mode = sshcommand\n").unwrap();
+        let err = load_settings_from_path(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("settings.toml"));
+
+        assert!(settings.set("mode", "aliases").is_err());
+        assert!(settings.set("colour", "auto").is_err());
+        assert_eq!(settings.get("mode").as_deref(), Some("sshcommand"));
+        settings.set("mode", "").unwrap();
+        assert_eq!(settings.mode, SwitchMode::Alias);
+        settings.set("color", "off").unwrap();
+        assert_eq!(settings.get("color").as_deref(), Some("false"));
+        assert!(settings.set("key_type", "dsa").is_err());
+        assert!(settings.set("host", "https://gitlab.com").is_err());
+    }
+
+    #[test]
+    fn test_key_paths_follow_the_templates() {
+        let mut settings = Settings::default();
+        assert_eq!(
+            derived_key_path(
+                &settings,
+                None,
+                "Work Laptop",
+                KeyType::Ed25519Sk,
+                "github.com"
+            ),
+            "~/.ssh/id_ed25519_sk_work_laptop"
+        );
+        settings
+            .set("key_dir", "~/.ssh/git-switch/{account}/")
+            .unwrap();
+        settings.set("key_name", "id_{type}_{host}").unwrap();
+        assert_eq!(
+            derived_key_path(&settings, None, "work", KeyType::Rsa, "gitlab.com"),
+            "~/.ssh/git-switch/work/id_rsa_gitlab.com"
+        );
+        assert_eq!(
+            derived_key_path(&settings, Some("/keys"), "work", KeyType::Rsa, "gitlab.com"),
+            "/keys/id_rsa_gitlab.com"
+        );
+        assert!(settings.set("key_name", "keys/id_{account}").is_err());
+        settings.set("key_comment", "{email} [{account}]").unwrap();
+        assert_eq!(
+            key_comment(&settings, "Work", "jdoe@corp.com"),
+            "jdoe@corp.com [Work]"
+        );
+        settings.set("key_name", "").unwrap();
+        assert_eq!(settings.key_name, None);
+    }
+}
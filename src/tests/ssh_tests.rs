@@ -1,10 +1,15 @@
+use crate::config::{Account, validate_accounts};
+use crate::ssh::{
+    KeyType, default_ssh_options, generate_ssh_key, managed_block, managed_blocks,
+    parse_agent_output, parse_service_state, parse_ssh_option, rejects_option,
+    remove_account_entries, rename_ssh_key_files, renamed_key_path, render_ssh_config,
+    replace_key_passphrase, security_key_args, ssh_add_args, unused_keys, upsert_account_entries,
+};
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
-use crate::ssh::{generate_ssh_key, update_ssh_config, remove_ssh_config_entry};
-
 /// Helper function to create a temporary SSH directory structure
 fn setup_ssh_test_env() -> (TempDir, PathBuf) {
     let temp_dir = TempDir::new().expect("Failed to create temp directory");
@@ -14,25 +19,41 @@ fn setup_ssh_test_env() -> (TempDir, PathBuf) {
 }
 
 /// Helper function to create a mock SSH config file
-fn create_mock_ssh_config(ssh_dir: &PathBuf, content: &str) -> PathBuf {
+fn create_mock_ssh_config(ssh_dir: &Path, content: &str) -> PathBuf {
     let config_path = ssh_dir.join("config");
     let mut file = File::create(&config_path).expect("Failed to create SSH config");
-    file.write_all(content.as_bytes()).expect("Failed to write SSH config");
+    file.write_all(content.as_bytes())
+        .expect("Failed to write SSH config");
     config_path
 }
 
+fn work_account() -> Account {
+    Account {
+        name: String::from("work"),
+        ssh_key: String::from("~/.ssh/id_ed25519_work"),
+        extra_hosts: vec![String::from("gitlab.com")],
+        ..Default::default()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_update_ssh_config() {
-        let (_, ssh_dir) = setup_ssh_test_env();
+        let (_temp_dir, ssh_dir) = setup_ssh_test_env();
         let config_path = create_mock_ssh_config(&ssh_dir, "");
 
         // Test adding new SSH config
-        let result = update_ssh_config("test", "~/.ssh/id_rsa_test");
-        assert!(result.is_ok(), "Failed to update SSH config");
+        let account = Account {
+            name: "test".to_string(),
+            ssh_key: "~/.ssh/id_rsa_test".to_string(),
+            ..Default::default()
+        };
+        let content = fs::read_to_string(&config_path).expect("Failed to read SSH config");
+        fs::write(&config_path, upsert_account_entries(&content, &account))
+            .expect("Failed to update SSH config");
 
         // Verify config content
         let config_content = fs::read_to_string(config_path).expect("Failed to read SSH config");
@@ -42,16 +63,16 @@ mod tests {
 
     #[test]
     fn test_remove_ssh_config_entry() {
-        let (_, ssh_dir) = setup_ssh_test_env();
-        
+        let (_temp_dir, ssh_dir) = setup_ssh_test_env();
+
         // Create initial config with two entries
-        let initial_config = r#"# Test1 GitHub Account
+        let initial_config = r#"# test1 GitHub Account
 Host github-test1
     HostName github.com
     User git
     IdentityFile ~/.ssh/id_rsa_test1
 
-# Test2 GitHub Account
+# test2 GitHub Account
 Host github-test2
     HostName github.com
     User git
@@ -61,8 +82,13 @@ Host github-test2
         let config_path = create_mock_ssh_config(&ssh_dir, initial_config);
 
         // Remove one entry
-        let result = remove_ssh_config_entry("test1");
-        assert!(result.is_ok(), "Failed to remove SSH config entry");
+        let account = Account {
+            name: "test1".to_string(),
+            ..Default::default()
+        };
+        let content = fs::read_to_string(&config_path).expect("Failed to read SSH config");
+        fs::write(&config_path, remove_account_entries(&content, &account).0)
+            .expect("Failed to remove SSH config entry");
 
         // Verify remaining content
         let config_content = fs::read_to_string(config_path).expect("Failed to read SSH config");
@@ -72,13 +98,318 @@ Host github-test2
 
     #[test]
     fn test_generate_ssh_key() {
-        let (_, ssh_dir) = setup_ssh_test_env();
+        let (_temp_dir, ssh_dir) = setup_ssh_test_env();
         let key_path = ssh_dir.join("id_rsa_test").to_string_lossy().to_string();
 
-        generate_ssh_key(&key_path);
+        let created = generate_ssh_key(&key_path, KeyType::Rsa, Some(4096), None, &[], "test")
+            .expect("Failed to generate SSH key");
+        assert!(created);
 
         // Verify key files were created
-        assert!(ssh_dir.join("id_rsa_test").exists(), "Private key not created");
-        assert!(ssh_dir.join("id_rsa_test.pub").exists(), "Public key not created");
+        assert!(
+            ssh_dir.join("id_rsa_test").exists(),
+            "Private key not created"
+        );
+        assert!(
+            ssh_dir.join("id_rsa_test.pub").exists(),
+            "Public key not created"
+        );
+    }
+
+    #[test]
+    fn test_remove_account_entries_keeps_user_content() {
+        let account = work_account();
+        let user = "Host *\n    AddKeysToAgent yes\n";
+        let config = format!(
+            "{}{}\nHost personal\n    IdentityFile ~/.ssh/id_personal\n",
+            user,
+            managed_block(&account)
+        );
+        assert!(config.contains("# >>> git-switch:work\n# work GitHub Account\n"));
+        assert_eq!(
+            managed_blocks(&config),
+            vec![(
+                String::from("work"),
+                vec![String::from("~/.ssh/id_ed25519_work"); 2]
+            )]
+        );
+        assert_eq!(
+            remove_account_entries(&config, &account).0,
+            format!(
+                "{}\nHost personal\n    IdentityFile ~/.ssh/id_personal\n",
+                user
+            )
+        );
+    }
+
+    #[test]
+    fn test_upsert_replaces_entries_in_place() {
+        let mut account = work_account();
+        let user = "Host *\n    AddKeysToAgent yes\n";
+        let trailer = "\nHost personal\n    IdentityFile ~/.ssh/id_personal\n";
+        let config = format!("{}{}{}", user, managed_block(&account), trailer);
+        assert_eq!(upsert_account_entries(&config, &account), config);
+
+        account.ssh_key = String::from("~/.ssh/id_ed25519_new");
+        let updated = upsert_account_entries(&config, &account);
+        assert_eq!(
+            updated,
+            format!("{}{}{}", user, managed_block(&account), trailer)
+        );
+        assert_eq!(updated.matches("Host github-work").count(), 1);
+
+        // Unmarked entries from older versions are replaced by a marked block
+        let legacy = format!(
+            "{}\n# work GitHub Account\nHost github-work\n    HostName github.com\n    User git\n    IdentityFile ~/.ssh/id_ed25519_work\n{}",
+            user, trailer
+        );
+        assert_eq!(
+            upsert_account_entries(&legacy, &account),
+            format!("{}{}{}", user, managed_block(&account), trailer)
+        );
+    }
+
+    #[test]
+    fn test_remove_account_entries_handles_unmarked_blocks_with_extra_options() {
+        let account = work_account();
+        let config = "Host *\n    AddKeysToAgent yes\n\n\
+                      # work GitHub Account\nHost github-work\n    HostName github.com\n    User git\n    IdentityFile ~/.ssh/id_ed25519_work\n    IdentitiesOnly yes\nPort 22\n\n\
+                      # mine\nHost box\n    User me\n";
+        assert_eq!(
+            remove_account_entries(config, &account).0,
+            "Host *\n    AddKeysToAgent yes\n\n# mine\nHost box\n    User me\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_agent_output() {
+        let output = "SSH_AUTH_SOCK=/tmp/ssh-XXXX/agent.41; export SSH_AUTH_SOCK;\nSSH_AGENT_PID=42; export SSH_AGENT_PID;\necho Agent pid 42;\n";
+        assert_eq!(
+            parse_agent_output(output),
+            vec![
+                (
+                    String::from("SSH_AUTH_SOCK"),
+                    String::from("/tmp/ssh-XXXX/agent.41")
+                ),
+                (String::from("SSH_AGENT_PID"), String::from("42")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_service_state() {
+        let stopped = "\r\nSERVICE_NAME: ssh-agent \r\n        TYPE               : 10  WIN32_OWN_PROCESS  \r\n        STATE              : 1  STOPPED \r\n        WIN32_EXIT_CODE    : 1077  (0x435)\r\n";
+        assert_eq!(parse_service_state(stopped), Some("STOPPED"));
+        let missing = "[SC] EnumQueryServicesStatus:OpenService FAILED 1060:\r\n\r\nThe specified service does not exist as an installed service.\r\n";
+        assert_eq!(parse_service_state(missing), None);
+    }
+
+    #[test]
+    fn test_key_type_from_public_key() {
+        assert_eq!(
+            KeyType::from_public_key("ssh-ed25519 AAAAC3Nz work@example.com"),
+            Some((KeyType::Ed25519, None))
+        );
+        assert_eq!(
+            KeyType::from_public_key("ecdsa-sha2-nistp384 AAAAE2Vj"),
+            Some((KeyType::Ecdsa, Some(384)))
+        );
+        assert_eq!(
+            KeyType::from_public_key("sk-ssh-ed25519@openssh.com AAAAGnNr"),
+            Some((KeyType::Ed25519Sk, None))
+        );
+        assert_eq!(KeyType::from_public_key("ssh-dss AAAAB3Nz"), None);
+        assert_eq!(KeyType::from_public_key(""), None);
+    }
+
+    #[test]
+    fn test_tags_are_validated() {
+        let mut account = Account {
+            name: String::from("work"),
+            username: String::from("jdoe"),
+            email: String::from("jdoe@example.com"),
+            auth: Some(String::from("https")),
+            tags: vec![String::from("client-x")],
+            ..Default::default()
+        };
+        assert!(validate_accounts(std::slice::from_ref(&account)).is_empty());
+        assert!(account.has_tag("Client-X"));
+        account.tags.push(String::from("two words"));
+        assert_eq!(validate_accounts(&[account]).len(), 1);
+    }
+
+    #[test]
+    fn test_render_ssh_config_options() {
+        let mut account = work_account();
+        account.extra_hosts.clear();
+        assert!(
+            render_ssh_config(&account)
+                .contains("    IdentityFile ~/.ssh/id_ed25519_work\n    IdentitiesOnly yes\n")
+        );
+        assert!(!default_ssh_options(false).contains(&("UseKeychain", "yes")));
+        assert!(default_ssh_options(true).contains(&("UseKeychain", "yes")));
+
+        let (option, value) = parse_ssh_option("Port=2222").unwrap();
+        account.ssh_options.insert(option, value);
+        account
+            .ssh_options
+            .insert(String::from("identitiesonly"), String::from("no"));
+        let rendered = render_ssh_config(&account);
+        assert!(rendered.ends_with("    Port 2222\n    identitiesonly no\n"));
+        assert!(!rendered.contains("IdentitiesOnly yes"));
+
+        assert!(parse_ssh_option("Port").is_err());
+        assert!(parse_ssh_option("hostname=evil.example").is_err());
+        assert_eq!(
+            parse_ssh_option("ProxyJump=").unwrap(),
+            (String::from("ProxyJump"), String::new())
+        );
+        account
+            .ssh_options
+            .insert(String::from("ProxyJump"), String::from("a\nHost *"));
+        assert!(
+            validate_accounts(&[account])
+                .iter()
+                .any(|e| e.contains("single-line value"))
+        );
+    }
+
+    #[test]
+    fn test_endpoint_override_only_applies_to_the_primary_host() {
+        let mut account = work_account();
+        account.hostname = Some(String::from("ssh.github.com"));
+        account.port = Some(443);
+        account.proxy_jump = Some(String::from("jdoe@bastion.corp:2222"));
+        let rendered = render_ssh_config(&account);
+        assert!(rendered.contains(
+            "Host github-work\n    HostName ssh.github.com\n    User git\n    IdentityFile ~/.ssh/id_ed25519_work\n    Port 443\n    ProxyJump jdoe@bastion.corp:2222\n"
+        ));
+        assert_eq!(rendered.matches("ProxyJump").count(), 1);
+        assert!(rendered.contains("Host gitlab-work\n    HostName gitlab.com\n"));
+        assert_eq!(rendered.matches("Port").count(), 1);
+
+        account.hostname = Some(String::from("two words"));
+        assert!(
+            validate_accounts(&[account])
+                .iter()
+                .any(|e| e.contains("not a host name"))
+        );
+    }
+
+    #[test]
+    fn test_resident_keys_get_a_user_id_per_account() {
+        assert_eq!(
+            security_key_args("Work Laptop", &[String::from("resident")]),
+            ["resident", "user=git-switch-work_laptop"]
+        );
+        assert_eq!(
+            security_key_args("work", &[String::from("verify-required")]),
+            ["verify-required"]
+        );
+        let mut account = work_account();
+        account.key_options = vec![String::from("no-touch-required")];
+        assert!(
+            validate_accounts(&[account])
+                .iter()
+                .any(|e| e.contains("key option"))
+        );
+    }
+
+    #[test]
+    fn test_renamed_key_path_only_follows_derived_names() {
+        assert_eq!(
+            renamed_key_path("~/.ssh/id_ed25519_work", "Work", "Day Job"),
+            Some(String::from("~/.ssh/id_ed25519_day_job"))
+        );
+        assert_eq!(renamed_key_path("~/.ssh/id_rsa", "work", "job"), None);
+    }
+
+    #[test]
+    fn test_rename_key_files_moves_companions_and_refuses_to_overwrite() {
+        let dir = TempDir::new().unwrap();
+        let old = dir.path().join("id_ed25519_work");
+        let new = dir.path().join("id_ed25519_job");
+        fs::write(&old, "private").unwrap();
+        fs::write(dir.path().join("id_ed25519_work.pub"), "public").unwrap();
+
+        rename_ssh_key_files(old.to_str().unwrap(), new.to_str().unwrap()).unwrap();
+        assert!(!old.exists());
+        assert_eq!(fs::read_to_string(&new).unwrap(), "private");
+        assert!(dir.path().join("id_ed25519_job.pub").exists());
+
+        fs::write(&old, "other").unwrap();
+        assert!(rename_ssh_key_files(old.to_str().unwrap(), new.to_str().unwrap()).is_err());
+        assert_eq!(fs::read_to_string(&old).unwrap(), "other");
+    }
+
+    #[test]
+    fn test_ssh_add_args_use_the_keychain_only_with_apple_ssh_add() {
+        assert_eq!(
+            ssh_add_args("/k/id_work", Some(3600), false),
+            vec!["-t", "3600", "/k/id_work"]
+        );
+        assert_eq!(
+            ssh_add_args("/k/id_work", None, true),
+            vec!["--apple-use-keychain", "/k/id_work"]
+        );
+        // Homebrew's OpenSSH and Apple's answer to an agent with no keys
+        assert!(rejects_option(
+            "ssh-add: illegal option -- -\nusage: ssh-add [-cDdKkLlqvXx] [-E fingerprint_hash]"
+        ));
+        assert!(!rejects_option("The agent has no identities."));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_replace_key_passphrase_without_prompting() {
+        let dir = TempDir::new().unwrap();
+        let key = dir.path().join("id_ed25519_work");
+        let key = key.to_str().unwrap();
+        let keygen = |args: &[&str]| {
+            std::process::Command::new("ssh-keygen")
+                .args(args)
+                .stdin(std::process::Stdio::null())
+                .output()
+                .unwrap()
+                .status
+                .success()
+        };
+        assert!(keygen(&[
+            "-q",
+            "-t",
+            "ed25519",
+            "-N",
+            "old secret",
+            "-f",
+            key
+        ]));
+
+        replace_key_passphrase(key, "old secret", "new secret").unwrap();
+        assert!(keygen(&["-y", "-P", "new secret", "-f", key]));
+        assert!(!keygen(&["-y", "-P", "old secret", "-f", key]));
+        assert!(replace_key_passphrase(key, "wrong", "other").is_err());
+    }
+
+    #[test]
+    fn test_unused_keys_skips_keys_of_saved_accounts() {
+        let dir = TempDir::new().unwrap();
+        for name in [
+            "id_rsa",
+            "id_rsa.pub",
+            "id_work",
+            "id_work.pub",
+            "lonely",
+            "config",
+        ] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let account = Account {
+            ssh_key: dir.path().join("id_work").to_string_lossy().into_owned(),
+            ..work_account()
+        };
+
+        assert_eq!(
+            unused_keys(dir.path(), &[account]),
+            vec![dir.path().join("id_rsa")]
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use crate::config::Account;
+use crate::sync::{Prefer, merge_accounts};
+
+fn account(name: &str, email: &str) -> Account {
+    Account {
+        name: name.to_string(),
+        username: format!("{}user", name),
+        email: email.to_string(),
+        ssh_key: format!("~/.ssh/id_ed25519_{}", name),
+        ..Default::default()
+    }
+}
+
+fn names(accounts: &[Account]) -> Vec<&str> {
+    accounts.iter().map(|acc| acc.name.as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_takes_changes_from_both_sides() {
+        let base = vec![
+            account("work", "work@example.com"),
+            account("home", "home@example.com"),
+            account("old", "old@example.com"),
+        ];
+        // Here: work changed, laptop added. There: home changed, old removed, oss added.
+        let local = vec![
+            account("work", "work@corp.example"),
+            account("home", "home@example.com"),
+            account("old", "old@example.com"),
+            account("laptop", "laptop@example.com"),
+        ];
+        let remote = vec![
+            account("work", "work@example.com"),
+            account("home", "me@home.example"),
+            account("oss", "oss@example.com"),
+        ];
+
+        let merge = merge_accounts(&base, &local, &remote, Prefer::Local);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(names(&merge.accounts), ["work", "home", "laptop", "oss"]);
+        assert_eq!(merge.accounts[0].email, "work@corp.example");
+        assert_eq!(merge.accounts[1].email, "me@home.example");
+    }
+
+    #[test]
+    fn test_merge_conflicts_follow_the_preferred_side() {
+        let base = vec![account("work", "work@example.com")];
+        let local = vec![account("work", "here@example.com")];
+        let remote = vec![account("work", "there@example.com")];
+
+        let pushed = merge_accounts(&base, &local, &remote, Prefer::Local);
+        assert_eq!(pushed.conflicts, ["work"]);
+        assert_eq!(pushed.accounts[0].email, "here@example.com");
+        let pulled = merge_accounts(&base, &local, &remote, Prefer::Remote);
+        assert_eq!(pulled.accounts[0].email, "there@example.com");
+
+        // Before the first sync there is no base; nothing is dropped
+        let first = merge_accounts(
+            &[],
+            &local,
+            &[account("home", "h@example.com")],
+            Prefer::Remote,
+        );
+        assert_eq!(names(&first.accounts), ["work", "home"]);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::doctor::{Severity, token_findings};
+use crate::provider::{KeyApi, split_headers};
+use crate::tokens::{
+    TokenInfo, TokenIssue, load_tokens_from_path, parse_day, record_in_path, review_token,
+};
+use tempfile::TempDir;
+
+fn github_token(scopes: Option<&[&str]>, expires_on: Option<&str>) -> TokenInfo {
+    TokenInfo {
+        host: String::from("github.com"),
+        source: String::from("GITHUB_TOKEN"),
+        fingerprint: String::from("0011223344556677"),
+        login: String::from("jdoe"),
+        scopes: scopes.map(|scopes| scopes.iter().map(|s| s.to_string()).collect()),
+        expires_on: expires_on.map(str::to_string),
+        checked_at: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_headers_and_dates() {
+        let (headers, body) = split_headers(
+            "HTTP/2 200\r\nX-OAuth-Scopes: repo, admin:public_key\r\ngithub-authentication-token-expiration: 2026-11-01 12:00:00 UTC\r\n\r\n{\"login\":\"jdoe\"}",
+        );
+        assert_eq!(body, "{\"login\":\"jdoe\"}");
+        assert!(headers.contains(&(
+            String::from("x-oauth-scopes"),
+            String::from("repo, admin:public_key")
+        )));
+
+        assert_eq!(parse_day("1970-01-01"), Some(0));
+        assert_eq!(parse_day("2000-03-01"), Some(11017));
+        assert_eq!(
+            parse_day("2026-11-01 12:00:00 UTC"),
+            parse_day("2026-11-01")
+        );
+        assert_eq!(parse_day("never"), None);
+    }
+
+    #[test]
+    fn test_review_token_scopes_and_expiry() {
+        let today = parse_day("2026-10-20").unwrap();
+        let classic = github_token(Some(&["repo", "read:user"]), Some("2026-10-25"));
+        assert_eq!(
+            review_token(&classic, KeyApi::GitHub, today, 14),
+            vec![
+                TokenIssue::MissingScope(&["admin:public_key", "write:public_key"]),
+                TokenIssue::BroadScopes(vec![String::from("repo")]),
+                TokenIssue::ExpiresSoon(String::from("2026-10-25"), 5),
+            ]
+        );
+        assert!(review_token(&classic, KeyApi::GitHub, today, 3).len() == 2);
+        assert_eq!(
+            review_token(&classic, KeyApi::GitHub, today + 10, 14).last(),
+            Some(&TokenIssue::Expired(String::from("2026-10-25")))
+        );
+
+        // Fine-grained tokens don't report scopes, so only expiry is checked
+        let fine_grained = github_token(None, None);
+        let findings = token_findings(&fine_grained, KeyApi::GitHub, "GitHub", today, 14);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Ok);
+    }
+
+    #[test]
+    fn test_record_replaces_earlier_checks_of_the_source() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tokens.toml");
+        record_in_path(&github_token(None, None), &path).unwrap();
+        let renewed = github_token(Some(&["write:public_key"]), Some("2027-01-01"));
+        record_in_path(&renewed, &path).unwrap();
+        assert_eq!(load_tokens_from_path(&path), vec![renewed]);
+    }
+}
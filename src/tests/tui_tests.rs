@@ -0,0 +1,77 @@
+use crate::config::Account;
+use crate::tui::{Action, Dashboard, Snapshot};
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+use ratatui::crossterm::event::KeyCode;
+
+fn snapshot() -> Snapshot {
+    let account = |name: &str| Account {
+        name: name.to_string(),
+        username: format!("{}user", name),
+        email: format!("{}@example.com", name),
+        ssh_key: format!("~/.ssh/id_ed25519_{}", name),
+        ..Default::default()
+    };
+    Snapshot {
+        accounts: vec![account("work"), account("home")],
+        identity: ("homeuser".to_string(), "home@example.com".to_string()),
+        agent_keys: Some(vec![("SHA256:abc".to_string(), Some("home".to_string()))]),
+        remote: Some("git@github-home:me/dotfiles.git".to_string()),
+    }
+}
+
+fn screen(dashboard: &Dashboard) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+    terminal.draw(|frame| dashboard.render(frame)).unwrap();
+    let buffer = terminal.backend().buffer().clone();
+    buffer
+        .content()
+        .chunks(buffer.area.width as usize)
+        .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashboard_shows_accounts_and_active_identity() {
+        let dashboard = Dashboard::new(snapshot());
+        // Opens on the active account
+        assert_eq!(dashboard.selected, 1);
+        let screen = screen(&dashboard);
+        assert!(screen.contains("Identity: homeuser <home@example.com>  (home)"));
+        assert!(screen.contains("git@github-home:me/dotfiles.git"));
+        assert!(screen.contains("● home  home@example.com"));
+        assert!(screen.contains("work  work@example.com"));
+        assert!(screen.contains("Loaded:   yes"));
+        assert!(screen.contains("home  SHA256:abc"));
+    }
+
+    #[test]
+    fn test_keys_move_the_selection_and_pick_actions() {
+        let mut dashboard = Dashboard::new(snapshot());
+        assert_eq!(dashboard.handle_key(KeyCode::Up), None);
+        assert_eq!(dashboard.handle_key(KeyCode::Up), None);
+        assert_eq!(dashboard.selected, 0);
+        assert_eq!(
+            dashboard.handle_key(KeyCode::Enter),
+            Some(Action::Use("work".to_string()))
+        );
+        dashboard.handle_key(KeyCode::Char('j'));
+        assert_eq!(
+            dashboard.handle_key(KeyCode::Char('v')),
+            Some(Action::Verify("home".to_string()))
+        );
+        assert_eq!(dashboard.handle_key(KeyCode::Char('q')), Some(Action::Quit));
+
+        // The selection follows the account when the list changes
+        let mut fewer = snapshot();
+        fewer.accounts.remove(0);
+        dashboard.refresh(fewer);
+        assert_eq!(dashboard.selected, 0);
+        assert_eq!(dashboard.selected_account().unwrap().name, "home");
+    }
+}
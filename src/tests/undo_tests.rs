@@ -0,0 +1,49 @@
+use crate::files::{self, MAX_BACKUPS};
+use crate::undo::{UndoFile, UndoRecord, load_undo_from_path, save_undo_to_path};
+use std::fs;
+use tempfile::TempDir;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_keeps_the_first_backup_for_undo() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config");
+        fs::write(&path, "v0").unwrap();
+        for version in 1..=MAX_BACKUPS + 2 {
+            files::write_with_backup(&path, &format!("v{}", version)).unwrap();
+        }
+        assert_eq!(
+            files::list_backups(dir.path(), "config").unwrap().len(),
+            MAX_BACKUPS
+        );
+
+        // Other tests write through the journal too
+        let (_, backup) = files::take_journal()
+            .into_iter()
+            .find(|(noted, _)| noted.ends_with("config") && noted.starts_with(dir.path()))
+            .expect("config not journaled");
+        let backup = backup.expect("no backup");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "v0");
+
+        let record = UndoRecord {
+            command: String::from("remove work"),
+            ts: 1_760_684_400,
+            files: vec![
+                UndoFile {
+                    path: path.clone(),
+                    backup: Some(backup),
+                },
+                UndoFile {
+                    path: dir.path().join("accounts.toml"),
+                    backup: None,
+                },
+            ],
+        };
+        let record_path = dir.path().join("undo.toml");
+        save_undo_to_path(&record, &record_path).unwrap();
+        assert_eq!(load_undo_from_path(&record_path), Some(record));
+    }
+}
@@ -0,0 +1,230 @@
+//! Checks of provider API tokens: whether they can manage SSH keys, whether
+//! they grant much more than that, and when they expire.
+//!
+//! Results are kept in `~/.git-switch/tokens.toml`, keyed by a fingerprint
+//! of the token (never the token itself), so `doctor` only asks the provider
+//! again once a check is a day old.
+
+use crate::config::get_data_dir;
+use crate::provider::KeyApi;
+use crate::utils::now_secs;
+use crate::{problem, say};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Days before expiry from which `doctor` warns about a token.
+pub const DEFAULT_EXPIRY_WINDOW_DAYS: u32 = 14;
+
+/// Age in seconds after which `doctor` validates a token again.
+pub const RECHECK_AFTER_SECS: u64 = 24 * 60 * 60;
+
+/// What git-switch learned about a token the last time it was validated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub host: String,
+    /// Where the token came from: an environment variable or a provider CLI
+    /// such as gh.
+    pub source: String,
+    pub fingerprint: String,
+    /// User the token belongs to.
+    pub login: String,
+    /// Granted scopes, or `None` if the provider doesn't report them (e.g.
+    /// GitHub's fine-grained tokens).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+    /// Expiry date as `YYYY-MM-DD`; `None` for tokens that don't expire.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_on: Option<String>,
+    /// Unix time of the check.
+    pub checked_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenIssue {
+    /// None of the scopes that allow managing SSH keys was granted.
+    MissingScope(&'static [&'static str]),
+    /// Granted scopes git-switch never needs.
+    BroadScopes(Vec<String>),
+    Expired(String),
+    /// Expiry date and the days left.
+    ExpiresSoon(String, i64),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TokenStore {
+    #[serde(default, rename = "token")]
+    tokens: Vec<TokenInfo>,
+}
+
+/// Scopes any one of which lets a token add SSH keys to its user. Empty
+/// for providers whose tokens don't report scopes.
+pub fn key_scopes(api: KeyApi) -> &'static [&'static str] {
+    match api {
+        KeyApi::GitHub => &["admin:public_key", "write:public_key"],
+        KeyApi::GitLab => &["api"],
+        KeyApi::Gitea | KeyApi::SourceHut | KeyApi::Bitbucket => &[],
+    }
+}
+
+/// Scopes that put far more than the user's keys at risk if the token leaks.
+fn broad_scopes(api: KeyApi) -> &'static [&'static str] {
+    match api {
+        KeyApi::GitHub => &[
+            "repo",
+            "delete_repo",
+            "workflow",
+            "admin:org",
+            "admin:repo_hook",
+            "admin:org_hook",
+            "admin:enterprise",
+            "write:packages",
+            "delete:packages",
+            "user",
+        ],
+        KeyApi::GitLab => &["sudo", "admin_mode", "write_repository", "write_registry"],
+        KeyApi::Gitea | KeyApi::SourceHut | KeyApi::Bitbucket => &[],
+    }
+}
+
+/// Identifies a token without storing it: the start of its SHA-256.
+pub fn fingerprint(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Days since 1970-01-01 of a `YYYY-MM-DD` date (anything after the date,
+/// like a time, is ignored).
+pub fn parse_day(date: &str) -> Option<i64> {
+    let mut parts = date.get(..10)?.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Howard Hinnant's days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+/// Compares a token against what key upload needs and its expiry date
+/// against `today` (days since 1970-01-01).
+pub fn review_token(
+    info: &TokenInfo,
+    api: KeyApi,
+    today: i64,
+    window_days: u32,
+) -> Vec<TokenIssue> {
+    let mut issues = Vec::new();
+    if let Some(scopes) = &info.scopes {
+        let needed = key_scopes(api);
+        if !needed.is_empty() && !needed.iter().any(|scope| scopes.iter().any(|s| s == scope)) {
+            issues.push(TokenIssue::MissingScope(needed));
+        }
+        let broad: Vec<String> = scopes
+            .iter()
+            .filter(|scope| broad_scopes(api).contains(&scope.as_str()))
+            .cloned()
+            .collect();
+        if !broad.is_empty() {
+            issues.push(TokenIssue::BroadScopes(broad));
+        }
+    }
+    if let Some(expires_on) = &info.expires_on
+        && let Some(expiry) = parse_day(expires_on)
+    {
+        let days_left = expiry - today;
+        if days_left < 0 {
+            issues.push(TokenIssue::Expired(expires_on.clone()));
+        } else if days_left <= i64::from(window_days) {
+            issues.push(TokenIssue::ExpiresSoon(expires_on.clone(), days_left));
+        }
+    }
+    issues
+}
+
+impl TokenIssue {
+    pub fn describe(&self) -> String {
+        match self {
+            TokenIssue::MissingScope(needed) => format!(
+                "cannot manage SSH keys; it needs the {} scope",
+                needed.join(" or ")
+            ),
+            TokenIssue::BroadScopes(scopes) => {
+                format!("grants more than git-switch needs ({})", scopes.join(", "))
+            }
+            TokenIssue::Expired(date) => format!("expired on {}", date),
+            TokenIssue::ExpiresSoon(date, days) => {
+                format!("expires on {} ({} day(s) left)", date, days)
+            }
+        }
+    }
+}
+
+pub fn tokens_path() -> PathBuf {
+    get_data_dir().join("tokens.toml")
+}
+
+pub(crate) fn load_tokens_from_path(path: &Path) -> Vec<TokenInfo> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml::from_str::<TokenStore>(&content).ok())
+        .map(|store| store.tokens)
+        .unwrap_or_default()
+}
+
+/// Stores the result of a check, replacing earlier ones for the same host
+/// and token source.
+pub(crate) fn record_in_path(info: &TokenInfo, path: &Path) -> io::Result<()> {
+    let mut tokens = load_tokens_from_path(path);
+    tokens.retain(|known| !(known.host == info.host && known.source == info.source));
+    tokens.push(info.clone());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content = toml::to_string_pretty(&TokenStore { tokens }).map_err(io::Error::other)?;
+    fs::write(path, content)
+}
+
+pub fn load_tokens() -> Vec<TokenInfo> {
+    load_tokens_from_path(&tokens_path())
+}
+
+pub fn record(info: &TokenInfo) -> io::Result<()> {
+    record_in_path(info, &tokens_path())
+}
+
+/// Checks a token about to be used for a key upload: missing scopes and
+/// expiry fail, broad scopes and near expiry are only reported.
+pub fn vet_for_upload(info: &TokenInfo, api: KeyApi, provider_name: &str) -> Result<(), String> {
+    if let Err(e) = record(info) {
+        problem!("⚠️ Failed to save the token check: {}", e);
+    }
+    let today = (now_secs() / 86400) as i64;
+    for issue in review_token(info, api, today, DEFAULT_EXPIRY_WINDOW_DAYS) {
+        let message = format!(
+            "The {} token from {} {}",
+            provider_name,
+            info.source,
+            issue.describe()
+        );
+        match issue {
+            TokenIssue::MissingScope(_) | TokenIssue::Expired(_) => return Err(message),
+            TokenIssue::BroadScopes(_) => {
+                say!("⚠️ {}; a token limited to SSH keys is safer.", message)
+            }
+            TokenIssue::ExpiresSoon(..) => say!("⚠️ {}.", message),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,324 @@
+//! `git-switch tui`: a dashboard of the saved accounts, the active identity,
+//! the keys in ssh-agent and the current repository's remote, with keys to
+//! switch, add, edit and verify accounts.
+//!
+//! Actions run the regular commands with the terminal restored, so their
+//! output and prompts look as they do on the command line; the dashboard
+//! comes back when the user presses Enter.
+
+use crate::commands::{
+    AccountEdit, AddOptions, UseOptions, add_account_wizard, edit_account, use_account,
+    verify_account,
+};
+use crate::config::{Account, load_accounts, match_identity};
+use crate::error::{GitSwitchError, IoContext, Result};
+use crate::git::{config_value, is_inside_work_tree};
+use crate::problem;
+use crate::ssh::{agent_fingerprints, key_fingerprint};
+use crate::utils::{is_interactive, prompt_line};
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+
+const HELP: &str = "↑/↓ select · Enter/u use · a add · e edit · v verify · r refresh · q quit";
+
+/// Everything the dashboard shows, gathered when it opens and after each action.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub accounts: Vec<Account>,
+    /// Effective `user.name` and `user.email`.
+    pub identity: (String, String),
+    /// Keys in ssh-agent with the account each belongs to; `None` if the
+    /// agent can't be reached.
+    pub agent_keys: Option<Vec<(String, Option<String>)>>,
+    /// `origin` of the repository in the current directory, if any.
+    pub remote: Option<String>,
+}
+
+impl Snapshot {
+    pub fn gather() -> Snapshot {
+        let accounts = load_accounts();
+        let agent_keys = agent_fingerprints().map(|loaded| {
+            loaded
+                .into_iter()
+                .map(|fingerprint| {
+                    let owner = accounts
+                        .iter()
+                        .find(|acc| key_fingerprint(&acc.ssh_key).as_ref() == Some(&fingerprint))
+                        .map(|acc| acc.name.clone());
+                    (fingerprint, owner)
+                })
+                .collect()
+        });
+        Snapshot {
+            identity: (
+                config_value(None, "user.name").unwrap_or_default(),
+                config_value(None, "user.email").unwrap_or_default(),
+            ),
+            remote: if is_inside_work_tree() {
+                config_value(None, "remote.origin.url")
+            } else {
+                None
+            },
+            agent_keys,
+            accounts,
+        }
+    }
+
+    /// The saved account matching the effective identity.
+    pub fn active(&self) -> Option<&Account> {
+        match_identity(&self.accounts, &self.identity.0, &self.identity.1)
+    }
+}
+
+/// What a key press asks the dashboard to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Use(String),
+    Add,
+    Edit(String),
+    Verify(String),
+    Refresh,
+    Quit,
+}
+
+/// State of the dashboard between frames.
+pub struct Dashboard {
+    pub snapshot: Snapshot,
+    pub selected: usize,
+    /// Outcome of the last action, shown above the key help.
+    pub message: String,
+}
+
+impl Dashboard {
+    /// Opens on the active account.
+    pub fn new(snapshot: Snapshot) -> Dashboard {
+        let selected = snapshot
+            .active()
+            .and_then(|active| snapshot.accounts.iter().position(|acc| acc == active))
+            .unwrap_or(0);
+        Dashboard {
+            snapshot,
+            selected,
+            message: String::new(),
+        }
+    }
+
+    pub fn selected_account(&self) -> Option<&Account> {
+        self.snapshot.accounts.get(self.selected)
+    }
+
+    /// Moves the selection, or returns the action the key stands for.
+    pub fn handle_key(&mut self, code: KeyCode) -> Option<Action> {
+        let count = self.snapshot.accounts.len();
+        let selected = self.selected_account().map(|acc| acc.name.clone());
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(count.saturating_sub(1));
+                None
+            }
+            KeyCode::Enter | KeyCode::Char('u') => selected.map(Action::Use),
+            KeyCode::Char('e') => selected.map(Action::Edit),
+            KeyCode::Char('v') => selected.map(Action::Verify),
+            KeyCode::Char('a') => Some(Action::Add),
+            KeyCode::Char('r') => Some(Action::Refresh),
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            _ => None,
+        }
+    }
+
+    /// Replaces the snapshot after an action, keeping the selection on the
+    /// same account where it still exists.
+    pub fn refresh(&mut self, snapshot: Snapshot) {
+        let name = self.selected_account().map(|acc| acc.name.clone());
+        self.selected = name
+            .and_then(|name| snapshot.accounts.iter().position(|acc| acc.name == name))
+            .unwrap_or(0)
+            .min(snapshot.accounts.len().saturating_sub(1));
+        self.snapshot = snapshot;
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        let [top, middle, bottom] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Min(6),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+        let [accounts_area, details_area] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+                .areas(middle);
+        let [account_area, agent_area] =
+            Layout::vertical([Constraint::Min(5), Constraint::Length(6)]).areas(details_area);
+
+        let snapshot = &self.snapshot;
+        let (name, email) = &snapshot.identity;
+        let active = snapshot.active();
+        let identity = vec![
+            Line::from(format!(
+                "Identity: {} <{}>  ({})",
+                name,
+                email,
+                active.map_or("no saved account".to_string(), |acc| acc.name.clone())
+            )),
+            Line::from(format!(
+                "Remote:   {}",
+                snapshot.remote.as_deref().unwrap_or("(none)")
+            )),
+        ];
+        frame.render_widget(
+            Paragraph::new(identity).block(Block::bordered().title(" git-switch ")),
+            top,
+        );
+
+        let items: Vec<ListItem> = snapshot
+            .accounts
+            .iter()
+            .map(|acc| {
+                let marker = if Some(acc) == active { "●" } else { " " };
+                ListItem::new(format!("{} {}  {}", marker, acc.name, acc.email))
+            })
+            .collect();
+        let list = if items.is_empty() {
+            List::new([ListItem::new("No saved accounts; press a to add one.")])
+        } else {
+            List::new(items).highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        };
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(
+            list.block(Block::bordered().title(" Accounts ")),
+            accounts_area,
+            &mut state,
+        );
+
+        let details = match self.selected_account() {
+            Some(acc) => vec![
+                Line::from(format!("Username: {}", acc.username)),
+                Line::from(format!("Email:    {}", acc.email)),
+                Line::from(format!("Hosts:    {}", acc.hosts().join(", "))),
+                Line::from(format!("SSH key:  {}", acc.ssh_key)),
+                Line::from(format!(
+                    "Loaded:   {}",
+                    if snapshot
+                        .agent_keys
+                        .iter()
+                        .flatten()
+                        .any(|(_, owner)| { owner.as_deref() == Some(acc.name.as_str()) })
+                    {
+                        "yes"
+                    } else {
+                        "no"
+                    }
+                )),
+            ],
+            None => Vec::new(),
+        };
+        frame.render_widget(
+            Paragraph::new(details).block(Block::bordered().title(" Account ")),
+            account_area,
+        );
+
+        let agent: Vec<Line> = match &snapshot.agent_keys {
+            None => vec![Line::from("Could not reach the SSH agent.")],
+            Some(keys) if keys.is_empty() => vec![Line::from("No keys loaded.")],
+            Some(keys) => keys
+                .iter()
+                .map(|(fingerprint, owner)| {
+                    Line::from(format!(
+                        "{}  {}",
+                        owner.as_deref().unwrap_or("(unmanaged)"),
+                        fingerprint
+                    ))
+                })
+                .collect(),
+        };
+        frame.render_widget(
+            Paragraph::new(agent).block(Block::bordered().title(" SSH agent ")),
+            agent_area,
+        );
+
+        frame.render_widget(
+            Paragraph::new(vec![Line::from(self.message.as_str()), Line::from(HELP)]),
+            bottom,
+        );
+    }
+}
+
+/// Runs an action with the terminal in its normal state.
+fn perform(action: &Action) -> Result<String> {
+    match action {
+        Action::Use(name) => {
+            use_account(name, UseOptions::default())?;
+            Ok(format!("✅ Switched to '{}'.", name))
+        }
+        Action::Add => {
+            add_account_wizard(AddOptions::default())?;
+            Ok("✅ Added the account.".to_string())
+        }
+        Action::Edit(name) => {
+            let ask = |label: &str| prompt_line(label).filter(|value| !value.is_empty());
+            let edit = AccountEdit {
+                email: ask("New email (empty keeps it): "),
+                username: ask("New username (empty keeps it): "),
+                ..Default::default()
+            };
+            edit_account(name, edit)?;
+            Ok(format!("✅ Updated '{}'.", name))
+        }
+        Action::Verify(name) => {
+            verify_account(name, false)?;
+            Ok(format!("✅ '{}' signs in as expected.", name))
+        }
+        Action::Refresh | Action::Quit => Ok(String::new()),
+    }
+}
+
+/// Shows the dashboard until the user quits.
+pub fn run() -> Result<()> {
+    if !is_interactive() {
+        return Err(GitSwitchError::InvalidInput(
+            "The dashboard needs a terminal.".to_string(),
+        ));
+    }
+    let mut terminal = ratatui::init();
+    let mut dashboard = Dashboard::new(Snapshot::gather());
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| dashboard.render(frame)) {
+            break Err(e).context("Failed to draw the dashboard");
+        }
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            Ok(_) => continue,
+            Err(e) => break Err(e).context("Failed to read the keyboard"),
+        };
+        match dashboard.handle_key(key.code) {
+            None => {}
+            Some(Action::Quit) => break Ok(()),
+            Some(Action::Refresh) => {
+                dashboard.refresh(Snapshot::gather());
+                dashboard.message.clear();
+            }
+            Some(action) => {
+                ratatui::restore();
+                println!();
+                let outcome = perform(&action);
+                if let Err(e) = &outcome {
+                    problem!("❌ {}", e);
+                }
+                prompt_line("\nPress Enter to return to the dashboard...");
+                terminal = ratatui::init();
+                dashboard.refresh(Snapshot::gather());
+                dashboard.message = outcome.unwrap_or_else(|e| format!("❌ {}", e));
+            }
+        }
+    };
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,78 @@
+//! The files the last command changed, recorded in
+//! `~/.git-switch/undo.toml` with the backups of their earlier content, so
+//! `git-switch undo` can put them back:
+//!
+//! ```toml
+//! command = "remove work"
+//! ts = 1760684400
+//!
+//! [[file]]
+//! path = "/home/jdoe/.ssh/config"
+//! backup = "/home/jdoe/.ssh/config.20261017-070000.bak"
+//! ```
+//!
+//! A file without a backup didn't exist before the command.
+
+use crate::config::get_data_dir;
+use crate::debug;
+use crate::files;
+use crate::utils::now_secs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoRecord {
+    /// The command line, without the program name.
+    pub command: String,
+    /// Unix time the command finished.
+    pub ts: u64,
+    #[serde(default, rename = "file")]
+    pub files: Vec<UndoFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoFile {
+    pub path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+}
+
+pub fn undo_path() -> PathBuf {
+    get_data_dir().join("undo.toml")
+}
+
+pub(crate) fn load_undo_from_path(path: &Path) -> Option<UndoRecord> {
+    let content = fs::read_to_string(path).ok()?;
+    toml::from_str(&content).ok()
+}
+
+pub(crate) fn save_undo_to_path(record: &UndoRecord, path: &Path) -> io::Result<()> {
+    let content = toml::to_string_pretty(record).map_err(io::Error::other)?;
+    files::write_atomic(path, &content)
+}
+
+pub fn load_undo() -> Option<UndoRecord> {
+    load_undo_from_path(&undo_path())
+}
+
+/// Replaces the undo record with the files `command` changed. Commands that
+/// changed none leave the previous record in place.
+pub fn record_command(command: &str) {
+    let journal = files::take_journal();
+    if journal.is_empty() {
+        return;
+    }
+    let record = UndoRecord {
+        command: command.to_string(),
+        ts: now_secs(),
+        files: journal
+            .into_iter()
+            .map(|(path, backup)| UndoFile { path, backup })
+            .collect(),
+    };
+    if let Err(e) = save_undo_to_path(&record, &undo_path()) {
+        debug!("could not write the undo record: {}", e);
+    }
+}
@@ -1,25 +1,276 @@
+//! Running subprocesses and prompting on the terminal.
+
+use crate::error::{GitSwitchError, Result};
+use crate::events;
+use std::io::{self, IsTerminal, Write};
 use std::path::Path; // Import the Path type
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How much of the commands it runs git-switch shows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Show nothing; failures still carry the command's stderr.
+    Quiet,
+    /// Echo each command line.
+    #[default]
+    Normal,
+    /// Echo each command line and the output it captured.
+    Verbose,
+    /// Also show the commands run to read something and the files written.
+    Debug,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
 
-pub fn run_command(command_str: &str, args: &[&str]) -> bool {
-    println!("$ {} {}", command_str, args.join(" ")); // Renamed 'command' to 'command_str'
-    let status = Command::new(command_str)
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        2 => Verbosity::Verbose,
+        _ => Verbosity::Debug,
+    }
+}
+
+/// What a command run with [`run_command`] printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs a command with its stdout and stderr captured; stdin and the
+/// terminal stay available, so passphrase prompts still work. The command
+/// line and output are echoed according to [`verbosity`]. Fails if the
+/// command can't be started or exits unsuccessfully.
+pub fn run_command(command_str: &str, args: &[&str]) -> Result<CommandOutput> {
+    let verbosity = verbosity();
+    if verbosity >= Verbosity::Normal {
+        crate::say!("$ {} {}", command_str, args.join(" "));
+    }
+    let output = Command::new(command_str)
         .args(args)
-        .status()
-        .unwrap_or_else(|e| {
-            eprintln!("❌ Failed to execute command '{}': {}", command_str, e);
-            std::process::exit(1); // Consider returning a Result instead of exiting
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|source| GitSwitchError::CommandNotFound {
+            command: command_str.to_string(),
+            source,
+        })?;
+
+    events::subprocess(command_str, args, output.status.code());
+
+    let captured = CommandOutput {
+        code: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    };
+    if verbosity >= Verbosity::Verbose {
+        if !captured.stdout.is_empty() {
+            crate::report!("{}", captured.stdout.trim_end());
+        }
+        eprint!("{}", captured.stderr);
+    }
+    if !output.status.success() {
+        return Err(GitSwitchError::CommandFailed {
+            command: format!("{} {}", command_str, args.join(" ")),
+            code: captured.code,
+            stderr: captured.stderr.trim().to_string(),
         });
+    }
+    Ok(captured)
+}
+
+/// Runs a command quietly with captured output, recording it in the event stream.
+pub fn command_output(command_str: &str, args: &[&str]) -> io::Result<Output> {
+    crate::debug!("$ {} {}", command_str, args.join(" "));
+    let output = Command::new(command_str).args(args).output();
+    events::subprocess(
+        command_str,
+        args,
+        output.as_ref().ok().and_then(|out| out.status.code()),
+    );
+    output
+}
+
+/// Name of this machine, for key titles and sync commits.
+pub fn hostname() -> String {
+    command_output("hostname", &[])
+        .ok()
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+/// The `(year, month, day)` (UTC) `days` days after 1970-01-01, by Howard
+/// Hinnant's `civil_from_days`.
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The `YYYY-MM-DD` date (UTC) `days` days after 1970-01-01.
+pub fn format_day(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
 
-    if !status.success() {
-        eprintln!("❌ Error running {} {:?}", command_str, args);
-        return false;
+/// A Unix time as `YYYY-MM-DD HH:MM` (UTC).
+pub fn format_timestamp(secs: u64) -> String {
+    format!(
+        "{} {:02}:{:02}",
+        format_day((secs / 86_400) as i64),
+        secs % 86_400 / 3600,
+        secs % 3600 / 60
+    )
+}
+
+/// Seconds since the Unix epoch.
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// Today's date (UTC) as `YYYY-MM-DD`.
+pub fn today() -> String {
+    format_day((now_secs() / 86_400) as i64)
+}
+
+/// Parses a duration such as `90`, `45s`, `30m`, `8h` or `1d` into
+/// seconds; a bare number is seconds.
+pub fn parse_duration(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len()),
+    );
+    let scale = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => {
+            return Err(format!(
+                "'{}' is not a duration like 90s, 30m, 8h or 1d",
+                value
+            ));
+        }
+    };
+    match number.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(n * scale),
+        _ => Err(format!(
+            "'{}' is not a duration like 90s, 30m, 8h or 1d",
+            value
+        )),
     }
-    true
+}
+
+/// Formats seconds as the two largest units, e.g. `7h 12m` or `45s`.
+pub fn format_duration(secs: u64) -> String {
+    let parts = [
+        (secs / 86_400, "d"),
+        (secs % 86_400 / 3600, "h"),
+        (secs % 3600 / 60, "m"),
+        (secs % 60, "s"),
+    ];
+    let first = parts.iter().position(|(n, _)| *n > 0).unwrap_or(3);
+    parts[first..]
+        .iter()
+        .take(2)
+        .filter(|(n, _)| *n > 0 || first == 3)
+        .map(|(n, unit)| format!("{}{}", n, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 /// Checks if a file or directory exists at the given path.
 /// Now accepts a `&Path` instead of `&str`.
+#[allow(dead_code)]
 pub fn file_exists(path: &Path) -> bool {
     path.exists()
 }
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Makes every prompt answer with its default for the rest of the process.
+pub fn set_non_interactive() {
+    NON_INTERACTIVE.store(true, Ordering::Relaxed);
+}
+
+/// Prompts for a secret on the terminal without echoing it. Returns `None`
+/// like [`prompt_line`] when there's no terminal or `--non-interactive` is set.
+pub fn prompt_password(message: &str) -> Option<String> {
+    if NON_INTERACTIVE.load(Ordering::Relaxed) {
+        return None;
+    }
+    rpassword::prompt_password(message).ok()
+}
+
+/// Reads the first line from stdin without its line ending; for secrets
+/// passed by scripts (`--passphrase-stdin`).
+pub fn read_stdin_line() -> io::Result<String> {
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Whether git-switch may show pickers: stdin and stderr are terminals and
+/// `--non-interactive` isn't set.
+pub fn is_interactive() -> bool {
+    !NON_INTERACTIVE.load(Ordering::Relaxed)
+        && io::stdin().is_terminal()
+        && io::stderr().is_terminal()
+}
+
+/// Lets the user choose one of `items` with the arrow keys, typing to
+/// filter. Returns `None` when the user presses Esc or [`is_interactive`]
+/// is false.
+pub fn pick(prompt: &str, items: &[String], default: usize) -> Option<usize> {
+    if !is_interactive() {
+        return None;
+    }
+    dialoguer::FuzzySelect::new()
+        .with_prompt(prompt)
+        .items(items)
+        .default(default)
+        .interact_opt()
+        .ok()
+        .flatten()
+}
+
+/// Prints `message` and reads one trimmed line from stdin.
+/// Returns `None` when stdin is closed (e.g. in non-interactive scripts) or
+/// `--non-interactive` is set, so callers fall back to their default.
+pub fn prompt_line(message: &str) -> Option<String> {
+    if NON_INTERACTIVE.load(Ordering::Relaxed) {
+        return None;
+    }
+    print!("{}", message);
+    io::stdout().flush().ok()?;
+    let mut response = String::new();
+    match io::stdin().read_line(&mut response) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(response.trim().to_string()),
+    }
+}
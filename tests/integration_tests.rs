@@ -1,287 +1,1211 @@
-use std::fs::{self, File};
-use std::io::Write; // For flushing stdout/stderr during debugging
-use std::process::Command;
-use tempfile::TempDir;
+#[path = "../src/testkit.rs"]
+mod testkit;
 
-/// Helper function to set up a test environment
-fn setup_test_environment() -> TempDir {
-    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+use testkit::Sandbox;
 
-    let ssh_dir = temp_dir.path().join(".ssh");
-    fs::create_dir_all(&ssh_dir).expect("Failed to create .ssh directory in temp_dir");
+#[test]
+fn test_full_account_lifecycle() {
+    let sandbox = Sandbox::new();
 
-    File::create(ssh_dir.join("config")).expect("Failed to create SSH config in temp_dir");
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success()
+        .stdout_contains("Account 'work' added successfully");
+    assert!(sandbox.path(".ssh/id_ed25519_work").exists());
+    assert!(
+        sandbox
+            .read(".ssh/config")
+            .contains("# >>> git-switch:work")
+    );
 
-    temp_dir
-}
+    sandbox
+        .run(&["list"])
+        .success()
+        .stdout_contains("workuser")
+        .stdout_contains("work@example.com");
+
+    sandbox
+        .run(&["use", "work", "--global"])
+        .success()
+        .stdout_contains("Switched to Git account: work");
+    // The global config written is the sandbox's, not the developer's
+    assert_eq!(
+        sandbox
+            .git(&["config", "--global", "user.email"])
+            .success()
+            .stdout()
+            .trim(),
+        "work@example.com"
+    );
+    assert_eq!(sandbox.run(&["current"]).success().stdout(), "work\n");
+    sandbox
+        .cmd(&["current", "--format", "json"])
+        .env("GIT_SWITCH_SESSION", "work")
+        .run()
+        .success()
+        .stdout_contains("\"source\":\"session\"");
+
+    sandbox
+        .run(&["remove", "work", "--dry-run"])
+        .success()
+        .stdout_contains("SSH config entries: Host github-work")
+        .stdout_contains(".ssh/id_ed25519_work.pub");
+    assert!(sandbox.path(".ssh/id_ed25519_work").exists());
+
+    sandbox.run(&["remove", "work"]).success().stdout_contains(
+        "Account 'work' and its associated SSH configurations and keys have been removed",
+    );
 
-/// Helper function to run git-switch command
-fn run_git_switch(args: &[&str], temp_dir: &TempDir) -> std::process::Output {
-    let mut cmd = Command::new("cargo");
-    cmd.args(["run", "--quiet", "--"]) // Added --quiet to suppress Cargo's own output
-        .args(args)
-        // REMOVED: .current_dir(temp_dir.path()) // This was causing the "could not find Cargo.toml" error
-        .env("HOME", temp_dir.path()) // Crucial: Set HOME to the temp directory for git-switch's operations
-        .env("RUST_BACKTRACE", "1"); // Get full backtrace if git-switch panics
-
-    let output = cmd.output().expect("Failed to execute git-switch command");
-
-    // For debugging, print output if the command failed or if you always want to see it
-    // This can be very helpful if tests still fail.
-    // if !output.status.success() || std::env::var("VERBOSE_TEST_OUTPUT").is_ok() {
-    //     eprintln!("Running 'git-switch {}' with HOME={}", args.join(" "), temp_dir.path().display());
-    //     eprintln!("git-switch status: {}", output.status);
-    //     eprintln!("git-switch stdout:\n<<<<\n{}\n>>>>", String::from_utf8_lossy(&output.stdout));
-    //     eprintln!("git-switch stderr:\n<<<<\n{}\n>>>>", String::from_utf8_lossy(&output.stderr));
-    //     std::io::stderr().flush().unwrap();
-    // }
-    output
+    sandbox
+        .run(&["list"])
+        .success()
+        .stdout_contains("No saved accounts");
+    sandbox.run(&["current"]).code(1);
+
+    let history = sandbox.run(&["history"]).success().stdout();
+    let actions: Vec<&str> = history
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(3))
+        .collect();
+    assert_eq!(actions, ["add", "use", "remove"]);
+    assert!(history.contains("workuser <work@example.com>"));
+    sandbox
+        .run(&["history", "--limit", "1", "--format", "json"])
+        .success()
+        .stdout_contains("\"action\": \"remove\"");
 }
 
 #[test]
-fn test_full_account_lifecycle() {
-    let temp_dir = setup_test_environment();
-
-    // Test adding an account
-    let add_output = run_git_switch(&["add", "work", "workuser", "work@example.com"], &temp_dir);
-    if !add_output.status.success() {
-        eprintln!(
-            "ADD COMMAND FAILED in test_full_account_lifecycle:\nStatus: {}\nStdout: {}\nStderr: {}",
-            add_output.status,
-            String::from_utf8_lossy(&add_output.stdout),
-            String::from_utf8_lossy(&add_output.stderr)
-        );
-        std::io::stderr().flush().unwrap();
-    }
-    assert!(add_output.status.success(), "git-switch add command failed");
-    let output_str = String::from_utf8_lossy(&add_output.stdout);
-    assert!(
-        output_str.contains("Account 'work' added successfully"),
-        "Stdout did not contain success message for add. Actual stdout: {}",
-        output_str
-    );
+fn test_undo_restores_a_removed_account_and_redoes() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&["undo"])
+        .code(2)
+        .stderr_contains("Nothing to undo");
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+    sandbox.run(&["remove", "work", "--yes"]).success();
 
-    // Test listing accounts
-    let list_output = run_git_switch(&["list"], &temp_dir);
-    if !list_output.status.success() {
-        eprintln!(
-            "LIST COMMAND (after add) FAILED in test_full_account_lifecycle:\nStatus: {}\nStdout: {}\nStderr: {}",
-            list_output.status,
-            String::from_utf8_lossy(&list_output.stdout),
-            String::from_utf8_lossy(&list_output.stderr)
-        );
-        std::io::stderr().flush().unwrap();
-    }
+    sandbox
+        .run(&["undo"])
+        .success()
+        .stdout_contains("work is back")
+        .stdout_contains("Undid `remove work --yes`");
+    sandbox.run(&["list"]).success().stdout_contains("workuser");
     assert!(
-        list_output.status.success(),
-        "git-switch list command failed after add"
+        sandbox
+            .read(".ssh/config")
+            .contains("# >>> git-switch:work")
     );
-    let list_str = String::from_utf8_lossy(&list_output.stdout);
+
+    sandbox
+        .run(&["undo"])
+        .success()
+        .stdout_contains("work is gone again");
+    sandbox
+        .run(&["list"])
+        .success()
+        .stdout_contains("No saved accounts");
+}
+
+#[test]
+fn test_invalid_commands() {
+    let sandbox = Sandbox::new();
+
+    // An existing account makes `use nonexistent` exercise the lookup itself
+    sandbox
+        .run(&["add", "dummy", "dummyuser", "dummy@example.com"])
+        .success();
+
+    sandbox
+        .run(&["use", "nonexistent", "--global"])
+        .code(3)
+        .stderr_contains("Account with name or username 'nonexistent' not found");
+    sandbox
+        .run(&["rule", "add", "github.com", "dummy"])
+        .code(2)
+        .stderr_contains("needs a host and a path");
+    sandbox.run(&["check"]).code(4);
+
+    // The account picker needs a terminal
+    sandbox
+        .run(&["use"])
+        .code(2)
+        .stderr_contains("No account name given");
+    // So does the guided `add`
+    sandbox
+        .run(&["add"])
+        .code(2)
+        .stderr_contains("a terminal for the guided setup");
+
+    let invalid = sandbox.run(&["invalidsubcommand"]);
+    invalid.failure();
+    let stderr = invalid.stderr();
     assert!(
-        list_str.contains("workuser"),
-        "List output did not contain 'workuser'. Actual: {}",
-        list_str
+        stderr.contains("unrecognized subcommand")
+            || stderr.contains("For more information, try '--help'"),
+        "Stderr for invalid subcommand did not contain expected error. Actual stderr: {}",
+        stderr
     );
-    assert!(
-        list_str.contains("work@example.com"),
-        "List output did not contain 'work@example.com'. Actual: {}",
-        list_str
+}
+
+#[test]
+fn test_multiple_accounts() {
+    let sandbox = Sandbox::new();
+
+    sandbox
+        .run(&["add", "personal", "personaluser", "personal@example.com"])
+        .success();
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+
+    // Clashing names and usernames need --force; bad values never pass
+    sandbox
+        .run(&["add", "Work", "other", "other@example.com"])
+        .code(2)
+        .stderr_contains("pass --force to replace it");
+    sandbox
+        .run(&["add", "second", "workuser", "second@example.com"])
+        .code(2)
+        .stderr_contains("already uses the username 'workuser'");
+    sandbox
+        .run(&["add", "a|b", "ab", "ab@example.com", "--force"])
+        .code(2);
+    sandbox
+        .run(&["add", "ab", "ab", "not an email", "--force"])
+        .code(2)
+        .stderr_contains("is not an email address");
+    sandbox
+        .run(&[
+            "add",
+            "work",
+            "workuser",
+            "work@corp.example.com",
+            "--force",
+            "--agent-ttl",
+            "8h",
+        ])
+        .success()
+        .stdout_contains("Replaced the saved account 'work'");
+
+    sandbox
+        .run(&["list"])
+        .success()
+        .stdout_contains("personaluser")
+        .stdout_contains("work@corp.example.com");
+
+    sandbox
+        .run(&["use", "personal", "--global"])
+        .success()
+        .stdout_contains("Switched to Git account: personal");
+    sandbox
+        .run(&["use", "work", "--global"])
+        .success()
+        .stdout_contains("Switched to Git account: work")
+        .stdout_contains("ssh-add -t 28800");
+    sandbox
+        .run(&["status"])
+        .success()
+        .stdout_contains("work: ~/.ssh/id_ed25519_work, expires in ");
+    sandbox
+        .run(&["use", "personal", "--global", "--exclusive"])
+        .success()
+        .stdout_contains("Removed the keys of work from ssh-agent");
+    // Only shell code on stdout, so `eval "$(...)"` works
+    let exports = sandbox
+        .run(&["use", "personal", "--global", "--print-env"])
+        .success()
+        .stdout();
+    assert!(exports.lines().all(|line| line.starts_with("export ")));
+    assert!(exports.contains(
+        "export GIT_SSH_COMMAND='ssh -i ~/.ssh/id_ed25519_personal -o IdentitiesOnly=yes';"
+    ));
+    sandbox
+        .run(&["remove", "personal", "--keep-keys"])
+        .success()
+        .stdout_contains("Kept SSH key ~/.ssh/id_ed25519_personal");
+    assert!(sandbox.path(".ssh/id_ed25519_personal").exists());
+}
+
+#[test]
+fn test_prune_cleans_up_after_removed_accounts() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&["add", "old", "olduser", "old@example.com"])
+        .success();
+    sandbox
+        .run(&["add", "keep", "keepuser", "keep@example.com"])
+        .success();
+    sandbox.run(&["remove", "old", "--config-only"]).success();
+
+    sandbox
+        .run(&["prune", "--dry-run"])
+        .success()
+        .stdout_contains("SSH config entries of 'old'")
+        .stdout_contains("Key file ~/.ssh/id_ed25519_old");
+    assert!(sandbox.path(".ssh/id_ed25519_old").exists());
+
+    // Without a terminal to ask on, only --yes deletes
+    sandbox
+        .run(&["prune"])
+        .failure()
+        .stderr_contains("pass --yes");
+    assert!(sandbox.path(".ssh/id_ed25519_old").exists());
+    sandbox.run(&["prune", "--yes"]).success();
+    assert!(!sandbox.path(".ssh/id_ed25519_old").exists());
+    assert!(sandbox.path(".ssh/id_ed25519_keep").exists());
+    let ssh_config = sandbox.read(".ssh/config");
+    assert!(!ssh_config.contains("git-switch:old"));
+    assert!(ssh_config.contains("git-switch:keep"));
+    sandbox
+        .run(&["prune"])
+        .success()
+        .stdout_contains("Nothing to prune");
+}
+
+#[test]
+fn test_add_rolls_back_when_a_step_fails() {
+    let sandbox = Sandbox::new();
+    // An SSH config that can't be written makes the last step of add fail
+    std::fs::remove_file(sandbox.path(".ssh/config")).unwrap();
+    std::fs::create_dir(sandbox.path(".ssh/config")).unwrap();
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .code(5)
+        .stderr_contains("Rolled back adding account 'work'");
+    assert!(!sandbox.path(".ssh/id_ed25519_work").exists());
+    assert!(!sandbox.path(".ssh/id_ed25519_work.pub").exists());
+    sandbox
+        .run(&["list"])
+        .success()
+        .stdout_contains("No saved accounts.");
+}
+
+#[test]
+fn test_clone_sets_repository_identity() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+
+    // Serve the account's alias URL from a local repository
+    let origin = sandbox.path("origin");
+    sandbox
+        .git(&["init", "--quiet", origin.to_str().unwrap()])
+        .success();
+    sandbox
+        .git(&[
+            "config",
+            "--global",
+            &format!("url.{}/.insteadOf", origin.display()),
+            "git@github-work:workuser/project.git",
+        ])
+        .success();
+
+    // --quiet leaves neither command echo nor progress messages
+    let cloned = sandbox.run(&["--quiet", "clone", "work", "project", "checkout"]);
+    assert_eq!(cloned.success().stdout(), "");
+    assert_eq!(
+        sandbox
+            .git(&["-C", "checkout", "config", "--local", "user.email"])
+            .success()
+            .stdout()
+            .trim(),
+        "work@example.com"
     );
+}
 
-    // Test using the account
-    let use_output = run_git_switch(&["use", "work"], &temp_dir);
-    if !use_output.status.success() {
-        eprintln!(
-            "USE COMMAND FAILED in test_full_account_lifecycle:\nStatus: {}\nStdout: {}\nStderr: {}",
-            use_output.status,
-            String::from_utf8_lossy(&use_output.stdout),
-            String::from_utf8_lossy(&use_output.stderr)
-        );
-        std::io::stderr().flush().unwrap();
-    }
-    assert!(use_output.status.success(), "git-switch use command failed");
-    let use_str = String::from_utf8_lossy(&use_output.stdout);
-    assert!(
-        use_str.contains("Switched to Git account: work"),
-        "Stdout did not contain success message for use. Actual: {}",
-        use_str
+#[test]
+fn test_use_rewrites_remote_through_host_alias() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+    sandbox.git(&["init", "--quiet", "repo"]).success();
+    let repo = sandbox.path("repo");
+    let origin = || {
+        sandbox
+            .git(&["-C", "repo", "remote", "get-url", "origin"])
+            .success()
+            .stdout()
+            .trim()
+            .to_string()
+    };
+
+    sandbox
+        .cmd(&["use", "work", "--local", "--update-remote", "acme/api"])
+        .current_dir(&repo)
+        .run()
+        .success();
+    assert_eq!(origin(), "git@github-work:acme/api.git");
+
+    sandbox
+        .cmd(&[
+            "use",
+            "work",
+            "--local",
+            "--update-remote",
+            "api",
+            "--plain-remote",
+        ])
+        .current_dir(&repo)
+        .run()
+        .success();
+    assert_eq!(origin(), "git@github.com:workuser/api.git");
+}
+
+#[test]
+fn test_config_defaults_apply_to_add() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&["config", "set", "key_type", "ecdsa"])
+        .success();
+    sandbox
+        .run(&["config", "set", "host", "gitlab.com"])
+        .success();
+    sandbox
+        .run(&["config", "get", "host"])
+        .success()
+        .stdout_contains("gitlab.com");
+    sandbox
+        .run(&["config", "set", "emoji", "maybe"])
+        .failure()
+        .stderr_contains("use true or false");
+
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+    assert!(sandbox.path(".ssh/id_ecdsa_work").exists());
+    assert!(sandbox.read(".ssh/config").contains("Host gitlab-work"));
+
+    sandbox.run(&["config", "unset", "host"]).success();
+    sandbox
+        .run(&["config", "list"])
+        .success()
+        .stdout_contains("host = github.com")
+        .stdout_contains("key_type = ecdsa");
+
+    // A settings file broken by hand is reported, never saved over
+    let broken = sandbox.read(".git-switch/settings.toml").replace('"', "");
+    std::fs::write(sandbox.path(".git-switch/settings.toml"), &broken).unwrap();
+    sandbox
+        .run(&["config", "set", "mode", "sshcommand"])
+        .failure()
+        .stderr_contains("Failed to parse");
+    assert_eq!(sandbox.read(".git-switch/settings.toml"), broken);
+}
+
+#[test]
+fn test_sshcommand_mode_leaves_ssh_config_alone() {
+    let sandbox = Sandbox::new();
+    sandbox.run(&["settings", "mode", "sshcommand"]).success();
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+    assert!(!sandbox.read(".ssh/config").contains("github-work"));
+    sandbox.git(&["init", "--quiet", "repo"]).success();
+    let repo = sandbox.path("repo");
+    let ssh_command = || {
+        sandbox
+            .git(&["-C", "repo", "config", "--local", "core.sshCommand"])
+            .stdout()
+            .trim()
+            .to_string()
+    };
+
+    sandbox
+        .cmd(&["use", "work", "--update-remote", "acme/api"])
+        .current_dir(&repo)
+        .run()
+        .success();
+    assert_eq!(
+        ssh_command(),
+        "ssh -i ~/.ssh/id_ed25519_work -o IdentitiesOnly=yes"
     );
+    sandbox
+        .git(&["-C", "repo", "remote", "get-url", "origin"])
+        .success()
+        .stdout_contains("git@github.com:acme/api.git");
 
-    // Test removing the account
-    let remove_output = run_git_switch(&["remove", "work"], &temp_dir);
-    if !remove_output.status.success() {
-        eprintln!(
-            "REMOVE COMMAND FAILED in test_full_account_lifecycle:\nStatus: {}\nStdout: {}\nStderr: {}",
-            remove_output.status,
-            String::from_utf8_lossy(&remove_output.stdout),
-            String::from_utf8_lossy(&remove_output.stderr)
-        );
-        std::io::stderr().flush().unwrap();
-    }
-    assert!(
-        remove_output.status.success(),
-        "git-switch remove command failed"
+    // Switching with host aliases drops the command git-switch set
+    sandbox
+        .cmd(&["use", "work", "--mode", "alias", "--no-remote"])
+        .current_dir(&repo)
+        .run()
+        .success();
+    assert_eq!(ssh_command(), "");
+}
+
+#[test]
+fn test_global_use_sends_owner_urls_through_the_account() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&[
+            "add",
+            "work",
+            "workuser",
+            "work@example.com",
+            "--org",
+            "acme",
+        ])
+        .success();
+    sandbox
+        .run(&["add", "personal", "me", "me@example.com"])
+        .success();
+    let url_for = |url: &str| {
+        sandbox
+            .git(&["ls-remote", "--get-url", url])
+            .stdout()
+            .trim()
+            .to_string()
+    };
+
+    sandbox
+        .run(&["use", "work", "--global", "--no-remote"])
+        .success();
+    assert_eq!(
+        url_for("git@github.com:acme/api.git"),
+        "git@github-work:acme/api.git"
     );
-    let remove_str = String::from_utf8_lossy(&remove_output.stdout);
-    assert!(
-        remove_str.contains(
-            "Account 'work' and its associated SSH configurations and keys have been removed"
-        ),
-        "Stdout did not contain success message for remove. Actual: {}",
-        remove_str
+    assert_eq!(
+        url_for("ssh://git@github.com/acme/api.git"),
+        "git@github-work:acme/api.git"
     );
-
-    // Verify account was removed
-    let final_list = run_git_switch(&["list"], &temp_dir);
-    assert!(
-        final_list.status.success(),
-        "git-switch list command failed after remove"
+    assert_eq!(
+        url_for("git@github.com:someone/dotfiles.git"),
+        "git@github.com:someone/dotfiles.git"
     );
-    let final_list_str = String::from_utf8_lossy(&final_list.stdout);
-    assert!(
-        final_list_str.contains("No saved accounts"),
-        "Final list output did not indicate no saved accounts. Actual: {}",
-        final_list_str
+
+    // A rule of the user's own through the alias outlives the switches
+    sandbox
+        .git(&[
+            "config",
+            "--global",
+            "url.git@github-work:mine/.insteadOf",
+            "gh:mine/",
+        ])
+        .success();
+    sandbox
+        .run(&["edit", "work", "--remove-org", "acme"])
+        .success();
+
+    // Switching away takes the rules out again, even for a dropped owner
+    sandbox
+        .run(&["use", "personal", "--global", "--no-remote"])
+        .success();
+    assert_eq!(
+        url_for("git@github.com:acme/api.git"),
+        "git@github.com:acme/api.git"
     );
+    assert_eq!(url_for("gh:mine/app.git"), "git@github-work:mine/app.git");
 }
 
 #[test]
-fn test_invalid_commands() {
-    let temp_dir = setup_test_environment();
-
-    // This test expects the `add` command to work. If it fails due to Cargo.toml issues,
-    // this test might also fail prematurely or in unexpected ways.
-    // Ensure an account exists so 'use non-existent' is a valid test of that specific logic.
-    let add_dummy_output = run_git_switch(
-        &["add", "dummy", "dummyuser", "dummy@example.com"],
-        &temp_dir,
+fn test_use_keeps_remote_settings_and_rewrites_other_remotes() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&["add", "personal", "personaluser", "personal@example.com"])
+        .success();
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+    sandbox.git(&["init", "--quiet", "repo"]).success();
+    let repo = sandbox.path("repo");
+    let git = |args: &[&str]| {
+        let mut full = vec!["-C", "repo"];
+        full.extend_from_slice(args);
+        sandbox.git(&full).success().stdout().trim().to_string()
+    };
+    git(&[
+        "remote",
+        "add",
+        "origin",
+        "git@github-personal:acme/api.git",
+    ]);
+    git(&[
+        "remote",
+        "set-url",
+        "--push",
+        "origin",
+        "git@github.com:personaluser/api.git",
+    ]);
+    git(&["config", "branch.main.remote", "origin"]);
+    git(&[
+        "remote",
+        "add",
+        "upstream",
+        "git@github.com:upstream/api.git",
+    ]);
+    git(&["remote", "add", "mirror", "git@gitlab.com:acme/api.git"]);
+
+    sandbox
+        .cmd(&["use", "work", "--local", "--update-remote", "acme/api"])
+        .current_dir(&repo)
+        .run()
+        .success();
+    assert_eq!(
+        git(&["remote", "get-url", "origin"]),
+        "git@github-work:acme/api.git"
     );
-    assert!(
-        add_dummy_output.status.success(),
-        "Setup for test_invalid_commands: failed to add dummy account"
+    assert_eq!(
+        git(&["remote", "get-url", "--push", "origin"]),
+        "git@github-work:personaluser/api.git"
     );
-
-    let invalid_use_output = run_git_switch(&["use", "nonexistent"], &temp_dir);
-    if !invalid_use_output.status.success() {
-        eprintln!(
-            "USE NONEXISTENT COMMAND non-zero exit in test_invalid_commands:\nStatus: {}\nStdout: {}\nStderr: {}",
-            invalid_use_output.status,
-            String::from_utf8_lossy(&invalid_use_output.stdout),
-            String::from_utf8_lossy(&invalid_use_output.stderr)
-        );
-        std::io::stderr().flush().unwrap();
-    }
-    assert!(
-        invalid_use_output.status.success(),
-        "git-switch use nonexistent exited with non-zero status unexpectedly"
+    assert_eq!(git(&["config", "branch.main.remote"]), "origin");
+    assert_eq!(
+        git(&["remote", "get-url", "upstream"]),
+        "git@github.com:upstream/api.git"
     );
 
-    let error_str_stdout = String::from_utf8_lossy(&invalid_use_output.stdout);
-    let expected_msg = "Account with name or username 'nonexistent' not found";
-    assert!(
-        error_str_stdout.contains(expected_msg),
-        "Stdout for 'use nonexistent' did not contain '{}'. Actual stdout: {}",
-        expected_msg,
-        error_str_stdout
-    );
+    // A checked-out submodule with its own remote
+    sandbox
+        .git(&["init", "--quiet", "repo/vendor/lib"])
+        .success();
+    sandbox
+        .git(&[
+            "-C",
+            "repo/vendor/lib",
+            "remote",
+            "add",
+            "origin",
+            "git@github.com:acme/lib.git",
+        ])
+        .success();
+    std::fs::write(
+        repo.join(".gitmodules"),
+        "[submodule \"lib\"]\n\tpath = vendor/lib\n\turl = git@github.com:acme/lib.git\n",
+    )
+    .unwrap();
 
-    let invalid_cmd_output = run_git_switch(&["invalidsubcommand"], &temp_dir);
-    if invalid_cmd_output.status.success() {
-        eprintln!(
-            "INVALID SUBCOMMAND unexpectedly succeeded in test_invalid_commands:\nStatus: {}\nStdout: {}\nStderr: {}",
-            invalid_cmd_output.status,
-            String::from_utf8_lossy(&invalid_cmd_output.stdout),
-            String::from_utf8_lossy(&invalid_cmd_output.stderr)
-        );
-        std::io::stderr().flush().unwrap();
-    }
-    assert!(
-        !invalid_cmd_output.status.success(),
-        "git-switch with invalid subcommand should fail (exit non-zero)"
+    sandbox
+        .cmd(&["use", "personal", "--local", "--rewrite-remotes"])
+        .current_dir(&repo)
+        .run()
+        .success()
+        .stdout_contains("Remote 'upstream' now uses git@github-personal:upstream/api.git")
+        .stdout_contains("Remote 'origin' in vendor/lib now uses git@github-personal:acme/lib.git");
+    assert_eq!(
+        git(&["config", "submodule.lib.url"]),
+        "git@github-personal:acme/lib.git"
     );
-
-    let cmd_error_str_stderr = String::from_utf8_lossy(&invalid_cmd_output.stderr);
-    assert!(
-        cmd_error_str_stderr.contains("unrecognized subcommand")
-            || cmd_error_str_stderr.contains("For more information, try '--help'"),
-        "Stderr for invalid subcommand did not contain expected error. Actual stderr: {}",
-        cmd_error_str_stderr
+    assert_eq!(
+        git(&["remote", "get-url", "origin"]),
+        "git@github-personal:acme/api.git"
+    );
+    assert_eq!(
+        git(&["remote", "get-url", "--push", "origin"]),
+        "git@github-personal:personaluser/api.git"
+    );
+    assert_eq!(
+        git(&["remote", "get-url", "mirror"]),
+        "git@gitlab.com:acme/api.git"
     );
 }
 
 #[test]
-fn test_multiple_accounts() {
-    let temp_dir = setup_test_environment();
+fn test_rules_pick_account_for_auto_and_clone() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&["add", "personal", "personaluser", "personal@example.com"])
+        .success();
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+    sandbox
+        .run(&["rule", "add", "github.com/mycompany/*", "work"])
+        .success();
+    sandbox
+        .run(&["rule", "list"])
+        .success()
+        .stdout_contains("github.com/mycompany/* → work");
+    sandbox
+        .run(&["show", "workuser"])
+        .success()
+        .stdout_contains("github-work")
+        .stdout_contains("~/.ssh/id_ed25519_work")
+        .stdout_contains("Rules         github.com/mycompany/*");
 
-    let add_personal_output = run_git_switch(
-        &["add", "personal", "personaluser", "personal@example.com"],
-        &temp_dir,
-    );
-    if !add_personal_output.status.success() {
-        eprintln!(
-            "ADD PERSONAL COMMAND FAILED in test_multiple_accounts:\nStatus: {}\nStdout: {}\nStderr: {}",
-            add_personal_output.status,
-            String::from_utf8_lossy(&add_personal_output.stdout),
-            String::from_utf8_lossy(&add_personal_output.stderr)
-        );
-        std::io::stderr().flush().unwrap();
-    }
-    assert!(
-        add_personal_output.status.success(),
-        "Failed to add 'personal' account"
+    sandbox.git(&["init", "--quiet", "repo"]).success();
+    sandbox
+        .git(&[
+            "-C",
+            "repo",
+            "remote",
+            "add",
+            "origin",
+            "git@github.com:mycompany/api.git",
+        ])
+        .success();
+    sandbox
+        .cmd(&["auto"])
+        .current_dir(sandbox.path("repo"))
+        .run()
+        .success()
+        .stdout_contains("account 'work'");
+    assert_eq!(
+        sandbox
+            .git(&["-C", "repo", "config", "--local", "user.email"])
+            .success()
+            .stdout()
+            .trim(),
+        "work@example.com"
     );
 
-    let add_work_output =
-        run_git_switch(&["add", "work", "workuser", "work@example.com"], &temp_dir);
-    if !add_work_output.status.success() {
-        eprintln!(
-            "ADD WORK COMMAND FAILED in test_multiple_accounts:\nStatus: {}\nStdout: {}\nStderr: {}",
-            add_work_output.status,
-            String::from_utf8_lossy(&add_work_output.stdout),
-            String::from_utf8_lossy(&add_work_output.stderr)
-        );
-        std::io::stderr().flush().unwrap();
+    sandbox.git(&["init", "--quiet", "unmatched"]).success();
+    sandbox
+        .cmd(&["auto"])
+        .current_dir(sandbox.path("unmatched"))
+        .run()
+        .code(1)
+        .stderr_contains("No rule matches");
+    // The shell hook's variant stays quiet wherever there's nothing to do
+    for dir in ["repo", "unmatched", "."] {
+        let outcome = sandbox
+            .cmd(&["auto", "--silent"])
+            .current_dir(sandbox.path(dir))
+            .run();
+        outcome.success();
+        assert_eq!(outcome.stdout() + &outcome.stderr(), "");
     }
-    assert!(
-        add_work_output.status.success(),
-        "Failed to add 'work' account"
+
+    // The default account is the fallback for rules and for `use` without a name
+    sandbox.run(&["default", "personal"]).success();
+    sandbox
+        .run(&["list"])
+        .success()
+        .stdout_contains("personal (default)");
+    sandbox
+        .cmd(&["auto"])
+        .current_dir(sandbox.path("unmatched"))
+        .run()
+        .success()
+        .stdout_contains("using the default account 'personal'");
+    assert_eq!(
+        sandbox
+            .git(&["-C", "unmatched", "config", "--local", "user.email"])
+            .success()
+            .stdout()
+            .trim(),
+        "personal@example.com"
     );
+    sandbox
+        .run(&["use", "--global"])
+        .success()
+        .stdout_contains("using the default account 'personal'");
+    sandbox.run(&["default", "--clear"]).success();
+    sandbox
+        .run(&["default"])
+        .success()
+        .stdout_contains("No default account");
 
-    let list_output = run_git_switch(&["list"], &temp_dir);
-    assert!(
-        list_output.status.success(),
-        "List command failed after adding multiple accounts"
+    let origin = sandbox.path("origin");
+    sandbox
+        .git(&["init", "--quiet", origin.to_str().unwrap()])
+        .success();
+    sandbox
+        .git(&[
+            "config",
+            "--global",
+            &format!("url.{}/.insteadOf", origin.display()),
+            "git@github-work:mycompany/web",
+        ])
+        .success();
+    sandbox
+        .run(&["clone", "github.com/mycompany/web", "web"])
+        .success()
+        .stdout_contains("account 'work'");
+    assert_eq!(
+        sandbox
+            .git(&["-C", "web", "config", "--local", "user.email"])
+            .success()
+            .stdout()
+            .trim(),
+        "work@example.com"
     );
-    let list_str = String::from_utf8_lossy(&list_output.stdout);
-    assert!(
-        list_str.contains("personaluser"),
-        "List output did not contain 'personaluser'. Actual: {}",
-        list_str
+
+    // A rules file broken by hand is reported, never saved over
+    let broken = sandbox
+        .read(".git-switch/rules.toml")
+        .replace("\"work\"", "work");
+    std::fs::write(sandbox.path(".git-switch/rules.toml"), &broken).unwrap();
+    sandbox
+        .run(&["rule", "list"])
+        .failure()
+        .stderr_contains("Failed to parse");
+    sandbox
+        .run(&["rule", "add", "github.com/other/*", "personal"])
+        .failure();
+    assert_eq!(sandbox.read(".git-switch/rules.toml"), broken);
+}
+
+#[test]
+fn test_fix_previews_then_applies_account_identities() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+    for repo in ["src/api", "src/notes"] {
+        sandbox.git(&["init", "--quiet", repo]).success();
+        sandbox
+            .git(&["-C", repo, "config", "user.email", "someone@else.com"])
+            .success();
+    }
+    sandbox
+        .git(&[
+            "-C",
+            "src/api",
+            "remote",
+            "add",
+            "origin",
+            "git@github.com:workuser/api.git",
+        ])
+        .success();
+    let api_email = || {
+        sandbox
+            .git(&["-C", "src/api", "config", "--local", "user.email"])
+            .success()
+            .stdout()
+            .trim()
+            .to_string()
+    };
+
+    sandbox
+        .run(&["fix", "src", "--dry-run", "--rewrite-remotes"])
+        .success()
+        .stdout_contains("account 'work'")
+        .stdout_contains("origin → git@github-work:workuser/api.git")
+        .stdout_contains("1 of 2 repositories to fix; 1 without a known account left alone")
+        .stdout_contains("Dry run");
+    assert_eq!(api_email(), "someone@else.com");
+
+    // Declining the confirmation changes nothing
+    sandbox
+        .cmd(&["fix", "src"])
+        .stdin("n\n")
+        .run()
+        .code(8)
+        .stderr_contains("Nothing was changed");
+    assert_eq!(api_email(), "someone@else.com");
+
+    sandbox
+        .cmd(&["fix", "src", "--rewrite-remotes"])
+        .stdin("y\n")
+        .run()
+        .success()
+        .stdout_contains("Fixed 1 repositories");
+    assert_eq!(api_email(), "work@example.com");
+    assert_eq!(
+        sandbox
+            .git(&["-C", "src/api", "remote", "get-url", "origin"])
+            .success()
+            .stdout()
+            .trim(),
+        "git@github-work:workuser/api.git"
     );
+}
+
+#[test]
+fn test_commit_hook_refuses_wrong_identity() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+    sandbox.git(&["init", "--quiet", "repo"]).success();
+    sandbox
+        .git(&[
+            "-C",
+            "repo",
+            "remote",
+            "add",
+            "origin",
+            "git@github-work:acme/api.git",
+        ])
+        .success();
+    sandbox
+        .git(&["-C", "repo", "config", "user.name", "Someone"])
+        .success();
+    sandbox
+        .git(&["-C", "repo", "config", "user.email", "someone@else.com"])
+        .success();
+    let repo = sandbox.path("repo");
+    sandbox
+        .cmd(&["hook", "install"])
+        .current_dir(&repo)
+        .run()
+        .success();
+
+    sandbox
+        .git(&[
+            "-C",
+            "repo",
+            "commit",
+            "--quiet",
+            "--allow-empty",
+            "-m",
+            "wrong",
+        ])
+        .failure()
+        .stderr_contains("belongs to account 'work'");
+
+    sandbox
+        .git(&["-C", "repo", "config", "user.email", "work@example.com"])
+        .success();
+    sandbox
+        .git(&[
+            "-C",
+            "repo",
+            "commit",
+            "--quiet",
+            "--allow-empty",
+            "-m",
+            "right",
+        ])
+        .success();
+}
+
+#[test]
+fn test_config_paths_can_be_overridden() {
+    let sandbox = Sandbox::new().env("GIT_SWITCH_CONFIG", "~/portable/accounts.toml");
+    sandbox
+        .run(&[
+            "add",
+            "work",
+            "workuser",
+            "work@example.com",
+            "--ssh-config",
+            "~/portable/ssh_config",
+        ])
+        .success();
+    assert!(sandbox.read("portable/accounts.toml").contains("workuser"));
     assert!(
-        list_str.contains("workuser"),
-        "List output did not contain 'workuser'. Actual: {}",
-        list_str
+        sandbox
+            .read("portable/ssh_config")
+            .contains("# >>> git-switch:work")
     );
+    assert!(!sandbox.path(".git-switch-accounts").exists());
+    assert!(!sandbox.read(".ssh/config").contains("git-switch:work"));
+
+    // --config wins over the environment
+    sandbox
+        .run(&["list", "--config", "~/other.toml"])
+        .success()
+        .stdout_contains("No saved accounts");
+}
+
+#[test]
+fn test_legacy_accounts_file_moves_to_xdg_config() {
+    let sandbox = Sandbox::new();
+    std::fs::write(
+        sandbox.path(".git-switch-accounts"),
+        "legacy|legacyuser|legacy@example.com|~/.ssh/id_rsa_legacy\n",
+    )
+    .unwrap();
 
-    let use_personal_output = run_git_switch(&["use", "personal"], &temp_dir);
+    sandbox
+        .run(&["list"])
+        .success()
+        .stdout_contains("legacyuser")
+        .stderr_contains("Moved");
+    assert!(!sandbox.path(".git-switch-accounts").exists());
     assert!(
-        use_personal_output.status.success(),
-        "Failed to switch to 'personal' account"
+        sandbox
+            .read(".config/git-switch/accounts.toml")
+            .contains("legacyuser")
     );
     assert!(
-        String::from_utf8_lossy(&use_personal_output.stdout)
-            .contains("Switched to Git account: personal"),
-        "Switch to 'personal' did not produce expected stdout message."
+        sandbox
+            .path(".config/git-switch/accounts.toml.bak")
+            .exists()
     );
+}
+
+#[test]
+fn test_add_with_passphrase_encrypts_key_and_loads_it() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .cmd(&[
+            "add",
+            "work",
+            "workuser",
+            "work@example.com",
+            "--passphrase-stdin",
+        ])
+        .stdin("correct horse\n")
+        .run()
+        .success();
+
+    let key = sandbox.path(".ssh/id_ed25519_work");
+    let key = key.to_str().unwrap();
+    sandbox
+        .program("ssh-keygen", &["-y", "-P", "", "-f", key])
+        .run()
+        .code(255);
+    sandbox
+        .program("ssh-keygen", &["-y", "-P", "correct horse", "-f", key])
+        .run()
+        .success();
+    sandbox
+        .program("ssh-add", &["-l"])
+        .run()
+        .success()
+        .stdout_contains("(ED25519)");
+
+    sandbox
+        .cmd(&[
+            "add",
+            "empty",
+            "emptyuser",
+            "e@example.com",
+            "--passphrase-stdin",
+        ])
+        .stdin("\n")
+        .run()
+        .code(2);
+}
+
+#[test]
+fn test_key_show_prints_public_key() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+
+    let public_key = sandbox.read(".ssh/id_ed25519_work.pub");
+    let shown = sandbox
+        .run(&["key", "show", "workuser"])
+        .success()
+        .stderr_contains("SHA256:")
+        .stdout();
+    assert_eq!(shown, public_key);
+
+    // The sandbox has no display to hold a clipboard
+    sandbox
+        .run(&["key", "copy", "work"])
+        .code(9)
+        .stderr_contains("git-switch key show work");
+}
+
+#[test]
+fn test_tags_select_accounts_for_list_and_key_rotate() {
+    let sandbox = Sandbox::new();
+    for (name, tag) in [
+        ("acme", "client-x"),
+        ("globex", "client-x"),
+        ("home", "personal"),
+    ] {
+        sandbox
+            .run(&[
+                "add",
+                name,
+                name,
+                &format!("{}@example.com", name),
+                "--tag",
+                tag,
+            ])
+            .success();
+    }
+    sandbox
+        .run(&["edit", "home", "--tag", "client-x", "--untag", "personal"])
+        .success();
+    sandbox
+        .run(&["edit", "home", "--untag", "client-x"])
+        .success();
+
+    let listed = sandbox
+        .run(&["list", "--tag", "client-x"])
+        .success()
+        .stdout();
+    assert!(listed.contains("acme") && listed.contains("globex"));
+    assert!(!listed.contains("home"));
+    sandbox
+        .run(&["list", "--tag", "nobody"])
+        .success()
+        .stdout_contains("No accounts are tagged 'nobody'.");
+    sandbox
+        .run(&["add", "bad", "bad", "bad@example.com", "--tag", "a,b"])
+        .code(2);
+
+    let before = sandbox.read(".ssh/id_ed25519_acme.pub");
+    sandbox
+        .run(&["key", "rotate", "--tag", "client-x"])
+        .success()
+        .stdout_contains("Rotated the key of 'globex'");
+    assert_ne!(sandbox.read(".ssh/id_ed25519_acme.pub"), before);
+    assert_eq!(sandbox.read(".ssh/id_ed25519_acme.old.pub"), before);
+    sandbox.run(&["key", "rotate", "--tag", "nobody"]).code(2);
+}
+
+#[test]
+fn test_add_registers_existing_key_and_remove_keeps_it() {
+    let sandbox = Sandbox::new();
+    std::fs::create_dir_all(sandbox.path("keys")).unwrap();
+    let key = sandbox.path("keys/mine");
+    sandbox
+        .program(
+            "ssh-keygen",
+            &["-q", "-t", "ed25519", "-N", "", "-f", key.to_str().unwrap()],
+        )
+        .run()
+        .success();
+    std::fs::remove_file(sandbox.path("keys/mine.pub")).unwrap();
 
-    let use_work_output = run_git_switch(&["use", "work"], &temp_dir);
+    sandbox
+        .run(&[
+            "add",
+            "work",
+            "workuser",
+            "work@example.com",
+            "--ssh-key",
+            "~/keys/mine",
+        ])
+        .success()
+        .stdout_contains("Using existing SSH key");
+    assert!(sandbox.path("keys/mine.pub").exists());
+    assert!(!sandbox.path(".ssh/id_ed25519_work").exists());
     assert!(
-        use_work_output.status.success(),
-        "Failed to switch to 'work' account"
+        sandbox
+            .read(".config/git-switch/accounts.toml")
+            .contains("ssh_key = \"~/keys/mine\"")
     );
+    assert!(sandbox.read(".ssh/config").contains("keys/mine"));
+
+    sandbox.run(&["remove", "work"]).success();
+    assert!(key.exists());
+
+    sandbox
+        .run(&["add", "other", "u", "u@example.com", "--ssh-key", "~/nope"])
+        .code(2);
+}
+
+#[test]
+fn test_import_creates_accounts_from_ssh_config() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .program(
+            "ssh-keygen",
+            &[
+                "-q",
+                "-t",
+                "ed25519",
+                "-N",
+                "",
+                "-f",
+                sandbox.path(".ssh/id_work").to_str().unwrap(),
+            ],
+        )
+        .run()
+        .success();
+    std::fs::write(
+        sandbox.path(".ssh/config"),
+        "Host github-work\n    HostName github.com\n    IdentityFile ~/.ssh/id_work\n",
+    )
+    .unwrap();
+    sandbox
+        .git(&["config", "--global", "user.name", "Jane Doe"])
+        .success();
+    sandbox
+        .git(&["config", "--global", "user.email", "jane@example.com"])
+        .success();
+
+    sandbox
+        .run(&["import", "--dry-run"])
+        .success()
+        .stdout_contains("Would import as 'work' (Jane Doe <jane@example.com>)");
+    sandbox
+        .run(&["import", "--yes"])
+        .success()
+        .stdout_contains("Imported 1 account(s)");
+    sandbox
+        .run(&["list"])
+        .success()
+        .stdout_contains("jane@example.com");
+    let ssh_config = sandbox.read(".ssh/config");
+    assert!(ssh_config.starts_with("Host github-work\n"));
+    assert!(ssh_config.contains("# >>> git-switch:work"));
+
+    // Already imported
+    sandbox
+        .run(&["import", "--yes"])
+        .success()
+        .stdout_contains("No SSH config entries");
+}
+
+#[test]
+fn test_export_and_import_move_accounts_and_rules() {
+    let old = Sandbox::new();
+    old.run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+    old.run(&["rule", "add", "github.com/mycompany/*", "work"])
+        .success();
+    old.run(&["export", "--format", "json"])
+        .success()
+        .stdout_contains("\"ssh_key\": \"~/.ssh/id_ed25519_work\"");
+    old.run(&["export", "-o", "setup.toml"]).success();
+    let export = old.read("setup.toml");
+    assert!(export.contains("[[rule]]"));
+
+    let new = Sandbox::new();
+    std::fs::write(new.path("setup.toml"), &export).unwrap();
+    new.run(&["import", "setup.toml"])
+        .success()
+        .stdout_contains("No key at ~/.ssh/id_ed25519_work")
+        .stdout_contains("Imported 1 account(s) and 1 rule(s)");
+    new.run(&["list"])
+        .success()
+        .stdout_contains("work@example.com");
+    assert!(new.read(".ssh/config").contains("# >>> git-switch:work"));
+
+    // Names already taken are skipped
+    new.run(&["import", "setup.toml"])
+        .success()
+        .stdout_contains("Imported 0 account(s) and 0 rule(s)");
+}
+
+#[test]
+fn test_sync_keeps_two_machines_in_step() {
+    let laptop = Sandbox::new();
+    let desktop = Sandbox::new();
+    let remote = laptop.path("sync.git");
+    let url = remote.to_str().unwrap();
+    laptop.git(&["init", "--quiet", "--bare", url]).success();
+
+    laptop
+        .run(&["add", "work", "workuser", "work@example.com"])
+        .success();
+    laptop
+        .run(&["sync", "setup", url])
+        .success()
+        .stdout_contains("Pushed 1 account(s)");
+
+    desktop
+        .run(&["add", "home", "homeuser", "home@example.com"])
+        .success();
+    desktop
+        .run(&["sync", "setup", url])
+        .success()
+        .stdout_contains("No key at ~/.ssh/id_ed25519_work")
+        .stdout_contains("Pushed 2 account(s)");
     assert!(
-        String::from_utf8_lossy(&use_work_output.stdout).contains("Switched to Git account: work"),
-        "Switch to 'work' did not produce expected stdout message."
+        desktop
+            .read(".ssh/config")
+            .contains("# >>> git-switch:work")
     );
+
+    laptop.run(&["remove", "work"]).success();
+    laptop.run(&["sync", "pull"]).success();
+    laptop
+        .run(&["list"])
+        .success()
+        .stdout_contains("home@example.com");
+    laptop.run(&["sync", "push"]).success();
+
+    // The removal reaches the other machine
+    desktop
+        .run(&["sync", "pull"])
+        .success()
+        .stdout_contains("- work");
+    let list = desktop.run(&["list"]);
+    assert!(!list.success().stdout().contains("work@example.com"));
 }
@@ -0,0 +1,49 @@
+//! Uses git-switch as a library, the way embedding tools would.
+
+use git_switch::Account;
+use git_switch::config::{
+    delete_account_from_path, find_account, load_accounts_from_path, save_account_to_path,
+};
+use git_switch::git::RemoteUrl;
+use git_switch::git::alias_remote_url;
+use git_switch::ssh::{remove_account_entries, upsert_account_entries};
+use tempfile::TempDir;
+
+fn work() -> Account {
+    Account {
+        name: String::from("work"),
+        username: String::from("jdoe-corp"),
+        email: String::from("jdoe@corp.com"),
+        ssh_key: String::from("~/.ssh/id_ed25519_work"),
+        ..Account::default()
+    }
+}
+
+#[test]
+fn test_accounts_file_round_trip() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("accounts");
+
+    save_account_to_path(&work(), &path).unwrap();
+    let accounts = load_accounts_from_path(&path);
+    assert_eq!(find_account(&accounts, "jdoe-corp"), Some(&work()));
+
+    delete_account_from_path("work", &path).unwrap();
+    assert!(load_accounts_from_path(&path).is_empty());
+}
+
+#[test]
+fn test_ssh_config_and_remote_urls() {
+    let config = upsert_account_entries("Host *\n    AddKeysToAgent yes\n", &work());
+    assert!(config.contains("Host github-work"));
+    assert!(config.starts_with("Host *\n"));
+    let (config, removed) = remove_account_entries(&config, &work());
+    assert!(removed.is_some());
+    assert!(!config.contains("github-work"));
+
+    let remote = RemoteUrl::parse("https://github.com/corp/api.git").unwrap();
+    assert_eq!(
+        alias_remote_url(&work(), &remote),
+        "git@github-work:corp/api.git"
+    );
+}